@@ -0,0 +1,77 @@
+//! Benchmarks of lean genealogies of large groups, with the memory they use
+//! compared to full genealogies, which also store the nodes of their trees,
+//! as counted by the allocator.
+
+use coalescence::Coalescent;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// System allocator counting the bytes currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_pointer = System.realloc(pointer, layout, new_size);
+        if !new_pointer.is_null() {
+            ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_pointer
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Value built by ``build``, with the bytes it still holds on the heap once built.
+fn measured<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = build();
+    let after = ALLOCATED.load(Ordering::Relaxed);
+    (value, after.saturating_sub(before))
+}
+
+fn lean_genealogies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sample_lean_genealogy");
+    group.sample_size(10);
+    for &group_size in &[1_000, 100_000, 1_000_000] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let coalescent = Coalescent::new(group_size, rng.clone());
+
+        let (lean, lean_size) = measured(|| coalescent.sample_lean_genealogy(&mut rng));
+        let (full, full_size) = measured(|| coalescent.sample_genealogy(&mut rng));
+        println!(
+            "n = {}: lean genealogy of {:.1} MB, full genealogy of {:.1} MB",
+            group_size,
+            lean_size as f64 / 1e6,
+            full_size as f64 / 1e6,
+        );
+        drop((lean, full));
+
+        group.bench_with_input(BenchmarkId::from_parameter(group_size), &group_size, |b, _| {
+            b.iter(|| coalescent.sample_lean_genealogy(&mut rng))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lean_genealogies);
+criterion_main!(benches);
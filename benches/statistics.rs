@@ -0,0 +1,64 @@
+//! Benchmarks of statistics of genealogies computed from the flat arrays of events,
+//! compared to the computations through branches or searches per pair.
+
+use coalescence::Coalescent;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+fn divergence_matrices(c: &mut Criterion) {
+    let mut group = c.benchmark_group("divergence_matrix");
+    group.sample_size(10);
+    for &group_size in &[100, 1000, 10_000] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let genealogy = Coalescent::new(group_size, rng.clone()).sample_genealogy(&mut rng);
+
+        // Walking up the tree for each pair takes minutes for ten thousand individuals
+        if group_size <= 1000 {
+            group.bench_with_input(BenchmarkId::new("per_pair", group_size), &group_size, |b, &group_size| {
+                b.iter(|| {
+                    (0..group_size)
+                        .map(|first| {
+                            (0..group_size)
+                                .map(|second| genealogy.divergence(first, second).unwrap())
+                                .collect::<Vec<f64>>()
+                        })
+                        .collect::<Vec<Vec<f64>>>()
+                })
+            });
+        }
+        group.bench_with_input(BenchmarkId::new("flat", group_size), &group_size, |b, _| {
+            b.iter(|| genealogy.to_matrix())
+        });
+    }
+    group.finish();
+}
+
+fn branch_spectra(c: &mut Criterion) {
+    let mut group = c.benchmark_group("branch_spectrum");
+    group.sample_size(10);
+    for &group_size in &[1000, 10_000, 100_000] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let genealogy = Coalescent::new(group_size, rng.clone()).sample_genealogy(&mut rng);
+        let root = genealogy.node(2 * group_size - 2);
+
+        group.bench_with_input(BenchmarkId::new("branches", group_size), &group_size, |b, _| {
+            b.iter(|| genealogy.clade_statistics(&root).branch_spectrum)
+        });
+        group.bench_with_input(BenchmarkId::new("flat", group_size), &group_size, |b, _| {
+            b.iter(|| genealogy.branch_spectrum())
+        });
+    }
+    for &group_size in &[10_000, 100_000] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let genealogy = Coalescent::new(group_size, rng.clone()).sample_lean_genealogy(&mut rng);
+
+        group.bench_with_input(BenchmarkId::new("lean", group_size), &group_size, |b, _| {
+            b.iter(|| genealogy.branch_spectrum())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, divergence_matrices, branch_spectra);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Benchmarks of the simulation of waiting times, event by event or in batches.
+
+use coalescence::Coalescent;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use rand_distr::{Distribution, Exp};
+use rand_pcg::Pcg32;
+
+fn waiting_times(c: &mut Criterion) {
+    let mut group = c.benchmark_group("waiting_times");
+    for &group_size in &[10, 100, 1000] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let coalescent = Coalescent::new(group_size, rng.clone());
+
+        group.bench_with_input(BenchmarkId::new("per_event", group_size), &group_size, |b, &group_size| {
+            b.iter(|| {
+                (2..=group_size)
+                    .rev()
+                    .map(|sets| {
+                        let rate = (sets * (sets - 1) / 2) as f64;
+                        Exp::new(rate).unwrap().sample(&mut rng)
+                    })
+                    .collect::<Vec<f64>>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", group_size), &group_size, |b, _| {
+            b.iter(|| coalescent.sample_waiting_times(&mut rng))
+        });
+        group.bench_with_input(BenchmarkId::new("batch_of_100_replicates", group_size), &group_size, |b, _| {
+            b.iter(|| coalescent.sample_waiting_times_batch(100, &mut rng))
+        });
+    }
+    group.finish();
+}
+
+fn genealogies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sample_genealogy");
+    for &group_size in &[10, 100] {
+        let mut rng = Pcg32::seed_from_u64(group_size as u64);
+        let coalescent = Coalescent::new(group_size, rng.clone());
+
+        group.bench_with_input(BenchmarkId::from_parameter(group_size), &group_size, |b, _| {
+            b.iter(|| coalescent.sample_genealogy(&mut rng))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, waiting_times, genealogies);
+criterion_main!(benches);
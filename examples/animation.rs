@@ -0,0 +1,83 @@
+//! Animation of lineages merging backwards in time, in the terminal.
+//!
+//! ```text
+//! cargo run --example animation [group size] [seconds per unit of time]
+//! ```
+//!
+//! Each column is an individual, and each row a slice of time into the past.
+//! Lineages are drawn in the column of their smallest individual, and events
+//! are read one by one from the event stream of the coalescent.
+
+// Functions
+use rand::thread_rng;
+
+// Structs
+use coalescence::Coalescent;
+
+// Traits
+use coalescence::EventStream;
+use std::io::Write;
+
+/// Rows drawn for each unit of time.
+const ROWS_PER_UNIT: f64 = 8.0;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let group_size: usize = args.first().and_then(|arg| arg.parse().ok()).unwrap_or(8);
+    let seconds_per_unit: f64 = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(2.0);
+
+    let mut coalescent = Coalescent::new(group_size, thread_rng());
+    let mut representatives: Vec<usize> = (0..group_size).collect(); // of each individual
+    let mut stdout = std::io::stdout();
+
+    // Clear the screen and label individuals
+
+    print!("\x1b[2J\x1b[H");
+    let labels: String = (0..group_size).map(|individual| format!("{:<3}", individual % 1000)).collect();
+    println!("{}   time   lineages", labels);
+
+    for (time, event) in coalescent.events().scan_times() {
+        // Lineages waiting for the event
+
+        let rows = ((event.time_step * ROWS_PER_UNIT).round() as usize).max(1);
+        let pause = std::time::Duration::from_secs_f64(event.time_step * seconds_per_unit / rows as f64);
+        for _ in 0..rows {
+            println!("{}", draw(&representatives, None));
+            stdout.flush().unwrap();
+            std::thread::sleep(pause);
+        }
+
+        // Join the two lineages in the column of the leftmost one
+
+        let [first, second] = [representatives[event.indexes[0]], representatives[event.indexes[1]]];
+        let (left, right) = (first.min(second), first.max(second));
+        println!("{}   {:.3}   {}", draw(&representatives, Some((left, right))), time, event.lineages);
+        for representative in representatives.iter_mut() {
+            if *representative == right {
+                *representative = left;
+            }
+        }
+    }
+    println!("{}", draw(&representatives, None));
+}
+
+/// Row with a vertical bar for each lineage, and a horizontal bar joining two of them.
+fn draw(representatives: &[usize], join: Option<(usize, usize)>) -> String {
+    (0..representatives.len())
+        .map(|column| {
+            let lineage = representatives.contains(&column);
+            match join {
+                Some((left, _)) if column == left => "+--",
+                Some((_, right)) if column == right => "+  ",
+                Some((left, right)) if left < column && column < right => match lineage {
+                    true => "|--",
+                    false => "---",
+                },
+                _ => match lineage {
+                    true => "|  ",
+                    false => "   ",
+                },
+            }
+        })
+        .collect()
+}
@@ -3,8 +3,7 @@ use preexplorer::prelude::*;
 use rand::prelude::*;
 
 // Structs
-use coalescence::Coalescent;
-use partitions::PartitionVec;
+use coalescence::{Coalescent, Partition};
 
 // Traits
 use itertools_num::ItertoolsNum;
@@ -43,7 +42,7 @@ fn main() {
     }
 }
 
-fn plot_genealogy(realization: Vec<(f64, PartitionVec<()>)>) {
+fn plot_genealogy(realization: Vec<(f64, Partition)>) {
     let (delta_times, values): (Vec<_>, Vec<_>) = realization.into_iter().unzip();
     let times: Vec<f64> = delta_times.iter().cumsum().collect();
 
@@ -70,7 +69,7 @@ fn plot_genealogy(realization: Vec<(f64, PartitionVec<()>)>) {
         .unwrap();
 }
 
-fn plot_group_size(realization: Vec<(f64, PartitionVec<()>)>) {
+fn plot_group_size(realization: Vec<(f64, Partition)>) {
     let (delta_times, values): (Vec<_>, Vec<_>) = realization.into_iter().unzip();
 
     let times: Vec<f64> = delta_times.iter().cumsum().collect();
@@ -6,7 +6,7 @@ use rayon::prelude::*;
 use rand::thread_rng;
 
 // Structs
-use coalescence::Coalescent;
+use coalescence::{Coalescent, SimulationStudy, Statistic};
 use average::Variance;
 use rand_distr::Poisson;
 
@@ -99,7 +99,7 @@ fn main() {
     }
 }
 
-fn plot_with_error(group_sizes: &Vec<usize>, empirical: Vec<Variance>, topic: &str) {
+fn plot_with_error(group_sizes: &[usize], empirical: Vec<Variance>, topic: &str) {
     let mut data = Vec::new();
     for i in 0..empirical.len() {
         data.push(group_sizes[i] as f64);
@@ -121,7 +121,7 @@ fn plot_with_error(group_sizes: &Vec<usize>, empirical: Vec<Variance>, topic: &s
 }
 
 
-fn heterozygosity_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Variance> {
+fn heterozygosity_sample(group_sizes: &[usize], samples: usize) -> Vec<Variance> {
     let mut empirical = Vec::new();
 
     for group_size in group_sizes {
@@ -131,12 +131,12 @@ fn heterozygosity_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Varian
             .map(|_| {
                 let coalescent = Coalescent::new(*group_size, thread_rng());
                 let mut rng = thread_rng();
-                let depth = coalescent.sample_genealogy(&mut rng).divergence(0, 1) / 2.0;
+                let depth = coalescent.sample_genealogy(&mut rng).divergence(0, 1).unwrap() / 2.0;
                 let poi = Poisson::new(depth).unwrap();
 
                 let mutations_1: u64 = poi.sample(&mut rng);
                 let mutations_2: u64 = poi.sample(&mut rng);
-                ((mutations_1 - mutations_2) % 2 == 0) as u32 as f64
+                (mutations_1 % 2 == mutations_2 % 2) as u32 as f64
             })
             .collect::<Vec<f64>>()
             .iter()
@@ -149,7 +149,7 @@ fn heterozygosity_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Varian
 }
 
 
-fn divergence_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Variance> {
+fn divergence_sample(group_sizes: &[usize], samples: usize) -> Vec<Variance> {
     let mut empirical_depths = Vec::new();
 
     for group_size in group_sizes {
@@ -159,7 +159,7 @@ fn divergence_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Variance>
             .map(|_| {
                 let coalescent = Coalescent::new(*group_size, thread_rng());
                 let mut rng = thread_rng();
-                coalescent.sample_genealogy(&mut rng).divergence(0, 1)
+                coalescent.sample_genealogy(&mut rng).random_pair_divergence(&mut rng)
             })
             .collect::<Vec<f64>>()
             .iter()
@@ -171,71 +171,37 @@ fn divergence_sample(group_sizes: &Vec<usize>, samples: usize) -> Vec<Variance>
     empirical_depths
 }
 
-fn plot_emp_var(group_sizes: &Vec<usize>, empirical: Vec<Variance>, topic: &str) {
+fn plot_emp_var(group_sizes: &[usize], empirical: Vec<Variance>, topic: &str) {
     (group_sizes, empirical.iter().map(|v| v.sample_variance()))
         .preexplore()
         .title(format!("{}: empirical variance", topic))
         .labelx("initial group size")
         .labely("time")
         .logx(2)
-        .plot(&format!("{}", topic))
+        .plot(topic)
         .unwrap();
 }
 
 
 
-fn pairwise_divergence_comparison(group_sizes: &Vec<usize>, samples: usize) -> (Vec<Variance>, Vec<f64>) {
-    let mut empirical_depths = Vec::new();
-    let mut theoretical_depths = Vec::new();
-
-    for group_size in group_sizes {
-        let empirical_depth: Variance = (0..samples)
-            .collect::<Vec<usize>>()
-            .par_iter()
-            .map(|_| {
-                let coalescent = Coalescent::new(*group_size, thread_rng());
-                let mut rng = thread_rng();
-                coalescent.sample_genealogy(&mut rng).mean_pairwise_divergence()
-            })
-            .collect::<Vec<f64>>()
-            .iter()
-            .collect();
-        let theoretical_depth = 2.0;
-        
-        empirical_depths.push(empirical_depth);
-        theoretical_depths.push(theoretical_depth);
-    }
+fn pairwise_divergence_comparison(group_sizes: &[usize], samples: usize) -> (Vec<Variance>, Vec<f64>) {
+    let empirical_divergences = study_sample(group_sizes, samples, Statistic::MeanPairwiseDivergence);
+    let theoretical_divergences = group_sizes.iter().map(|_| 2.0).collect();
     
-    (empirical_depths, theoretical_depths)
+    (empirical_divergences, theoretical_divergences)
 }
 
-fn length_comparison(group_sizes: &Vec<usize>, samples: usize) -> (Vec<Variance>, Vec<f64>) {
-    let mut empirical_depths = Vec::new();
-    let mut theoretical_depths = Vec::new();
-
-    for group_size in group_sizes {
-        let empirical_depth: Variance = (0..samples)
-            .collect::<Vec<usize>>()
-            .par_iter()
-            .map(|_| {
-                let coalescent = Coalescent::new(*group_size, thread_rng());
-                let mut rng = thread_rng();
-                coalescent.sample_genealogy(&mut rng).length()
-            })
-            .collect::<Vec<f64>>()
-            .iter()
-            .collect();
-
-        let theoretical_depth = 2.0 * ((*group_size as f64 - 1.0).ln() + 0.57721 + 1.0 / (2 * (*group_size  - 1) ) as f64 );
-        
-        empirical_depths.push(empirical_depth);
-        theoretical_depths.push(theoretical_depth);
-    }
+fn length_comparison(group_sizes: &[usize], samples: usize) -> (Vec<Variance>, Vec<f64>) {
+    let empirical_lengths = study_sample(group_sizes, samples, Statistic::Length);
+    let theoretical_lengths = group_sizes
+        .iter()
+        .map(|group_size| 2.0 * ((*group_size as f64 - 1.0).ln() + 0.57721 + 1.0 / (2 * (*group_size  - 1) ) as f64 ))
+        .collect();
     
-    (empirical_depths, theoretical_depths)
+    (empirical_lengths, theoretical_lengths)
 }
 
-fn plot_comparison(group_sizes: &Vec<usize>, empirical: Vec<Variance>, theoretical: Vec<f64>, topic: &str) {
+fn plot_comparison(group_sizes: &[usize], empirical: Vec<Variance>, theoretical: Vec<f64>, topic: &str) {
     pre::process::Comparison::new(vec![
         (group_sizes, empirical.iter().map(|v| v.mean()).collect::<Vec<f64>>())
             .preexplore()
@@ -251,35 +217,39 @@ fn plot_comparison(group_sizes: &Vec<usize>, empirical: Vec<Variance>, theoretic
         .labelx("initial group size")
         .labely("time")
         .logx(2)
-        .plot(&format!("{}", topic))
+        .plot(topic)
         .unwrap();
 }
 
 /// # Output
 /// 
 /// (empirical, theoretical). 
-fn depth_comparison(group_sizes: &Vec<usize>, samples: usize) -> (Vec<Variance>, Vec<f64>) {
-    let mut empirical_depths = Vec::new();
-    let mut theoretical_depths = Vec::new();
-
-    for group_size in group_sizes {
-        let empirical_depth: Variance = (0..samples)
-            .collect::<Vec<usize>>()
-            .par_iter()
-            .map(|_| {
-                let coalescent = Coalescent::new(*group_size, thread_rng());
-                let mut rng = thread_rng();
-                coalescent.sample_genealogy(&mut rng).depth()
-            })
-            .collect::<Vec<f64>>()
-            .iter()
-            .collect();
-
-        let theoretical_depth = 2.0 * (1.0 - 1.0 / *group_size as f64);
-        
-        empirical_depths.push(empirical_depth);
-        theoretical_depths.push(theoretical_depth);
-    }
+fn depth_comparison(group_sizes: &[usize], samples: usize) -> (Vec<Variance>, Vec<f64>) {
+    let empirical_depths = study_sample(group_sizes, samples, Statistic::Depth);
+    let theoretical_depths = group_sizes
+        .iter()
+        .map(|group_size| 2.0 * (1.0 - 1.0 / *group_size as f64))
+        .collect();
     
     (empirical_depths, theoretical_depths)
-}
\ No newline at end of file
+}
+
+/// Empirical distribution of a statistic for each group size. 
+fn study_sample(group_sizes: &[usize], samples: usize, statistic: Statistic) -> Vec<Variance> {
+    let records = SimulationStudy::new(group_sizes.to_vec(), samples)
+        .statistics(vec![statistic])
+        .run(&mut thread_rng());
+
+    group_sizes
+        .iter()
+        .map(|group_size| {
+            records
+                .iter()
+                .filter(|record| record.group_size == *group_size)
+                .map(|record| record.values[0])
+                .collect::<Vec<f64>>()
+                .iter()
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,388 @@
+//! Genealogies with annotations on nodes, and unary nodes on branches, in Newick.
+//!
+//! Events that do not join lineages, like migrations, admixture or sampling of an ancestor,
+//! are recorded as unary nodes: each one lies on a branch, at a time between its lower and
+//! upper nodes, and has its own annotations. Nodes are numbered as in ``branches`` on
+//! Genealogy, and annotations are pairs of a key and a value, like the deme of a lineage.
+//!
+//! In Newick, individuals are named by their index, and each unary node opens a pair of
+//! parentheses with a single child. Annotations follow the node in a comment, without any
+//! escaping, in one of the ``NewickStyle``: either BEAST metadata, of the form
+//! ``[&key=value,other=value]``, as read by FigTree or IcyTree, which can color branches
+//! by any annotation, or NHX tags, of the form ``[&&NHX:key=value:other=value]``.
+//!
+//! In the DOT format of Graphviz, each segment of a branch between two nodes is an edge
+//! colored by an annotation of its lower node, like the deme of the lineage, so that
+//! migrations of structured simulations show at a glance.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::AnnotatedGenealogy;
+//!
+//! let genealogy = coalescence::Genealogy::from_events(2, &[(1.0, [0, 1])]).unwrap();
+//! let mut annotated = AnnotatedGenealogy::from(genealogy);
+//! annotated.annotate(0, "deme", "0");
+//! annotated.add_unary_node(0, 0.25, vec![("deme".to_string(), "1".to_string())]);
+//!
+//! assert_eq!(annotated.to_newick(), "((0[&deme=0]:0.25)[&deme=1]:0.75,1:1);");
+//! ```
+//!
+
+// Types
+use crate::{Genealogy, MutatedGenealogy};
+use std::collections::HashMap;
+
+/// Annotations of a node, as pairs of a key and a value.
+pub type Annotations = Vec<(String, String)>;
+
+/// Form of the comments holding annotations in Newick, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NewickStyle {
+    /// BEAST metadata, ``[&key=value]``.
+    Beast,
+    /// New Hampshire extended tags, ``[&&NHX:key=value]``.
+    Nhx,
+}
+
+/// Node on a branch with a single child, see the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryNode {
+    /// Time of the node in the past.
+    pub time: f64,
+    pub annotations: Annotations,
+}
+
+/// Genealogy with annotations of its nodes and unary nodes on its branches.
+#[derive(Debug, Clone)]
+pub struct AnnotatedGenealogy {
+    genealogy: Genealogy,
+    annotations: Vec<Annotations>,    // of each node
+    unary_nodes: Vec<Vec<UnaryNode>>, // on the branch above each node, sorted by time
+}
+
+impl AnnotatedGenealogy {
+    /// The genealogy, without annotations.
+    pub fn genealogy(&self) -> &Genealogy {
+        &self.genealogy
+    }
+
+    /// Sets the annotation ``key`` of a node to ``value``, replacing any previous value.
+    ///
+    /// # Panics
+    ///
+    /// If the node is not in the genealogy.
+    pub fn annotate(&mut self, node: usize, key: &str, value: &str) -> &mut Self {
+        assert!(node < self.annotations.len(), "The node must be in the genealogy.");
+
+        let annotations = &mut self.annotations[node];
+        match annotations.iter_mut().find(|(other, _)| other == key) {
+            Some((_, old)) => *old = value.to_string(),
+            None => annotations.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// Annotations of a node, in the order they were first set.
+    ///
+    /// # Panics
+    ///
+    /// If the node is not in the genealogy.
+    pub fn annotations(&self, node: usize) -> &[(String, String)] {
+        &self.annotations[node]
+    }
+
+    /// Annotates each node with its ``"age"``, i.e. its time in the past.
+    pub fn annotate_ages(&mut self) -> &mut Self {
+        let times = self.genealogy.nodes().1.to_vec();
+        for (node, time) in times.iter().enumerate() {
+            self.annotate(node, "age", &time.to_string());
+        }
+        self
+    }
+
+    /// Annotates each node but the root with the number of ``"mutations"`` on the branch
+    /// above it, branches being matched by their identifier, see ``BranchId``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{AnnotatedGenealogy, MutationModel, NewickStyle};
+    ///
+    /// let genealogy = coalescence::fixtures::balanced();
+    /// let mutated = genealogy.hash_mutations(MutationModel::Poisson { theta: 10.0 }, 7);
+    /// let mut annotated = AnnotatedGenealogy::from(genealogy);
+    /// annotated.annotate_mutations(&mutated).annotate_ages();
+    ///
+    /// let newick = annotated.to_newick_with(NewickStyle::Nhx);
+    /// assert!(newick.starts_with("((0[&&NHX:mutations="));
+    /// assert!(newick.ends_with("[&&NHX:age=4];"));
+    /// ```
+    pub fn annotate_mutations(&mut self, mutated: &MutatedGenealogy) -> &mut Self {
+        let mut counts: HashMap<_, usize> = HashMap::new();
+        for mutation in mutated.mutations() {
+            *counts.entry(mutation.branch).or_default() += 1;
+        }
+        for branch in self.genealogy.branches() {
+            let count = counts.get(&branch.id).cloned().unwrap_or(0);
+            self.annotate(branch.child, "mutations", &count.to_string());
+        }
+        self
+    }
+
+    /// Adds a unary node at time ``time`` on the branch above ``node``. Unary nodes at the
+    /// same time on a branch are kept in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// If the node is the root or not in the genealogy, or if the time is not between the
+    /// times of the node and of its parent.
+    pub fn add_unary_node(&mut self, node: usize, time: f64, annotations: Annotations) -> &mut Self {
+        let (parents, times) = self.genealogy.nodes();
+        assert!(node < parents.len() && parents[node] != node, "The node must be in the genealogy, below the root.");
+        assert!(
+            times[node] <= time && time <= times[parents[node]],
+            "Unary nodes must be on the branch, between its lower and upper nodes."
+        );
+
+        let unary_nodes = &mut self.unary_nodes[node];
+        let index = unary_nodes.partition_point(|other| other.time <= time);
+        unary_nodes.insert(index, UnaryNode { time, annotations });
+        self
+    }
+
+    /// Unary nodes on the branch above a node, from the most recent one.
+    ///
+    /// # Panics
+    ///
+    /// If the node is not in the genealogy.
+    pub fn unary_nodes(&self, node: usize) -> &[UnaryNode] {
+        &self.unary_nodes[node]
+    }
+
+    /// Genealogy in the Newick format, with its unary nodes and annotations as BEAST
+    /// metadata, see the module documentation.
+    pub fn to_newick(&self) -> String {
+        self.to_newick_with(NewickStyle::Beast)
+    }
+
+    /// Genealogy in the Newick format, with annotations in the given style.
+    pub fn to_newick_with(&self, style: NewickStyle) -> String {
+        // Children come before their parents, so that subtrees are written bottom-up
+        let (parents, times) = self.genealogy.nodes();
+        let mut children = vec![Vec::new(); parents.len()];
+        for (node, &parent) in parents.iter().enumerate().filter(|&(node, &parent)| node != parent) {
+            children[parent].push(node);
+        }
+
+        let mut subtrees: Vec<String> = Vec::with_capacity(parents.len());
+        for (node, below) in children.iter().enumerate() {
+            let mut subtree = match below.is_empty() {
+                true => node.to_string(),
+                false => {
+                    let below: Vec<String> = below.iter().map(|&child| std::mem::take(&mut subtrees[child])).collect();
+                    format!("({})", below.join(","))
+                },
+            };
+            subtree += &comment(&self.annotations[node], style);
+
+            if parents[node] != node {
+                let mut time = times[node];
+                for unary_node in &self.unary_nodes[node] {
+                    subtree = format!("({}:{}){}", subtree, unary_node.time - time, comment(&unary_node.annotations, style));
+                    time = unary_node.time;
+                }
+                subtree += &format!(":{}", times[parents[node]] - time);
+            }
+            subtrees.push(subtree);
+        }
+
+        subtrees.pop().unwrap_or_default() + ";"
+    }
+
+    /// Genealogy in the DOT format, with the root at the top, where each segment of a
+    /// branch is colored by the value of the annotation ``key`` of its lower node, with a
+    /// legend of the values. Nodes at the same time are drawn at the same height, but
+    /// lengths are not to scale: times are in the tooltips of nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// let structured = StructuredCoalescent::secondary_contact(2.0, 0.5, 1.0);
+    /// let annotated = structured.sample_annotated_genealogy(&[3, 3], &mut rand::thread_rng());
+    /// let dot = annotated.to_dot("deme");
+    ///
+    /// assert!(dot.starts_with("digraph genealogy {"));
+    /// assert!(dot.contains("label=\"deme 1\""));
+    /// // Open with: dot -Tsvg genealogy.dot > genealogy.svg
+    /// ```
+    pub fn to_dot(&self, key: &str) -> String {
+        const PALETTE: [&str; 8] = ["#1b9e77", "#d95f02", "#7570b3", "#e7298a", "#66a61e", "#e6ab02", "#a6761d", "#666666"];
+
+        // Colors in the order values first appear, from the individuals up
+        let mut values: Vec<String> = Vec::new();
+        let mut color = |annotations: &[(String, String)]| match annotations.iter().find(|(other, _)| other == key) {
+            Some((_, value)) => {
+                let index = values.iter().position(|other| other == value).unwrap_or_else(|| {
+                    values.push(value.clone());
+                    values.len() - 1
+                });
+                PALETTE[index % PALETTE.len()]
+            },
+            None => "black",
+        };
+
+        let (parents, times) = self.genealogy.nodes();
+        let group_size = self.genealogy.group_size();
+        let mut lines = vec!["digraph genealogy {".to_string(), "    node [shape=point];".to_string()];
+        for (node, &parent) in parents.iter().enumerate() {
+            lines.push(match node < group_size {
+                true => format!("    n{} [shape=plaintext, label=\"{}\", tooltip=\"time {}\"];", node, node, times[node]),
+                false => format!("    n{} [tooltip=\"time {}\"];", node, times[node]),
+            });
+            if parent == node {
+                continue;
+            }
+
+            // Segments between the node, the unary nodes above it and its parent
+            let mut lower = (format!("n{}", node), &self.annotations[node]);
+            for (index, unary_node) in self.unary_nodes[node].iter().enumerate() {
+                let upper = format!("u{}_{}", node, index);
+                lines.push(format!("    {} [width=0.05, tooltip=\"time {}\"];", upper, unary_node.time));
+                lines.push(format!("    {} -> {} [color=\"{}\"];", upper, lower.0, color(lower.1)));
+                lower = (upper, &unary_node.annotations);
+            }
+            lines.push(format!("    n{} -> {} [color=\"{}\"];", parent, lower.0, color(lower.1)));
+        }
+
+        // Nodes at the same time, e.g. individuals sampled together
+        let mut order: Vec<usize> = (0..parents.len()).collect();
+        order.sort_by(|&a, &b| times[a].partial_cmp(&times[b]).unwrap());
+        for same in order.chunk_by(|&a, &b| times[a] == times[b]).filter(|same| same.len() > 1) {
+            let same: Vec<String> = same.iter().map(|node| format!("n{};", node)).collect();
+            lines.push(format!("    {{ rank=same; {} }}", same.join(" ")));
+        }
+
+        if !values.is_empty() {
+            lines.push(format!("    subgraph cluster_legend {{ label=\"{}\";", key));
+            for (index, value) in values.iter().enumerate() {
+                lines.push(format!(
+                    "        legend_{} [shape=plaintext, label=\"{} {}\", fontcolor=\"{}\"];",
+                    index, key, value, PALETTE[index % PALETTE.len()]
+                ));
+            }
+            lines.push("    }".to_string());
+        }
+        lines.push("}".to_string());
+
+        lines.join("\n") + "\n"
+    }
+}
+
+impl From<Genealogy> for AnnotatedGenealogy {
+    fn from(genealogy: Genealogy) -> Self {
+        let number_of_nodes = genealogy.nodes().0.len();
+
+        AnnotatedGenealogy {
+            genealogy,
+            annotations: vec![Vec::new(); number_of_nodes],
+            unary_nodes: vec![Vec::new(); number_of_nodes],
+        }
+    }
+}
+
+impl Genealogy {
+    /// Genealogy in the Newick format, individuals being named by their index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let genealogy = coalescence::fixtures::balanced();
+    ///
+    /// assert_eq!(genealogy.to_newick(), "((0:1,1:1):3,(2:2,3:2):2);");
+    /// ```
+    pub fn to_newick(&self) -> String {
+        AnnotatedGenealogy::from(self.clone()).to_newick()
+    }
+}
+
+/// Annotations as a Newick comment, or nothing if there are none.
+fn comment(annotations: &[(String, String)], style: NewickStyle) -> String {
+    if annotations.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = annotations.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+    match style {
+        NewickStyle::Beast => format!("[&{}]", pairs.join(",")),
+        NewickStyle::Nhx => format!("[&&NHX:{}]", pairs.join(":")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unary_nodes() {
+        let mut annotated = AnnotatedGenealogy::from(crate::fixtures::balanced());
+        annotated
+            .add_unary_node(4, 3.0, vec![("deme".to_string(), "1".to_string())])
+            .add_unary_node(4, 1.5, Vec::new())
+            .annotate(6, "deme", "1")
+            .annotate(6, "deme", "0");
+        let times: Vec<f64> = annotated.unary_nodes(4).iter().map(|node| node.time).collect();
+        assert_eq!(times, vec![1.5, 3.0]);
+        assert_eq!(annotated.annotations(6), &[("deme".to_string(), "0".to_string())]);
+        assert_eq!(annotated.to_newick(), "((((0:1,1:1):0.5):1.5)[&deme=1]:1,(2:2,3:2):2)[&deme=0];");
+
+        // Unary nodes at the ends of branches are kept
+        annotated.add_unary_node(2, 0.0, Vec::new());
+        assert!(annotated.to_newick().contains("(2:0):2"));
+        assert_eq!(crate::Genealogy::from_events(1, &[]).unwrap().to_newick(), "0;");
+    }
+
+    #[test]
+    fn styles() {
+        let genealogy = crate::Genealogy::from_events(2, &[(0.5, [0, 1])]).unwrap();
+        let mutated = genealogy.sample_mutations(crate::MutationModel::FixedSegregatingSites { sites: 3 }, &mut rand::thread_rng());
+        let mut annotated = AnnotatedGenealogy::from(genealogy);
+        annotated.annotate(2, "deme", "1").annotate_ages();
+        assert_eq!(annotated.to_newick(), "(0[&age=0]:0.5,1[&age=0]:0.5)[&deme=1,age=0.5];");
+        assert_eq!(annotated.to_newick_with(NewickStyle::Nhx), "(0[&&NHX:age=0]:0.5,1[&&NHX:age=0]:0.5)[&&NHX:deme=1:age=0.5];");
+
+        // Mutations on both branches, none above the root
+        annotated.annotate_mutations(&mutated);
+        let count = |node: usize| annotated.annotations(node)[1].1.parse::<usize>().unwrap();
+        assert_eq!(count(0) + count(1), 3);
+        assert_eq!(annotated.annotations(2).len(), 2);
+    }
+
+    #[test]
+    fn dot() {
+        let genealogy = crate::Genealogy::from_events(2, &[(1.0, [0, 1])]).unwrap();
+        let mut annotated = AnnotatedGenealogy::from(genealogy);
+        annotated.annotate(0, "deme", "0").annotate(1, "deme", "1").annotate(2, "deme", "1");
+        annotated.add_unary_node(0, 0.25, vec![("deme".to_string(), "1".to_string())]);
+        let dot = annotated.to_dot("deme");
+
+        // The branch of 0 is in deme 0 up to its migration, then in deme 1 like the branch of 1
+        assert!(dot.contains("    u0_0 -> n0 [color=\"#1b9e77\"];\n    n2 -> u0_0 [color=\"#d95f02\"];\n"));
+        assert!(dot.contains("    n2 -> n1 [color=\"#d95f02\"];\n"));
+        assert!(dot.contains("    { rank=same; n0; n1; }\n"));
+        assert!(dot.contains("legend_1 [shape=plaintext, label=\"deme 1\", fontcolor=\"#d95f02\"];"));
+
+        // Without the annotation, segments are black and there is no legend
+        let dot = annotated.to_dot("age");
+        assert_eq!(dot.matches("color=\"black\"").count(), 3);
+        assert!(!dot.contains("legend"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn unary_nodes_on_branches() {
+        AnnotatedGenealogy::from(crate::fixtures::balanced()).add_unary_node(4, 0.5, Vec::new());
+    }
+}
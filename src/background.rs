@@ -0,0 +1,323 @@
+//! Coalescent under background selection, with lineages in fitness classes.
+//!
+//! Deleterious mutations arrive at rate ``u`` per genome and generation and each one
+//! reduces fitness by ``s``. At mutation–selection balance, the number of mutations
+//! carried by a genome is Poisson with mean ``λ = u / s``, so the population is split
+//! into fitness classes of relative sizes ``f_k = e^{-λ} λ^k / k!`` (Gordo, Navarro
+//! and Charlesworth, 2002).
+//!
+//! Backwards in time, a lineage of class ``k`` moves to class ``k - 1`` at rate
+//! ``N s k``, as it goes back past the arrival of one of its mutations, and two
+//! lineages only coalesce within a class, at rate ``1 / f_k``, time being in units
+//! of ``N`` generations. Since all lineages end up in the least loaded class, of
+//! relative size ``e^{-λ}``, genealogies are shallower than under neutrality
+//! when selection is strong, as if the effective size were reduced.
+//!
+//! Events are simulated by the Gillespie algorithm, see ``GillespieStep``.
+//!
+//! At a neutral site linked to selected regions, background selection is usually
+//! summarized instead by its B-value: the factor by which it reduces the effective
+//! size, ``B = exp(-Σ u t / (t + r (1 - t))²)`` over regions with deleterious mutation
+//! rate ``u``, heterozygous selection coefficient ``t`` and recombination fraction ``r``
+//! with the site (Hudson and Kaplan, 1995; Nordborg, Charlesworth and Charlesworth, 1996).
+//! Genealogies at the site then follow the standard coalescent with a smaller size.
+//!
+
+// Types
+use crate::{Coalescent, Genealogy};
+use rand_pcg::Pcg32;
+
+// Traits
+use crate::traits::GillespieStep;
+use rand::Rng;
+
+/// Probability beyond the last fitness class that is simulated.
+const TAIL_PROBABILITY: f64 = 1e-12;
+
+/// Coalescent under background selection against deleterious mutations.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::BackgroundSelection;
+///
+/// let background = BackgroundSelection::new(1e4, 0.02, 0.01);
+/// let genealogy = background.sample_genealogy(10, &mut rand::thread_rng());
+///
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// assert!((background.effective_size() - (-2.0f64).exp()).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundSelection {
+    population_size: f64,
+    mutation_rate: f64,
+    selection_coefficient: f64,
+    frequencies: Vec<f64>,
+}
+
+impl BackgroundSelection {
+    /// Creates a new model of a population of ``population_size`` genomes with deleterious
+    /// mutations at rate ``mutation_rate`` per genome and generation, each with selection
+    /// coefficient ``selection_coefficient``.
+    ///
+    /// # Panics
+    ///
+    /// If the population size or the selection coefficient are not positive and finite,
+    /// or if the mutation rate is negative or not finite.
+    pub fn new(population_size: f64, mutation_rate: f64, selection_coefficient: f64) -> Self {
+        assert!(population_size > 0.0 && population_size.is_finite(), "The population size must be positive and finite.");
+        assert!(mutation_rate >= 0.0 && mutation_rate.is_finite(), "The mutation rate must be non-negative and finite.");
+        assert!(
+            selection_coefficient > 0.0 && selection_coefficient.is_finite(),
+            "The selection coefficient must be positive and finite."
+        );
+
+        // Poisson frequencies of classes, up to a negligible tail
+        let lambda = mutation_rate / selection_coefficient;
+        let mut frequencies = vec![(-lambda).exp()];
+        let mut total = frequencies[0];
+        while 1.0 - total > TAIL_PROBABILITY && (frequencies.len() as f64) < lambda * 10.0 + 50.0 {
+            let k = frequencies.len() as f64;
+            let next = frequencies[frequencies.len() - 1] * lambda / k;
+            frequencies.push(next);
+            total += next;
+        }
+
+        BackgroundSelection { population_size, mutation_rate, selection_coefficient, frequencies }
+    }
+
+    /// Number of genomes of the population.
+    pub fn population_size(&self) -> f64 {
+        self.population_size
+    }
+
+    /// Rate of deleterious mutations per genome and generation.
+    pub fn mutation_rate(&self) -> f64 {
+        self.mutation_rate
+    }
+
+    /// Selection coefficient of each deleterious mutation.
+    pub fn selection_coefficient(&self) -> f64 {
+        self.selection_coefficient
+    }
+
+    /// Relative sizes of the fitness classes, by number of deleterious mutations.
+    pub fn class_frequencies(&self) -> &[f64] {
+        &self.frequencies
+    }
+
+    /// Effective size relative to the population size when selection is strong,
+    /// i.e. the relative size ``e^{-u / s}`` of the least loaded class.
+    pub fn effective_size(&self) -> f64 {
+        self.frequencies[0]
+    }
+
+    /// Sample a genealogy of ``group_size`` individuals, taken at random from the population.
+    ///
+    /// # Panics
+    ///
+    /// If the group is empty.
+    pub fn sample_genealogy<R>(&self, group_size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(group_size > 0, "The group must be non-empty.");
+
+        // Individual and class of each lineage, classes drawn at mutation–selection balance
+
+        let mut lineages: Vec<(usize, usize)> = (0..group_size)
+            .map(|individual| {
+                let mut point = rng.gen::<f64>();
+                let class = self
+                    .frequencies
+                    .iter()
+                    .position(|&frequency| {
+                        point -= frequency;
+                        point < 0.0
+                    })
+                    .unwrap_or(self.frequencies.len() - 1);
+                (individual, class)
+            })
+            .collect();
+        let mut steps = Vec::with_capacity(group_size - 1);
+        let mut time_steps = Vec::with_capacity(group_size - 1);
+        let mut elapsed = 0.0;
+
+        while lineages.len() > 1 {
+            let mut counts = vec![0; self.frequencies.len()];
+            for &(_, class) in &lineages {
+                counts[class] += 1;
+            }
+            let rates = ClassRates { model: self, lineages: &lineages, counts: &counts };
+            let (waiting_time, event) = rates.gillespie_step(rng).unwrap();
+            elapsed += waiting_time;
+
+            match event {
+                ClassEvent::Move(lineage) => lineages[lineage].1 -= 1,
+                ClassEvent::Coalesce(class) => {
+                    let members: Vec<usize> = (0..lineages.len()).filter(|&lineage| lineages[lineage].1 == class).collect();
+                    let chosen = rand::seq::index::sample(rng, members.len(), 2);
+                    let (first, second) = (members[chosen.index(0)], members[chosen.index(1)]);
+                    steps.push([lineages[first].0, lineages[second].0]);
+                    time_steps.push(elapsed);
+                    elapsed = 0.0;
+                    lineages.swap_remove(second);
+                },
+            }
+        }
+
+        Genealogy::from_steps(group_size, steps, time_steps)
+    }
+}
+
+/// Region under purifying selection, linked to a neutral site.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectedRegion {
+    /// Rate of deleterious mutations in the region, per generation.
+    pub mutation_rate: f64,
+    /// Selection coefficient of each mutation in heterozygotes.
+    pub selection_coefficient: f64,
+    /// Probability of recombination between the region and the neutral site, per generation.
+    pub recombination_fraction: f64,
+}
+
+/// B-value of a neutral site: the factor by which background selection in the
+/// given regions reduces its effective size, see the module documentation.
+///
+/// # Panics
+///
+/// If some mutation rate is negative, if some selection coefficient is not in (0, 1],
+/// or if some recombination fraction is not in [0, 0.5].
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{b_value, SelectedRegion};
+///
+/// // Without recombination, the reduction of the least loaded class, e^{-u / t}
+/// let linked = SelectedRegion { mutation_rate: 0.01, selection_coefficient: 0.02, recombination_fraction: 0.0 };
+/// assert!((b_value(&[linked]) - (-0.5f64).exp()).abs() < 1e-12);
+///
+/// // Recombination weakens the effect
+/// let distant = SelectedRegion { recombination_fraction: 0.1, ..linked };
+/// assert!(b_value(&[distant]) > 0.98);
+/// ```
+pub fn b_value(regions: &[SelectedRegion]) -> f64 {
+    let exponent: f64 = regions
+        .iter()
+        .map(|region| {
+            let SelectedRegion { mutation_rate: u, selection_coefficient: t, recombination_fraction: r } = *region;
+            assert!(u >= 0.0 && u.is_finite(), "Mutation rates must be non-negative and finite.");
+            assert!(t > 0.0 && t <= 1.0, "Selection coefficients must be in (0, 1].");
+            assert!((0.0..=0.5).contains(&r), "Recombination fractions must be in [0, 0.5].");
+            u * t / (t + r * (1.0 - t)).powi(2)
+        })
+        .sum();
+
+    (-exponent).exp()
+}
+
+impl Coalescent<Pcg32> {
+    /// Sampler of genealogies at a neutral site linked to regions under background selection,
+    /// i.e. of the standard coalescent with effective size reduced by their ``b_value``,
+    /// by scaling all rates by ``1 / B`` as in ``with_temperature``. See ``sampler``.
+    ///
+    /// # Panics
+    ///
+    /// In the same cases as ``b_value``, or if the B-value underflows to zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{b_value, Coalescent, SelectedRegion};
+    ///
+    /// let region = SelectedRegion { mutation_rate: 0.05, selection_coefficient: 0.05, recombination_fraction: 0.0 };
+    /// let coalescent = Coalescent::with_background_selection(10, &[region]);
+    ///
+    /// assert!((coalescent.beta() - 1f64.exp()).abs() < 1e-12);
+    /// ```
+    pub fn with_background_selection(group_size: usize, regions: &[SelectedRegion]) -> Self {
+        Coalescent::sampler(group_size).with_temperature(1.0 / b_value(regions))
+    }
+}
+
+/// Event of lineages in fitness classes.
+enum ClassEvent {
+    /// A lineage goes back past one of its mutations.
+    Move(usize),
+    /// Two lineages of a class coalesce.
+    Coalesce(usize),
+}
+
+/// Rates of moves of each lineage, followed by rates of coalescence in each class.
+struct ClassRates<'a> {
+    model: &'a BackgroundSelection,
+    lineages: &'a [(usize, usize)],
+    counts: &'a [usize],
+}
+
+impl<'a> GillespieStep for ClassRates<'a> {
+    type Event = ClassEvent;
+
+    fn event_rates(&self, rates: &mut Vec<f64>) {
+        let scaled_selection = self.model.population_size * self.model.selection_coefficient;
+        rates.extend(self.lineages.iter().map(|&(_, class)| scaled_selection * class as f64));
+        rates.extend(
+            self.counts
+                .iter()
+                .zip(&self.model.frequencies)
+                .map(|(&count, frequency)| (count * count.saturating_sub(1)) as f64 / 2.0 / frequency),
+        );
+    }
+
+    fn event(&self, index: usize) -> ClassEvent {
+        match index < self.lineages.len() {
+            true => ClassEvent::Move(index),
+            false => ClassEvent::Coalesce(index - self.lineages.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduced_effective_size() {
+        let mut rng = rand::thread_rng();
+        let samples = 4000;
+        let mean_depth = |model: &BackgroundSelection, rng: &mut rand::rngs::ThreadRng| {
+            (0..samples).map(|_| model.sample_genealogy(2, rng).depth()).sum::<f64>() / samples as f64
+        };
+
+        // Without mutations, Kingman: E[TMRCA] = 1
+        let neutral = BackgroundSelection::new(1e4, 0.0, 0.01);
+        assert_eq!(neutral.class_frequencies(), &[1.0]);
+        assert!((mean_depth(&neutral, &mut rng) - 1.0).abs() < 0.06);
+
+        // Strong selection: E[TMRCA] close to e^{-λ}
+        let background = BackgroundSelection::new(1e4, 0.1, 0.1);
+        assert!((background.class_frequencies().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let depth = mean_depth(&background, &mut rng);
+        assert!((depth - (-1.0f64).exp()).abs() < 0.03, "{}", depth);
+    }
+
+    #[test]
+    fn b_values() {
+        // Many loosely linked regions add up their effects
+        let region = SelectedRegion { mutation_rate: 1e-3, selection_coefficient: 0.01, recombination_fraction: 0.01 };
+        let regions = vec![region; 10];
+        let single = b_value(&[region]);
+        assert!((b_value(&regions) - single.powi(10)).abs() < 1e-12);
+        assert!((single - (-1e-5 / (0.01 + 0.01 * 0.99f64).powi(2)).exp()).abs() < 1e-12);
+        assert_eq!(b_value(&[]), 1.0);
+
+        // Rescaled Kingman: E[TMRCA] of two individuals is B
+        let full = SelectedRegion { mutation_rate: 0.1, selection_coefficient: 0.1, recombination_fraction: 0.0 };
+        let coalescent = Coalescent::with_background_selection(2, &[full]);
+        let mut rng = rand::thread_rng();
+        let samples = 4000;
+        let depth = (0..samples).map(|_| coalescent.sample_genealogy(&mut rng).depth()).sum::<f64>() / samples as f64;
+        assert!((depth - (-1.0f64).exp()).abs() < 0.03);
+    }
+}
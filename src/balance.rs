@@ -0,0 +1,330 @@
+//! Balance of the topologies of genealogies, by the indices of Sackin and Colless.
+//!
+//! Sackin's index is the sum over individuals of their number of ancestors, and
+//! Colless' index the sum over common ancestors of the difference between the
+//! number of individuals below each of their children. Both are small for
+//! balanced trees and large for caterpillars, but they grow with the size of the
+//! group, so they are compared through z-scores against a model of shapes:
+//!
+//! - the Yule model, which is also the distribution of topologies of the coalescent,
+//!   where the left child of the root has a number of individuals uniform in 1..n;
+//! - the uniform model, where all labeled topologies are equally likely.
+//!
+//! Means and variances are computed exactly, by recursion over the sizes of the
+//! children of the root, in O(n²) time.
+//!
+//! Genealogies of each ``TreeModel`` are sampled through the same method, so that
+//! statistics of their shapes and of their times can be compared with the same code.
+//!
+
+// Types
+use crate::{BirthDeath, Coalescent, Genealogy};
+
+// Traits
+use rand::Rng;
+
+/// Model of random topologies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeModel {
+    /// Yule model, i.e. the topologies of the coalescent.
+    Yule,
+    /// Uniform distribution over labeled topologies.
+    Uniform,
+}
+
+/// Model of random trees, with times.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::balance::TreeModel;
+///
+/// let mut rng = rand::thread_rng();
+/// for model in &[TreeModel::Kingman, TreeModel::Yule { birth_rate: 1.0 }] {
+///     let genealogy = model.sample_genealogy(20, &mut rng);
+///     assert_eq!(genealogy.validate(), Ok(()));
+///     assert!(genealogy.sackin_z_score(model.shape_model()).is_finite());
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeModel {
+    /// Coalescent in a population of constant size one, see ``Coalescent``.
+    Kingman,
+    /// Pure birth process, where each lineage splits at the given rate, with every lineage
+    /// sampled at present, see ``BirthDeath::yule``.
+    Yule { birth_rate: f64 },
+}
+
+impl TreeModel {
+    /// Sample a genealogy of ``group_size`` individuals.
+    ///
+    /// # Panics
+    ///
+    /// If the group is empty, or if the birth rate is not positive and finite.
+    pub fn sample_genealogy<R>(&self, group_size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(group_size > 0, "The group must not be empty.");
+
+        match *self {
+            TreeModel::Kingman => Coalescent::sampler(group_size).sample_genealogy(rng),
+            TreeModel::Yule { birth_rate } => BirthDeath::yule(birth_rate).sample_genealogy(group_size, rng),
+        }
+    }
+
+    /// Model of the topologies of sampled genealogies, which is the Yule model for both:
+    /// they differ by their times, not by their shapes.
+    pub fn shape_model(&self) -> ShapeModel {
+        match self {
+            TreeModel::Kingman | TreeModel::Yule { .. } => ShapeModel::Yule,
+        }
+    }
+}
+
+/// Mean and variance of a balance index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BalanceMoments {
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl BalanceMoments {
+    /// Number of standard deviations of ``value`` away from the mean,
+    /// which is not a number if the index is constant.
+    pub fn z_score(&self, value: f64) -> f64 {
+        (value - self.mean) / self.variance.sqrt()
+    }
+}
+
+/// Moments of Sackin's index of ``group_size`` individuals under the given model.
+///
+/// # Panics
+///
+/// If the group is empty.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::balance::{sackin_moments, ShapeModel};
+///
+/// // Under the Yule model, E[S] = 2 n (1/2 + ... + 1/n)
+/// let moments = sackin_moments(4, ShapeModel::Yule);
+/// assert!((moments.mean - 8.0 * (1.0 / 2.0 + 1.0 / 3.0 + 1.0 / 4.0)).abs() < 1e-12);
+/// ```
+pub fn sackin_moments(group_size: usize, model: ShapeModel) -> BalanceMoments {
+    moments(group_size, model, |n, _| n as f64)
+}
+
+/// Moments of Colless' index of ``group_size`` individuals under the given model.
+///
+/// # Panics
+///
+/// If the group is empty.
+pub fn colless_moments(group_size: usize, model: ShapeModel) -> BalanceMoments {
+    moments(group_size, model, |n, k| (n as f64 - 2.0 * k as f64).abs())
+}
+
+/// Moments of an additive index, which adds ``increment(n, k)`` at a node of ``n``
+/// individuals whose left child has ``k`` of them.
+fn moments<F>(group_size: usize, model: ShapeModel, increment: F) -> BalanceMoments
+where
+    F: Fn(usize, usize) -> f64,
+{
+    assert!(group_size > 0, "The group must not be empty.");
+
+    // Logarithm of the number (2n - 3)!! of labeled topologies, and of factorials
+    let mut log_topologies = vec![0.0; group_size + 1];
+    let mut log_factorials = vec![0.0; group_size + 1];
+    for n in 2..=group_size {
+        log_topologies[n] = log_topologies[n - 1] + ((2 * n - 3) as f64).ln();
+        log_factorials[n] = log_factorials[n - 1] + (n as f64).ln();
+    }
+    let split_probability = |n: usize, k: usize| match model {
+        ShapeModel::Yule => 1.0 / (n - 1) as f64,
+        ShapeModel::Uniform => {
+            let log_choose = log_factorials[n] - log_factorials[k] - log_factorials[n - k];
+            (log_choose + log_topologies[k] + log_topologies[n - k] - log_topologies[n]).exp() / 2.0
+        },
+    };
+
+    // First and second moments, the left and right children being independent given their sizes
+    let mut first = vec![0.0; group_size + 1];
+    let mut second = vec![0.0; group_size + 1];
+    for n in 2..=group_size {
+        for k in 1..n {
+            let probability = split_probability(n, k);
+            let increment = increment(n, k);
+            let (left, right) = (first[k], first[n - k]);
+            first[n] += probability * (left + right + increment);
+            second[n] += probability
+                * (second[k] + second[n - k] + increment * increment
+                    + 2.0 * left * right
+                    + 2.0 * increment * (left + right));
+        }
+    }
+
+    let mean = first[group_size];
+    BalanceMoments { mean, variance: (second[group_size] - mean * mean).max(0.0) }
+}
+
+impl Genealogy {
+    /// Sackin's index: the sum over individuals of their number of ancestors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert_eq!(coalescence::fixtures::balanced().sackin(), 8);
+    /// assert_eq!(coalescence::fixtures::caterpillar(4).sackin(), 9);
+    /// ```
+    pub fn sackin(&self) -> usize {
+        self.clade_sizes().iter().flatten().sum()
+    }
+
+    /// Colless' index: the sum over common ancestors of the difference between
+    /// the number of individuals below each of their children, over all pairs of
+    /// children of those of multiple mergers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert_eq!(coalescence::fixtures::balanced().colless(), 0);
+    /// assert_eq!(coalescence::fixtures::caterpillar(4).colless(), 3);
+    /// ```
+    pub fn colless(&self) -> usize {
+        let mut colless = 0;
+        for sizes in self.clade_sizes() {
+            for (position, &left) in sizes.iter().enumerate() {
+                for &right in &sizes[position + 1..] {
+                    colless += match left > right {
+                        true => left - right,
+                        false => right - left,
+                    };
+                }
+            }
+        }
+
+        colless
+    }
+
+    /// Sackin's index normalized under the given model, as a z-score.
+    /// It is not a number for less than four individuals, where the index is constant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::balance::ShapeModel;
+    ///
+    /// let genealogy = coalescence::fixtures::caterpillar(40);
+    /// assert!(genealogy.sackin_z_score(ShapeModel::Yule) > 3.0);
+    /// assert!(genealogy.sackin_z_score(ShapeModel::Uniform) > 0.0);
+    /// ```
+    pub fn sackin_z_score(&self, model: ShapeModel) -> f64 {
+        sackin_moments(self.group_size(), model).z_score(self.sackin() as f64)
+    }
+
+    /// Colless' index normalized under the given model, as a z-score.
+    /// It is not a number for less than four individuals, where the index is constant.
+    pub fn colless_z_score(&self, model: ShapeModel) -> f64 {
+        colless_moments(self.group_size(), model).z_score(self.colless() as f64)
+    }
+
+    /// Number of individuals below each child of every common ancestor.
+    fn clade_sizes(&self) -> Vec<Vec<usize>> {
+        let group_size = self.group_size();
+        let (parents, _) = self.nodes();
+        let mut sizes = vec![0; parents.len()];
+        let mut children = vec![Vec::with_capacity(2); parents.len() - group_size];
+        for size in sizes.iter_mut().take(group_size) {
+            *size = 1;
+        }
+
+        // Children come before their parent
+        for node in 0..parents.len() {
+            if parents[node] != node {
+                children[parents[node] - group_size].push(sizes[node]);
+                sizes[parents[node]] += sizes[node];
+            }
+        }
+
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_groups() {
+        // Four individuals: the balanced topology has probability 1/3 under Yule and 1/5 when uniform
+        for &(model, balanced) in &[(ShapeModel::Yule, 1.0 / 3.0), (ShapeModel::Uniform, 0.2)] {
+            let sackin = sackin_moments(4, model);
+            let mean = balanced * 8.0 + (1.0 - balanced) * 9.0;
+            assert!((sackin.mean - mean).abs() < 1e-12);
+            assert!((sackin.variance - balanced * (1.0 - balanced)).abs() < 1e-12);
+            let colless = colless_moments(4, model);
+            assert!((colless.mean - (1.0 - balanced) * 3.0).abs() < 1e-12);
+            assert_eq!(sackin_moments(3, model).variance, 0.0);
+        }
+
+        // Children of multiple mergers are compared pairwise
+        let genealogy = Genealogy::from_mergers(5, &[(1.0, vec![0, 1]), (2.0, vec![0, 2, 3, 4])]).unwrap();
+        assert_eq!(genealogy.sackin(), 7);
+        assert_eq!(genealogy.colless(), 3);
+    }
+
+    #[test]
+    fn closed_forms() {
+        for &n in &[5, 10, 50] {
+            let nf = n as f64;
+            let h1: f64 = (1..=n).map(|i| 1.0 / i as f64).sum();
+            let h2: f64 = (1..=n).map(|i| 1.0 / (i * i) as f64).sum();
+
+            // Kirkpatrick and Slatkin (1993)
+            let yule = sackin_moments(n, ShapeModel::Yule);
+            assert!((yule.mean - 2.0 * nf * (h1 - 1.0)).abs() < 1e-9 * yule.mean);
+            let variance = 7.0 * nf * nf - 4.0 * nf * nf * h2 - 2.0 * nf * h1 - nf;
+            assert!((yule.variance - variance).abs() < 1e-7 * variance);
+
+            // Mir, Rosselló and Rotger (2013): n ((2n - 2)!! / (2n - 3)!! - 1)
+            let ratio: f64 = (1..n).map(|j| (2 * j) as f64 / (2 * j - 1) as f64).product();
+            let uniform = sackin_moments(n, ShapeModel::Uniform);
+            assert!((uniform.mean - nf * (ratio - 1.0)).abs() < 1e-9 * uniform.mean);
+        }
+    }
+
+    #[test]
+    fn coalescent_agrees_with_yule() {
+        let (n, samples) = (12, 4000);
+        let coalescent = crate::Coalescent::sampler(n);
+        let mut rng = rand::thread_rng();
+        let (mut sackin, mut colless) = (0.0, 0.0);
+        for _ in 0..samples {
+            let genealogy = coalescent.sample_genealogy(&mut rng);
+            sackin += genealogy.sackin_z_score(ShapeModel::Yule) / samples as f64;
+            colless += genealogy.colless_z_score(ShapeModel::Yule) / samples as f64;
+        }
+        assert!(sackin.abs() < 0.06, "{}", sackin);
+        assert!(colless.abs() < 0.06, "{}", colless);
+    }
+
+    #[test]
+    fn tree_models() {
+        // Same shapes, but node times of the Yule tree are independent exponentials, whose
+        // maximum has mean 1 + 1/2 + ... + 1/(n - 1), against 2 (1 - 1/n) for the coalescent
+        let (n, samples) = (10, 4000);
+        let mut rng = rand::thread_rng();
+        for &(model, depth) in &[(TreeModel::Kingman, 1.8), (TreeModel::Yule { birth_rate: 2.0 }, 2.829 / 2.0)] {
+            let (mut sackin, mut mean_depth) = (0.0, 0.0);
+            for _ in 0..samples {
+                let genealogy = model.sample_genealogy(n, &mut rng);
+                sackin += genealogy.sackin_z_score(model.shape_model()) / samples as f64;
+                mean_depth += genealogy.depth() / samples as f64;
+            }
+            assert!(sackin.abs() < 0.06, "{:?} {}", model, sackin);
+            assert!((mean_depth - depth).abs() < 0.05, "{:?} {}", model, mean_depth);
+        }
+        assert_eq!(TreeModel::Yule { birth_rate: 1.0 }.sample_genealogy(1, &mut rng).depth(), 0.0);
+    }
+}
@@ -0,0 +1,111 @@
+//! Balancing selection.
+//!
+//! Balancing selection keeps two alleles at a selected site at an equilibrium
+//! frequency, p for the first allele and 1 - p for the second one. Carriers of
+//! each allele form a class, and the genealogy at the site follows a structured
+//! coalescent with those classes as demes (Hudson and Kaplan, 1988): pairs of lineages
+//! coalesce at rate 1 / p among carriers of the first allele and 1 / (1 - p) among
+//! carriers of the second one, and lineages only change class by mutation.
+//! With mutation at rate θ/2 per lineage towards each allele, a lineage carrying one
+//! allele moves backwards to the other class at rate θ/2 times the ratio of their frequencies.
+//! When mutation is rare, carriers of different alleles have very deep common ancestors.
+//!
+
+// Types
+use crate::{Genealogy, StructuredCoalescent};
+
+// Traits
+use rand::Rng;
+
+/// Two alleles kept at an equilibrium frequency by balancing selection.
+///
+/// # Examples
+///
+/// Carriers of different alleles diverge much more than carriers of the same allele.
+/// ```
+/// use coalescence::BalancingSelection;
+///
+/// let balancing = BalancingSelection::new(0.5, 0.01);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = balancing.sample_genealogy(5, 5, &mut rng);
+///
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// assert_eq!(genealogy.group_size(), 10);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BalancingSelection {
+    frequency: f64,
+    mutation_rate: f64,
+}
+
+impl BalancingSelection {
+    /// Creates a new model where the first allele has equilibrium frequency ``frequency``,
+    /// and mutation between alleles happens at scaled rate θ = 4 N μ.
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is not strictly between zero and one, or if the mutation rate
+    /// is not positive and finite.
+    pub fn new(frequency: f64, mutation_rate: f64) -> Self {
+        assert!(frequency > 0.0 && frequency < 1.0, "The frequency must be strictly between zero and one.");
+        assert!(
+            mutation_rate > 0.0 && mutation_rate.is_finite(),
+            "The mutation rate must be positive and finite."
+        );
+
+        BalancingSelection { frequency, mutation_rate }
+    }
+
+    /// Equilibrium frequency of the first allele.
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Scaled mutation rate θ between alleles.
+    pub fn mutation_rate(&self) -> f64 {
+        self.mutation_rate
+    }
+
+    /// Structured coalescent of the carriers of each allele, where the first deme
+    /// is the class of the first allele.
+    pub fn structured_coalescent(&self) -> StructuredCoalescent {
+        let (first, second) = (self.frequency, 1.0 - self.frequency);
+        let rate = self.mutation_rate / 2.0;
+        let migration = vec![vec![0.0, rate * second / first], vec![rate * first / second, 0.0]];
+
+        StructuredCoalescent::new(vec![first, second], migration)
+    }
+
+    /// Sample the genealogy at the selected site of a group with ``first`` carriers
+    /// of the first allele, numbered first, and ``second`` carriers of the second allele.
+    pub fn sample_genealogy<R>(&self, first: usize, second: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        self.structured_coalescent().sample_genealogy(&[first, second], rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_divergence() {
+        let balancing = BalancingSelection::new(0.5, 0.01);
+        let samples = 1000;
+
+        let mut rng = rand::thread_rng();
+        let mut fraction_deeper = |first: usize, second: usize, threshold: f64| {
+            (0..samples)
+                .filter(|_| balancing.sample_genealogy(first, second, &mut rng).depth() > threshold)
+                .count() as f64 / samples as f64
+        };
+
+        // Carriers of the same allele coalesce at rate 1 / p, unless one of them mutates first,
+        // while carriers of different alleles wait for a mutation, at rate θ/2 each
+        assert!(fraction_deeper(2, 0, 3.0) < 0.1);
+        assert!(fraction_deeper(1, 1, 10.0) > 0.8);
+    }
+}
@@ -11,17 +11,16 @@
 //! 
 
 // Types
-use partitions::PartitionVec;
-use rand_distr::Exp;
-use crate::Genealogy;
+use rand_distr::Exp1;
+use crate::{Demography, Events, Forest, Genealogy, LeanGenealogy, Partition};
+use std::ops::ControlFlow;
+use std::time::Instant;
+use rand_pcg::Pcg32;
 
 // Traits
 use markovian::traits::CMarkovChainTrait;
 use rand::distributions::Distribution;
-use rand::seq::IteratorRandom;
-use rand::Rng;
-use core::fmt::Debug;
-use std::iter::FromIterator;
+use rand::{Rng, SeedableRng};
 
 
 /// n-Coalescent process in the space of partitions of the set {1, 2, ..., n}.
@@ -31,24 +30,37 @@ use std::iter::FromIterator;
 ///
 /// A Coalescent can be seen as:
 /// - State-iterator: an iterator with a current state, changing randomly to another
-/// state when ``next`` method is called. See
-/// [Iterator](https://doc.rust-lang.org/nightly/core/iter/trait.Iterator.html)
-/// and [MarkovChainTrait](file:///C:/Users/rasau/projects/markovian/target/doc/markovian/discrete_time/struct.MarkovChain.html)
-/// implementation.
+///   state when ``next`` method is called. See
+///   [Iterator](https://doc.rust-lang.org/nightly/core/iter/trait.Iterator.html)
+///   and [MarkovChainTrait](file:///C:/Users/rasau/projects/markovian/target/doc/markovian/discrete_time/struct.MarkovChain.html)
+///   implementation.
 /// - Random genealogy generator: random variable over possible genealogies from the
-/// current state. See method [sample_genealogy](file:///C:/Users/rasau/projects/coalescence/target/doc/coalescence/coalescent/struct.Coalescent.html#method.sample_genealogy).
+///   current state. See method [sample_genealogy](file:///C:/Users/rasau/projects/coalescence/target/doc/coalescence/coalescent/struct.Coalescent.html#method.sample_genealogy).
+///
+/// # Random number generators
+///
+/// The internal random number generator, R, is only used by the state-iterator methods:
+/// ``next``, ``next_step``, ``peek_next_step`` and ``events``. All sampling methods,
+/// like ``sample_genealogy``, draw from the generator they are given instead and advance it,
+/// without cloning it: with the same seed, the same calls give the same results, and
+/// consecutive calls give independent results. Any generator works, including
+/// ``&mut dyn RngCore``. When the internal generator is not needed, see ``sampler``.
 #[derive(Debug, Clone)]
 pub struct Coalescent<R>
 where
-    R: Rng + Clone + core::fmt::Debug,
+    R: Rng,
 {
-    state: PartitionVec<()>, // No selection
+    state: Partition, // No selection
     rng: R,
+    demography: Demography,
+    time: f64,
+    sampling_times: Vec<f64>, // one per individual
+    beta: f64, // inverse temperature, scaling all rates
 }
 
 impl<R> Coalescent<R>
 where
-    R: Rng + Clone + core::fmt::Debug,
+    R: Rng,
 {
     /// Creates a new Coalescent. 
     /// 
@@ -62,20 +74,143 @@ where
     /// let mut coalescent = coalescence::Coalescent::new(group_size, rng);
     /// ``` 
     pub fn new(group_size: usize, rng: R) -> Self {
-        let state: PartitionVec<()> =
-            PartitionVec::from_iter((0..group_size).map(|_| ()));
+        let state: Partition =
+            Partition::singletons(group_size);
+
+        let demography = Demography::constant();
+        let time = 0.0;
+        let sampling_times = vec![0.0; group_size];
+        let beta = 1.0;
 
-        Coalescent { state, rng }
+        Coalescent { state, rng, demography, time, sampling_times, beta }
     }
 
-    /// Mutable reference to the internal random number generator. 
+    /// Tempered coalescent, where the density of genealogies is raised to the power β, 
+    /// for methods like parallel tempering. With a constant population size, this 
+    /// amounts to scaling all rates of coalescence by β, while the topology keeps 
+    /// its distribution: β = 1 is the standard coalescent, smaller values give 
+    /// flatter distributions of longer genealogies. 
     /// 
-    /// # Remarks
+    /// With a demography, all rates are scaled by β as well. 
+    /// 
+    /// # Panics
+    /// 
+    /// If β is not positive and finite. 
     /// 
-    /// This method is for debugging mainly. 
+    /// # Examples
+    /// 
+    /// ```
+    /// let rng = rand::thread_rng();
+    /// let coalescent = coalescence::Coalescent::new(10, rng).with_temperature(0.5);
     /// 
-    pub(crate) fn rng(&mut self) -> &mut R {
-        &mut self.rng
+    /// assert_eq!(coalescent.beta(), 0.5);
+    /// ```
+    pub fn with_temperature(mut self, beta: f64) -> Self {
+        assert!(beta > 0.0 && beta.is_finite(), "The inverse temperature must be positive and finite.");
+
+        self.beta = beta;
+        self
+    }
+
+    /// Inverse temperature β, see ``with_temperature``. 
+    pub fn beta(&self) -> f64 {
+        self.beta
+    }
+
+    /// Add ``count`` individuals sampled at ``time`` in the past, e.g. ancient 
+    /// samples or samples of a past generation. They are numbered after the 
+    /// individuals already in the group, and their lineages only take part in 
+    /// the process once it reaches that time. 
+    /// 
+    /// # Panics
+    /// 
+    /// If ``time`` is negative or not finite. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let rng = rand::thread_rng();
+    /// let mut coalescent = coalescence::Coalescent::new(5, rng);
+    /// coalescent.schedule_sample(2.0, 3);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let genealogy = coalescent.sample_genealogy(&mut rng);
+    /// 
+    /// assert_eq!(genealogy.group_size(), 8);
+    /// assert_eq!(genealogy.sampling_times()[7], 2.0);
+    /// assert!(genealogy.depth() > 2.0);
+    /// assert!(!genealogy.is_ultrametric());
+    /// ``` 
+    pub fn schedule_sample(&mut self, time: f64, count: usize) -> &mut Self {
+        assert!(time >= 0.0 && time.is_finite(), "Sampling times must be non-negative and finite.");
+
+        for _ in 0..count {
+            self.state.push();
+            self.sampling_times.push(time);
+        }
+        self
+    }
+
+    /// Time in the past at which each individual is sampled. 
+    pub fn sampling_times(&self) -> &[f64] {
+        &self.sampling_times
+    }
+
+    /// Change the demographic history of the population,
+    /// which is of constant size by default. 
+    /// 
+    /// # Examples
+    /// 
+    /// A growing population has shallower genealogies. 
+    /// ```
+    /// use coalescence::Demography;
+    /// let group_size = 100;
+    /// let rng = rand::thread_rng();
+    /// let mut coalescent = coalescence::Coalescent::new(group_size, rng);
+    /// coalescent.set_demography(Demography::exponential(100.0));
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let genealogy = coalescent.sample_genealogy(&mut rng);
+    /// 
+    /// assert!(genealogy.depth() < 1.0);
+    /// ``` 
+    pub fn set_demography(&mut self, demography: Demography) -> &mut Self {
+        self.demography = demography;
+        self
+    }
+
+    /// Demographic history of the population. 
+    pub fn demography(&self) -> &Demography {
+        &self.demography
+    }
+
+    /// Time elapsed since the start of the process. 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Number of lineages at the current time, i.e. sets of the current state with 
+    /// some individual sampled by then, see ``schedule_sample``. Without scheduled 
+    /// samples, it is the number of sets of the state. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let mut coalescent = coalescence::Coalescent::new(3, rand::thread_rng());
+    /// coalescent.schedule_sample(100.0, 1);
+    /// 
+    /// assert_eq!(coalescent.active_lineages(), 3);
+    /// coalescent.next();
+    /// assert_eq!(coalescent.active_lineages(), 2);
+    /// ``` 
+    pub fn active_lineages(&self) -> usize {
+        let parameters = Parameters { 
+            demography: &self.demography, 
+            sampling_times: &self.sampling_times, 
+            beta: self.beta,
+        };
+
+        parameters.active_lineages(&self.state, self.time).len()
     }
 
     /// Change the internal random number generator for another. 
@@ -146,36 +281,69 @@ where
     /// assert_eq!(path.len(), group_size);
     /// ```
     ///
-    pub fn sample_path<S>(&self, rng: &mut S) -> Vec<(f64, PartitionVec<()>)> 
+    pub fn sample_path<S>(&self, rng: &mut S) -> Vec<(f64, Partition)> 
     where
-        S: Rng + Clone + Debug,
+        S: Rng + ?Sized,
     {
-        // Initialize a Coalescent
+        // Initialize
 
-        let initial_group_size: usize = self.state().len();
-        let other_rng = rng.clone();
-        let mut coalescent_process = Coalescent::new(initial_group_size, other_rng);
+        let mut state: Partition =
+            Partition::singletons(self.state().len());
+        let mut time = 0.0;
 
         // Generate a realizations
 
-        let mut realizations = vec![(0.0, coalescent_process.state().clone())];
-        loop {
-            let (time_step, state) = match coalescent_process.next() {
-                Some((t, s)) => (t, s),
-                None => break,
-            };
-            realizations.push((time_step, state));
+        let mut realizations = vec![(0.0, state.clone())];
+        while let Some((time_step, value_indexes)) = self.draw_step(&state, time, rng) {
+            state.union(value_indexes[0], value_indexes[1]);
+            time += time_step;
+            realizations.push((time_step, state.clone()));
         }
 
-        // Update rng
-
-        *rng = coalescent_process.rng().clone();
-
         // Finish
 
         realizations
     }
 
+    /// Lazily sample a path, like ``sample_path`` but without storing it: 
+    /// the first item is ``(0.0, snapshot)`` of the initial state and 
+    /// the others are the time steps and snapshots after each event. 
+    /// 
+    /// Only one partition is kept, so long paths of large groups can be consumed 
+    /// step by step; the whole partition is available between steps with ``state`` on ``PathIter``. 
+    ///
+    /// # Remarks
+    ///
+    /// No internal state changes, including the internal
+    /// random number generator. This is why this methods requires a rng.  
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let group_size = 100;
+    /// let coalescent = coalescence::Coalescent::sampler(group_size);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let lineages: Vec<usize> = coalescent
+    ///     .path_iter(&mut rng)
+    ///     .map(|(_time_step, snapshot)| snapshot.amount_of_sets)
+    ///     .collect();
+    ///
+    /// assert_eq!(lineages, (1..=group_size).rev().collect::<Vec<usize>>());
+    /// ```
+    pub fn path_iter<'a, S>(&'a self, rng: &'a mut S) -> PathIter<'a, R, S>
+    where
+        S: Rng + ?Sized,
+    {
+        PathIter { 
+            coalescent: self, 
+            rng, 
+            state: Partition::singletons(self.state().len()), 
+            time: 0.0, 
+            started: false,
+        }
+    }
+
     /// Peeks a possible next pair of indices to be joint, chosen 
     /// according to the stochastic process. This does not change the 
     /// state of the ``Coalescent``. 
@@ -196,40 +364,24 @@ where
     /// assert!(!current_state.same_set(index_pair[0], index_pair[1]));
     /// ``` 
     pub fn peek_next_step(&mut self) -> Option<(f64, [usize; 2])> {
-        let current_partition_size = self.state.amount_of_sets();
-
-        if current_partition_size == 1 {
-            None
-        } else {
-            // Simulate time step
+        let Coalescent { state, rng, demography, time, sampling_times, beta } = self;
+        let parameters = Parameters { demography, sampling_times, beta: *beta };
 
-            let rate = (current_partition_size * (current_partition_size - 1) / 2) as f64;
-            let exp = Exp::new(rate).unwrap();
-            let time_step = exp.sample(&mut self.rng());
-
-            // Choose between possible transitions
-
-            let mut set_indexes = [0; 2];
-            (0..current_partition_size).choose_multiple_fill(&mut self.rng(), &mut set_indexes);
-
-            // Get values from these sets
-            let value_indexes: Vec<usize> = (0..2)
-                .map(|i| {
-                    self.state
-                        .all_sets()
-                        .nth(set_indexes[i])
-                        .unwrap() // set
-                        .nth(0)
-                        .unwrap() // (value_index, value)
-                        .0
-                })
-                .collect();
-            let value_indexes = [value_indexes[0], value_indexes[1]];
+        parameters.draw_step(state, *time, rng)
+    }
 
-            // Return
+    /// Draws the next step from a state at a given time, see ``peek_next_step``. 
+    fn draw_step<S>(&self, state: &Partition, time: f64, rng: &mut S) -> Option<(f64, [usize; 2])>
+    where
+        S: Rng + ?Sized,
+    {
+        let parameters = Parameters { 
+            demography: &self.demography, 
+            sampling_times: &self.sampling_times, 
+            beta: self.beta,
+        };
 
-            Some((time_step, value_indexes))
-        }
+        parameters.draw_step(state, time, rng)
     }
 
     /// Changes to a next state of the ``Coalescent``, chosen 
@@ -253,12 +405,146 @@ where
         match self.peek_next_step() {
             Some((time_step, value_indexes)) => {
                 self.state.union(value_indexes[0], value_indexes[1]);
+                self.time += time_step;
                 Some((time_step, value_indexes))
             },
             None => None,
         }
     }
 
+    /// Stream of events of the process, advancing it. 
+    /// 
+    /// See ``EventStream`` for adapters over events. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let group_size = 10;
+    /// let rng = rand::thread_rng();
+    /// let mut coalescent = coalescence::Coalescent::new(group_size, rng);
+    ///
+    /// let lineages: Vec<usize> = coalescent.events().map(|event| event.lineages).collect();
+    /// 
+    /// assert_eq!(lineages, (1..group_size).rev().collect::<Vec<usize>>());
+    /// ``` 
+    pub fn events(&mut self) -> Events<'_, R> {
+        Events::new(self)
+    }
+
+    /// Sample all the (n - 1) waiting times between events of a genealogy 
+    /// in one batch, where n is the size of the group. 
+    /// 
+    /// The standard exponential variables are generated first, in a single pass, 
+    /// and then scaled by the rates of coalescence and the demography.
+    /// This is how ``sample_genealogy`` simulates times. 
+    ///
+    /// # Panics
+    ///
+    /// If there are scheduled samples, see ``schedule_sample``, 
+    /// since times then depend on the whole history. 
+    ///
+    /// # Remarks
+    ///
+    /// No internal state changes, including the internal
+    /// random number generator. This is why this methods requires a rng.  
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let group_size = 100;
+    /// let rng = rand::thread_rng();
+    /// let coalescent = coalescence::Coalescent::new(group_size, rng);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let waiting_times = coalescent.sample_waiting_times(&mut rng);
+    ///
+    /// assert_eq!(waiting_times.len(), group_size - 1);
+    /// assert!(waiting_times.iter().all(|&time_step| time_step > 0.0));
+    /// ```
+    pub fn sample_waiting_times<S>(&self, rng: &mut S) -> Vec<f64>
+    where
+        S: Rng + ?Sized,
+    {
+        assert!(!self.has_scheduled_samples(), "Waiting times depend on the history of scheduled samples.");
+
+        let group_size = self.state().len();
+        let mut waiting_times = vec![0.0; group_size.saturating_sub(1)];
+        self.fill_waiting_times(&mut waiting_times, rng);
+
+        waiting_times
+    }
+
+    /// Sample the waiting times of many genealogies in one batch, 
+    /// see ``sample_waiting_times``. 
+    /// 
+    /// Returns the waiting times of all replicates one after the other, 
+    /// each replicate being a chunk of length (n - 1). 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let group_size = 10;
+    /// let rng = rand::thread_rng();
+    /// let coalescent = coalescence::Coalescent::new(group_size, rng);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let batch = coalescent.sample_waiting_times_batch(1000, &mut rng);
+    ///
+    /// assert_eq!(batch.len(), 1000 * (group_size - 1));
+    /// let depths: Vec<f64> = batch.chunks(group_size - 1).map(|chunk| chunk.iter().sum()).collect();
+    /// assert_eq!(depths.len(), 1000);
+    /// ```
+    pub fn sample_waiting_times_batch<S>(&self, replicates: usize, rng: &mut S) -> Vec<f64>
+    where
+        S: Rng + ?Sized,
+    {
+        assert!(!self.has_scheduled_samples(), "Waiting times depend on the history of scheduled samples.");
+
+        let events = self.state().len().saturating_sub(1);
+        let mut waiting_times = vec![0.0; replicates * events];
+        if events > 0 {
+            for chunk in waiting_times.chunks_mut(events) {
+                self.fill_waiting_times(chunk, rng);
+            }
+        }
+
+        waiting_times
+    }
+
+    fn has_scheduled_samples(&self) -> bool {
+        self.sampling_times.iter().any(|&sampling_time| sampling_time > 0.0)
+    }
+
+    fn fill_waiting_times<S>(&self, waiting_times: &mut [f64], rng: &mut S)
+    where
+        S: Rng + ?Sized,
+    {
+        let group_size = waiting_times.len() + 1;
+
+        // Standard exponential variables
+
+        for intensity in waiting_times.iter_mut() {
+            *intensity = Exp1.sample(rng);
+        }
+
+        // Scale by the rate of each event, with k = n, n - 1, ..., 2 sets
+
+        for (event, intensity) in waiting_times.iter_mut().enumerate() {
+            let sets = (group_size - event) as f64;
+            *intensity *= 2.0 / (sets * (sets - 1.0) * self.beta);
+        }
+
+        // Apply the demography, which depends on the elapsed time
+
+        if !self.demography.is_constant() {
+            let mut time = 0.0;
+            for time_step in waiting_times.iter_mut() {
+                *time_step = self.demography.waiting_time(time, *time_step);
+                time += *time_step;
+            }
+        }
+    }
+
     /// Sample a genealogy: from the current state until there is only one set
     /// in the partition. Returns a ``Genealogy`` where postprocess is possible. 
     ///
@@ -281,57 +567,276 @@ where
     ///
     pub fn sample_genealogy<S>(&self, rng: &mut S) -> Genealogy
     where
-        S: Rng + Clone + Debug,
+        S: Rng + ?Sized,
+    {
+        match self.sample_genealogy_until(rng, |_, _| ControlFlow::Continue(())) {
+            ControlFlow::Continue(genealogy) => genealogy,
+            ControlFlow::Break(_) => unreachable!("Sampling is never aborted."),
+        }
+    }
+
+    /// Sample a genealogy as ``sample_genealogy``, calling ``f`` after each event with
+    /// the time of the event and the partition after it. If ``f`` breaks, sampling stops
+    /// there and the forest of subtrees up to that time is returned instead, see ``truncate_at``
+    /// on Genealogy, which saves time in rejection sampling.
+    ///
+    /// # Examples
+    ///
+    /// Give up on genealogies deeper than one.
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// let coalescent = coalescence::Coalescent::sampler(10);
+    /// let mut rng = rand::thread_rng();
+    /// let sampled = coalescent.sample_genealogy_until(&mut rng, |time, _| match time > 1.0 {
+    ///     true => ControlFlow::Break(()),
+    ///     false => ControlFlow::Continue(()),
+    /// });
+    ///
+    /// match sampled {
+    ///     ControlFlow::Continue(genealogy) => assert!(genealogy.depth() <= 1.0),
+    ///     ControlFlow::Break(forest) => assert!(forest.horizon() > 1.0 && forest.len() >= 1),
+    /// }
+    /// ```
+    pub fn sample_genealogy_until<S, F>(&self, rng: &mut S, mut f: F) -> ControlFlow<Forest, Genealogy>
+    where
+        S: Rng + ?Sized,
+        F: FnMut(f64, &Partition) -> ControlFlow<()>,
     {
-       // Initialize a Coalescent
+        // Initialize
 
         let group_size: usize = self.state().len();
-        let other_rng = rng.clone();
-        let mut coalescent_process = Coalescent::new(group_size, other_rng);
 
         // Generate a transitions
 
-        let mut path: Vec<PartitionVec<()>> = Vec::with_capacity(group_size - 1);
         let mut steps: Vec<[usize; 2]> = Vec::with_capacity(group_size - 1);
         let mut time_steps: Vec<f64> = Vec::with_capacity(group_size - 1);
 
-        let mut state: PartitionVec<()> =
-            PartitionVec::from_iter((0..group_size).map(|_| ()));
+        let mut state: Partition =
+            Partition::singletons(group_size);
 
-        path.push(state.clone());
-        loop {
-            let (time_step, value_indexes) = match coalescent_process.next_step() {
-                Some(step) => step,
-                None => break,
-            };
-            state.union(value_indexes[0], value_indexes[1]);
+        let mut time = 0.0;
+        let mut aborted = false;
+        match self.has_scheduled_samples() {
+            false => {
+                let waiting_times = self.sample_waiting_times(rng);
+                for time_step in waiting_times {
+                    let value_indexes = choose_pair(&state, rng);
+                    state.union(value_indexes[0], value_indexes[1]);
+                    time += time_step;
 
-            path.push(state.clone());
-            steps.push(value_indexes);
-            time_steps.push(time_step);
-        }
+                    steps.push(value_indexes);
+                    time_steps.push(time_step);
+                    if f(time, &state).is_break() {
+                        aborted = true;
+                        break;
+                    }
+                }
+            },
+            true => {
+                // Pairs depend on the samples present at each time
 
-        // Update rng
+                while let Some((time_step, value_indexes)) = self.draw_step(&state, time, rng) {
+                    state.union(value_indexes[0], value_indexes[1]);
+                    time += time_step;
 
-        *rng = coalescent_process.rng().clone();
+                    steps.push(value_indexes);
+                    time_steps.push(time_step);
+                    if f(time, &state).is_break() {
+                        aborted = true;
+                        break;
+                    }
+                }
+            },
+        }
 
         // Finish
 
-        Genealogy::new(path, steps, time_steps)
+        let genealogy = Genealogy::from_steps(group_size, steps, time_steps).with_sampling_times(self.sampling_times.clone());
+        match aborted {
+            true => ControlFlow::Break(genealogy.truncate_at(time)),
+            false => ControlFlow::Continue(genealogy),
+        }
+    }
+
+    /// Sample a genealogy as ``sample_genealogy``, stopping as soon as an event exceeds
+    /// the budget, so that interactive applications get an answer even for parameters
+    /// whose genealogies are very large or very slow to sample. The budget is checked
+    /// after each event, and a budget of no events stops before the first one.
+    ///
+    /// # Errors
+    ///
+    /// If the budget is exceeded, with the forest of subtrees up to the last event and
+    /// the limit that was reached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Budget, Limit};
+    ///
+    /// let coalescent = coalescence::Coalescent::sampler(10);
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// let budget = Budget { max_events: Some(4), max_seconds: Some(1.0) };
+    /// let exceeded = coalescent.sample_genealogy_bounded(&mut rng, budget).unwrap_err();
+    /// assert_eq!(exceeded.limit, Limit::Events(4));
+    /// assert_eq!(exceeded.forest.len(), 6);
+    ///
+    /// let genealogy = coalescent.sample_genealogy_bounded(&mut rng, Budget::default()).unwrap();
+    /// assert_eq!(genealogy.group_size(), 10);
+    /// ```
+    pub fn sample_genealogy_bounded<S>(&self, rng: &mut S, budget: Budget) -> Result<Genealogy, BudgetExceeded>
+    where
+        S: Rng + ?Sized,
+    {
+        let group_size = self.state().len();
+        if budget.max_events == Some(0) && group_size > 1 {
+            let forest = Genealogy::from_steps(group_size, Vec::new(), Vec::new())
+                .with_sampling_times(self.sampling_times.clone())
+                .truncate_at(0.0);
+            return Err(BudgetExceeded { forest, limit: Limit::Events(0) });
+        }
+
+        let start = Instant::now();
+        let mut events = 0;
+        let mut limit = None;
+        let sampled = self.sample_genealogy_until(rng, |_, partition| {
+            events += 1;
+            if partition.amount_of_sets() > 1 {
+                limit = match budget.max_seconds {
+                    Some(seconds) if start.elapsed().as_secs_f64() > seconds => Some(Limit::Seconds(seconds)),
+                    _ => budget.max_events.filter(|&max_events| events >= max_events).map(Limit::Events),
+                };
+            }
+            match limit {
+                Some(_) => ControlFlow::Break(()),
+                None => ControlFlow::Continue(()),
+            }
+        });
+
+        match sampled {
+            ControlFlow::Continue(genealogy) => Ok(genealogy),
+            ControlFlow::Break(forest) => Err(BudgetExceeded { forest, limit: limit.unwrap() }),
+        }
+    }
+
+    /// Sample a genealogy storing only its events, see ``LeanGenealogy``,
+    /// which takes memory and time linear in the size of the group.
+    ///
+    /// Lineages are kept by an individual of each one, so that choosing the pair
+    /// of each event takes constant time.
+    ///
+    /// # Remarks
+    ///
+    /// No internal state changes, including the internal
+    /// random number generator. This is why this methods requires a rng.
+    ///
+    /// # Panics
+    ///
+    /// If some samples are scheduled in the past, see ``schedule_sample``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let group_size = 1_000_000;
+    /// let coalescent = coalescence::Coalescent::new(group_size, rand::thread_rng());
+    ///
+    /// let genealogy = coalescent.sample_lean_genealogy(&mut rand::thread_rng());
+    ///
+    /// assert_eq!(genealogy.steps().len(), group_size - 1);
+    /// ```
+    pub fn sample_lean_genealogy<S>(&self, rng: &mut S) -> LeanGenealogy
+    where
+        S: Rng + ?Sized,
+    {
+        let group_size = self.state().len();
+        let time_steps = self.sample_waiting_times(rng);
+
+        let mut lineages: Vec<usize> = (0..group_size).collect();
+        let mut steps: Vec<[usize; 2]> = Vec::with_capacity(time_steps.len());
+        for _ in 0..time_steps.len() {
+            let pair = rand::seq::index::sample(rng, lineages.len(), 2);
+            let (first, second) = (pair.index(0).min(pair.index(1)), pair.index(0).max(pair.index(1)));
+            steps.push([lineages[first], lineages[second]]);
+            lineages.swap_remove(second);
+        }
+
+        LeanGenealogy::new(group_size, steps, time_steps)
+    }
+
+    /// Sample a genealogy conditional on having exactly ``k`` ancestral lineages at time ``t``, 
+    /// by rejection: genealogies are sampled until one has ``k`` lineages at time ``t``. 
+    /// For expected values under a constant population size, see 
+    /// ``conditional_expected_length`` and ``conditional_expected_depth`` in ``theory``. 
+    /// 
+    /// # Remarks
+    /// 
+    /// The expected number of trials is the inverse of the probability of ``k`` lineages 
+    /// at time ``t``, see ``theory::lineages_probability``, which can be large. 
+    /// 
+    /// # Panics
+    /// 
+    /// If ``k`` is zero or larger than the group, if ``t`` is negative, 
+    /// or if some samples are scheduled in the past, see ``schedule_sample``. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let coalescent = coalescence::Coalescent::sampler(10);
+    /// 
+    /// let mut rng = rand::thread_rng();
+    /// let genealogy = coalescent.sample_genealogy_given_lineages(3, 0.5, &mut rng);
+    /// 
+    /// assert_eq!(genealogy.partition_at(0.5).amount_of_sets(), 3);
+    /// ```
+    pub fn sample_genealogy_given_lineages<S>(&self, k: usize, t: f64, rng: &mut S) -> Genealogy
+    where
+        S: Rng + ?Sized,
+    {
+        assert!(0 < k && k <= self.state().len(), "The number of lineages must be between one and the group size.");
+        assert!(t >= 0.0, "Time must be non-negative.");
+        assert!(!self.has_scheduled_samples(), "Lineages at a time depend on the history of scheduled samples.");
+
+        loop {
+            let genealogy = self.sample_genealogy(rng);
+            if genealogy.partition_at(t).amount_of_sets() == k {
+                return genealogy;
+            }
+        }
+    }
+}
+
+impl Coalescent<Pcg32> {
+    /// Creates a Coalescent to be used only through sampling methods, like ``sample_genealogy``,
+    /// which take a random number generator. Its internal generator has a fixed seed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rand::SeedableRng;
+    ///
+    /// let coalescent = coalescence::Coalescent::sampler(10);
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    ///
+    /// let genealogy = coalescent.sample_genealogy(&mut rng);
+    /// assert_eq!(genealogy.group_size(), 10);
+    /// ```
+    pub fn sampler(group_size: usize) -> Self {
+        Coalescent::new(group_size, Pcg32::seed_from_u64(0))
     }
 }
 
-impl<R> CMarkovChainTrait<PartitionVec<()>> for Coalescent<R>
+impl<R> CMarkovChainTrait<Partition> for Coalescent<R>
 where
-    R: Rng + Clone + core::fmt::Debug,
+    R: Rng,
 {
     /// Current state of the process. 
-    fn state(&self) -> &PartitionVec<()> {
+    fn state(&self) -> &Partition {
         &self.state
     }
 
     /// Change the current state of the process.
-    fn set_state(&mut self, state: PartitionVec<()>) -> &mut Self {
+    fn set_state(&mut self, state: Partition) -> &mut Self {
         self.state = state;
         self
     }
@@ -339,9 +844,9 @@ where
 
 impl<R> Iterator for Coalescent<R>
 where
-    R: Rng + Clone + core::fmt::Debug,
+    R: Rng,
 {
-    type Item = (f64, PartitionVec<()>);
+    type Item = (f64, Partition);
 
 
     /// Changes the state of the ``Coalescent`` to a new state, chosen 
@@ -362,9 +867,391 @@ where
         match self.peek_next_step() {
             Some((time_step, value_indexes)) => {
                 self.state.union(value_indexes[0], value_indexes[1]);
+                self.time += time_step;
                 Some((time_step, self.state.clone()))
             },
             None => None,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Limits on sampling a genealogy, see ``sample_genealogy_bounded`` on Coalescent.
+/// There are no limits by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    /// Largest number of events.
+    pub max_events: Option<usize>,
+    /// Longest wall-clock time, in seconds.
+    pub max_seconds: Option<f64>,
+}
+
+/// Limit of a ``Budget`` that was reached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Events(usize),
+    Seconds(f64),
+}
+
+/// Error when sampling exceeds its budget, with the partial result.
+#[derive(Debug, Clone)]
+pub struct BudgetExceeded {
+    /// Subtrees up to the last event, see ``truncate_at`` on Genealogy.
+    pub forest: Forest,
+    pub limit: Limit,
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.limit {
+            Limit::Events(events) => write!(f, "sampling stopped after {} events, with {} lineages left", events, self.forest.len()),
+            Limit::Seconds(seconds) => write!(f, "sampling stopped after {} seconds, with {} lineages left", seconds, self.forest.len()),
+        }
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Summary of the partition at some step of a path, see ``path_iter`` on Coalescent. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionSnapshot {
+    /// Indexes of an individual of each set that was joined, or ``None`` for the initial state.
+    pub joined: Option<[usize; 2]>,
+    /// Size of the set that was joined, or one for the initial state.
+    pub len_of_joined: usize,
+    /// Number of sets of the partition.
+    pub amount_of_sets: usize,
+}
+
+/// Iterator over a sampled path of a ``Coalescent``, storing only the current partition. 
+///
+/// This struct is created by the ``path_iter`` method on Coalescent<R>.
+/// See its documentation for more.
+/// 
+/// # Examples
+/// 
+/// Inspecting the whole partition between steps. 
+/// ```
+/// let coalescent = coalescence::Coalescent::sampler(10);
+/// let mut rng = rand::thread_rng();
+/// let mut path = coalescent.path_iter(&mut rng);
+/// 
+/// while let Some((_time_step, snapshot)) = path.next() {
+///     if let Some([first, second]) = snapshot.joined {
+///         assert!(path.state().same_set(first, second));
+///     }
+/// }
+/// assert_eq!(path.state().amount_of_sets(), 1);
+/// ```
+#[derive(Debug)]
+pub struct PathIter<'a, R, S>
+where
+    R: Rng,
+    S: Rng + ?Sized,
+{
+    coalescent: &'a Coalescent<R>,
+    rng: &'a mut S,
+    state: Partition,
+    time: f64,
+    started: bool,
+}
+
+impl<'a, R, S> PathIter<'a, R, S>
+where
+    R: Rng,
+    S: Rng + ?Sized,
+{
+    /// Current partition of the path. 
+    pub fn state(&self) -> &Partition {
+        &self.state
+    }
+
+    /// Time elapsed since the start of the path. 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+impl<'a, R, S> Iterator for PathIter<'a, R, S>
+where
+    R: Rng,
+    S: Rng + ?Sized,
+{
+    type Item = (f64, PartitionSnapshot);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            let snapshot = PartitionSnapshot { 
+                joined: None, 
+                len_of_joined: 1, 
+                amount_of_sets: self.state.amount_of_sets(),
+            };
+            return Some((0.0, snapshot));
+        }
+        let (time_step, indexes) = self.coalescent.draw_step(&self.state, self.time, self.rng)?;
+        self.state.union(indexes[0], indexes[1]);
+        self.time += time_step;
+        let snapshot = PartitionSnapshot { 
+            joined: Some(indexes), 
+            len_of_joined: self.state.len_of_set(indexes[0]), 
+            amount_of_sets: self.state.amount_of_sets(),
+        };
+        Some((time_step, snapshot))
+    }
+}
+
+/// Parameters of the process, apart from its state and random number generator. 
+struct Parameters<'a> {
+    demography: &'a Demography,
+    sampling_times: &'a [f64],
+    beta: f64,
+}
+
+impl<'a> Parameters<'a> {
+    /// Draws the next step from a state at a given time, see ``peek_next_step`` on Coalescent. 
+    fn draw_step<S>(&self, state: &Partition, mut time: f64, rng: &mut S) -> Option<(f64, [usize; 2])>
+    where
+        S: Rng + ?Sized,
+    {
+        let mut elapsed = 0.0;
+
+        loop {
+            let lineages = self.active_lineages(state, time);
+            let next_sample = self.sampling_times
+                .iter()
+                .cloned()
+                .filter(|&sampling_time| sampling_time > time)
+                .fold(f64::INFINITY, f64::min);
+
+            // Wait for new samples
+
+            if lineages.len() < 2 {
+                if next_sample.is_infinite() {
+                    return None;
+                }
+                elapsed += next_sample - time;
+                time = next_sample;
+                continue;
+            }
+
+            // Simulate time step
+
+            let current_partition_size = lineages.len();
+            let rate = (current_partition_size * (current_partition_size - 1) / 2) as f64 * self.beta;
+            let intensity: f64 = Exp1.sample(rng);
+            let time_step = self.demography.waiting_time(time, intensity / rate);
+            if time + time_step > next_sample {
+                elapsed += next_sample - time;
+                time = next_sample;
+                continue;
+            }
+
+            // Choose between possible transitions
+
+            let set_indexes = choose_two(current_partition_size, rng);
+            let value_indexes = [lineages[set_indexes[0]], lineages[set_indexes[1]]];
+
+            // Return
+
+            return Some((elapsed + time_step, value_indexes));
+        }
+    }
+
+    /// One individual of each lineage present at time ``t``, 
+    /// i.e. sets of the state with some individual sampled by then. 
+    fn active_lineages(&self, state: &Partition, t: f64) -> Vec<usize> {
+        state
+            .representatives()
+            .iter()
+            .cloned()
+            .filter(|&representative| self.sampling_times[representative] <= t || state.len_of_set(representative) > 1)
+            .collect()
+    }
+}
+
+/// Choose uniformly a pair of sets of the partition, 
+/// returning one element of each. 
+fn choose_pair<S>(state: &Partition, rng: &mut S) -> [usize; 2] 
+where
+    S: Rng + ?Sized,
+{
+    let set_indexes = choose_two(state.amount_of_sets(), rng);
+
+    // Get values from these sets, in constant time
+    let representatives = state.representatives();
+
+    [representatives[set_indexes[0]], representatives[set_indexes[1]]]
+}
+
+/// Choose uniformly two distinct indexes below ``amount``, in random order, 
+/// in constant time: the second one is drawn among the others by skipping the first. 
+pub(crate) fn choose_two<S>(amount: usize, rng: &mut S) -> [usize; 2] 
+where
+    S: Rng + ?Sized,
+{
+    let first = rng.gen_range(0, amount);
+    let second = rng.gen_range(0, amount - 1);
+
+    [first, second + (second >= first) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    #[test]
+    fn tempering() {
+        // With β = 1, the same genealogies as the standard coalescent
+        let standard = Coalescent::new(10, Pcg32::seed_from_u64(1));
+        let tempered = Coalescent::new(10, Pcg32::seed_from_u64(1)).with_temperature(1.0);
+        let mut rng = Pcg32::seed_from_u64(2);
+        let mut other_rng = Pcg32::seed_from_u64(2);
+        for _ in 0..10 {
+            let genealogy = standard.sample_genealogy(&mut rng);
+            let other = tempered.sample_genealogy(&mut other_rng);
+            assert_eq!(genealogy.depth(), other.depth());
+            assert_eq!(genealogy.mean_pairwise_divergence(), other.mean_pairwise_divergence());
+        }
+        let mut standard = Coalescent::new(10, Pcg32::seed_from_u64(3));
+        let mut tempered = Coalescent::new(10, Pcg32::seed_from_u64(3)).with_temperature(1.0);
+        assert_eq!(standard.next_step(), tempered.next_step());
+
+        // Otherwise, times are scaled by 1 / β, e.g. E[TMRCA] = 2 (1 - 1/n) / β
+        let samples = 4000;
+        let tempered = Coalescent::new(2, rng.clone()).with_temperature(4.0);
+        let mean_depth = (0..samples)
+            .map(|_| tempered.sample_genealogy(&mut rng).depth())
+            .sum::<f64>() / samples as f64;
+        assert!((mean_depth - 0.25).abs() < 0.025);
+    }
+
+    #[test]
+    fn lazy_paths() {
+        // Same path as sample_path from the same stream, scheduled samples included
+        let mut coalescent = Coalescent::sampler(8);
+        coalescent.schedule_sample(0.3, 3);
+        let path = coalescent.sample_path(&mut Pcg32::seed_from_u64(5));
+        let mut rng = Pcg32::seed_from_u64(5);
+        let mut lazy = coalescent.path_iter(&mut rng);
+        for (time_step, partition) in path {
+            let (other_step, snapshot) = lazy.next().unwrap();
+            assert_eq!(time_step, other_step);
+            assert_eq!(&partition, lazy.state());
+            assert_eq!(snapshot.amount_of_sets, partition.amount_of_sets());
+            if let Some([first, _]) = snapshot.joined {
+                assert_eq!(snapshot.len_of_joined, partition.len_of_set(first));
+            }
+        }
+        assert_eq!(lazy.next(), None);
+    }
+
+    #[test]
+    fn streams() {
+        let mut coalescent = Coalescent::sampler(6);
+        let mut rng = Pcg32::seed_from_u64(4);
+        let mut other_rng = Pcg32::seed_from_u64(4);
+
+        // Sampling methods advance the given generator
+        let first = coalescent.sample_genealogy(&mut rng);
+        let second = coalescent.sample_genealogy(&mut rng);
+        assert_ne!(first.depth(), second.depth());
+        assert_eq!(first.depth(), coalescent.sample_genealogy(&mut other_rng).depth());
+        assert_eq!(second.depth(), coalescent.sample_genealogy(&mut other_rng).depth());
+
+        // Also with scheduled samples or trait objects, with the same stream both ways
+        coalescent.schedule_sample(0.5, 2);
+        let path = coalescent.sample_path(&mut rng);
+        let dyn_rng: &mut dyn rand::RngCore = &mut other_rng;
+        assert_eq!(path, coalescent.sample_path(dyn_rng));
+        let genealogy = coalescent.sample_genealogy(&mut rng);
+        let other = coalescent.sample_genealogy(&mut other_rng);
+        assert_eq!(genealogy.depth(), other.depth());
+        assert_eq!(genealogy.mean_pairwise_divergence(), other.mean_pairwise_divergence());
+
+        // The state-iterator methods only use the internal generator
+        let mut standard = Coalescent::new(6, Pcg32::seed_from_u64(4));
+        let (time_step, _) = standard.next_step().unwrap();
+        assert_eq!(time_step, Coalescent::new(6, Pcg32::seed_from_u64(4)).next_step().unwrap().0);
+    }
+
+    #[test]
+    fn early_stopping() {
+        let coalescent = Coalescent::sampler(8);
+        let mut events = 0;
+        let sampled = coalescent.sample_genealogy_until(&mut Pcg32::seed_from_u64(2), |_, partition| {
+            events += 1;
+            match partition.amount_of_sets() == 5 {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        });
+        assert_eq!(events, 3);
+        match sampled {
+            ControlFlow::Break(forest) => {
+                assert_eq!(forest.len(), 5);
+                assert_eq!(forest.trees().iter().map(|tree| tree.group_size()).sum::<usize>(), 8);
+            },
+            ControlFlow::Continue(_) => panic!("Sampling was not aborted."),
+        }
+
+        // Without breaking, the same genealogy as sample_genealogy
+        let genealogy = coalescent.sample_genealogy(&mut Pcg32::seed_from_u64(2));
+        let until = coalescent.sample_genealogy_until(&mut Pcg32::seed_from_u64(2), |_, _| ControlFlow::Continue(()));
+        match until {
+            ControlFlow::Continue(other) => assert_eq!(other.depth(), genealogy.depth()),
+            ControlFlow::Break(_) => panic!("Sampling was aborted."),
+        }
+    }
+
+    #[test]
+    fn budgets() {
+        let mut coalescent = Coalescent::sampler(6);
+        let exceeded = coalescent
+            .sample_genealogy_bounded(&mut Pcg32::seed_from_u64(2), Budget { max_events: Some(2), max_seconds: None })
+            .unwrap_err();
+        assert_eq!(exceeded.limit, Limit::Events(2));
+        assert_eq!(exceeded.forest.len(), 4);
+        assert_eq!(exceeded.to_string(), "sampling stopped after 2 events, with 4 lineages left");
+
+        // No events at all, before sampling anything
+        let budget = Budget { max_events: Some(0), max_seconds: None };
+        let exceeded = coalescent.sample_genealogy_bounded(&mut Pcg32::seed_from_u64(2), budget).unwrap_err();
+        assert_eq!(exceeded.limit, Limit::Events(0));
+        assert_eq!(exceeded.forest.len(), 6);
+        assert!(exceeded.forest.trees().iter().all(|tree| tree.group_size() == 1));
+
+        // A budget of exactly all events is enough, and no time is left for a slow model
+        let budget = Budget { max_events: Some(5), max_seconds: None };
+        assert!(coalescent.sample_genealogy_bounded(&mut Pcg32::seed_from_u64(2), budget).is_ok());
+        coalescent.schedule_sample(0.5, 2);
+        let budget = Budget { max_events: None, max_seconds: Some(0.0) };
+        let exceeded = coalescent.sample_genealogy_bounded(&mut Pcg32::seed_from_u64(2), budget).unwrap_err();
+        assert_eq!(exceeded.limit, Limit::Seconds(0.0));
+        assert!(exceeded.forest.len() > 1);
+    }
+
+    #[test]
+    fn uniform_pairs() {
+        // Each of the 15 unordered pairs of 6 sets, and both orders, equally likely
+        let (amount, samples) = (6, 150_000);
+        let mut rng = Pcg32::seed_from_u64(4);
+        let mut counts = vec![vec![0usize; amount]; amount];
+        for _ in 0..samples {
+            let [first, second] = choose_two(amount, &mut rng);
+            assert_ne!(first, second);
+            counts[first][second] += 1;
+        }
+        let expected = samples as f64 / (amount * (amount - 1)) as f64;
+        let chi_square: f64 = counts
+            .iter()
+            .enumerate()
+            .flat_map(|(first, row)| row.iter().enumerate().filter(move |&(second, _)| second != first))
+            .map(|(_, &count)| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        // 99.9% quantile of the chi-square distribution with 29 degrees of freedom
+        assert!(chi_square < 58.3, "{}", chi_square);
+        assert_eq!(choose_two(2, &mut rng).iter().sum::<usize>(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Simulation studies in Apache Arrow IPC and Parquet files, with the ``arrow`` feature.
+//!
+//! Both files have the columns of ``SimulationStudy::write_csv``: ``group_size``,
+//! ``theta``, ``growth_rate`` and ``replicate``, then a column of floats per statistic,
+//! so that they can be analyzed with polars or pandas, e.g. by ``polars.read_ipc`` or
+//! ``pandas.read_parquet``, without the size and the parsing of CSV.
+//!
+//! Records are streamed from ``SimulationStudy::for_each_record`` in batches of
+//! ``BATCH_SIZE`` rows, so that studies of any size are written in bounded memory.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::SimulationStudy;
+//!
+//! let study = SimulationStudy::new(vec![5, 10], 50).thetas(vec![1.0]);
+//! let mut file = Vec::new();
+//! study.write_arrow_ipc(&mut file, &mut rand::thread_rng()).unwrap();
+//!
+//! assert!(file.starts_with(b"ARROW1"));
+//! ```
+//!
+
+// Types
+use crate::{Record, SimulationStudy};
+use arrow::array::{ArrayRef, Float64Builder, UInt64Builder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use std::sync::Arc;
+
+// Traits
+use rand::Rng;
+use std::io::Write;
+
+/// Number of rows of each batch of records.
+pub const BATCH_SIZE: usize = 8192;
+
+impl SimulationStudy {
+    /// Schema of the records, see the module documentation.
+    pub fn arrow_schema(&self) -> SchemaRef {
+        let mut fields = vec![
+            Field::new("group_size", DataType::UInt64, false),
+            Field::new("theta", DataType::Float64, false),
+            Field::new("growth_rate", DataType::Float64, false),
+            Field::new("replicate", DataType::UInt64, false),
+        ];
+        fields.extend(self.statistic_names().iter().map(|name| Field::new(*name, DataType::Float64, false)));
+
+        Arc::new(Schema::new(fields))
+    }
+
+    /// Run the study, writing the records to ``writer`` as an Arrow IPC file.
+    ///
+    /// # Errors
+    ///
+    /// If writing fails.
+    pub fn write_arrow_ipc<W, R>(&self, writer: W, rng: &mut R) -> std::io::Result<()>
+    where
+        W: Write,
+        R: Rng + ?Sized,
+    {
+        let schema = self.arrow_schema();
+        let mut writer = FileWriter::try_new(writer, &schema).map_err(std::io::Error::other)?;
+        self.write_batches(&schema, rng, |batch| writer.write(batch).map_err(std::io::Error::other))?;
+        writer.finish().map_err(std::io::Error::other)
+    }
+
+    /// Run the study, writing the records to ``writer`` as a Parquet file, without compression.
+    ///
+    /// # Errors
+    ///
+    /// If writing fails.
+    pub fn write_parquet<W, R>(&self, writer: W, rng: &mut R) -> std::io::Result<()>
+    where
+        W: Write + Send,
+        R: Rng + ?Sized,
+    {
+        let schema = self.arrow_schema();
+        let mut writer = ArrowWriter::try_new(writer, schema.clone(), None).map_err(std::io::Error::other)?;
+        self.write_batches(&schema, rng, |batch| writer.write(batch).map_err(std::io::Error::other))?;
+        writer.close().map(|_| ()).map_err(std::io::Error::other)
+    }
+
+    /// Run the study, passing the records to ``write`` in batches of ``BATCH_SIZE`` rows.
+    fn write_batches<R, F>(&self, schema: &SchemaRef, rng: &mut R, mut write: F) -> std::io::Result<()>
+    where
+        R: Rng + ?Sized,
+        F: FnMut(&RecordBatch) -> std::io::Result<()>,
+    {
+        let mut columns = Columns::new(self.statistic_names().len());
+        let mut result = Ok(());
+        self.for_each_record(rng, |record| {
+            if result.is_ok() {
+                columns.push(&record);
+                if columns.rows == BATCH_SIZE {
+                    result = columns.finish(schema).and_then(|batch| write(&batch));
+                }
+            }
+        });
+        result?;
+
+        match columns.rows {
+            0 => Ok(()),
+            _ => write(&columns.finish(schema)?),
+        }
+    }
+}
+
+/// Columns of the records of a batch.
+struct Columns {
+    group_sizes: UInt64Builder,
+    thetas: Float64Builder,
+    growth_rates: Float64Builder,
+    replicates: UInt64Builder,
+    values: Vec<Float64Builder>, // one per statistic
+    rows: usize,
+}
+
+impl Columns {
+    fn new(statistics: usize) -> Self {
+        Columns {
+            group_sizes: UInt64Builder::with_capacity(BATCH_SIZE),
+            thetas: Float64Builder::with_capacity(BATCH_SIZE),
+            growth_rates: Float64Builder::with_capacity(BATCH_SIZE),
+            replicates: UInt64Builder::with_capacity(BATCH_SIZE),
+            values: (0..statistics).map(|_| Float64Builder::with_capacity(BATCH_SIZE)).collect(),
+            rows: 0,
+        }
+    }
+
+    fn push(&mut self, record: &Record) {
+        self.group_sizes.append_value(record.group_size as u64);
+        self.thetas.append_value(record.theta);
+        self.growth_rates.append_value(record.growth_rate);
+        self.replicates.append_value(record.replicate as u64);
+        for (column, &value) in self.values.iter_mut().zip(&record.values) {
+            column.append_value(value);
+        }
+        self.rows += 1;
+    }
+
+    /// Batch of the rows pushed so far, emptying the columns.
+    fn finish(&mut self, schema: &SchemaRef) -> std::io::Result<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.group_sizes.finish()),
+            Arc::new(self.thetas.finish()),
+            Arc::new(self.growth_rates.finish()),
+            Arc::new(self.replicates.finish()),
+        ];
+        columns.extend(self.values.iter_mut().map(|column| Arc::new(column.finish()) as ArrayRef));
+        self.rows = 0;
+
+        RecordBatch::try_new(schema.clone(), columns).map_err(std::io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float64Array, UInt64Array};
+    use arrow::ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    /// Records of the batches, checking their schema.
+    fn records(study: &SimulationStudy, batches: Vec<RecordBatch>) -> Vec<Record> {
+        let mut records = Vec::new();
+        for batch in batches {
+            assert_eq!(batch.schema(), study.arrow_schema());
+            let integers = |index: usize| batch.column(index).as_any().downcast_ref::<UInt64Array>().unwrap().clone();
+            let floats = |index: usize| batch.column(index).as_any().downcast_ref::<Float64Array>().unwrap().clone();
+            let (group_sizes, replicates) = (integers(0), integers(3));
+            let (thetas, growth_rates) = (floats(1), floats(2));
+            let values: Vec<Float64Array> = (4..batch.num_columns()).map(floats).collect();
+            for row in 0..batch.num_rows() {
+                records.push(Record {
+                    group_size: group_sizes.value(row) as usize,
+                    theta: thetas.value(row),
+                    growth_rate: growth_rates.value(row),
+                    replicate: replicates.value(row) as usize,
+                    values: values.iter().map(|column| column.value(row)).collect(),
+                });
+            }
+        }
+        records
+    }
+
+    #[test]
+    fn round_trips() {
+        // More records than a batch
+        let study = SimulationStudy::new(vec![3, 6], BATCH_SIZE / 2 + 1).thetas(vec![0.0, 2.0]);
+        let expected = study.run(&mut Pcg32::seed_from_u64(5));
+
+        let mut ipc = Vec::new();
+        study.write_arrow_ipc(&mut ipc, &mut Pcg32::seed_from_u64(5)).unwrap();
+        let reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert!(batches.len() > 1);
+        assert_eq!(records(&study, batches), expected);
+
+        let path = std::env::temp_dir().join("coalescence-study-test.parquet");
+        study.write_parquet(std::fs::File::create(&path).unwrap(), &mut Pcg32::seed_from_u64(5)).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap().build().unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(records(&study, batches), expected);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn empty_studies() {
+        let study = SimulationStudy::new(vec![4], 0);
+        let mut ipc = Vec::new();
+        study.write_arrow_ipc(&mut ipc, &mut rand::thread_rng()).unwrap();
+        let reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        assert_eq!(reader.schema(), study.arrow_schema());
+        assert_eq!(reader.count(), 0);
+    }
+}
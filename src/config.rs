@@ -0,0 +1,391 @@
+//! Simulation studies described by configuration files in TOML.
+//!
+//! A file fixes everything a study depends on: the model, the grid of parameters,
+//! the number of replicates, the statistics and how to write them, and the seed of
+//! the random number generator, so that rerunning the same file gives the same output.
+//!
+//! ```text
+//! model = "kingman"   # the default, and the only model of simulation studies for now
+//! seed = 42           # default 0
+//! replicates = 100
+//!
+//! [parameters]
+//! group_sizes = [10, 20]
+//! thetas = [1.0, 5.0]          # default [0.0]
+//! growth_rates = [0.0, 2.0]    # default [0.0]
+//!
+//! [output]
+//! statistics = ["depth", "segregating_sites"]   # by name, the default of SimulationStudy if missing
+//! format = "csv"                                # or "json-lines", default "csv"
+//! path = "results.csv"                          # default the standard output
+//! ```
+//!
+//! Files are read with the ``toml`` crate, with the ``config`` feature. Unknown keys are
+//! errors, so that misspelled options do not go unnoticed.
+//!
+//! Statistics are looked up in a ``StatisticRegistry``, so that files can name
+//! custom statistics registered by the application, see ``StudyConfig::from_toml_with``.
+//!
+
+// Types
+use crate::models::ModelKind;
+use crate::{Record, SimulationStudy, StatisticRegistry};
+use rand_pcg::Pcg32;
+use toml::Value;
+
+// Traits
+use rand::SeedableRng;
+use std::io::Write;
+
+/// Error when reading a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The text is not valid TOML, at the given line, starting from one.
+    Syntax { line: usize, message: String },
+    /// The values do not describe a study.
+    Schema(String),
+    /// Simulation studies do not support the model yet.
+    UnsupportedModel(ModelKind),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Syntax { line, message } => write!(f, "invalid TOML at line {}: {}", line, message),
+            ConfigError::Schema(message) => write!(f, "invalid configuration: {}", message),
+            ConfigError::UnsupportedModel(kind) => write!(f, "simulation studies do not support the model {} yet", kind),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Format of the records of a study.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One row per record after a header row, see ``SimulationStudy::write_csv``.
+    Csv,
+    /// One JSON object per line and record, with a field per parameter and statistic.
+    JsonLines,
+}
+
+impl OutputFormat {
+    /// Name of the format in configuration files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::JsonLines => "json-lines",
+        }
+    }
+}
+
+/// Simulation study read from a configuration file, see the module documentation.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::config::StudyConfig;
+///
+/// let toml = r#"
+///     seed = 7
+///     replicates = 3
+///
+///     [parameters]
+///     group_sizes = [5, 10]
+///     thetas = [1, 2.5]
+///
+///     [output]
+///     statistics = ["depth", "segregating_sites"]
+/// "#;
+/// let config = StudyConfig::from_toml(toml).unwrap();
+///
+/// let mut csv = Vec::new();
+/// config.write(&mut csv).unwrap();
+/// let csv = String::from_utf8(csv).unwrap();
+/// assert_eq!(csv.lines().count(), 1 + 2 * 2 * 3);
+///
+/// // Same seed, same output
+/// let mut other = Vec::new();
+/// config.write(&mut other).unwrap();
+/// assert_eq!(csv.as_bytes(), &other[..]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StudyConfig {
+    /// Model of genealogies.
+    pub model: ModelKind,
+    /// Seed of the random number generator.
+    pub seed: u64,
+    /// Grid of parameters, replicates and statistics.
+    pub study: SimulationStudy,
+    /// Format of the records.
+    pub format: OutputFormat,
+    /// File to write the records to, or ``None`` for the standard output.
+    pub path: Option<String>,
+}
+
+impl StudyConfig {
+    /// Reads a configuration from TOML text, with the built-in statistics.
+    ///
+    /// # Errors
+    ///
+    /// If the text is not valid TOML, if some key is unknown or some value has the wrong
+    /// type, if ``replicates`` or ``group_sizes`` are missing, or if the model is not
+    /// supported by simulation studies.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        StudyConfig::from_toml_with(text, &StatisticRegistry::new())
+    }
+
+    /// Reads a configuration from TOML text, looking up statistics in ``registry``.
+    ///
+    /// # Errors
+    ///
+    /// In the same cases as ``from_toml``.
+    pub fn from_toml_with(text: &str, registry: &StatisticRegistry) -> Result<Self, ConfigError> {
+        let entries = parse(text)?;
+        const KEYS: [(&str, &str); 9] = [
+            ("", "model"),
+            ("", "seed"),
+            ("", "replicates"),
+            ("parameters", "group_sizes"),
+            ("parameters", "thetas"),
+            ("parameters", "growth_rates"),
+            ("output", "statistics"),
+            ("output", "format"),
+            ("output", "path"),
+        ];
+        if let Some(entry) = entries.iter().find(|entry| !KEYS.contains(&(entry.table.as_str(), entry.key.as_str()))) {
+            return Err(schema(&format!("unknown key \"{}\"", entry.name())));
+        }
+        let get = |table: &str, key: &str| entries.iter().find(|entry| entry.table == table && entry.key == key);
+
+        let model = match get("", "model") {
+            Some(entry) => string(entry)?.parse().map_err(|error| schema(&format!("{}", error)))?,
+            None => ModelKind::Kingman,
+        };
+        if model != ModelKind::Kingman {
+            return Err(ConfigError::UnsupportedModel(model));
+        }
+        let seed = get("", "seed").map(integer).transpose()?.unwrap_or(0);
+        let replicates = get("", "replicates").map(integer).ok_or_else(|| schema("missing key \"replicates\""))??;
+
+        let group_sizes = get("parameters", "group_sizes")
+            .ok_or_else(|| schema("missing key \"parameters.group_sizes\""))
+            .and_then(|entry| array(entry, as_integer))?;
+        let group_sizes = group_sizes.into_iter().map(|size| size as usize).collect();
+        let mut study = SimulationStudy::new(group_sizes, replicates as usize);
+        if let Some(entry) = get("parameters", "thetas") {
+            study = study.thetas(array(entry, as_f64)?);
+        }
+        if let Some(entry) = get("parameters", "growth_rates") {
+            study = study.growth_rates(array(entry, as_f64)?);
+        }
+
+        if let Some(entry) = get("output", "statistics") {
+            let names = array(entry, |value| value.as_str().map(|name| name.to_string()))?;
+            let statistics = registry.select(&names).map_err(|error| schema(&error.to_string()))?;
+            study = study.summary_statistics(statistics);
+        }
+        let format = match get("output", "format") {
+            Some(entry) => match string(entry)? {
+                "csv" => OutputFormat::Csv,
+                "json-lines" => OutputFormat::JsonLines,
+                other => return Err(schema(&format!("unknown format \"{}\", expected csv or json-lines", other))),
+            },
+            None => OutputFormat::Csv,
+        };
+        let path = get("output", "path").map(|entry| string(entry).map(|path| path.to_string())).transpose()?;
+
+        Ok(StudyConfig { model, seed, study, format, path })
+    }
+
+    /// Reads a configuration from a TOML file.
+    pub fn from_file<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let text = std::fs::read_to_string(path)?;
+        StudyConfig::from_toml(&text).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+
+    /// Run the study from its seed, writing the records to ``writer`` in the format of the configuration.
+    pub fn write<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let mut rng = Pcg32::seed_from_u64(self.seed);
+        match self.format {
+            OutputFormat::Csv => self.study.write_csv(writer, &mut rng),
+            OutputFormat::JsonLines => {
+                let mut result = Ok(());
+                self.study.for_each_record(&mut rng, |record| {
+                    if result.is_ok() {
+                        result = writeln!(writer, "{}", self.json_line(&record));
+                    }
+                });
+                result
+            },
+        }
+    }
+
+    /// Run the study, writing the records to the path of the configuration, or to the standard output.
+    pub fn execute(&self) -> std::io::Result<()> {
+        match &self.path {
+            Some(path) => self.write(std::io::BufWriter::new(std::fs::File::create(path)?)),
+            None => self.write(std::io::stdout().lock()),
+        }
+    }
+
+    fn json_line(&self, record: &Record) -> String {
+        let mut fields = vec![
+            format!("\"group_size\": {}", record.group_size),
+            format!("\"theta\": {:?}", record.theta),
+            format!("\"growth_rate\": {:?}", record.growth_rate),
+            format!("\"replicate\": {}", record.replicate),
+        ];
+        for (name, value) in self.study.statistic_names().iter().zip(&record.values) {
+            fields.push(format!("\"{}\": {:?}", name, value));
+        }
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+fn schema(message: &str) -> ConfigError {
+    ConfigError::Schema(message.to_string())
+}
+
+fn integer(entry: &Entry) -> Result<u64, ConfigError> {
+    match as_integer(&entry.value) {
+        Some(value) => Ok(value),
+        None => Err(schema(&format!("\"{}\" must be a non-negative integer", entry.name()))),
+    }
+}
+
+fn string(entry: &Entry) -> Result<&str, ConfigError> {
+    entry.value.as_str().ok_or_else(|| schema(&format!("\"{}\" must be a string", entry.name())))
+}
+
+fn array<T, F>(entry: &Entry, f: F) -> Result<Vec<T>, ConfigError>
+where
+    F: Fn(&Value) -> Option<T>,
+{
+    let error = || schema(&format!("\"{}\" must be an array of values of the same kind", entry.name()));
+    match &entry.value {
+        Value::Array(values) => values.iter().map(|value| f(value).ok_or_else(error)).collect(),
+        _ => Err(error()),
+    }
+}
+
+/// Key of a table with its value, the top-level table being ``""``.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    table: String,
+    key: String,
+    value: Value,
+}
+
+impl Entry {
+    /// Dotted name of the key.
+    fn name(&self) -> String {
+        match self.table.is_empty() {
+            true => self.key.clone(),
+            false => format!("{}.{}", self.table, self.key),
+        }
+    }
+}
+
+/// Every key of the text, keys of the tables ``parameters`` and ``output`` with their table.
+fn parse(text: &str) -> Result<Vec<Entry>, ConfigError> {
+    let table: toml::Table = text.parse().map_err(|error: toml::de::Error| {
+        let line = error.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+        ConfigError::Syntax { line, message: error.message().to_string() }
+    })?;
+
+    let mut entries = Vec::new();
+    for (key, value) in table {
+        match value {
+            Value::Table(values) => entries.extend(values.into_iter().map(|(other, value)| Entry {
+                table: key.clone(),
+                key: other,
+                value,
+            })),
+            value => entries.push(Entry { table: String::new(), key, value }),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Non-negative integers.
+fn as_integer(value: &Value) -> Option<u64> {
+    match value {
+        Value::Integer(value) if *value >= 0 => Some(*value as u64),
+        _ => None,
+    }
+}
+
+/// Integers and floats.
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let toml = r#"
+            # Comments, multi-line arrays and every kind of value
+            replicates = 2
+            seed = 1_000
+
+            [parameters]
+            group_sizes = [
+                4,  # small
+                8,
+            ]
+            growth_rates = [0.0, 1e-1]
+
+            [output]
+            format = "json-lines"
+            path = "with # hash.jsonl"
+        "#;
+        let config = StudyConfig::from_toml(toml).unwrap();
+        assert_eq!(config.seed, 1000);
+        assert_eq!(config.format, OutputFormat::JsonLines);
+        assert_eq!(config.path.as_deref(), Some("with # hash.jsonl"));
+        assert_eq!(config.study, SimulationStudy::new(vec![4, 8], 2).growth_rates(vec![0.0, 0.1]));
+
+        let mut lines = Vec::new();
+        config.write(&mut lines).unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        assert_eq!(lines.lines().count(), 2 * 2 * 2);
+        assert!(lines.lines().all(|line| {
+            line.starts_with("{\"group_size\": ") && line.contains("\"mean_pairwise_divergence\": ")
+        }));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |toml: &str| StudyConfig::from_toml(toml).unwrap_err();
+        assert!(matches!(error("replicates = [1, 2"), ConfigError::Syntax { line: 1, .. }));
+        assert!(matches!(error("replicates = 1\nreplicates = 2"), ConfigError::Syntax { line: 2, .. }));
+        assert!(matches!(error("replicates = 1 2"), ConfigError::Syntax { .. }));
+        assert!(matches!(error("replicates = 1"), ConfigError::Schema(_)));
+        assert!(matches!(error("replicates = -1\n[parameters]\ngroup_sizes = [2]"), ConfigError::Schema(_)));
+        assert!(matches!(error("replicas = 1\n[parameters]\ngroup_sizes = [2]"), ConfigError::Schema(_)));
+        assert!(matches!(error("replicates = 1\n[parameters]\ngroup_sizes = [2, \"3\"]"), ConfigError::Schema(_)));
+        assert!(matches!(
+            error("replicates = 1\n[parameters]\ngroup_sizes = [2]\n[output]\nstatistics = [\"sackin\"]"),
+            ConfigError::Schema(_)
+        ));
+        assert_eq!(
+            error("model = \"sweep\"\nreplicates = 1\n[parameters]\ngroup_sizes = [2]"),
+            ConfigError::UnsupportedModel(ModelKind::Sweep)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Published demographic models of human populations.
+//!
+//! Each model is given in the units of the crate: sizes relative to a reference effective
+//! size, and times, growth rates and migration rates scaled by its number of gene copies,
+//! so that they can be simulated as they are. The ``TimeScale`` of each model converts
+//! genealogies back into generations or years, with 25 years per generation, and samples
+//! are taken from its populations in the order of ``populations``.
+//!
+//! Parameters are those of the original publications, as also catalogued by stdpopsim.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::demographies;
+//! use coalescence::Years;
+//!
+//! let model = demographies::gutenkunst_out_of_africa();
+//! assert_eq!(model.populations, vec!["YRI", "CEU", "CHB"]);
+//!
+//! let mut rng = rand::thread_rng();
+//! let genealogy = model.model.sample_genealogy(&[2, 2, 2], &mut rng);
+//! let age: Years = genealogy.depth_in(&model.scale);
+//!
+//! assert!(age > Years(0.0));
+//! ```
+//!
+
+// Types
+use crate::{CoalescentTime, Demography, Generations, StructuredCoalescent, TimeScale};
+
+// Traits
+use crate::TimeUnit;
+
+/// Years per generation of all models.
+const GENERATION_TIME: f64 = 25.0;
+
+/// Demographic model of a publication, with the scale of its units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published<M> {
+    /// Short name of the model.
+    pub name: &'static str,
+    /// Reference of the publication.
+    pub citation: &'static str,
+    /// Names of the populations, one per deme.
+    pub populations: Vec<&'static str>,
+    /// Relation between coalescent units and generations.
+    pub scale: TimeScale,
+    /// The model itself.
+    pub model: M,
+}
+
+/// Conversion of parameters per generation into coalescent units, for a diploid
+/// reference population.
+struct Units {
+    effective_size: f64,
+    scale: TimeScale,
+}
+
+impl Units {
+    fn new(effective_size: f64) -> Self {
+        Units { effective_size, scale: TimeScale::diploid(effective_size, GENERATION_TIME) }
+    }
+
+    fn size(&self, effective_size: f64) -> f64 {
+        effective_size / self.effective_size
+    }
+
+    fn time(&self, generations: f64) -> f64 {
+        Generations(generations).convert::<CoalescentTime>(&self.scale).0
+    }
+
+    /// Per generation rate of growth or migration.
+    fn rate(&self, rate: f64) -> f64 {
+        rate * self.scale.copies()
+    }
+
+    /// Symmetric migration matrix from per generation rates.
+    fn migration(&self, rates: &[(usize, usize, f64)], demes: usize) -> Vec<Vec<f64>> {
+        let mut migration = vec![vec![0.0; demes]; demes];
+        for &(first, second, rate) in rates {
+            migration[first][second] = self.rate(rate);
+            migration[second][first] = self.rate(rate);
+        }
+
+        migration
+    }
+}
+
+/// Three populations out of Africa, fitted to the site frequency spectra of Yoruba,
+/// Europeans and Han Chinese: Africans grew 220 thousand years ago, and Eurasians
+/// left Africa 140 thousand years ago through a bottleneck, then Europeans and Asians
+/// split 21.2 thousand years ago and grew exponentially, with migration between all.
+///
+/// Sizes are relative to the ancestral effective size of 7300.
+pub fn gutenkunst_out_of_africa() -> Published<StructuredCoalescent> {
+    let units = Units::new(7300.0);
+    let (ancestral, african, bottleneck) = (units.size(7300.0), units.size(12_300.0), units.size(2100.0));
+    let (european_growth, asian_growth): (f64, f64) = (0.004, 0.0055);
+    let (split, out_of_africa, expansion) = (848.0, 5600.0, 8800.0);
+    let european = units.size(1000.0 * (european_growth * split).exp());
+    let asian = units.size(510.0 * (asian_growth * split).exp());
+
+    let model = StructuredCoalescent::new(
+        vec![african, european, asian],
+        units.migration(&[(0, 1, 3e-5), (0, 2, 1.9e-5), (1, 2, 9.6e-5)], 3),
+    )
+    .with_growth_rates(vec![0.0, units.rate(european_growth), units.rate(asian_growth)])
+    .add_epoch(units.time(split), vec![african, bottleneck, 0.0], units.migration(&[(0, 1, 25e-5)], 3))
+    .add_pulse(units.time(split), 2, 1, 1.0)
+    .add_epoch(units.time(out_of_africa), vec![african, 0.0, 0.0], vec![vec![0.0; 3]; 3])
+    .add_pulse(units.time(out_of_africa), 1, 0, 1.0)
+    .add_epoch(units.time(expansion), vec![ancestral, 0.0, 0.0], vec![vec![0.0; 3]; 3]);
+
+    Published {
+        name: "OutOfAfrica_3G09",
+        citation: "Gutenkunst et al. (2009), PLoS Genetics 5(10): e1000695",
+        populations: vec!["YRI", "CEU", "CHB"],
+        scale: units.scale,
+        model,
+    }
+}
+
+/// Two populations out of Africa, fitted to exome sequences of African and European
+/// Americans: Africans grew 148 thousand years ago, Europeans left Africa 51 thousand
+/// years ago through a bottleneck and grew slowly since 23 thousand years ago, and both
+/// grew fast in the last 5115 years, with migration between them.
+///
+/// Sizes are relative to the ancestral effective size of 7310.
+pub fn tennessen_out_of_africa() -> Published<StructuredCoalescent> {
+    let units = Units::new(7310.0);
+    let (ancestral, african, bottleneck) = (units.size(7310.0), 14_474.0, units.size(1861.0));
+    let (african_growth, european_growth, slow_growth): (f64, f64, f64) = (0.0166, 0.0195, 0.00307);
+    let (fast, slow, out_of_africa, expansion) = (204.6, 920.0, 2040.0, 5920.0);
+    let european_start = 1032.0 * (slow_growth * (slow - fast)).exp();
+    let european = units.size(european_start * (european_growth * fast).exp());
+
+    let model = StructuredCoalescent::new(
+        vec![units.size(african * (african_growth * fast).exp()), european],
+        units.migration(&[(0, 1, 2.5e-5)], 2),
+    )
+    .with_growth_rates(vec![units.rate(african_growth), units.rate(european_growth)])
+    .add_epoch(units.time(fast), vec![units.size(african), units.size(european_start)], units.migration(&[(0, 1, 2.5e-5)], 2))
+    .with_growth_rates(vec![0.0, units.rate(slow_growth)])
+    .add_epoch(units.time(slow), vec![units.size(african), bottleneck], units.migration(&[(0, 1, 15e-5)], 2))
+    .add_epoch(units.time(out_of_africa), vec![units.size(african), 0.0], vec![vec![0.0; 2]; 2])
+    .add_pulse(units.time(out_of_africa), 1, 0, 1.0)
+    .add_epoch(units.time(expansion), vec![ancestral, 0.0], vec![vec![0.0; 2]; 2]);
+
+    Published {
+        name: "OutOfAfrica_2T12",
+        citation: "Tennessen et al. (2012), Science 337(6090): 64-69",
+        populations: vec!["AFR", "EUR"],
+        scale: units.scale,
+        model,
+    }
+}
+
+/// African population of ``tennessen_out_of_africa`` on its own: an expansion 148 thousand
+/// years ago, and exponential growth in the last 5115 years.
+///
+/// Sizes are relative to the present effective size, of about 432 thousand.
+pub fn tennessen_african() -> Published<Demography> {
+    let (growth_rate, fast, expansion): (f64, f64, f64) = (0.0166, 204.6, 5920.0);
+    let present = 14_474.0 * (growth_rate * fast).exp();
+    let units = Units::new(present);
+
+    let model = Demography::exponential(units.rate(growth_rate))
+        .add_epoch(units.time(fast), units.size(14_474.0), 0.0)
+        .add_epoch(units.time(expansion), units.size(7310.0), 0.0);
+
+    Published {
+        name: "Africa_1T12",
+        citation: "Tennessen et al. (2012), Science 337(6090): 64-69",
+        populations: vec!["AFR"],
+        scale: units.scale,
+        model,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Years;
+
+    #[test]
+    fn sizes_in_generations() {
+        let model = gutenkunst_out_of_africa();
+        let european = model.model.size_at(1, 0.0) * 7300.0;
+        assert!((european - 1000.0 * (0.004f64 * 848.0).exp()).abs() < 1e-6);
+        let split = CoalescentTime(848.0 / 14_600.0);
+        assert!((model.model.size_at(1, split.0 - 1e-9) * 7300.0 - 1000.0).abs() < 1e-3);
+        assert_eq!(model.model.size_at(1, split.0 + 1e-9) * 7300.0, 2100.0);
+        assert!((split.convert::<Years>(&model.scale).0 - 21_200.0).abs() < 1e-6);
+
+        let african = tennessen_african();
+        assert_eq!(african.model.size_at(0.0), 1.0);
+        let expansion = Generations(5920.0).convert::<CoalescentTime>(&african.scale).0;
+        assert!((african.model.size_at(expansion + 1.0) * african.scale.copies() / 2.0 - 7310.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn published_models_simulate() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let genealogy = gutenkunst_out_of_africa().model.sample_genealogy(&[3, 3, 3], &mut rng);
+            assert_eq!(genealogy.validate(), Ok(()));
+            let genealogy = tennessen_out_of_africa().model.sample_genealogy(&[4, 4], &mut rng);
+            assert_eq!(genealogy.validate(), Ok(()));
+        }
+
+        let mut coalescent = crate::Coalescent::sampler(10);
+        coalescent.set_demography(tennessen_african().model);
+        assert_eq!(coalescent.sample_genealogy(&mut rng).validate(), Ok(()));
+    }
+}
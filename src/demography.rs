@@ -0,0 +1,442 @@
+//! Demographic history of a population.
+//!
+//! The size of the population changes through time, which changes the rate
+//! of coalescence: with k lineages and relative size ν(t), each pair of
+//! lineages coalesces at rate 1 / ν(t). Time is measured in the units of
+//! the coalescent process with the present population size, and it runs
+//! backwards, from the present into the past.
+//!
+//! Besides epochs of exponential growth, there are presets for common histories:
+//! two epochs, expansions by a factor, skylines and logistic growth towards a
+//! carrying capacity.
+//!
+
+/// Number of exponential epochs approximating logistic growth.
+const LOGISTIC_EPOCHS: usize = 64;
+
+/// Ratio, in the past, between the present size and the size at which logistic
+/// growth is approximated by exponential growth.
+const LOGISTIC_TAIL: f64 = 1e4;
+
+/// Invalid parameter of a demography.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DemographyError {
+    /// A size is not positive and finite.
+    InvalidSize(f64),
+    /// A growth rate is not finite.
+    InvalidGrowthRate(f64),
+    /// An epoch does not start after the previous one, or not at a finite time.
+    UnorderedEpoch { start: f64, previous: f64 },
+}
+
+impl std::fmt::Display for DemographyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DemographyError::InvalidSize(size) => write!(f, "size {} must be positive and finite", size),
+            DemographyError::InvalidGrowthRate(rate) => write!(f, "growth rate {} must be finite", rate),
+            DemographyError::UnorderedEpoch { start, previous } => {
+                write!(f, "epoch starting at {} must start at a finite time after the previous one, at {}", start, previous)
+            },
+        }
+    }
+}
+
+impl std::error::Error for DemographyError {}
+
+/// Population size as a function of time.
+///
+/// A sequence of epochs, each one with a size at its start and an exponential
+/// growth rate: with growth rate g, the size at time t into the epoch is
+/// the size at its start times exp(-g t), so that positive rates correspond
+/// to a population that was smaller in the past.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::Demography;
+///
+/// let demography = Demography::exponential(2.0);
+/// assert_eq!(demography.size_at(0.0), 1.0);
+/// assert!(demography.size_at(1.0) < 1.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Demography {
+    epochs: Vec<Epoch>, // sorted by start, the first one starting at zero
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Epoch {
+    start: f64,
+    size: f64,
+    growth_rate: f64,
+}
+
+impl Demography {
+    /// Population of constant size one.
+    pub fn constant() -> Self {
+        Demography::exponential(0.0)
+    }
+
+    /// Population of present size one, growing exponentially at the given rate.
+    ///
+    /// # Remarks
+    ///
+    /// With a negative rate, the population grows into the past without bound
+    /// and there is a positive probability that lineages never coalesce.
+    /// Waiting times are then infinite.
+    ///
+    /// # Panics
+    ///
+    /// If the growth rate is not finite.
+    pub fn exponential(growth_rate: f64) -> Self {
+        assert!(growth_rate.is_finite(), "The growth rate must be finite.");
+
+        let epochs = vec![Epoch { start: 0.0, size: 1.0, growth_rate }];
+        Demography { epochs }
+    }
+
+    /// Population of present size one that had size ``ancient_size`` before time ``time``
+    /// in the past, e.g. a bottleneck or an expansion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Demography;
+    ///
+    /// let expansion = Demography::two_epoch(0.5, 0.1);
+    /// assert_eq!(expansion.size_at(0.2), 1.0);
+    /// assert_eq!(expansion.size_at(1.0), 0.1);
+    /// ```
+    pub fn two_epoch(time: f64, ancient_size: f64) -> Self {
+        Demography::constant().add_epoch(time, ancient_size, 0.0)
+    }
+
+    /// Piecewise-constant sizes, as in skyline plots: ``sizes[0]`` from the present
+    /// until ``change_times[0]``, then ``sizes[1]`` until ``change_times[1]``, and so on,
+    /// the last size staying forever. Sizes are relative to the unit of time, so the
+    /// present size need not be one.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one size more than change times, if change times are not positive
+    /// and increasing, or if some size is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Demography;
+    ///
+    /// let skyline = Demography::skyline(&[2.0, 0.5, 1.0], &[0.1, 1.0]);
+    /// assert_eq!(skyline.size_at(0.0), 2.0);
+    /// assert_eq!(skyline.size_at(0.5), 0.5);
+    /// assert_eq!(skyline.size_at(10.0), 1.0);
+    /// ```
+    pub fn skyline(sizes: &[f64], change_times: &[f64]) -> Self {
+        assert_eq!(sizes.len(), change_times.len() + 1, "There must be one size more than change times.");
+        let size = sizes[0];
+        assert!(size > 0.0 && size.is_finite(), "The size must be positive and finite.");
+
+        let demography = Demography { epochs: vec![Epoch { start: 0.0, size, growth_rate: 0.0 }] };
+        change_times
+            .iter()
+            .zip(&sizes[1..])
+            .fold(demography, |demography, (&start, &size)| demography.add_epoch(start, size, 0.0))
+    }
+
+    /// Population of present size one that expanded by ``factor`` at time ``time``
+    /// in the past, being ``1 / factor`` before, or which declined if ``factor`` is
+    /// less than one.
+    ///
+    /// # Panics
+    ///
+    /// If the time is not positive and finite, or the factor is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Demography;
+    ///
+    /// let expansion = Demography::expansion(0.5, 10.0);
+    /// assert_eq!(expansion, Demography::two_epoch(0.5, 0.1));
+    /// ```
+    pub fn expansion(time: f64, factor: f64) -> Self {
+        assert!(factor > 0.0 && factor.is_finite(), "The factor must be positive and finite.");
+
+        Demography::two_epoch(time, 1.0 / factor)
+    }
+
+    /// Population of present size one under logistic growth at rate ``growth_rate``
+    /// towards ``carrying_capacity``, relative to the present size: forwards in time,
+    /// the size ``K / (1 + (K - 1) e^{r t})`` at time ``t`` in the past grows from
+    /// zero, exponentially at first, and saturates at the carrying capacity.
+    ///
+    /// It is approximated by epochs of exponential growth, matching the size at their start.
+    ///
+    /// # Panics
+    ///
+    /// If the growth rate is not positive and finite, or the carrying capacity is not
+    /// finite and at least one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Demography;
+    ///
+    /// let logistic = Demography::logistic(2.0, 1.5);
+    /// assert_eq!(logistic.size_at(0.0), 1.0);
+    /// assert!((logistic.size_at(1.0) - 1.5 / (1.0 + 0.5 * 2f64.exp())).abs() < 1e-3);
+    /// assert!(logistic.size_at(20.0) < 1e-6);
+    /// ```
+    pub fn logistic(growth_rate: f64, carrying_capacity: f64) -> Self {
+        assert!(growth_rate > 0.0 && growth_rate.is_finite(), "The growth rate must be positive and finite.");
+        assert!(
+            carrying_capacity >= 1.0 && carrying_capacity.is_finite(),
+            "The carrying capacity must be finite and at least the present size."
+        );
+        if carrying_capacity == 1.0 {
+            return Demography::constant();
+        }
+
+        // Epochs until the size is small enough for growth to be exponential
+        let excess = carrying_capacity - 1.0;
+        let size = |t: f64| carrying_capacity / (1.0 + excess * (growth_rate * t).exp());
+        let end = (LOGISTIC_TAIL * carrying_capacity / excess).ln().max(0.0) / growth_rate;
+        let dt = end / LOGISTIC_EPOCHS as f64;
+        let mut epochs = Vec::with_capacity(LOGISTIC_EPOCHS + 1);
+        for epoch in 0..LOGISTIC_EPOCHS {
+            let start = epoch as f64 * dt;
+            let rate = (size(start) / size(start + dt)).ln() / dt;
+            epochs.push(Epoch { start, size: size(start), growth_rate: rate });
+        }
+        epochs.push(Epoch { start: end, size: size(end), growth_rate });
+
+        Demography { epochs }
+    }
+
+    /// Adds an epoch starting at time ``start`` in the past, with size ``size`` at its start
+    /// and growth rate ``growth_rate``.
+    ///
+    /// # Panics
+    ///
+    /// If the epoch is invalid, see ``try_add_epoch``.
+    pub fn add_epoch(self, start: f64, size: f64, growth_rate: f64) -> Self {
+        self.try_add_epoch(start, size, growth_rate).unwrap_or_else(|error| panic!("Invalid epoch: {}.", error))
+    }
+
+    /// Adds an epoch as ``add_epoch``, or explains why it is invalid.
+    ///
+    /// # Errors
+    ///
+    /// If ``start`` is not finite and after the start of the last epoch, if the size is
+    /// not positive and finite, or if the growth rate is not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Demography, DemographyError};
+    ///
+    /// let error = Demography::two_epoch(1.0, 0.5).try_add_epoch(0.5, 2.0, 0.0).unwrap_err();
+    ///
+    /// assert_eq!(error, DemographyError::UnorderedEpoch { start: 0.5, previous: 1.0 });
+    /// assert_eq!(error.to_string(), "epoch starting at 0.5 must start at a finite time after the previous one, at 1");
+    /// ```
+    pub fn try_add_epoch(mut self, start: f64, size: f64, growth_rate: f64) -> Result<Self, DemographyError> {
+        let previous = self.epochs.last().unwrap().start;
+        if !(start > previous && start.is_finite()) {
+            return Err(DemographyError::UnorderedEpoch { start, previous });
+        }
+        if !(size > 0.0 && size.is_finite()) {
+            return Err(DemographyError::InvalidSize(size));
+        }
+        if !growth_rate.is_finite() {
+            return Err(DemographyError::InvalidGrowthRate(growth_rate));
+        }
+
+        self.epochs.push(Epoch { start, size, growth_rate });
+        Ok(self)
+    }
+
+    /// Relative size of the population at time ``t`` in the past.
+    pub fn size_at(&self, t: f64) -> f64 {
+        let epoch = self.epoch_at(t);
+        epoch.size * (-epoch.growth_rate * (t - epoch.start)).exp()
+    }
+
+    /// Growth rate of the population at time ``t`` in the past.
+    pub fn growth_rate_at(&self, t: f64) -> f64 {
+        self.epoch_at(t).growth_rate
+    }
+
+    /// Whether the population has constant size one, avoiding any transformation of times.
+    pub(crate) fn is_constant(&self) -> bool {
+        self.epochs.iter().all(|epoch| epoch.size == 1.0 && epoch.growth_rate == 0.0)
+    }
+
+    /// Time it takes, starting at time ``t``, for a pair of lineages to accumulate
+    /// ``intensity`` of coalescence, i.e. the waiting time corresponding to a
+    /// waiting time of ``intensity`` in a population of constant size one.
+    pub(crate) fn waiting_time(&self, t: f64, intensity: f64) -> f64 {
+        let mut current = t;
+        let mut remaining = intensity;
+
+        for (index, epoch) in self.epochs.iter().enumerate() {
+            let end = self.epochs.get(index + 1).map(|next| next.start).unwrap_or(f64::INFINITY);
+            if end <= current {
+                continue;
+            }
+
+            let size = self.size_at(current);
+            let growth_rate = epoch.growth_rate;
+            let available = match growth_rate == 0.0 {
+                true => (end - current) / size,
+                false => ((growth_rate * (end - current)).exp_m1()) / (growth_rate * size),
+            };
+
+            if remaining <= available {
+                let elapsed = match growth_rate == 0.0 {
+                    true => remaining * size,
+                    false => {
+                        let argument = remaining * growth_rate * size;
+                        match argument > -1.0 {
+                            true => argument.ln_1p() / growth_rate,
+                            false => f64::INFINITY,
+                        }
+                    },
+                };
+                return current + elapsed - t;
+            }
+
+            remaining -= available;
+            current = end;
+        }
+
+        f64::INFINITY
+    }
+
+    /// Intensity of coalescence of a pair of lineages between times ``start`` and ``end``,
+    /// i.e. the integral of ``1 / size``, the inverse of ``waiting_time``.
+    pub(crate) fn intensity(&self, start: f64, end: f64) -> f64 {
+        let mut intensity = 0.0;
+        for (index, epoch) in self.epochs.iter().enumerate() {
+            let epoch_end = self.epochs.get(index + 1).map(|next| next.start).unwrap_or(f64::INFINITY);
+            let (from, to) = (start.max(epoch.start), end.min(epoch_end));
+            if from >= to {
+                continue;
+            }
+            intensity += match epoch.growth_rate == 0.0 {
+                true => (to - from) / epoch.size,
+                false => {
+                    let g = epoch.growth_rate;
+                    ((g * (to - epoch.start)).exp() - (g * (from - epoch.start)).exp()) / (g * epoch.size)
+                },
+            };
+        }
+
+        intensity
+    }
+
+    fn epoch_at(&self, t: f64) -> &Epoch {
+        self.epochs
+            .iter()
+            .rev()
+            .find(|epoch| epoch.start <= t)
+            .unwrap_or(&self.epochs[0])
+    }
+}
+
+impl Default for Demography {
+    fn default() -> Self {
+        Demography::constant()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_size() {
+        let demography = Demography::constant();
+
+        assert_eq!(demography.waiting_time(3.0, 0.5), 0.5);
+        assert_eq!(demography.size_at(10.0), 1.0);
+    }
+
+    #[test]
+    fn waiting_times_invert_intensity() {
+        for &growth_rate in &[-0.5, 0.5, 3.0] {
+            let demography = Demography::exponential(growth_rate);
+            let (t, intensity) = (0.3, 0.2);
+            let elapsed = demography.waiting_time(t, intensity);
+
+            // Numerical integral of 1 / size
+            let steps = 10_000;
+            let dt = elapsed / steps as f64;
+            let integral: f64 = (0..steps)
+                .map(|i| dt / demography.size_at(t + (i as f64 + 0.5) * dt))
+                .sum();
+
+            assert!((integral - intensity).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn epochs() {
+        let demography = Demography::exponential(1.0).add_epoch(1.0, 2.0, 0.0);
+        assert!((demography.size_at(0.5) - (-0.5f64).exp()).abs() < 1e-12);
+        assert_eq!(demography.size_at(3.0), 2.0);
+        assert_eq!(demography.growth_rate_at(3.0), 0.0);
+
+        // Intensity accumulated across the change of epochs
+        let demography = Demography::two_epoch(1.0, 0.5);
+        assert!((demography.waiting_time(0.0, 1.5) - 1.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn never_coalescing() {
+        let demography = Demography::exponential(-1.0);
+
+        assert_eq!(demography.waiting_time(0.0, 2.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn skyline_intensity() {
+        let skyline = Demography::skyline(&[2.0, 0.5, 1.0], &[0.1, 1.0]);
+        assert!((skyline.intensity(0.0, 2.0) - (0.1 / 2.0 + 0.9 / 0.5 + 1.0)).abs() < 1e-12);
+        for &(t, intensity) in &[(0.0, 0.3), (0.05, 2.0), (1.5, 0.1)] {
+            let elapsed = skyline.waiting_time(t, intensity);
+            assert!((skyline.intensity(t, t + elapsed) - intensity).abs() < 1e-12);
+        }
+        let growing = Demography::exponential(1.5).add_epoch(1.0, 0.3, -0.5);
+        let elapsed = growing.waiting_time(0.4, 1.2);
+        assert!((growing.intensity(0.4, 0.4 + elapsed) - 1.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn presets() {
+        let expansion = Demography::expansion(0.2, 4.0);
+        assert_eq!(expansion.size_at(0.1), 1.0);
+        assert_eq!(expansion.size_at(0.3), 0.25);
+
+        // Sizes of the logistic approximation match the closed form
+        let (growth_rate, capacity) = (1.5, 3.0);
+        let logistic = Demography::logistic(growth_rate, capacity);
+        for &t in &[0.07, 0.6, 2.5, 9.0] {
+            let exact = capacity / (1.0 + (capacity - 1.0) * (growth_rate * t).exp());
+            assert!((logistic.size_at(t) / exact - 1.0).abs() < 1e-3, "{}", t);
+        }
+        assert_eq!(Demography::logistic(1.0, 1.0), Demography::constant());
+    }
+
+    #[test]
+    fn invalid_epochs() {
+        let demography = Demography::constant();
+        let error = demography.clone().try_add_epoch(1.0, -2.0, 0.0).unwrap_err();
+        assert_eq!(error, DemographyError::InvalidSize(-2.0));
+        assert_eq!(error.to_string(), "size -2 must be positive and finite");
+        let error = demography.clone().try_add_epoch(1.0, 2.0, f64::NAN).unwrap_err();
+        assert!(matches!(error, DemographyError::InvalidGrowthRate(rate) if rate.is_nan()));
+        assert!(demography.clone().try_add_epoch(0.0, 2.0, 0.0).is_err());
+        assert!(demography.try_add_epoch(f64::INFINITY, 2.0, 0.0).is_err());
+    }
+}
@@ -0,0 +1,539 @@
+//! Genetic differentiation between demes.
+//!
+//! Individuals of a group are split in two demes: the first ``first`` individuals
+//! are from one deme and the rest from the other one, as numbered by
+//! ``StructuredCoalescent``. Hudson's estimator of the fixation index
+//! (Hudson, Slatkin and Maddison, 1992) compares the mean number of differences
+//! between pairs of individuals of the same deme, H_w, averaged over both demes,
+//! with the mean number between pairs from different demes, H_b:
+//! Fst = 1 - H_w / H_b.
+//!
+//! Under symmetric migration between two demes of the same size, where each lineage
+//! moves at rate m, expected coalescence times give Fst = 1 / (1 + 4 m).
+//!
+//! The f-statistics of Patterson et al. (2012) compare allele frequencies between
+//! populations, each one given by a range of individuals: with frequencies a, b, c, d
+//! at a site, f2(A, B) = (a - b)², f3(C; A, B) = (c - a)(c - b) and
+//! f4(A, B; C, D) = (a - b)(c - d), corrected for the finite size of samples and summed
+//! over sites. Under a tree without admixture, f4 is zero when the pairs (A, B) and (C, D)
+//! are on each side of the tree, and f3 is negative only if C is admixed.
+//!
+//! The joint site frequency spectrum of two demes counts sites by their number of derived
+//! alleles in each deme: entry ``[i][j]`` is the number of sites with ``i`` carriers among
+//! the first ``first`` individuals and ``j`` among the rest. Its expectation under a model
+//! is approximated by the mean lengths of branches of simulated genealogies, and it is
+//! written in the ``.fs`` format read by dadi and moments for demographic inference.
+//!
+
+// Types
+use crate::{Genealogy, Haplotypes, MutationModel, StructuredCoalescent};
+use std::ops::Range;
+
+// Traits
+use rand::Rng;
+
+/// Distribution of Fst across replicates, for one migration rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FstDistribution {
+    pub migration_rate: f64,
+    /// Fst of each replicate with differences between demes.
+    pub values: Vec<f64>,
+}
+
+impl FstDistribution {
+    /// Mean of the values, zero if there are none.
+    pub fn mean(&self) -> f64 {
+        match self.values.is_empty() {
+            true => 0.0,
+            false => self.values.iter().sum::<f64>() / self.values.len() as f64,
+        }
+    }
+}
+
+/// Hudson's Fst between the first ``first`` individuals and the rest,
+/// or ``None`` if there are no differences between demes.
+///
+/// # Panics
+///
+/// If some deme has less than two individuals.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{MutationModel, StructuredCoalescent};
+/// use coalescence::differentiation::hudson_fst;
+///
+/// let migration = vec![vec![0.0, 0.05], vec![0.05, 0.0]];
+/// let structured = StructuredCoalescent::new(vec![1.0, 1.0], migration);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = structured.sample_genealogy(&[10, 10], &mut rng);
+/// let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites: 50 }, &mut rng);
+/// let fst = hudson_fst(&haplotypes, 10).unwrap();
+///
+/// assert!(fst <= 1.0);
+/// ```
+pub fn hudson_fst(haplotypes: &Haplotypes, first: usize) -> Option<f64> {
+    let sites = (0..haplotypes.segregating_sites()).map(|site| (1.0, haplotypes.carriers(site)));
+    fst(sites, haplotypes.group_size(), first)
+}
+
+/// Hudson's Fst with the expected number of differences given the genealogy,
+/// i.e. with lengths of branches instead of mutations, or ``None`` if the genealogy has no branches.
+///
+/// # Panics
+///
+/// If some deme has less than two individuals.
+pub fn branch_fst(genealogy: &Genealogy, first: usize) -> Option<f64> {
+    let branches = genealogy.branches();
+    let sites = branches.iter().map(|branch| (branch.length(), &branch.leaves[..]));
+    fst(sites, genealogy.group_size(), first)
+}
+
+/// Distribution of Hudson's Fst for each migration rate, between two demes of the same size
+/// exchanging migrants symmetrically, with ``samples[i]`` individuals from deme ``i``.
+///
+/// # Panics
+///
+/// If some deme has less than two individuals, or if some migration rate is not
+/// positive, as isolated demes never meet.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::MutationModel;
+/// use coalescence::differentiation::fst_distributions;
+///
+/// let mut rng = rand::thread_rng();
+/// let model = MutationModel::Poisson { theta: 5.0 };
+/// let distributions = fst_distributions(&[0.1, 10.0], [5, 5], model, 50, &mut rng);
+///
+/// assert_eq!(distributions.len(), 2);
+/// assert!(distributions[0].mean() > distributions[1].mean());
+/// ```
+pub fn fst_distributions<R>(
+    migration_rates: &[f64],
+    samples: [usize; 2],
+    model: MutationModel,
+    replicates: usize,
+    rng: &mut R,
+) -> Vec<FstDistribution>
+where
+    R: Rng + ?Sized,
+{
+    migration_rates
+        .iter()
+        .map(|&migration_rate| {
+            assert!(migration_rate > 0.0, "Migration rates must be positive.");
+            let migration = vec![vec![0.0, migration_rate], vec![migration_rate, 0.0]];
+            let structured = StructuredCoalescent::new(vec![1.0, 1.0], migration);
+
+            let values = (0..replicates)
+                .filter_map(|_| {
+                    let genealogy = structured.sample_genealogy(&samples, rng);
+                    let haplotypes = genealogy.sample_haplotypes(model, rng);
+                    hudson_fst(&haplotypes, samples[0])
+                })
+                .collect();
+
+            FstDistribution { migration_rate, values }
+        })
+        .collect()
+}
+
+/// Joint site frequency spectrum of the first ``first`` individuals and the rest, with
+/// ``first + 1`` rows and ``n - first + 1`` columns, see the module documentation.
+///
+/// # Panics
+///
+/// If ``first`` is larger than the group.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{MutationModel, StructuredCoalescent};
+/// use coalescence::differentiation::joint_site_frequency_spectrum;
+///
+/// let structured = StructuredCoalescent::secondary_contact(2.0, 0.5, 1.0);
+/// let mut rng = rand::thread_rng();
+/// let genealogy = structured.sample_genealogy(&[4, 6], &mut rng);
+/// let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites: 30 }, &mut rng);
+/// let spectrum = joint_site_frequency_spectrum(&haplotypes, 4);
+///
+/// assert_eq!((spectrum.len(), spectrum[0].len()), (5, 7));
+/// assert_eq!(spectrum.iter().flatten().sum::<usize>(), 30);
+/// ```
+pub fn joint_site_frequency_spectrum(haplotypes: &Haplotypes, first: usize) -> Vec<Vec<usize>> {
+    let mut spectrum = vec![vec![0; haplotypes.group_size() - first + 1]; first + 1];
+    for site in 0..haplotypes.segregating_sites() {
+        let [carriers_1, carriers_2] = split_carriers(haplotypes.carriers(site), first);
+        spectrum[carriers_1][carriers_2] += 1;
+    }
+    spectrum
+}
+
+/// Joint spectrum of lengths of branches, i.e. the expected joint site frequency spectrum
+/// given the genealogy, divided by θ / 2.
+///
+/// # Panics
+///
+/// If ``first`` is larger than the group.
+pub fn branch_joint_spectrum(genealogy: &Genealogy, first: usize) -> Vec<Vec<f64>> {
+    let mut spectrum = vec![vec![0.0; genealogy.group_size() - first + 1]; first + 1];
+    for branch in genealogy.branches() {
+        let [carriers_1, carriers_2] = split_carriers(&branch.leaves, first);
+        spectrum[carriers_1][carriers_2] += branch.length();
+    }
+    spectrum
+}
+
+/// Expected joint site frequency spectrum of two demes with mutation rate θ, with
+/// ``samples[i]`` individuals from deme ``i``, approximated from ``replicates`` genealogies.
+///
+/// # Panics
+///
+/// If the model does not have two demes, or if there are no replicates.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::StructuredCoalescent;
+/// use coalescence::differentiation::expected_joint_spectrum;
+///
+/// // Demes isolated since their split share few polymorphisms
+/// let isolated = StructuredCoalescent::new(vec![1.0, 1.0], vec![vec![0.0; 2]; 2]).add_pulse(4.0, 1, 0, 1.0);
+/// let spectrum = expected_joint_spectrum(&isolated, [3, 3], 2.0, 500, &mut rand::thread_rng());
+///
+/// assert!(spectrum[1][1] < 0.2 * spectrum[3][0]);
+/// ```
+pub fn expected_joint_spectrum<R>(
+    structured: &StructuredCoalescent,
+    samples: [usize; 2],
+    theta: f64,
+    replicates: usize,
+    rng: &mut R,
+) -> Vec<Vec<f64>>
+where
+    R: Rng + ?Sized,
+{
+    assert_eq!(structured.demes(), 2, "There must be two demes.");
+    assert!(replicates > 0, "There must be some replicates.");
+
+    let mut spectrum = vec![vec![0.0; samples[1] + 1]; samples[0] + 1];
+    for _ in 0..replicates {
+        let genealogy = structured.sample_genealogy(&samples, rng);
+        for (row, lengths) in spectrum.iter_mut().zip(branch_joint_spectrum(&genealogy, samples[0])) {
+            for (total, length) in row.iter_mut().zip(lengths) {
+                *total += theta / 2.0 * length / replicates as f64;
+            }
+        }
+    }
+    spectrum
+}
+
+/// Joint spectrum in the ``.fs`` format of dadi and moments: a line with the dimensions,
+/// ``unfolded`` and the names of the demes, a line with the entries by rows, and a line
+/// with the mask, which hides the monomorphic entries ``[0][0]`` and ``[n₁][n₂]``.
+///
+/// # Panics
+///
+/// If the spectrum is empty or not rectangular.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::differentiation::to_dadi;
+///
+/// let spectrum = vec![vec![0, 3], vec![2, 0]];
+/// let fs = to_dadi(&spectrum, ["YRI", "CEU"]);
+///
+/// assert_eq!(fs, "2 2 unfolded \"YRI\" \"CEU\"\n0 3 2 0\n1 0 0 1\n");
+/// ```
+pub fn to_dadi<T: std::fmt::Display>(spectrum: &[Vec<T>], populations: [&str; 2]) -> String {
+    let (rows, columns) = (spectrum.len(), spectrum.first().map(Vec::len).unwrap_or(0));
+    assert!(rows > 0 && columns > 0, "The spectrum must not be empty.");
+    assert!(spectrum.iter().all(|row| row.len() == columns), "The spectrum must be rectangular.");
+
+    let entries: Vec<String> = spectrum.iter().flatten().map(|entry| entry.to_string()).collect();
+    let mask: Vec<&str> = (0..rows * columns)
+        .map(|index| match index == 0 || index == rows * columns - 1 {
+            true => "1",
+            false => "0",
+        })
+        .collect();
+
+    format!(
+        "{} {} unfolded \"{}\" \"{}\"\n{}\n{}\n",
+        rows,
+        columns,
+        populations[0],
+        populations[1],
+        entries.join(" "),
+        mask.join(" ")
+    )
+}
+
+/// Number of carriers among the first ``first`` individuals and among the rest.
+fn split_carriers(carriers: &[usize], first: usize) -> [usize; 2] {
+    let carriers_1 = carriers.iter().filter(|&&carrier| carrier < first).count();
+    [carriers_1, carriers.len() - carriers_1]
+}
+
+/// Fst from weighted sites, each one with its sorted carriers.
+fn fst<'a, I>(sites: I, group_size: usize, first: usize) -> Option<f64>
+where
+    I: Iterator<Item = (f64, &'a [usize])>,
+{
+    let (size_1, size_2) = (first, group_size.saturating_sub(first));
+    assert!(size_1 >= 2 && size_2 >= 2, "Each deme must have at least two individuals.");
+
+    let (mut within_1, mut within_2, mut between) = (0.0, 0.0, 0.0);
+    for (weight, carriers) in sites {
+        let [carriers_1, carriers_2] = split_carriers(carriers, first);
+        within_1 += weight * (carriers_1 * (size_1 - carriers_1)) as f64;
+        within_2 += weight * (carriers_2 * (size_2 - carriers_2)) as f64;
+        between += weight * (carriers_1 * (size_2 - carriers_2) + (size_1 - carriers_1) * carriers_2) as f64;
+    }
+
+    // Mean differences per pair
+
+    let pairs = |size: usize| (size * (size - 1) / 2) as f64;
+    let within = (within_1 / pairs(size_1) + within_2 / pairs(size_2)) / 2.0;
+    let between = between / (size_1 * size_2) as f64;
+
+    match between > 0.0 {
+        true => Some(1.0 - within / between),
+        false => None,
+    }
+}
+
+/// Statistic of allele frequencies between populations, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FStatistic {
+    /// f2(A, B).
+    F2(Range<usize>, Range<usize>),
+    /// f3(C; A, B), with the target population C first.
+    F3(Range<usize>, Range<usize>, Range<usize>),
+    /// f4(A, B; C, D).
+    F4(Range<usize>, Range<usize>, Range<usize>, Range<usize>),
+}
+
+impl FStatistic {
+    fn populations(&self) -> Vec<&Range<usize>> {
+        match self {
+            FStatistic::F2(a, b) => vec![a, b],
+            FStatistic::F3(c, a, b) => vec![c, a, b],
+            FStatistic::F4(a, b, c, d) => vec![a, b, c, d],
+        }
+    }
+
+    /// Value at a site with the given carriers, sorted.
+    fn site(&self, carriers: &[usize]) -> f64 {
+        // Frequency and unbiased estimate of its variance due to sampling
+        let populations = self.populations();
+        let statistics: Vec<(f64, f64)> = populations
+            .iter()
+            .map(|population| {
+                let size = population.len() as f64;
+                let count = carriers.iter().filter(|carrier| population.contains(carrier)).count();
+                let frequency = count as f64 / size;
+                (frequency, frequency * (1.0 - frequency) / (size - 1.0))
+            })
+            .collect();
+        let frequency = |i: usize| statistics[i].0;
+        let correction = |i: usize| statistics[i].1;
+
+        match self {
+            FStatistic::F2(..) => (frequency(0) - frequency(1)).powi(2) - correction(0) - correction(1),
+            FStatistic::F3(..) => (frequency(0) - frequency(1)) * (frequency(0) - frequency(2)) - correction(0),
+            FStatistic::F4(..) => (frequency(0) - frequency(1)) * (frequency(2) - frequency(3)),
+        }
+    }
+
+    fn check(&self, group_size: usize) {
+        for population in self.populations() {
+            assert!(population.len() >= 2, "Each population must have at least two individuals.");
+            assert!(population.end <= group_size, "Populations must be within the group.");
+        }
+    }
+}
+
+/// Range of individuals sampled from each deme, as numbered by ``StructuredCoalescent``.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::differentiation::deme_ranges;
+///
+/// assert_eq!(deme_ranges(&[2, 0, 3]), vec![0..2, 2..2, 2..5]);
+/// ```
+pub fn deme_ranges(samples: &[usize]) -> Vec<Range<usize>> {
+    let mut start = 0;
+    samples
+        .iter()
+        .map(|&size| {
+            start += size;
+            (start - size)..start
+        })
+        .collect()
+}
+
+/// f-statistic summed over segregating sites.
+///
+/// # Panics
+///
+/// If some population has less than two individuals, or is not within the group.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::MutationModel;
+/// use coalescence::differentiation::{f_statistic, FStatistic};
+///
+/// // Genealogy ((0, 1), (2, 3)): only sites fixed in one population count
+/// let genealogy = coalescence::fixtures::balanced();
+/// let mut rng = rand::thread_rng();
+/// let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites: 20 }, &mut rng);
+/// let fixed = (0..20).filter(|&site| haplotypes.carriers(site).len() == 2).count();
+///
+/// assert!((f_statistic(&haplotypes, &FStatistic::F2(0..2, 2..4)) - fixed as f64).abs() < 1e-12);
+/// ```
+pub fn f_statistic(haplotypes: &Haplotypes, statistic: &FStatistic) -> f64 {
+    statistic.check(haplotypes.group_size());
+    (0..haplotypes.segregating_sites())
+        .map(|site| statistic.site(haplotypes.carriers(site)))
+        .sum()
+}
+
+/// Expected f-statistic given the genealogy, with each branch weighted by its length
+/// instead of mutations: its mean over genealogies is the expected value of
+/// ``f_statistic`` for the model, up to the factor θ / 2.
+///
+/// # Panics
+///
+/// If some population has less than two individuals, or is not within the group.
+///
+/// # Examples
+///
+/// Pulses with probability one merge demes: A and B split at time 1, C and D at time 2,
+/// and both pairs at time 3.
+/// ```
+/// use coalescence::StructuredCoalescent;
+/// use coalescence::differentiation::{branch_f_statistic, deme_ranges, FStatistic};
+///
+/// let isolated = vec![vec![0.0; 4]; 4];
+/// let structured = StructuredCoalescent::new(vec![1.0; 4], isolated)
+///     .add_pulse(1.0, 1, 0, 1.0)
+///     .add_pulse(2.0, 3, 2, 1.0)
+///     .add_pulse(3.0, 2, 0, 1.0);
+///
+/// let samples = [5, 5, 5, 5];
+/// let ranges = deme_ranges(&samples);
+/// let statistic = FStatistic::F2(ranges[0].clone(), ranges[2].clone());
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = structured.sample_genealogy(&samples, &mut rng);
+///
+/// assert!(branch_f_statistic(&genealogy, &statistic) > 0.0);
+/// ```
+pub fn branch_f_statistic(genealogy: &Genealogy, statistic: &FStatistic) -> f64 {
+    statistic.check(genealogy.group_size());
+    genealogy
+        .branches()
+        .iter()
+        .map(|branch| branch.length() * statistic.site(&branch.leaves))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn island_model() {
+        // Ratio of mean times within and between demes: Fst = 1 / (1 + 4 m)
+        let migration_rate = 0.25;
+        let migration = vec![vec![0.0, migration_rate], vec![migration_rate, 0.0]];
+        let structured = StructuredCoalescent::new(vec![1.0, 1.0], migration);
+
+        let mut rng = rand::thread_rng();
+        let (mut within, mut between) = (0.0, 0.0);
+        for _ in 0..4000 {
+            let genealogy = structured.sample_genealogy(&[2, 2], &mut rng);
+            within += (genealogy.divergence(0, 1).unwrap() + genealogy.divergence(2, 3).unwrap()) / 2.0;
+            between += genealogy.divergence(0, 2).unwrap();
+        }
+        assert!((1.0 - within / between - 0.5).abs() < 0.05);
+
+        // Fully differentiated demes
+        let haplotypes = Haplotypes::new(4, vec![(0.5, vec![0, 1])]);
+        assert_eq!(hudson_fst(&haplotypes, 2), Some(1.0));
+        assert_eq!(hudson_fst(&Haplotypes::new(4, Vec::new()), 2), None);
+        let genealogy = crate::fixtures::balanced();
+        assert!(branch_fst(&genealogy, 2).unwrap() > 0.5);
+    }
+
+    #[test]
+    fn joint_spectra() {
+        // Balanced: {0}, {1} and {0, 1} in the first deme, {2}, {3} and {2, 3} in the other
+        let genealogy = crate::fixtures::balanced();
+        let spectrum = branch_joint_spectrum(&genealogy, 2);
+        assert_eq!(spectrum, vec![vec![0.0, 4.0, 2.0], vec![2.0, 0.0, 0.0], vec![3.0, 0.0, 0.0]]);
+
+        // Margins of the joint spectrum are the frequency spectrum of all individuals
+        let mut rng = rand::thread_rng();
+        let migration = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+        let genealogy = StructuredCoalescent::new(vec![1.0, 1.0], migration).sample_genealogy(&[3, 4], &mut rng);
+        let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta: 20.0 }, &mut rng);
+        let joint = joint_site_frequency_spectrum(&haplotypes, 3);
+        let mut sfs = vec![0; 6];
+        for (i, row) in joint.iter().enumerate() {
+            for (j, &count) in row.iter().enumerate().filter(|&(j, _)| 0 < i + j && i + j < 7) {
+                sfs[i + j - 1] += count;
+            }
+        }
+        assert_eq!(sfs, haplotypes.site_frequency_spectrum());
+        assert_eq!(joint[0][0] + joint[3][4], 0);
+    }
+
+    #[test]
+    fn f_statistics() {
+        let mut rng = rand::thread_rng();
+        let replicates = 500;
+        let mean = |structured: &StructuredCoalescent, samples: &[usize], statistic: &FStatistic, rng: &mut _| {
+            (0..replicates)
+                .map(|_| branch_f_statistic(&structured.sample_genealogy(samples, rng), statistic))
+                .sum::<f64>() / replicates as f64
+        };
+
+        // Tree ((A, B), (C, D)): f4 vanishes, while f2 grows with the time of the split
+        let isolated = vec![vec![0.0; 4]; 4];
+        let tree = StructuredCoalescent::new(vec![1.0; 4], isolated)
+            .add_pulse(0.5, 1, 0, 1.0)
+            .add_pulse(0.5, 3, 2, 1.0)
+            .add_pulse(2.0, 2, 0, 1.0);
+        let samples = [4, 4, 4, 4];
+        let ranges = deme_ranges(&samples);
+        let f4 = FStatistic::F4(ranges[0].clone(), ranges[1].clone(), ranges[2].clone(), ranges[3].clone());
+        let f2_close = FStatistic::F2(ranges[0].clone(), ranges[1].clone());
+        let f2_far = FStatistic::F2(ranges[0].clone(), ranges[2].clone());
+        let (f4, f2_close, f2_far) = (
+            mean(&tree, &samples, &f4, &mut rng),
+            mean(&tree, &samples, &f2_close, &mut rng),
+            mean(&tree, &samples, &f2_far, &mut rng),
+        );
+        assert!(f2_close > 0.0 && f2_far > 2.0 * f2_close);
+        assert!(f4.abs() < 0.1 * f2_far);
+
+        // C is a recent mix of A and B, which split long ago: f3(C; A, B) is negative
+        let isolated = vec![vec![0.0; 3]; 3];
+        let admixed = StructuredCoalescent::new(vec![1.0; 3], isolated)
+            .add_pulse(0.05, 2, 0, 0.5)
+            .add_pulse(0.05, 2, 1, 1.0)
+            .add_pulse(3.0, 1, 0, 1.0);
+        let samples = [6, 6, 6];
+        let ranges = deme_ranges(&samples);
+        let f3 = FStatistic::F3(ranges[2].clone(), ranges[0].clone(), ranges[1].clone());
+        assert!(mean(&admixed, &samples, &f3, &mut rng) < 0.0);
+    }
+}
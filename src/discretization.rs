@@ -0,0 +1,234 @@
+//! Discretization of the time to the common ancestor of a pair, as in coalescent hidden
+//! Markov models like the PSMC (Li and Durbin, 2011).
+//!
+//! Along a genome, the hidden state of such models is the interval of a grid of times
+//! which contains the time to the common ancestor of a pair of genomes. Transitions and
+//! emissions depend on the probability that the pair coalesces in each interval, on the
+//! probability that it coalesces there given that it did not coalesce before, and on the
+//! mean time within each interval. Under a ``Demography``, the first two are exact: with
+//! ``Λ(t)`` the intensity of coalescence up to time ``t``, a pair coalesces after ``t`` with
+//! probability ``e^{-Λ(t)}``. All three are also estimated by simulation, to check them or
+//! to prototype models without closed forms.
+//!
+//! The last interval of a grid has no upper bound.
+//!
+
+// Types
+use crate::Demography;
+use rand_distr::Exp1;
+
+// Traits
+use rand::Rng;
+
+/// Increasing times, starting at zero, splitting time into intervals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeGrid {
+    boundaries: Vec<f64>, // lower bound of each interval
+}
+
+impl TimeGrid {
+    /// Grid whose intervals start at the given times.
+    ///
+    /// # Panics
+    ///
+    /// If the first time is not zero, or if times are not increasing and finite.
+    pub fn new(boundaries: Vec<f64>) -> Self {
+        assert_eq!(boundaries.first(), Some(&0.0), "The first interval must start at zero.");
+        assert!(
+            boundaries.windows(2).all(|pair| pair[0] < pair[1]) && boundaries.iter().all(|time| time.is_finite()),
+            "Times must be increasing and finite."
+        );
+
+        TimeGrid { boundaries }
+    }
+
+    /// Grid of the PSMC, with ``intervals`` intervals whose lengths grow exponentially,
+    /// the last one starting at ``max_time``: interval ``i`` starts at
+    /// ``0.1 (e^{i log(1 + 10 max_time) / (intervals - 1)} - 1)``.
+    ///
+    /// # Panics
+    ///
+    /// If there are less than two intervals, or if ``max_time`` is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::discretization::TimeGrid;
+    ///
+    /// let grid = TimeGrid::psmc(64, 15.0);
+    ///
+    /// assert_eq!(grid.intervals(), 64);
+    /// assert!((grid.boundaries()[63] - 15.0).abs() < 1e-9);
+    /// ```
+    pub fn psmc(intervals: usize, max_time: f64) -> Self {
+        assert!(intervals > 1, "There must be at least two intervals.");
+        assert!(max_time > 0.0 && max_time.is_finite(), "The maximum time must be positive and finite.");
+
+        let scale = (1.0 + 10.0 * max_time).ln() / (intervals - 1) as f64;
+        TimeGrid::new((0..intervals).map(|i| 0.1 * (scale * i as f64).exp_m1()).collect())
+    }
+
+    /// Grid of ``intervals`` intervals in which a pair coalesces with the same probability,
+    /// in a population of constant size one.
+    ///
+    /// # Panics
+    ///
+    /// If there are no intervals.
+    pub fn quantiles(intervals: usize) -> Self {
+        assert!(intervals > 0, "There must be at least one interval.");
+
+        TimeGrid::new((0..intervals).map(|i| -(-(i as f64) / intervals as f64).ln_1p()).collect())
+    }
+
+    /// Number of intervals.
+    pub fn intervals(&self) -> usize {
+        self.boundaries.len()
+    }
+
+    /// Time at which each interval starts.
+    pub fn boundaries(&self) -> &[f64] {
+        &self.boundaries
+    }
+
+    /// Interval containing time ``t``.
+    ///
+    /// # Panics
+    ///
+    /// If ``t`` is negative.
+    pub fn interval_of(&self, t: f64) -> usize {
+        assert!(t >= 0.0, "Times must be non-negative.");
+
+        self.boundaries.partition_point(|&boundary| boundary <= t) - 1
+    }
+
+    /// Probability that a pair coalesces in each interval.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::discretization::TimeGrid;
+    /// use coalescence::Demography;
+    ///
+    /// let grid = TimeGrid::quantiles(4);
+    /// let probabilities = grid.coalescence_probabilities(&Demography::constant());
+    ///
+    /// assert!(probabilities.iter().all(|probability| (probability - 0.25).abs() < 1e-12));
+    /// ```
+    pub fn coalescence_probabilities(&self, demography: &Demography) -> Vec<f64> {
+        let survival = self.survival(demography);
+        survival
+            .iter()
+            .enumerate()
+            .map(|(interval, probability)| probability - survival.get(interval + 1).cloned().unwrap_or(0.0))
+            .collect()
+    }
+
+    /// Probability that a pair coalesces in each interval, given that it did not coalesce
+    /// before, the last one being one.
+    pub fn conditional_probabilities(&self, demography: &Demography) -> Vec<f64> {
+        let mut probabilities: Vec<f64> = self
+            .boundaries
+            .windows(2)
+            .map(|pair| -(-demography.intensity(pair[0], pair[1])).exp_m1())
+            .collect();
+        probabilities.push(1.0);
+
+        probabilities
+    }
+
+    /// Probability that a pair coalesces in each interval and mean time of coalescence
+    /// within each interval, estimated from ``replicates`` pairs. Intervals without
+    /// coalescences have mean time ``NaN``.
+    ///
+    /// # Panics
+    ///
+    /// If there are no replicates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::discretization::TimeGrid;
+    /// use coalescence::Demography;
+    ///
+    /// let grid = TimeGrid::psmc(8, 4.0);
+    /// let demography = Demography::exponential(1.0);
+    /// let (probabilities, mean_times) = grid.simulate(&demography, 10_000, &mut rand::thread_rng());
+    ///
+    /// let expected = grid.coalescence_probabilities(&demography);
+    /// assert!((probabilities[3] - expected[3]).abs() < 0.03);
+    /// assert!(grid.boundaries()[3] <= mean_times[3] && mean_times[3] < grid.boundaries()[4]);
+    /// ```
+    pub fn simulate<R>(&self, demography: &Demography, replicates: usize, rng: &mut R) -> (Vec<f64>, Vec<f64>)
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(replicates > 0, "There must be some replicates.");
+
+        let mut counts = vec![0usize; self.intervals()];
+        let mut totals = vec![0.0; self.intervals()];
+        for _ in 0..replicates {
+            let time = demography.waiting_time(0.0, rng.sample(Exp1));
+            if time.is_finite() {
+                let interval = self.interval_of(time);
+                counts[interval] += 1;
+                totals[interval] += time;
+            }
+        }
+
+        let probabilities = counts.iter().map(|&count| count as f64 / replicates as f64).collect();
+        let mean_times = totals.iter().zip(&counts).map(|(total, &count)| total / count as f64).collect();
+
+        (probabilities, mean_times)
+    }
+
+    /// Probability that a pair has not coalesced at the start of each interval.
+    fn survival(&self, demography: &Demography) -> Vec<f64> {
+        self.boundaries.iter().map(|&boundary| (-demography.intensity(0.0, boundary)).exp()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grids() {
+        let grid = TimeGrid::new(vec![0.0, 0.5, 2.0]);
+        assert_eq!(grid.interval_of(0.0), 0);
+        assert_eq!(grid.interval_of(0.5), 1);
+        assert_eq!(grid.interval_of(100.0), 2);
+
+        let psmc = TimeGrid::psmc(4, 1.0);
+        assert_eq!(psmc.boundaries()[0], 0.0);
+        assert!((psmc.boundaries()[1] - 0.1 * (11f64.ln() / 3.0).exp_m1()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn analytic_and_simulated() {
+        // Constant size one: exponential times
+        let grid = TimeGrid::new(vec![0.0, 1.0]);
+        let demography = Demography::constant();
+        let probabilities = grid.coalescence_probabilities(&demography);
+        assert!((probabilities[0] - (1.0 - (-1f64).exp())).abs() < 1e-12);
+        assert_eq!(grid.conditional_probabilities(&demography)[1], 1.0);
+
+        let demography = Demography::two_epoch(0.3, 0.2);
+        let grid = TimeGrid::psmc(10, 3.0);
+        let expected = grid.coalescence_probabilities(&demography);
+        assert!((expected.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // Coalescing in an interval is surviving all the previous ones
+        let conditional = grid.conditional_probabilities(&demography);
+        let mut survival = 1.0;
+        for (probability, conditional) in expected.iter().zip(&conditional) {
+            assert!((probability - survival * conditional).abs() < 1e-12);
+            survival *= 1.0 - conditional;
+        }
+
+        let (probabilities, mean_times) = grid.simulate(&demography, 40_000, &mut rand::thread_rng());
+        for interval in 0..grid.intervals() {
+            assert!((probabilities[interval] - expected[interval]).abs() < 0.01, "{}", interval);
+            assert!(mean_times[interval].is_nan() || mean_times[interval] >= grid.boundaries()[interval]);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Patristic distances between individuals, and their reconstruction into trees.
+//!
+//! The distance between two individuals along the genealogy is an additive metric,
+//! from which neighbor-joining (Saitou and Nei, 1987) recovers the topology exactly,
+//! without its root. Topologies are compared through their splits: each inner branch
+//! of an unrooted tree splits individuals in two, and is written as the side that does
+//! not contain individual 0, so that two trees have the same topology if and only if
+//! they have the same splits.
+//!
+//! Distance matrices are written in the PHYLIP format, read by most phylogenetic
+//! software: the number of individuals on a first line, then one line per individual
+//! with its name padded to ten characters and its distances to all individuals.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+use crate::Genealogy;
+
+impl Genealogy {
+    /// Matrix of distances between individuals along the genealogy, see ``divergence``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let matrix = coalescence::fixtures::balanced().to_matrix();
+    ///
+    /// assert_eq!(matrix[0], vec![0.0, 2.0, 8.0, 8.0]);
+    /// ```
+    pub fn to_matrix(&self) -> Vec<Vec<f64>> {
+        // Each event sets the distances between the individuals of the lineages it merges, in
+        // a flat matrix, which takes quadratic time overall instead of a search per pair
+        let group_size = self.group_size();
+        let sampling_times = self.sampling_times();
+        let mut matrix = vec![0.0; group_size * group_size];
+        let mut lineages = UnionFind::new(group_size);
+        let mut members: Vec<Vec<usize>> = (0..group_size).map(|individual| vec![individual]).collect();
+        for (time, indexes) in self.mergers() {
+            let merged: Vec<usize> = indexes.iter().map(|&index| lineages.find(index)).collect();
+            for (position, &first) in merged.iter().enumerate() {
+                for &second in &merged[position + 1..] {
+                    for &one in &members[first] {
+                        for &other in &members[second] {
+                            let distance = 2.0 * time - sampling_times[one] - sampling_times[other];
+                            matrix[one * group_size + other] = distance;
+                            matrix[other * group_size + one] = distance;
+                        }
+                    }
+                }
+            }
+            let representative = merged.iter().fold(merged[0], |representative, &lineage| lineages.union(representative, lineage));
+            for &lineage in merged.iter().filter(|&&lineage| lineage != representative) {
+                let joined = std::mem::take(&mut members[lineage]);
+                members[representative].extend(joined);
+            }
+        }
+
+        matrix.chunks(group_size.max(1)).map(|row| row.to_vec()).collect()
+    }
+
+    /// Matrix of distances in the PHYLIP format, individuals being named by their index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let genealogy = coalescence::Genealogy::from_events(2, &[(0.5, [0, 1])]).unwrap();
+    ///
+    /// assert_eq!(genealogy.to_phylip(), "2\n0          0 1\n1          1 0\n");
+    /// ```
+    pub fn to_phylip(&self) -> String {
+        let matrix = self.to_matrix();
+        let mut phylip = format!("{}\n", matrix.len());
+        for (index, row) in matrix.iter().enumerate() {
+            let distances: Vec<String> = row.iter().map(|distance| distance.to_string()).collect();
+            phylip.push_str(&format!("{:<10} {}\n", index, distances.join(" ")));
+        }
+
+        phylip
+    }
+
+    /// Splits of the unrooted topology of the genealogy, in the form of
+    /// ``neighbor_joining``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // The root of a balanced tree of four individuals is on its only inner branch
+    /// assert_eq!(coalescence::fixtures::balanced().splits(), vec![vec![2, 3]]);
+    /// ```
+    pub fn splits(&self) -> Vec<Vec<usize>> {
+        let group_size = self.group_size();
+        let clusters = self.branches().into_iter().map(|branch| branch.leaves).collect();
+        normalize(clusters, group_size)
+    }
+}
+
+/// Splits of the unrooted tree reconstructed by neighbor-joining from a matrix of
+/// distances, as sorted lists of individuals, in increasing order.
+///
+/// # Panics
+///
+/// If the matrix is not square.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::distances::neighbor_joining;
+///
+/// let genealogy = coalescence::Coalescent::sampler(20).sample_genealogy(&mut rand::thread_rng());
+///
+/// assert_eq!(neighbor_joining(&genealogy.to_matrix()), genealogy.splits());
+/// ```
+pub fn neighbor_joining(distances: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let group_size = distances.len();
+    assert!(distances.iter().all(|row| row.len() == group_size), "The matrix must be square.");
+
+    let mut distances = distances.to_vec();
+    let mut clusters: Vec<Vec<usize>> = (0..group_size).map(|index| vec![index]).collect();
+    let mut joined = Vec::new();
+    while clusters.len() > 3 {
+        // Pair minimizing (m - 2) d(i, j) - r(i) - r(j)
+        let m = clusters.len();
+        let totals: Vec<f64> = distances.iter().map(|row| row.iter().sum()).collect();
+        let mut best = (f64::INFINITY, 0, 1);
+        for first in 0..m {
+            for second in (first + 1)..m {
+                let criterion = (m - 2) as f64 * distances[first][second] - totals[first] - totals[second];
+                if criterion < best.0 {
+                    best = (criterion, first, second);
+                }
+            }
+        }
+
+        // The new cluster replaces the first one, the second one being removed
+        let (_, first, second) = best;
+        let between = distances[first][second];
+        let mut merged: Vec<f64> = (0..m)
+            .map(|other| (distances[first][other] + distances[second][other] - between) / 2.0)
+            .collect();
+        merged[first] = 0.0;
+        for (row, &distance) in distances.iter_mut().zip(&merged) {
+            row[first] = distance;
+        }
+        distances[first] = merged;
+        distances.swap_remove(second);
+        for row in distances.iter_mut() {
+            row.swap_remove(second);
+        }
+        let cluster = clusters.swap_remove(second);
+        clusters[first].extend(cluster);
+        joined.push(clusters[first].clone());
+    }
+
+    normalize(joined, group_size)
+}
+
+/// Nontrivial splits of clusters, as the side without individual 0, sorted and deduplicated.
+fn normalize(clusters: Vec<Vec<usize>>, group_size: usize) -> Vec<Vec<usize>> {
+    let mut splits: Vec<Vec<usize>> = clusters
+        .into_iter()
+        .map(|mut cluster| {
+            cluster.sort_unstable();
+            match cluster.binary_search(&0).is_ok() {
+                true => (0..group_size).filter(|index| cluster.binary_search(index).is_err()).collect(),
+                false => cluster,
+            }
+        })
+        .filter(|split| split.len() > 1 && split.len() + 1 < group_size)
+        .collect();
+    splits.sort_unstable();
+    splits.dedup();
+
+    splits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconstructs_topologies() {
+        let mut rng = rand::thread_rng();
+        for &group_size in &[3, 4, 7, 30] {
+            let coalescent = crate::Coalescent::sampler(group_size);
+            for _ in 0..20 {
+                let genealogy = coalescent.sample_genealogy(&mut rng);
+                let splits = genealogy.splits();
+                assert_eq!(splits.len(), group_size - 3);
+                assert_eq!(neighbor_joining(&genealogy.to_matrix()), splits);
+            }
+        }
+        assert_eq!(crate::fixtures::caterpillar(5).splits(), vec![vec![2, 3, 4], vec![3, 4]]);
+    }
+
+    #[test]
+    fn matrices_of_heterochronous_samples() {
+        let genealogy = crate::Coalescent::sampler(12).sample_genealogy(&mut rand::thread_rng());
+        let genealogy = genealogy.with_sampling_times((0..12).map(|i| i as f64 / 1000.0).collect());
+        let matrix = genealogy.to_matrix();
+        for (first, row) in matrix.iter().enumerate() {
+            for (second, &distance) in row.iter().enumerate() {
+                let expected = genealogy.divergence(first, second).unwrap();
+                assert!((distance - expected).abs() < 1e-12, "{} {}", first, second);
+            }
+        }
+        assert_eq!(crate::Genealogy::from_events(1, &[]).unwrap().to_matrix(), vec![vec![0.0]]);
+
+        // Mergers set the distances between all pairs of their lineages
+        let mergers = crate::Genealogy::from_mergers(5, &[(1.0, vec![0, 2, 3]), (3.0, vec![1, 4, 0])]).unwrap();
+        assert_eq!(mergers.to_matrix()[2], vec![2.0, 6.0, 0.0, 2.0, 6.0]);
+        assert_eq!(mergers.splits(), vec![vec![1, 4]]);
+    }
+
+    #[test]
+    fn phylip() {
+        let phylip = crate::fixtures::balanced().to_phylip();
+        let lines: Vec<&str> = phylip.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "4");
+        assert_eq!(lines[3], "2          8 8 0 4");
+    }
+}
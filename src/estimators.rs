@@ -0,0 +1,263 @@
+//! Estimators of the mutation rate θ from the site frequency spectrum.
+//!
+//! With ``ξ_i`` the number of sites where ``i`` of the ``n`` individuals carry the
+//! derived allele, every estimator here is a weighted average
+//!
+//! ``θ_ω = Σ ω_i i ξ_i / Σ ω_i``,
+//!
+//! which is unbiased under the standard neutral model since ``E[ξ_i] = θ / i``.
+//! Watterson's estimator has weights ``1 / i``, Tajima's π has weights ``n - i``,
+//! Fay and Wu's θ_H has weights ``i`` and Zeng's θ_L has weights one (Achaz, 2009).
+//!
+//! Variances come from the covariances of the spectrum of Fu (1995):
+//! ``Var(θ_ω) = α θ + β θ²``, where θ and θ² are estimated without bias from
+//! the number of segregating sites, as in Tajima's D.
+//!
+//! Estimators take a spectrum as a slice, so they apply equally to simulated data,
+//! see ``Haplotypes::site_frequency_spectrum``, and to spectra read from elsewhere.
+//!
+
+/// Estimate of θ with its estimated variance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThetaEstimate {
+    pub value: f64,
+    pub variance: f64,
+}
+
+/// Watterson's estimator, from the number of segregating sites.
+///
+/// # Panics
+///
+/// In the same cases as ``weighted``.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::estimators::watterson;
+///
+/// // 6 segregating sites in 4 individuals: θ = 6 / (1 + 1/2 + 1/3)
+/// let estimate = watterson(&[3, 2, 1]);
+/// assert!((estimate.value - 6.0 / (11.0 / 6.0)).abs() < 1e-12);
+/// ```
+pub fn watterson(sfs: &[usize]) -> ThetaEstimate {
+    weighted(sfs, &(1..=sfs.len()).map(|i| 1.0 / i as f64).collect::<Vec<f64>>())
+}
+
+/// Tajima's estimator, the mean number of differences between pairs of individuals.
+///
+/// # Panics
+///
+/// In the same cases as ``weighted``.
+pub fn pi(sfs: &[usize]) -> ThetaEstimate {
+    let n = sfs.len() + 1;
+    weighted(sfs, &(1..n).map(|i| (n - i) as f64).collect::<Vec<f64>>())
+}
+
+/// Fay and Wu's estimator θ_H, which gives most weight to derived alleles at high frequency.
+///
+/// # Panics
+///
+/// In the same cases as ``weighted``.
+pub fn fay_wu_h(sfs: &[usize]) -> ThetaEstimate {
+    weighted(sfs, &(1..=sfs.len()).map(|i| i as f64).collect::<Vec<f64>>())
+}
+
+/// Zeng's estimator θ_L, the mean number of derived alleles per site.
+///
+/// # Panics
+///
+/// In the same cases as ``weighted``.
+pub fn zeng_l(sfs: &[usize]) -> ThetaEstimate {
+    weighted(sfs, &vec![1.0; sfs.len()])
+}
+
+/// General estimator with weights ``ω_i``, the entry ``i - 1`` of ``weights``
+/// being the weight of sites with ``i`` derived alleles.
+///
+/// # Panics
+///
+/// If there are less than two individuals, i.e. the spectrum is empty, if there is
+/// not one weight per entry of the spectrum, or if some weight is negative or not
+/// finite, or all weights are zero.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{MutationModel, estimators};
+///
+/// let genealogy = coalescence::fixtures::caterpillar(10);
+/// let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta: 5.0 }, &mut rand::thread_rng());
+/// let sfs = haplotypes.site_frequency_spectrum();
+///
+/// let pi = estimators::pi(&sfs);
+/// assert!((pi.value - haplotypes.mean_pairwise_differences()).abs() < 1e-9);
+/// assert!(pi.variance >= 0.0);
+/// ```
+pub fn weighted(sfs: &[usize], weights: &[f64]) -> ThetaEstimate {
+    assert!(!sfs.is_empty(), "There must be at least two individuals.");
+    assert_eq!(weights.len(), sfs.len(), "There must be one weight per entry of the spectrum.");
+    assert!(weights.iter().all(|&weight| weight >= 0.0 && weight.is_finite()), "Weights must be non-negative and finite.");
+    let total: f64 = weights.iter().sum();
+    assert!(total > 0.0, "Some weight must be positive.");
+
+    let value = sfs
+        .iter()
+        .zip(weights)
+        .enumerate()
+        .map(|(index, (&count, &weight))| weight * (index + 1) as f64 * count as f64)
+        .sum::<f64>()
+        / total;
+
+    // Unbiased estimates of θ and θ² from the segregating sites
+    let n = sfs.len() + 1;
+    let (a1, a2) = harmonics(n);
+    let sites = sfs.iter().sum::<usize>() as f64;
+    let theta = sites / a1;
+    let theta_squared = sites * (sites - 1.0).max(0.0) / (a1 * a1 + a2);
+
+    let (alpha, beta) = variance_coefficients(n, weights);
+    ThetaEstimate { value, variance: alpha * theta + beta * theta_squared }
+}
+
+/// Coefficients ``α`` and ``β`` of the variance ``α θ + β θ²`` of the estimator with the
+/// given weights in a group of ``n`` individuals, under the standard neutral model.
+///
+/// # Panics
+///
+/// If there is not one weight per entry of the spectrum, i.e. ``n - 1`` of them, or all weights are zero.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::estimators::variance_coefficients;
+///
+/// // Watterson: θ / a1 + a2 θ² / a1²
+/// let (alpha, beta) = variance_coefficients(3, &[1.0, 0.5]);
+/// assert!((alpha - 1.0 / 1.5).abs() < 1e-12);
+/// assert!((beta - 1.25 / 2.25).abs() < 1e-12);
+/// ```
+pub fn variance_coefficients(n: usize, weights: &[f64]) -> (f64, f64) {
+    assert_eq!(weights.len() + 1, n, "There must be one weight per entry of the spectrum.");
+    let total: f64 = weights.iter().sum();
+    assert!(total > 0.0, "Some weight must be positive.");
+
+    // Coefficient of each entry of the spectrum
+    let coefficients: Vec<f64> = weights.iter().enumerate().map(|(index, weight)| weight * (index + 1) as f64 / total).collect();
+
+    let alpha = coefficients.iter().enumerate().map(|(index, c)| c * c / (index + 1) as f64).sum();
+    let covariances = sfs_covariances(n);
+    let beta = (0..n - 1)
+        .map(|i| (0..n - 1).map(|j| coefficients[i] * coefficients[j] * covariances[i][j]).sum::<f64>())
+        .sum();
+
+    (alpha, beta)
+}
+
+/// Coefficients ``σ_ij`` of θ² in the covariances of the spectrum of ``n`` individuals,
+/// ``Cov(ξ_i, ξ_j) = δ_ij θ / i + σ_ij θ²`` (Fu, 1995), the entry ``[i - 1][j - 1]`` being σ_ij.
+///
+/// # Panics
+///
+/// If there are less than two individuals.
+pub fn sfs_covariances(n: usize) -> Vec<Vec<f64>> {
+    assert!(n > 1, "There must be at least two individuals.");
+
+    // a(i) = 1 + 1/2 + ... + 1/(i - 1)
+    let a: Vec<f64> = (0..=n + 1)
+        .scan(0.0, |sum, i| {
+            let current = *sum;
+            if i > 0 {
+                *sum += 1.0 / i as f64;
+            }
+            Some(current)
+        })
+        .collect();
+    let a = |i: usize| a[i];
+    let nf = n as f64;
+    let beta = |i: usize| {
+        let (i_f, m) = (i as f64, (n - i) as f64);
+        2.0 * nf / ((nf - i_f + 1.0) * m) * (a(n + 1) - a(i)) - 2.0 / m
+    };
+
+    let mut sigma = vec![vec![0.0; n - 1]; n - 1];
+    for i in 1..n {
+        let i_f = i as f64;
+        sigma[i - 1][i - 1] = match (2 * i).cmp(&n) {
+            std::cmp::Ordering::Less => beta(i + 1),
+            std::cmp::Ordering::Equal => 2.0 * (a(n) - a(i)) / (nf - i_f) - 1.0 / (i_f * i_f),
+            std::cmp::Ordering::Greater => beta(i) - 1.0 / (i_f * i_f),
+        };
+        for j in 1..i {
+            let j_f = j as f64;
+            let value = match (i + j).cmp(&n) {
+                std::cmp::Ordering::Less => (beta(i + 1) - beta(i)) / 2.0,
+                std::cmp::Ordering::Equal => {
+                    (a(n) - a(i)) / (nf - i_f) + (a(n) - a(j)) / (nf - j_f)
+                        - (beta(i) + beta(j + 1)) / 2.0
+                        - 1.0 / (i_f * j_f)
+                },
+                std::cmp::Ordering::Greater => (beta(j) - beta(j + 1)) / 2.0 - 1.0 / (i_f * j_f),
+            };
+            sigma[i - 1][j - 1] = value;
+            sigma[j - 1][i - 1] = value;
+        }
+    }
+
+    sigma
+}
+
+/// Sums ``a1 = Σ 1 / i`` and ``a2 = Σ 1 / i²`` for ``i`` from 1 to ``n - 1``.
+pub(crate) fn harmonics(n: usize) -> (f64, f64) {
+    (1..n).fold((0.0, 0.0), |(a1, a2), i| (a1 + 1.0 / i as f64, a2 + 1.0 / (i * i) as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variances_agree_with_closed_forms() {
+        for &n in &[2, 5, 10, 11, 30] {
+            let nf = n as f64;
+            let (a1, a2) = harmonics(n);
+
+            // Watterson
+            let weights: Vec<f64> = (1..n).map(|i| 1.0 / i as f64).collect();
+            let (alpha, beta) = variance_coefficients(n, &weights);
+            assert!((alpha - 1.0 / a1).abs() < 1e-9);
+            assert!((beta - a2 / (a1 * a1)).abs() < 1e-9);
+
+            // Tajima (1983)
+            let weights: Vec<f64> = (1..n).map(|i| (n - i) as f64).collect();
+            let (alpha, beta) = variance_coefficients(n, &weights);
+            assert!((alpha - (nf + 1.0) / (3.0 * (nf - 1.0))).abs() < 1e-9);
+            assert!((beta - 2.0 * (nf * nf + nf + 3.0) / (9.0 * nf * (nf - 1.0))).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn estimators_are_unbiased() {
+        let (n, theta, samples) = (8, 4.0, 4000);
+        let coalescent = crate::Coalescent::sampler(n);
+        let mut rng = rand::thread_rng();
+        let estimators: [fn(&[usize]) -> ThetaEstimate; 4] = [watterson, pi, fay_wu_h, zeng_l];
+        let mut values = vec![Vec::new(); 4];
+        for _ in 0..samples {
+            let genealogy = coalescent.sample_genealogy(&mut rng);
+            let sfs = genealogy
+                .sample_haplotypes(crate::MutationModel::Poisson { theta }, &mut rng)
+                .site_frequency_spectrum();
+            for (values, estimator) in values.iter_mut().zip(estimators.iter()) {
+                values.push(estimator(&sfs));
+            }
+        }
+
+        for values in values {
+            let mean = values.iter().map(|estimate| estimate.value).sum::<f64>() / samples as f64;
+            let variance = values.iter().map(|estimate| (estimate.value - mean).powi(2)).sum::<f64>() / samples as f64;
+            let estimated = values.iter().map(|estimate| estimate.variance).sum::<f64>() / samples as f64;
+            assert!((mean - theta).abs() < 0.1 * theta, "{}", mean);
+            assert!((estimated - variance).abs() < 0.15 * variance, "{} {}", estimated, variance);
+        }
+    }
+}
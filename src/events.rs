@@ -0,0 +1,220 @@
+//! Streams of coalescence events.
+//!
+//! Events are produced one at a time, either by a running ``Coalescent`` or by
+//! replaying a ``Genealogy``, and can be transformed with the adapters of
+//! ``EventStream`` without collecting them first.
+//!
+
+// Types
+use crate::Coalescent;
+
+// Traits
+use rand::Rng;
+
+/// Coalescence of two lineages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    /// Time elapsed since the previous event.
+    pub time_step: f64,
+    /// Indexes of an individual of each lineage that is joined.
+    pub indexes: [usize; 2],
+    /// Number of lineages from this event on.
+    pub lineages: usize,
+}
+
+/// Iterator over the events of a ``Coalescent``, advancing it.
+///
+/// This struct is created by the ``events`` method on Coalescent<R>.
+/// See its documentation for more.
+#[derive(Debug)]
+pub struct Events<'a, R>
+where
+    R: Rng,
+{
+    coalescent: &'a mut Coalescent<R>,
+}
+
+impl<'a, R> Events<'a, R>
+where
+    R: Rng,
+{
+    pub(crate) fn new(coalescent: &'a mut Coalescent<R>) -> Self {
+        Events { coalescent }
+    }
+}
+
+impl<'a, R> Iterator for Events<'a, R>
+where
+    R: Rng,
+{
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.coalescent.next_step().map(|(time_step, indexes)| Event {
+            time_step,
+            indexes,
+            lineages: self.coalescent.active_lineages(),
+        })
+    }
+}
+
+/// Adapters for iterators over events.
+///
+/// # Examples
+///
+/// Times at which the number of lineages halves.
+/// ```
+/// use coalescence::EventStream;
+/// let group_size = 64;
+/// let rng = rand::thread_rng();
+/// let mut coalescent = coalescence::Coalescent::new(group_size, rng);
+///
+/// let times: Vec<f64> = coalescent
+///     .events()
+///     .take_until_lineages(4)
+///     .filter_events(|event| event.lineages.is_power_of_two())
+///     .scan_times()
+///     .map(|(time, _event)| time)
+///     .collect();
+///
+/// assert_eq!(times.len(), 4);
+/// assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+/// ```
+pub trait EventStream: Iterator<Item = Event> + Sized {
+    /// Pairs each event with the time at which it happens,
+    /// counted from the start of the stream.
+    fn scan_times(self) -> ScanTimes<Self> {
+        ScanTimes { events: self, time: 0.0 }
+    }
+
+    /// Keeps only the events satisfying the predicate. Time steps of the
+    /// discarded events are added to the next kept event, so that
+    /// times are preserved.
+    fn filter_events<P>(self, predicate: P) -> FilterEvents<Self, P>
+    where
+        P: FnMut(&Event) -> bool,
+    {
+        FilterEvents { events: self, predicate }
+    }
+
+    /// Stops after the event that leaves ``lineages`` lineages.
+    fn take_until_lineages(self, lineages: usize) -> TakeUntilLineages<Self> {
+        TakeUntilLineages { events: self, lineages, done: false }
+    }
+}
+
+impl<I> EventStream for I where I: Iterator<Item = Event> {}
+
+/// Iterator adapter created by ``EventStream::scan_times``.
+#[derive(Debug, Clone)]
+pub struct ScanTimes<I> {
+    events: I,
+    time: f64,
+}
+
+impl<I> Iterator for ScanTimes<I>
+where
+    I: Iterator<Item = Event>,
+{
+    type Item = (f64, Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.events.next()?;
+        self.time += event.time_step;
+        Some((self.time, event))
+    }
+}
+
+/// Iterator adapter created by ``EventStream::filter_events``.
+#[derive(Debug, Clone)]
+pub struct FilterEvents<I, P> {
+    events: I,
+    predicate: P,
+}
+
+impl<I, P> Iterator for FilterEvents<I, P>
+where
+    I: Iterator<Item = Event>,
+    P: FnMut(&Event) -> bool,
+{
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let mut skipped_time = 0.0;
+        for mut event in self.events.by_ref() {
+            if (self.predicate)(&event) {
+                event.time_step += skipped_time;
+                return Some(event);
+            }
+            skipped_time += event.time_step;
+        }
+        None
+    }
+}
+
+/// Iterator adapter created by ``EventStream::take_until_lineages``.
+#[derive(Debug, Clone)]
+pub struct TakeUntilLineages<I> {
+    events: I,
+    lineages: usize,
+    done: bool,
+}
+
+impl<I> Iterator for TakeUntilLineages<I>
+where
+    I: Iterator<Item = Event>,
+{
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        if self.done {
+            return None;
+        }
+        let event = self.events.next()?;
+        self.done = event.lineages <= self.lineages;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let group_size = 20;
+        let rng = rand::thread_rng();
+        let coalescent = Coalescent::new(group_size, rng);
+
+        let mut rng = rand::thread_rng();
+        let genealogy = coalescent.sample_genealogy(&mut rng);
+
+        let events: Vec<Event> = genealogy.events().collect();
+        assert_eq!(events.len(), group_size - 1);
+        assert_eq!(events.last().unwrap().lineages, 1);
+
+        // Filtering keeps the total time
+        let (time, _) = genealogy.events()
+            .filter_events(|event| event.lineages % 3 == 0)
+            .scan_times()
+            .last()
+            .unwrap();
+        assert!((time - genealogy.lineages_through_time()[group_size - 3].0).abs() < 1e-12);
+
+        let last = genealogy.events().take_until_lineages(5).last().unwrap();
+        assert_eq!(last.lineages, 5);
+    }
+
+    #[test]
+    fn lineages_of_scheduled_samples() {
+        // Lineages sampled in the past are only counted once they are sampled
+        let mut coalescent = Coalescent::new(4, rand::thread_rng());
+        coalescent.schedule_sample(1e3, 2);
+        assert_eq!(coalescent.active_lineages(), 4);
+
+        let events: Vec<Event> = coalescent.events().collect();
+        let lineages: Vec<usize> = events.iter().map(|event| event.lineages).collect();
+        assert_eq!(lineages, vec![3, 2, 1, 2, 1]);
+        assert_eq!(coalescent.active_lineages(), 1);
+    }
+}
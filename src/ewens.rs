@@ -0,0 +1,158 @@
+//! Ewens sampling formula.
+//!
+//! Under the infinite alleles model, every mutation creates a new allele, and
+//! the partition of a group of ``n`` individuals by allele follows the Ewens
+//! sampling formula with parameter θ: the probability of having ``a_j`` alleles
+//! carried by exactly ``j`` individuals, for each ``j``, is
+//!
+//! n! / (θ (θ + 1) ... (θ + n - 1)) Π (θ / j)^{a_j} / a_j!.
+//!
+//! These samplers draw partitions directly, without genealogies, which is
+//! useful to check simulations that drop mutations on genealogies.
+//!
+
+// Types
+use crate::Partition;
+
+// Traits
+use rand::Rng;
+
+/// Partition of ``n`` individuals by the Chinese restaurant process: individual ``i``
+/// joins an existing block of size ``s`` with probability s / (i + θ), and starts a
+/// new block with probability θ / (i + θ). The partition follows the Ewens sampling formula.
+///
+/// # Panics
+///
+/// If θ is not positive and finite.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::traits::RandomPartition;
+///
+/// let mut rng = rand::thread_rng();
+/// let partition = coalescence::ewens::chinese_restaurant_process(10, 1.0, &mut rng);
+///
+/// assert_eq!(partition.group_size(), 10);
+/// assert!(partition.number_of_blocks() >= 1);
+/// ```
+pub fn chinese_restaurant_process<R>(n: usize, theta: f64, rng: &mut R) -> Partition
+where
+    R: Rng + ?Sized,
+{
+    assert!(theta > 0.0 && theta.is_finite(), "The mutation rate must be positive and finite.");
+
+    let mut partition = Partition::with_capacity(n);
+    for individual in 0..n {
+        partition.push();
+
+        // Joining the block of a uniformly chosen previous individual is joining
+        // each block with probability proportional to its size
+        let point = rng.gen::<f64>() * (individual as f64 + theta);
+        if point < individual as f64 {
+            let other = (point as usize).min(individual - 1);
+            partition.union(individual, other);
+        }
+    }
+
+    partition
+}
+
+/// Number of alleles carried by each number of individuals in a group of ``n``,
+/// following the Ewens sampling formula: the entry ``j`` is the number of alleles
+/// carried by exactly ``j + 1`` individuals, as in ``block_size_counts`` of ``RandomPartition``.
+///
+/// Uses the Feller coupling: with independent indicators ξ_i of probability θ / (θ + i - 1),
+/// sizes of blocks are the spacings between consecutive successes of ξ_1, ..., ξ_n, 1,
+/// which takes time O(n).
+///
+/// # Panics
+///
+/// If θ is not positive and finite.
+///
+/// # Examples
+///
+/// ```
+/// let mut rng = rand::thread_rng();
+/// let counts = coalescence::ewens::sample_allele_counts(10, 2.0, &mut rng);
+///
+/// assert_eq!(counts.len(), 10);
+/// assert_eq!(counts.iter().enumerate().map(|(j, count)| (j + 1) * count).sum::<usize>(), 10);
+/// ```
+pub fn sample_allele_counts<R>(n: usize, theta: f64, rng: &mut R) -> Vec<usize>
+where
+    R: Rng + ?Sized,
+{
+    assert!(theta > 0.0 && theta.is_finite(), "The mutation rate must be positive and finite.");
+
+    let mut counts = vec![0; n];
+    let mut last_success = 1;
+    for i in 2..=(n + 1) {
+        let success = i == n + 1 || rng.gen::<f64>() < theta / (theta + i as f64 - 1.0);
+        if success {
+            counts[i - last_success - 1] += 1;
+            last_success = i;
+        }
+    }
+
+    counts
+}
+
+/// Probability of allele counts under the Ewens sampling formula, where the entry ``j``
+/// is the number of alleles carried by exactly ``j + 1`` individuals.
+///
+/// # Panics
+///
+/// If θ is not positive and finite.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::ewens::probability;
+///
+/// // Two individuals carry the same allele with probability 1 / (1 + θ)
+/// let theta = 3.0;
+/// assert!((probability(&[0, 1], theta) - 1.0 / (1.0 + theta)).abs() < 1e-12);
+/// assert!((probability(&[2, 0], theta) - theta / (1.0 + theta)).abs() < 1e-12);
+/// ```
+pub fn probability(counts: &[usize], theta: f64) -> f64 {
+    assert!(theta > 0.0 && theta.is_finite(), "The mutation rate must be positive and finite.");
+
+    let n: usize = counts.iter().enumerate().map(|(j, count)| (j + 1) * count).sum();
+    let mut log_probability: f64 = (1..=n).map(|i| (i as f64).ln() - (theta + i as f64 - 1.0).ln()).sum();
+    for (j, &count) in counts.iter().enumerate() {
+        log_probability += count as f64 * (theta / (j + 1) as f64).ln();
+        log_probability -= (1..=count).map(|i| (i as f64).ln()).sum::<f64>();
+    }
+
+    log_probability.exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::RandomPartition;
+
+    #[test]
+    fn samplers_follow_ewens_formula() {
+        let (n, theta, samples) = (3, 1.5, 20_000);
+        let configurations = [vec![3, 0, 0], vec![1, 1, 0], vec![0, 0, 1]];
+        let total: f64 = configurations.iter().map(|counts| probability(counts, theta)).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        let mut rng = rand::thread_rng();
+        let mut restaurant = [0; 3];
+        let mut feller = [0; 3];
+        for _ in 0..samples {
+            let counts = chinese_restaurant_process(n, theta, &mut rng).block_size_counts();
+            restaurant[configurations.iter().position(|other| other == &counts).unwrap()] += 1;
+            let counts = sample_allele_counts(n, theta, &mut rng);
+            feller[configurations.iter().position(|other| other == &counts).unwrap()] += 1;
+        }
+        for (index, counts) in configurations.iter().enumerate() {
+            let expected = probability(counts, theta);
+            assert!((restaurant[index] as f64 / samples as f64 - expected).abs() < 0.015);
+            assert!((feller[index] as f64 / samples as f64 - expected).abs() < 0.015);
+        }
+    }
+}
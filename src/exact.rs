@@ -0,0 +1,323 @@
+//! Exact probabilities of the coalescent process, in rational arithmetic.
+//!
+//! Probabilities of discrete events, like the choice of the pair of lineages that
+//! coalesces or the topology of a genealogy, are rational numbers. Computing them
+//! as ``Ratio`` avoids rounding errors, so that tests can check that they are
+//! equal, e.g. that the probabilities of all topologies add up to exactly one.
+//!
+//! Numerators and denominators are ``u128``, reduced after each operation, which
+//! is enough for groups of up to about 20 individuals. Operations panic on overflow
+//! instead of losing precision.
+//!
+
+// Types
+use crate::Genealogy;
+use crate::union_find::UnionFind;
+
+// Traits
+use std::ops::{Add, Div, Mul};
+
+/// Non-negative rational number.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::exact::Ratio;
+///
+/// let third = Ratio::new(1, 3);
+/// assert_eq!(third + third + third, Ratio::ONE);
+/// assert_eq!(Ratio::new(2, 6) * Ratio::integer(3), Ratio::ONE);
+/// assert_eq!(format!("{}", Ratio::new(10, 4)), "5/2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ratio {
+    numerator: u128,
+    denominator: u128,
+}
+
+impl Ratio {
+    /// Zero.
+    pub const ZERO: Ratio = Ratio { numerator: 0, denominator: 1 };
+
+    /// One.
+    pub const ONE: Ratio = Ratio { numerator: 1, denominator: 1 };
+
+    /// Ratio ``numerator / denominator``, in lowest terms.
+    ///
+    /// # Panics
+    ///
+    /// If the denominator is zero.
+    pub fn new(numerator: u128, denominator: u128) -> Self {
+        assert!(denominator > 0, "The denominator must be positive.");
+        let divisor = gcd(numerator, denominator);
+        Ratio { numerator: numerator / divisor, denominator: denominator / divisor }
+    }
+
+    /// Ratio of an integer.
+    pub fn integer(value: u128) -> Self {
+        Ratio { numerator: value, denominator: 1 }
+    }
+
+    /// Numerator, in lowest terms.
+    pub fn numerator(&self) -> u128 {
+        self.numerator
+    }
+
+    /// Denominator, in lowest terms.
+    pub fn denominator(&self) -> u128 {
+        self.denominator
+    }
+
+    /// Closest floating point number.
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b > 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a.max(1)
+}
+
+fn checked(value: Option<u128>) -> u128 {
+    value.expect("Overflow in exact arithmetic.")
+}
+
+impl Add for Ratio {
+    type Output = Ratio;
+
+    fn add(self, other: Ratio) -> Ratio {
+        let divisor = gcd(self.denominator, other.denominator);
+        let (first, second) = (self.denominator / divisor, other.denominator / divisor);
+        let numerator = checked(
+            checked(self.numerator.checked_mul(second)).checked_add(checked(other.numerator.checked_mul(first))),
+        );
+        Ratio::new(numerator, checked(self.denominator.checked_mul(second)))
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Ratio;
+
+    fn mul(self, other: Ratio) -> Ratio {
+        // Reduce crosswise first, to delay overflows
+        let (first, second) = (gcd(self.numerator, other.denominator), gcd(other.numerator, self.denominator));
+        Ratio {
+            numerator: checked((self.numerator / first).checked_mul(other.numerator / second)),
+            denominator: checked((self.denominator / second).checked_mul(other.denominator / first)),
+        }
+    }
+}
+
+impl Div for Ratio {
+    type Output = Ratio;
+
+    /// # Panics
+    ///
+    /// If the divisor is zero.
+    fn div(self, other: Ratio) -> Ratio {
+        assert!(other.numerator > 0, "Division by zero.");
+        self * Ratio { numerator: other.denominator, denominator: other.numerator }
+    }
+}
+
+impl std::iter::Sum for Ratio {
+    fn sum<I: Iterator<Item = Ratio>>(iter: I) -> Ratio {
+        iter.fold(Ratio::ZERO, Add::add)
+    }
+}
+
+impl std::iter::Product for Ratio {
+    fn product<I: Iterator<Item = Ratio>>(iter: I) -> Ratio {
+        iter.fold(Ratio::ONE, Mul::mul)
+    }
+}
+
+impl std::fmt::Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            _ => write!(f, "{}/{}", self.numerator, self.denominator),
+        }
+    }
+}
+
+/// Probability that a given pair of lineages coalesces next, out of ``lineages`` lineages.
+///
+/// # Panics
+///
+/// If there are less than two lineages.
+pub fn pair_probability(lineages: usize) -> Ratio {
+    assert!(lineages > 1, "There must be at least two lineages.");
+    let k = lineages as u128;
+    Ratio::new(2, k * (k - 1))
+}
+
+/// Probability of each ranked labelled history of ``n`` individuals, i.e. of the
+/// sequence of pairs that coalesce, which are all equally likely.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::exact::{ranked_history_probability, Ratio};
+///
+/// // 3 choices of pairs, and then a single one
+/// assert_eq!(ranked_history_probability(3), Ratio::new(1, 3));
+/// ```
+pub fn ranked_history_probability(n: usize) -> Ratio {
+    (2..=n).map(pair_probability).product()
+}
+
+/// Number of ranked labelled histories with the same topology as the genealogy,
+/// i.e. of orders of its events compatible with the tree: (n - 1)! divided by the
+/// number of events below each event, including itself.
+pub fn ranked_histories(genealogy: &Genealogy) -> u128 {
+    let mut sets = UnionFind::new(genealogy.group_size());
+    let mut product = 1u128;
+    for event in genealogy.events() {
+        let representative = sets.union(event.indexes[0], event.indexes[1]);
+        product = checked(product.checked_mul(sets.size(representative) as u128 - 1));
+    }
+    let orders = (1..genealogy.group_size() as u128).fold(1u128, |orders, i| checked(orders.checked_mul(i)));
+
+    orders / product
+}
+
+/// Probability of the labelled topology of the genealogy under the standard coalescent,
+/// forgetting the order of its events.
+///
+/// # Panics
+///
+/// If the individuals are not all sampled at time zero, or on overflow.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::exact::{topology_probability, Ratio};
+/// use coalescence::fixtures;
+///
+/// // Of the 15 topologies of four individuals, 12 are caterpillars and 3 are balanced
+/// let caterpillar = topology_probability(&fixtures::caterpillar(4));
+/// let balanced = topology_probability(&fixtures::balanced());
+/// assert_eq!(caterpillar, Ratio::new(1, 18));
+/// assert_eq!(balanced, Ratio::new(1, 9));
+/// assert_eq!(Ratio::integer(12) * caterpillar + Ratio::integer(3) * balanced, Ratio::ONE);
+/// ```
+pub fn topology_probability(genealogy: &Genealogy) -> Ratio {
+    assert!(
+        genealogy.sampling_times().iter().all(|&time| time == 0.0),
+        "All individuals must be sampled at time zero."
+    );
+
+    Ratio::integer(ranked_histories(genealogy)) * ranked_history_probability(genealogy.group_size())
+}
+
+/// Probability of allele counts under the Ewens sampling formula with a rational θ,
+/// where the entry ``j`` is the number of alleles carried by exactly ``j + 1`` individuals.
+/// See ``ewens::probability`` for the floating point version.
+///
+/// # Panics
+///
+/// If θ is zero, or on overflow.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::exact::{ewens_probability, Ratio};
+///
+/// // Two individuals carry the same allele with probability 1 / (1 + θ)
+/// let theta = Ratio::new(1, 2);
+/// assert_eq!(ewens_probability(&[0, 1], theta), Ratio::new(2, 3));
+/// ```
+pub fn ewens_probability(counts: &[usize], theta: Ratio) -> Ratio {
+    assert!(theta.numerator() > 0, "The mutation rate must be positive.");
+
+    let n: usize = counts.iter().enumerate().map(|(j, count)| (j + 1) * count).sum();
+    let mut probability: Ratio =
+        (1..=n as u128).map(|i| Ratio::integer(i) / (theta + Ratio::integer(i - 1))).product();
+    for (j, &count) in counts.iter().enumerate() {
+        let term = theta / Ratio::integer(j as u128 + 1);
+        for i in 1..=count as u128 {
+            probability = probability * term / Ratio::integer(i);
+        }
+    }
+
+    probability
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Clades of a genealogy, as sorted sets of individuals.
+    fn clades(genealogy: &Genealogy) -> Vec<Vec<usize>> {
+        let mut clades: Vec<Vec<usize>> = (0..genealogy.group_size() - 1)
+            .map(|step| {
+                let partition = genealogy.partition_after(step + 1);
+                let indexes = genealogy.events().nth(step).unwrap().indexes;
+                let mut clade: Vec<usize> = partition.set(indexes[0]).collect();
+                clade.sort_unstable();
+                clade
+            })
+            .collect();
+        clades.sort_unstable();
+        clades
+    }
+
+    /// All ranked labelled histories, as sequences of pairs of representatives.
+    fn histories(lineages: Vec<usize>) -> Vec<Vec<[usize; 2]>> {
+        if lineages.len() < 2 {
+            return vec![Vec::new()];
+        }
+        let mut all = Vec::new();
+        for second in 1..lineages.len() {
+            for first in 0..second {
+                let mut remaining = lineages.clone();
+                remaining.remove(second);
+                for mut history in histories(remaining) {
+                    history.insert(0, [lineages[first], lineages[second]]);
+                    all.push(history);
+                }
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn topologies_add_up_to_one() {
+        let n = 5;
+        let all = histories((0..n).collect());
+        assert_eq!(Ratio::integer(all.len() as u128) * ranked_history_probability(n), Ratio::ONE);
+
+        let mut topologies: HashMap<Vec<Vec<usize>>, (Ratio, u128)> = HashMap::new();
+        for steps in all {
+            let genealogy = Genealogy::from_steps(n, steps, vec![1.0; n - 1]);
+            let entry = topologies.entry(clades(&genealogy)).or_insert((topology_probability(&genealogy), 0));
+            entry.1 += 1;
+        }
+
+        // 105 topologies of five individuals
+        assert_eq!(topologies.len(), 105);
+        for (probability, histories) in topologies.values() {
+            assert_eq!(*probability, Ratio::integer(*histories) * ranked_history_probability(n));
+        }
+        assert_eq!(topologies.values().map(|(probability, _)| *probability).sum::<Ratio>(), Ratio::ONE);
+    }
+
+    #[test]
+    fn ewens_formula() {
+        let theta = Ratio::new(3, 2);
+        let configurations = [vec![4, 0, 0, 0], vec![2, 1, 0, 0], vec![0, 2, 0, 0], vec![1, 0, 1, 0], vec![0, 0, 0, 1]];
+        let total: Ratio = configurations.iter().map(|counts| ewens_probability(counts, theta)).sum();
+        assert_eq!(total, Ratio::ONE);
+        for counts in configurations.iter() {
+            let exact = ewens_probability(counts, theta).to_f64();
+            assert!((exact - crate::ewens::probability(counts, theta.to_f64())).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Fit of a demography to observed summary statistics by simulated annealing.
+//!
+//! The likelihood of a demography is rarely available, but summary statistics are
+//! easy to simulate. The fit searches the parameters of a two-epoch model, see
+//! ``Demography::two_epoch``, whose simulated statistics are closest to the observed
+//! ones: each iteration proposes a random move of the parameters, estimates the
+//! distance of the proposal by simulation, and accepts it with the Metropolis rule
+//! at a temperature that decreases geometrically.
+//!
+//! The distance is the sum over statistics of the squared relative difference between
+//! the mean over simulated replicates and the observed value.
+//!
+
+// Types
+use crate::{Coalescent, Demography, MutationModel, Statistic};
+use rand_distr::StandardNormal;
+
+// Traits
+use rand::distributions::Distribution;
+use rand::Rng;
+
+/// Parameters of a two-epoch demography, see ``Demography::two_epoch``.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoEpoch {
+    /// Time of the change of size in the past.
+    pub time: f64,
+    /// Size of the population before the change, relative to the present size.
+    pub ancient_size: f64,
+}
+
+impl TwoEpoch {
+    /// Demography of the parameters.
+    pub fn demography(&self) -> Demography {
+        Demography::two_epoch(self.time, self.ancient_size)
+    }
+}
+
+/// One iteration of a fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitStep {
+    /// Proposed parameters.
+    pub parameters: TwoEpoch,
+    /// Estimated distance of the proposal to the observed statistics.
+    pub distance: f64,
+    /// Temperature of the iteration.
+    pub temperature: f64,
+    /// Whether the proposal was accepted as the current parameters.
+    pub accepted: bool,
+}
+
+/// Result of a fit, with every iteration of the search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemographyFit {
+    /// Parameters with the smallest estimated distance.
+    pub best: TwoEpoch,
+    /// Estimated distance of the best parameters.
+    pub distance: f64,
+    /// Iterations of the search, starting with the initial parameters.
+    pub trajectory: Vec<FitStep>,
+}
+
+/// Simulated annealing over the parameters of a two-epoch demography.
+///
+/// By default, it runs 200 iterations of 100 replicates each, starting at temperature
+/// one and cooling by a factor 0.97 per iteration, with moves of standard deviation
+/// 0.3 on the logarithm of each parameter.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{Annealing, Statistic, TwoEpoch};
+///
+/// // Observed: 15 segregating sites and 4 pairwise differences in 10 individuals
+/// let statistics = [Statistic::SegregatingSites, Statistic::PairwiseDifferences];
+/// let start = TwoEpoch { time: 1.0, ancient_size: 1.0 };
+///
+/// let annealing = Annealing::new(20, 20);
+/// let fit = annealing.fit(10, 4.0, &statistics, &[15.0, 4.0], start, &mut rand::thread_rng());
+///
+/// assert_eq!(fit.trajectory.len(), 21);
+/// assert!(fit.trajectory.iter().all(|step| fit.distance <= step.distance));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Annealing {
+    iterations: usize,
+    replicates: usize,
+    temperature: f64,
+    cooling: f64,
+    step_size: f64,
+}
+
+impl Annealing {
+    /// Creates a new search of ``iterations`` iterations, estimating each distance
+    /// with ``replicates`` simulated replicates.
+    ///
+    /// # Panics
+    ///
+    /// If there are no replicates.
+    pub fn new(iterations: usize, replicates: usize) -> Self {
+        assert!(replicates > 0, "There must be at least one replicate.");
+
+        Annealing { iterations, replicates, temperature: 1.0, cooling: 0.97, step_size: 0.3 }
+    }
+
+    /// Temperature of the first iteration.
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Factor multiplying the temperature after each iteration.
+    pub fn cooling(mut self, cooling: f64) -> Self {
+        self.cooling = cooling;
+        self
+    }
+
+    /// Standard deviation of the moves on the logarithm of each parameter.
+    pub fn step_size(mut self, step_size: f64) -> Self {
+        self.step_size = step_size;
+        self
+    }
+
+    /// Fit a two-epoch demography to the ``observed`` values of ``statistics`` in a group
+    /// of ``group_size`` individuals with mutation rate θ, starting from ``start``.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one observed value per statistic, or if some parameter of ``start``
+    /// is not positive and finite.
+    pub fn fit<R>(
+        &self,
+        group_size: usize,
+        theta: f64,
+        statistics: &[Statistic],
+        observed: &[f64],
+        start: TwoEpoch,
+        rng: &mut R,
+    ) -> DemographyFit
+    where
+        R: Rng + ?Sized,
+    {
+        assert_eq!(statistics.len(), observed.len(), "There must be one observed value per statistic.");
+        assert!(
+            [start.time, start.ancient_size].iter().all(|&value| value > 0.0 && value.is_finite()),
+            "Parameters must be positive and finite."
+        );
+
+        let distance = |parameters: &TwoEpoch, rng: &mut R| {
+            self.distance(group_size, theta, statistics, observed, parameters, rng)
+        };
+
+        let mut current = (start, distance(&start, rng));
+        let mut best = current;
+        let mut temperature = self.temperature;
+        let mut trajectory = Vec::with_capacity(self.iterations + 1);
+        trajectory.push(FitStep { parameters: start, distance: current.1, temperature, accepted: true });
+
+        for _ in 0..self.iterations {
+            temperature *= self.cooling;
+            let mut walk = |value: f64| {
+                let normal: f64 = StandardNormal.sample(rng);
+                value * (self.step_size * normal).exp()
+            };
+            let proposal = TwoEpoch { time: walk(current.0.time), ancient_size: walk(current.0.ancient_size) };
+            let proposal_distance = distance(&proposal, rng);
+
+            let accepted = proposal_distance <= current.1
+                || rng.gen::<f64>() < (-(proposal_distance - current.1) / temperature).exp();
+            if accepted {
+                current = (proposal, proposal_distance);
+                if current.1 < best.1 {
+                    best = current;
+                }
+            }
+            trajectory.push(FitStep { parameters: proposal, distance: proposal_distance, temperature, accepted });
+        }
+
+        DemographyFit { best: best.0, distance: best.1, trajectory }
+    }
+
+    fn distance<R>(
+        &self,
+        group_size: usize,
+        theta: f64,
+        statistics: &[Statistic],
+        observed: &[f64],
+        parameters: &TwoEpoch,
+        rng: &mut R,
+    ) -> f64
+    where
+        R: Rng + ?Sized,
+    {
+        let mut coalescent = Coalescent::sampler(group_size);
+        coalescent.set_demography(parameters.demography());
+
+        let mut means = vec![0.0; statistics.len()];
+        for _ in 0..self.replicates {
+            let genealogy = coalescent.sample_genealogy(rng);
+            let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta }, rng);
+            for (mean, statistic) in means.iter_mut().zip(statistics) {
+                *mean += statistic.compute(&genealogy, &haplotypes) / self.replicates as f64;
+            }
+        }
+
+        means
+            .iter()
+            .zip(observed)
+            .map(|(mean, &value)| match value == 0.0 {
+                true => mean * mean,
+                false => ((mean - value) / value).powi(2),
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn recovers_expansion() {
+        // Expected depth with an ancient size of 0.1 before time 0.2: much smaller than 2 (1 - 1 / n)
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(3);
+        let truth = TwoEpoch { time: 0.2, ancient_size: 0.1 };
+        let statistics = [Statistic::Depth, Statistic::Length];
+        let mut coalescent = Coalescent::sampler(10);
+        coalescent.set_demography(truth.demography());
+        let replicates = 2000;
+        let mut observed = [0.0; 2];
+        for _ in 0..replicates {
+            let genealogy = coalescent.sample_genealogy(&mut rng);
+            observed[0] += genealogy.depth() / replicates as f64;
+            observed[1] += genealogy.length() / replicates as f64;
+        }
+
+        let start = TwoEpoch { time: 2.0, ancient_size: 1.0 };
+        let fit = Annealing::new(200, 50).temperature(0.1).fit(10, 0.0, &statistics, &observed, start, &mut rng);
+        assert!(fit.distance < fit.trajectory[0].distance);
+        assert!(fit.distance < 0.05);
+        assert!(fit.trajectory.iter().all(|step| fit.distance <= step.distance));
+    }
+}
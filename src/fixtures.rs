@@ -0,0 +1,119 @@
+//! Small deterministic genealogies, built by hand.
+//!
+//! Their statistics are known exactly, which makes them useful to test
+//! code computing statistics of genealogies.
+//!
+
+// Types
+use crate::Genealogy;
+
+/// Two individuals meeting at time one.
+///
+/// # Examples
+///
+/// ```
+/// let genealogy = coalescence::fixtures::cherry();
+///
+/// assert_eq!(genealogy.depth(), 1.0);
+/// assert_eq!(genealogy.length(), 2.0);
+/// assert_eq!(genealogy.divergence(0, 1).unwrap(), 2.0);
+/// ```
+pub fn cherry() -> Genealogy {
+    Genealogy::from_steps(2, vec![[0, 1]], vec![1.0])
+}
+
+/// Four individuals, where 0 and 1 meet at time one, 2 and 3 at time two,
+/// and both pairs at time four.
+///
+/// # Examples
+///
+/// ```
+/// let genealogy = coalescence::fixtures::balanced();
+///
+/// assert_eq!(genealogy.depth(), 4.0);
+/// assert_eq!(genealogy.length(), 2.0 + 4.0 + 3.0 + 2.0);
+/// assert_eq!(genealogy.divergence(2, 3).unwrap(), 4.0);
+/// assert_eq!(genealogy.mean_pairwise_divergence(), (2.0 + 4.0 + 4.0 * 8.0) / 6.0);
+/// ```
+pub fn balanced() -> Genealogy {
+    Genealogy::from_steps(4, vec![[0, 1], [2, 3], [0, 2]], vec![1.0, 1.0, 2.0])
+}
+
+/// Individuals joining one by one the lineage of the first one, at times 1, 2, ..., n - 1.
+///
+/// # Panics
+///
+/// If the group is empty.
+///
+/// # Examples
+///
+/// ```
+/// let genealogy = coalescence::fixtures::caterpillar(3);
+///
+/// assert_eq!(genealogy.depth(), 2.0);
+/// assert_eq!(genealogy.length(), 5.0);
+/// assert_eq!(genealogy.mean_pairwise_divergence(), (2.0 + 4.0 + 4.0) / 3.0);
+/// ```
+pub fn caterpillar(group_size: usize) -> Genealogy {
+    assert!(group_size > 0, "The group must not be empty.");
+
+    let steps = (1..group_size).map(|index| [0, index]).collect();
+    let time_steps = vec![1.0; group_size - 1];
+
+    Genealogy::from_steps(group_size, steps, time_steps)
+}
+
+/// All individuals meeting at the same ancestor would make a star. As genealogies
+/// of the coalescent are binary, this is the closest: individuals meet by pairs
+/// over a short time ``epsilon`` after time one.
+///
+/// # Panics
+///
+/// If the group is empty or ``epsilon`` is not in the interval (0, 1).
+///
+/// # Examples
+///
+/// ```
+/// let genealogy = coalescence::fixtures::almost_star(10, 1e-6);
+///
+/// assert!((genealogy.depth() - 1.0).abs() < 1e-6);
+/// assert!((genealogy.length() - 10.0).abs() < 1e-4);
+/// ```
+pub fn almost_star(group_size: usize, epsilon: f64) -> Genealogy {
+    assert!(group_size > 0, "The group must not be empty.");
+    assert!(epsilon > 0.0 && epsilon < 1.0, "Epsilon must be between zero and one.");
+
+    let events = group_size - 1;
+    let steps = (1..group_size).map(|index| [0, index]).collect();
+    let mut time_steps = vec![epsilon / events as f64; events];
+    if let Some(first) = time_steps.first_mut() {
+        *first = 1.0;
+    }
+
+    Genealogy::from_steps(group_size, steps, time_steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_are_valid() {
+        for genealogy in &[cherry(), balanced(), caterpillar(1), caterpillar(7), almost_star(5, 0.1)] {
+            assert_eq!(genealogy.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn known_answers() {
+        let genealogy = balanced();
+
+        assert_eq!(genealogy.partition_at(1.5).amount_of_sets(), 3);
+        assert_eq!(genealogy.mrca_node(&[1, 3]).age, 4.0);
+        assert_eq!(genealogy.lineages_through_time(), vec![(0.0, 4), (1.0, 3), (2.0, 2), (4.0, 1)]);
+
+        let caterpillar = caterpillar(5);
+        assert_eq!(caterpillar.length(), 5.0 + 4.0 + 3.0 + 2.0);
+        assert_eq!(caterpillar.divergence(3, 4).unwrap(), 8.0);
+    }
+}
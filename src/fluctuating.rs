@@ -0,0 +1,208 @@
+//! Coalescent in a population of fluctuating size.
+//!
+//! The size of the population follows a stochastic process, and genealogies are
+//! sampled conditional on a realization of it: first a trajectory of sizes, as a
+//! ``Demography``, and then a genealogy under that demography. Both are returned,
+//! so that they can be analysed jointly, e.g. to relate the depth of genealogies
+//! to the sizes they went through.
+//!
+//! Trajectories either come from user-supplied samples, e.g. from a model fitted
+//! elsewhere, or from an Ornstein–Uhlenbeck process on the logarithm of the size.
+//!
+
+// Types
+use crate::{Coalescent, Demography, Genealogy};
+use rand_distr::StandardNormal;
+
+// Traits
+use rand::distributions::Distribution;
+use rand::Rng;
+
+/// Ornstein–Uhlenbeck process on the logarithm of the population size, backwards in time.
+///
+/// The logarithm of the size starts at zero, i.e. at the present size, and reverts at rate
+/// ``reversion`` towards ``log_mean``, with volatility ``volatility``. It is sampled exactly on a
+/// grid of times ``step`` apart, the size being constant between points of the grid, up to
+/// ``horizon``, after which the size stays constant.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::OrnsteinUhlenbeck;
+///
+/// let process = OrnsteinUhlenbeck::new(0.0, 1.0, 0.5, 0.1, 10.0);
+/// let demography = process.sample_demography(&mut rand::thread_rng());
+///
+/// assert_eq!(demography.size_at(0.05), 1.0);
+/// assert!(demography.size_at(5.0) > 0.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrnsteinUhlenbeck {
+    log_mean: f64,
+    reversion: f64,
+    volatility: f64,
+    step: f64,
+    horizon: f64,
+}
+
+impl OrnsteinUhlenbeck {
+    /// Creates a new process.
+    ///
+    /// # Panics
+    ///
+    /// If ``log_mean`` is not finite, if ``reversion`` or ``volatility`` are negative or
+    /// not finite, or if ``step`` and ``horizon`` are not positive and finite.
+    pub fn new(log_mean: f64, reversion: f64, volatility: f64, step: f64, horizon: f64) -> Self {
+        assert!(log_mean.is_finite(), "The mean must be finite.");
+        assert!(reversion >= 0.0 && reversion.is_finite(), "The reversion rate must be non-negative and finite.");
+        assert!(volatility >= 0.0 && volatility.is_finite(), "The volatility must be non-negative and finite.");
+        assert!(step > 0.0 && step.is_finite(), "The step must be positive and finite.");
+        assert!(horizon > 0.0 && horizon.is_finite(), "The horizon must be positive and finite.");
+
+        OrnsteinUhlenbeck { log_mean, reversion, volatility, step, horizon }
+    }
+
+    /// Sample a trajectory of sizes.
+    pub fn sample_demography<R>(&self, rng: &mut R) -> Demography
+    where
+        R: Rng + ?Sized,
+    {
+        // Exact transition over one step; a Brownian motion without reversion
+        let decay = (-self.reversion * self.step).exp();
+        let deviation = match self.reversion > 0.0 {
+            true => self.volatility * ((1.0 - decay * decay) / (2.0 * self.reversion)).sqrt(),
+            false => self.volatility * self.step.sqrt(),
+        };
+
+        let mut demography = Demography::constant();
+        let mut log_size = 0.0;
+        let steps = (self.horizon / self.step).ceil() as usize;
+        for index in 1..steps {
+            let normal: f64 = StandardNormal.sample(rng);
+            log_size = self.log_mean + (log_size - self.log_mean) * decay + deviation * normal;
+            demography = demography.add_epoch(index as f64 * self.step, log_size.exp(), 0.0);
+        }
+
+        demography
+    }
+}
+
+/// Stochastic process of the population size.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeProcess {
+    /// One of the given trajectories, chosen uniformly.
+    Trajectories(Vec<Demography>),
+    /// Ornstein–Uhlenbeck process on the logarithm of the size.
+    OrnsteinUhlenbeck(OrnsteinUhlenbeck),
+}
+
+impl SizeProcess {
+    /// Sample a trajectory of sizes.
+    ///
+    /// # Panics
+    ///
+    /// If there are no trajectories to choose from.
+    pub fn sample_demography<R>(&self, rng: &mut R) -> Demography
+    where
+        R: Rng + ?Sized,
+    {
+        match self {
+            SizeProcess::Trajectories(trajectories) => {
+                assert!(!trajectories.is_empty(), "There must be at least one trajectory.");
+                trajectories[rng.gen_range(0, trajectories.len())].clone()
+            },
+            SizeProcess::OrnsteinUhlenbeck(process) => process.sample_demography(rng),
+        }
+    }
+}
+
+/// Coalescent process conditional on a random trajectory of the population size.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{Demography, FluctuatingCoalescent, SizeProcess};
+///
+/// let trajectories = vec![Demography::constant(), Demography::two_epoch(0.1, 0.01)];
+/// let fluctuating = FluctuatingCoalescent::new(10, SizeProcess::Trajectories(trajectories));
+///
+/// let (demography, genealogy) = fluctuating.sample(&mut rand::thread_rng());
+/// assert_eq!(demography.size_at(0.0), 1.0);
+/// assert_eq!(genealogy.group_size(), 10);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FluctuatingCoalescent {
+    group_size: usize,
+    process: SizeProcess,
+}
+
+impl FluctuatingCoalescent {
+    /// Creates a new coalescent of ``group_size`` individuals in a population whose size follows ``process``.
+    pub fn new(group_size: usize, process: SizeProcess) -> Self {
+        FluctuatingCoalescent { group_size, process }
+    }
+
+    /// Number of individuals.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// Process of the population size.
+    pub fn process(&self) -> &SizeProcess {
+        &self.process
+    }
+
+    /// Sample a trajectory of sizes and a genealogy conditional on it.
+    pub fn sample<R>(&self, rng: &mut R) -> (Demography, Genealogy)
+    where
+        R: Rng + ?Sized,
+    {
+        let demography = self.process.sample_demography(rng);
+        let mut coalescent = Coalescent::sampler(self.group_size);
+        coalescent.set_demography(demography.clone());
+        let genealogy = coalescent.sample_genealogy(rng);
+
+        (demography, genealogy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ornstein_uhlenbeck() {
+        let mut rng = rand::thread_rng();
+
+        // Without volatility, the size goes deterministically towards exp(log_mean)
+        let demography = OrnsteinUhlenbeck::new(1.0, 2.0, 0.0, 0.5, 5.0).sample_demography(&mut rng);
+        assert_eq!(demography.size_at(0.2), 1.0);
+        assert!((demography.size_at(0.7) - (1.0 - (-1.0f64).exp()).exp()).abs() < 1e-12);
+        assert!((demography.size_at(100.0) - 1f64.exp()).abs() < 1e-3);
+
+        // Stationary variance of the logarithm: volatility² / (2 reversion)
+        let process = OrnsteinUhlenbeck::new(0.0, 1.0, 1.0, 1.0, 20.0);
+        let samples = 4000;
+        let variance = (0..samples)
+            .map(|_| process.sample_demography(&mut rng).size_at(19.5).ln().powi(2))
+            .sum::<f64>()
+            / samples as f64;
+        assert!((variance - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn conditional_genealogies() {
+        // Kingman: E[TMRCA] of two individuals is one
+        let mut rng = rand::thread_rng();
+        let fluctuating = FluctuatingCoalescent::new(2, SizeProcess::Trajectories(vec![Demography::constant()]));
+        let samples = 5000;
+        let depth = (0..samples).map(|_| fluctuating.sample(&mut rng).1.depth()).sum::<f64>() / samples as f64;
+        assert!((depth - 1.0).abs() < 0.06);
+
+        // Small ancient sizes make genealogies shallow
+        let process = OrnsteinUhlenbeck::new(-3.0, 5.0, 0.1, 0.05, 10.0);
+        let fluctuating = FluctuatingCoalescent::new(2, SizeProcess::OrnsteinUhlenbeck(process));
+        let depth = (0..samples).map(|_| fluctuating.sample(&mut rng).1.depth()).sum::<f64>() / samples as f64;
+        assert!(depth < 0.5);
+    }
+}
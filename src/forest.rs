@@ -0,0 +1,64 @@
+//! Forest of genealogies.
+//!
+//! Cutting a genealogy at a time in the past leaves one subtree for each
+//! ancestor alive at that time. Each subtree is a genealogy of the
+//! individuals below it, whose root is joined to the cut by a stem.
+//!
+
+// Types
+use crate::Genealogy;
+
+/// Subtrees of a genealogy more recent than a time horizon.
+///
+/// This struct is created by the ``truncate_at`` method on Genealogy.
+/// See its documentation for more.
+#[derive(Debug, Clone)]
+pub struct Forest {
+    trees: Vec<Genealogy>,
+    individuals: Vec<Vec<usize>>, // sorted, with individual i of a tree being individuals[tree][i]
+    horizon: f64,
+}
+
+impl Forest {
+    pub(crate) fn new(trees: Vec<Genealogy>, individuals: Vec<Vec<usize>>, horizon: f64) -> Self {
+        Forest { trees, individuals, horizon }
+    }
+
+    /// Number of trees.
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Whether there are no trees, which only happens for an empty group.
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    /// Trees, ordered by their smallest individual.
+    ///
+    /// Individuals of each tree are relabeled from zero, in increasing order,
+    /// see ``individuals``. Branches keep their identifiers.
+    pub fn trees(&self) -> &[Genealogy] {
+        &self.trees
+    }
+
+    /// Individuals of the original genealogy in a tree, in increasing order.
+    pub fn individuals(&self, tree: usize) -> &[usize] {
+        &self.individuals[tree]
+    }
+
+    /// Time at which the genealogy was cut.
+    pub fn horizon(&self) -> f64 {
+        self.horizon
+    }
+
+    /// Length of the branch from the root of each tree to the horizon.
+    pub fn stem_lengths(&self) -> Vec<f64> {
+        self.trees.iter().map(|tree| self.horizon - tree.depth()).collect()
+    }
+
+    /// Sum of all the time represented in the edges of the forest, including stems.
+    pub fn length(&self) -> f64 {
+        self.trees.iter().map(|tree| tree.length()).sum::<f64>() + self.stem_lengths().iter().sum::<f64>()
+    }
+}
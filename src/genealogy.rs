@@ -6,31 +6,1056 @@
 //! 
 
 // Structs
-use partitions::PartitionVec;
+use crate::{CoalescentTime, Event, Forest, Haplotypes, Mutation, MutatedGenealogy, MutationModel, Partition, TimeScale};
+use crate::mutations::HashStream;
 use petgraph::Graph;
-use std::collections::HashMap;
+use rand_distr::Poisson;
 
 // Traits
-use std::iter::FromIterator;
+use crate::TimeUnit;
+use rand::distributions::Distribution;
+use rand::Rng;
+use petgraph::visit::EdgeRef;
+
+/// Relative tolerance, with respect to the depth, when comparing distances to the root.
+const ULTRAMETRIC_TOLERANCE: f64 = 1e-9;
 
 /// Genealogic tree. 
 /// 
 /// This struct is created by the ``sample_genealogy`` method on Coalescent<R>. 
-/// See its documentation for more.
+/// See its documentation for more. 
+/// 
+/// Each event merges two lineages, or more in models with multiple mergers and in 
+/// trees with collapsed polytomies, see ``from_mergers``: its common ancestor then 
+/// has as many children. 
 #[derive(Debug, Clone)]
 pub struct Genealogy {
-	path: Vec<PartitionVec<()>>, // including initial state
-	steps: Vec<[usize; 2]>,
+	steps: Vec<Vec<usize>>, // an individual of each lineage merged, at least two
 	time_steps: Vec<f64>, // all positive intervals
+	parents: Vec<usize>, // one per node, the root being its own parent, see ``branches``
+	times: Vec<f64>, // one per node, individuals at their sampling time
+	branch_ids: Vec<BranchId>, // one per node, see ``branches``
+	sampling_times: Vec<f64>, // one per individual, zero unless sampled in the past
 	graph: Option<Graph<(usize, usize), f64, petgraph::Undirected, u32>>,
 }
 
+/// Identifier of a branch of a genealogy. 
+/// 
+/// Identifiers are kept by operations that derive a genealogy from another one, 
+/// like rescaling, so that annotations of branches can be carried along. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BranchId(usize);
+
+#[cfg(feature = "serde_json")]
+impl BranchId {
+	/// Identifier with the given number, e.g. when reading a file. 
+	pub(crate) fn new(index: usize) -> Self {
+		BranchId(index)
+	}
+
+	/// Number of the identifier. 
+	pub(crate) fn index(self) -> usize {
+		self.0
+	}
+}
+
+/// Branch of a genealogy, from a node to its parent. 
+/// 
+/// See method ``branches`` on Genealogy for the numbering of nodes. 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+	pub id: BranchId,
+	/// Lower node of the branch. 
+	pub child: usize,
+	/// Upper node of the branch. 
+	pub parent: usize,
+	/// Time of the lower node. 
+	pub start: f64,
+	/// Time of the upper node. 
+	pub end: f64,
+	/// Individuals below the branch, in increasing order. 
+	pub leaves: Vec<usize>,
+}
+
+impl Branch {
+	/// Length of the branch. 
+	pub fn length(&self) -> f64 {
+		self.end - self.start
+	}
+}
+
+/// Node of a genealogy, i.e. an individual or a common ancestor. 
+/// 
+/// See method ``branches`` on Genealogy for the numbering of nodes. 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+	pub index: usize,
+	/// Time of the node in the past. 
+	pub age: f64,
+	/// Individuals below the node, in increasing order. 
+	pub leaves: Vec<usize>,
+}
+
+/// Statistics of the subtree below a node of a genealogy. 
+/// 
+/// See method ``clade_statistics`` on Genealogy. 
+#[derive(Debug, Clone, PartialEq)]
+pub struct CladeStatistics {
+	/// Number of individuals below the node. 
+	pub leaves: usize,
+	/// Total length of the branches below the node. 
+	pub length: f64,
+	/// Time from the node to its most recent individual. 
+	pub depth: f64,
+	/// Length of the branches below the node by number of individuals below them: 
+	/// entry ``k - 1`` for ``k`` individuals, with one entry per entry of the site 
+	/// frequency spectrum of the whole group. 
+	pub branch_spectrum: Vec<f64>,
+}
+
+/// Summaries of the lengths of the branches of a genealogy. 
+/// 
+/// See method ``branch_length_summary`` on Genealogy. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BranchLengthSummary {
+	pub min: f64,
+	pub max: f64,
+	pub mean: f64,
+	/// Gini coefficient, from zero when all branches have the same length 
+	/// towards one when a single branch holds all the length. 
+	pub gini: f64,
+}
+
+/// Violation of an invariant of a genealogy, as found by ``Genealogy::validate``. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diagnostic {
+	/// There is not one node per individual and per step, 
+	/// or there is not one time step per step.
+	LengthMismatch { nodes: usize, steps: usize, time_steps: usize },
+	/// A time step is zero, negative or not finite. 
+	InvalidTimeStep { event: usize, time_step: f64 },
+	/// A step refers to an individual that is not in the group. 
+	IndexOutOfRange { event: usize, index: usize },
+	/// A step joins two individuals that already have a common ancestor. 
+	AlreadyJoined { event: usize, indexes: [usize; 2] },
+	/// A step does not name individuals of at least two lineages. 
+	TooFewLineages { event: usize },
+	/// The children or the time of the node of an event do not correspond to its step. 
+	InconsistentState { event: usize },
+	/// An individual is sampled at the time of its first coalescence or later. 
+	SampledAfterCoalescence { leaf: usize },
+	/// An individual is not at the expected distance from the root, 
+	/// the depth of the tree minus its sampling time. 
+	NotUltrametric { leaf: usize, distance: f64, depth: f64 },
+}
+
+impl std::fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Diagnostic::LengthMismatch { nodes, steps, time_steps } => 
+				write!(f, "{} nodes, {} steps and {} time steps do not match", nodes, steps, time_steps),
+			Diagnostic::InvalidTimeStep { event, time_step } => 
+				write!(f, "time step {} is {}, but it must be positive and finite", event, time_step),
+			Diagnostic::IndexOutOfRange { event, index } => 
+				write!(f, "step {} refers to individual {}, outside of the group", event, index),
+			Diagnostic::AlreadyJoined { event, indexes } => 
+				write!(f, "step {} joins individuals {} and {}, which are already in the same set", event, indexes[0], indexes[1]),
+			Diagnostic::TooFewLineages { event } => 
+				write!(f, "step {} merges less than two lineages", event),
+			Diagnostic::InconsistentState { event } => 
+				write!(f, "node of event {} does not correspond to its step", event),
+			Diagnostic::SampledAfterCoalescence { leaf } => 
+				write!(f, "individual {} coalesces before being sampled", leaf),
+			Diagnostic::NotUltrametric { leaf, distance, depth } => 
+				write!(f, "individual {} is at distance {} from the root of a tree of depth {}, not matching its sampling time", leaf, distance, depth),
+		}
+	}
+}
+
+/// Error when building a genealogy, or querying it. 
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenealogyError {
+	/// There are no individuals. 
+	EmptyGroup,
+	/// Events do not join all individuals in a single ancestor: 
+	/// there must be exactly one event less than individuals. 
+	WrongNumberOfEvents { expected: usize, found: usize },
+	/// Events violate some invariant of genealogies. 
+	Invalid(Vec<Diagnostic>),
+	/// A graph is not a binary tree with non-negative branch lengths and 
+	/// individuals labeled from 0 to (n - 1). 
+	NotATree,
+	/// An individual is not in the group. 
+	IndexOutOfBounds { index: usize, group_size: usize },
+	/// Two individuals have no common ancestor, e.g. in different trees of a forest. 
+	NotCoalescing { indexes: [usize; 2] },
+	/// A merger does not join at least two lineages, or does not happen after 
+	/// the previous one, see ``from_mergers``. 
+	InvalidMerger { event: usize },
+}
+
+impl std::fmt::Display for GenealogyError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			GenealogyError::EmptyGroup => write!(f, "there are no individuals"),
+			GenealogyError::WrongNumberOfEvents { expected, found } => 
+				write!(f, "expected {} events to join all individuals, found {}", expected, found),
+			GenealogyError::Invalid(diagnostics) => {
+				write!(f, "invalid genealogy: ")?;
+				let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+				write!(f, "{}", messages.join("; "))
+			},
+			GenealogyError::NotATree => write!(f, "the graph is not a binary tree of labeled individuals"),
+			GenealogyError::IndexOutOfBounds { index, group_size } => 
+				write!(f, "individual {} is not in the group of {} individuals", index, group_size),
+			GenealogyError::NotCoalescing { indexes } => 
+				write!(f, "individuals {} and {} have no common ancestor", indexes[0], indexes[1]),
+			GenealogyError::InvalidMerger { event } => 
+				write!(f, "merger {} must join at least two lineages, after the previous merger", event),
+		}
+	}
+}
+
+impl std::error::Error for GenealogyError {}
+
 impl Genealogy {
 
-	pub(crate) fn new(path: Vec<PartitionVec<()>>, steps: Vec<[usize; 2]>, time_steps: Vec<f64>) -> Self {
+	/// Genealogy of ``group_size`` individuals joined by the given steps, 
+	/// after the given time steps. 
+	pub(crate) fn from_steps(group_size: usize, steps: Vec<[usize; 2]>, time_steps: Vec<f64>) -> Self {
+		let steps = steps.iter().map(|indexes| indexes.to_vec()).collect();
+		Genealogy::from_merger_steps(group_size, steps, time_steps)
+	}
+
+	/// Genealogy of ``group_size`` individuals merged by the given steps, each one with 
+	/// an individual of each lineage it merges, after the given time steps. 
+	pub(crate) fn from_merger_steps(group_size: usize, steps: Vec<Vec<usize>>, time_steps: Vec<f64>) -> Self {
+		let branch_ids = (0..group_size + steps.len()).map(BranchId).collect();
+		let sampling_times = vec![0.0; group_size];
+		let (parents, times) = Genealogy::flat_nodes(&steps, &time_steps, &sampling_times);
 		let graph = None;
 
-		Genealogy{path, steps, time_steps, graph}
+		Genealogy{steps, time_steps, parents, times, branch_ids, sampling_times, graph}
+	}
+
+	/// Parent and time of every node, from the steps, see ``nodes``. 
+	fn flat_nodes(steps: &[Vec<usize>], time_steps: &[f64], sampling_times: &[f64]) -> (Vec<usize>, Vec<f64>) {
+		let group_size = sampling_times.len();
+		let number_of_nodes = group_size + steps.len();
+		let mut parents: Vec<usize> = (0..number_of_nodes).collect();
+		let mut times = vec![0.0; number_of_nodes];
+		times[..group_size].copy_from_slice(sampling_times);
+
+		// Union-find over individuals, remembering the top node of each set
+		let mut sets = crate::union_find::UnionFind::new(group_size);
+		let mut top_nodes: Vec<usize> = (0..group_size).collect();
+
+		let mut cummulative_time = 0.0;
+		for (event, (indexes, time_step)) in steps.iter().zip(time_steps).enumerate() {
+			cummulative_time += time_step;
+			let node = group_size + event;
+			times[node] = cummulative_time;
+
+			for &index in indexes {
+				parents[top_nodes[sets.find(index)]] = node;
+			}
+			let root = indexes.iter().fold(indexes[0], |root, &index| sets.union(root, index));
+			top_nodes[root] = node;
+		}
+
+		(parents, times)
+	}
+
+	/// Set the time in the past at which each individual was sampled. 
+	pub(crate) fn with_sampling_times(mut self, sampling_times: Vec<f64>) -> Self {
+		self.times[..sampling_times.len()].copy_from_slice(&sampling_times);
+		self.sampling_times = sampling_times;
+		self
+	}
+
+	/// Set the identifier of the branch above each node. 
+	pub(crate) fn with_branch_ids(mut self, branch_ids: Vec<BranchId>) -> Self {
+		self.branch_ids = branch_ids;
+		self
+	}
+
+	/// Identifier of the branch above each node, see ``branches``. 
+	pub(crate) fn branch_ids(&self) -> &[BranchId] {
+		&self.branch_ids
+	}
+
+	/// Build the genealogy of ``group_size`` individuals from a list of events, 
+	/// each one given by its time in the past and an individual of each of the two 
+	/// lineages it joins, so that other models can use the methods of genealogies. 
+	/// 
+	/// # Errors
+	/// 
+	/// If there are no individuals, if there is not one event less than individuals, 
+	/// or if events violate some invariant, see ``validate``: times must increase, 
+	/// starting after zero, and each event must join two different lineages. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::{Diagnostic, Genealogy, GenealogyError};
+	/// 
+	/// let genealogy = Genealogy::from_events(3, &[(0.5, [0, 1]), (2.0, [2, 1])]).unwrap();
+	/// assert_eq!(genealogy.depth(), 2.0);
+	/// assert_eq!(genealogy.divergence(0, 1).unwrap(), 1.0);
+	/// 
+	/// let error = Genealogy::from_events(3, &[(0.5, [0, 1]), (2.0, [0, 1])]).unwrap_err();
+	/// assert_eq!(error, GenealogyError::Invalid(vec![Diagnostic::AlreadyJoined { event: 1, indexes: [0, 1] }]));
+	/// ```
+	pub fn from_events(group_size: usize, events: &[(f64, [usize; 2])]) -> Result<Self, GenealogyError> {
+		if group_size == 0 {
+			return Err(GenealogyError::EmptyGroup);
+		}
+		if events.len() != group_size - 1 {
+			return Err(GenealogyError::WrongNumberOfEvents { expected: group_size - 1, found: events.len() });
+		}
+
+		// Indexes must be checked before joining sets
+
+		let out_of_range: Vec<Diagnostic> = events
+			.iter()
+			.enumerate()
+			.filter_map(|(event, (_, indexes))| {
+				indexes.iter()
+					.find(|&&index| index >= group_size)
+					.map(|&index| Diagnostic::IndexOutOfRange { event, index })
+			})
+			.collect();
+		if !out_of_range.is_empty() {
+			return Err(GenealogyError::Invalid(out_of_range));
+		}
+
+		let steps = events.iter().map(|&(_, indexes)| indexes).collect();
+		let time_steps = events
+			.iter()
+			.scan(0.0, |previous, &(time, _)| {
+				let time_step = time - *previous;
+				*previous = time;
+				Some(time_step)
+			})
+			.collect();
+		let genealogy = Genealogy::from_steps(group_size, steps, time_steps);
+
+		genealogy.validate().map_err(GenealogyError::Invalid)?;
+		Ok(genealogy)
+	}
+
+	/// Multiply all times in the genealogy by a positive factor, 
+	/// e.g. to change the unit of time. 
+	/// 
+	/// # Panics
+	/// 
+	/// If the factor is not positive. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let mut genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let depth = genealogy.depth();
+	/// genealogy.rescale(2.0);
+	/// 
+	/// assert!((genealogy.depth() - 2.0 * depth).abs() < 1e-12);
+	/// ```
+	pub fn rescale(&mut self, factor: f64) -> &mut Self {
+		assert!(factor > 0.0, "The factor must be positive.");
+
+		for time in self.time_steps.iter_mut().chain(self.sampling_times.iter_mut()).chain(self.times.iter_mut()) {
+			*time *= factor;
+		}
+		self.graph = None;
+		self
+	}
+
+	/// Check the internal invariants of the genealogy, returning every violation found. 
+	/// 
+	/// A valid genealogy has one node per individual and per event and one time step 
+	/// per event, starts with every individual in its own set, joins two or more 
+	/// different sets at each event, as recorded in its steps, and all its time steps 
+	/// are positive and finite. 
+	/// Individuals must be sampled before their first coalescence. Finally, 
+	/// the distance along branches from each individual to the root must be 
+	/// the depth of the tree minus its sampling time. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// 
+	/// assert_eq!(genealogy.validate(), Ok(()));
+	/// ```
+	pub fn validate(&self) -> Result<(), Vec<Diagnostic>> {
+		let mut diagnostics = Vec::new();
+
+		// Sizes
+
+		let group_size = self.group_size();
+		let number_of_nodes = group_size + self.steps.len();
+		if self.parents.len() != number_of_nodes || self.times.len() != number_of_nodes || self.time_steps.len() != self.steps.len() {
+			diagnostics.push(Diagnostic::LengthMismatch { 
+				nodes: self.parents.len().min(self.times.len()), 
+				steps: self.steps.len(), 
+				time_steps: self.time_steps.len(),
+			});
+			return Err(diagnostics);
+		}
+
+		// Time steps
+
+		for (event, &time_step) in self.time_steps.iter().enumerate() {
+			if !(time_step > 0.0 && time_step.is_finite()) {
+				diagnostics.push(Diagnostic::InvalidTimeStep { event, time_step });
+			}
+		}
+
+		// Steps, then nodes against steps
+
+		let mut sets = crate::union_find::UnionFind::new(group_size);
+		for (event, indexes) in self.steps.iter().enumerate() {
+			if let Some(&index) = indexes.iter().find(|&&index| index >= group_size) {
+				diagnostics.push(Diagnostic::IndexOutOfRange { event, index });
+				continue;
+			}
+			if indexes.len() < 2 {
+				diagnostics.push(Diagnostic::TooFewLineages { event });
+				continue;
+			}
+			for (position, &index) in indexes.iter().enumerate() {
+				if let Some(&previous) = indexes[..position].iter().find(|&&previous| sets.find(previous) == sets.find(index)) {
+					diagnostics.push(Diagnostic::AlreadyJoined { event, indexes: [previous, index] });
+					break;
+				}
+			}
+			for &index in &indexes[1..] {
+				sets.union(indexes[0], index);
+			}
+		}
+		if diagnostics.is_empty() {
+			let (parents, times) = Genealogy::flat_nodes(&self.steps, &self.time_steps, &self.sampling_times);
+			let mut inconsistent: Vec<bool> = (group_size..number_of_nodes)
+				.map(|node| times[node] != self.times[node])
+				.collect();
+			for (&expected, &found) in parents.iter().zip(&self.parents) {
+				if expected != found {
+					for &parent in &[expected, found] {
+						if (group_size..number_of_nodes).contains(&parent) {
+							inconsistent[parent - group_size] = true;
+						}
+					}
+				}
+			}
+			for (event, _) in inconsistent.iter().enumerate().filter(|(_, &inconsistent)| inconsistent) {
+				diagnostics.push(Diagnostic::InconsistentState { event });
+			}
+		}
+
+		// Distances to the root, only meaningful for well formed genealogies
+
+		if diagnostics.is_empty() {
+			let (parents, times) = self.nodes();
+			for leaf in 0..group_size {
+				if parents[leaf] != leaf && times[parents[leaf]] <= self.sampling_times[leaf] {
+					diagnostics.push(Diagnostic::SampledAfterCoalescence { leaf });
+				}
+			}
+		}
+		if diagnostics.is_empty() {
+			let depth = self.depth();
+			for (leaf, distance) in self.leaf_depths().into_iter().enumerate() {
+				let expected = depth - self.sampling_times[leaf];
+				if (distance - expected).abs() > ULTRAMETRIC_TOLERANCE * depth.max(1.0) {
+					diagnostics.push(Diagnostic::NotUltrametric { leaf, distance, depth });
+				}
+			}
+		}
+
+		match diagnostics.is_empty() {
+			true => Ok(()),
+			false => Err(diagnostics),
+		}
+	}
+
+	/// Whether all individuals are at the same distance from the root of the tree,
+	/// up to rounding errors. This is not the case if individuals are sampled at 
+	/// different times. 
+	/// 
+	/// Distances are computed along the branches of the genealogic tree, 
+	/// from the parent of each node. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// 
+	/// assert!(genealogy.is_ultrametric());
+	/// ```
+	pub fn is_ultrametric(&self) -> bool {
+		let depth = self.depth();
+		let distances = self.leaf_depths();
+		distances
+			.iter()
+			.all(|distance| (distance - distances[0]).abs() <= ULTRAMETRIC_TOLERANCE * depth.max(1.0))
+	}
+
+	/// Distance from each individual to the root, along the branches of the tree. 
+	/// 
+	/// All of them are the depth for individuals sampled at present, 
+	/// but not for individuals sampled in the past. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.leaf_depths(), vec![4.0; 4]);
+	/// ```
+	pub fn leaf_depths(&self) -> Vec<f64> {
+		// Parents come after their children, so that distances are known from the root down
+		let mut distances = vec![0.0; self.parents.len()];
+		for node in (0..self.parents.len()).rev() {
+			let parent = self.parents[node];
+			if parent != node {
+				distances[node] = distances[parent] + self.times[parent] - self.times[node];
+			}
+		}
+
+		distances.truncate(self.group_size());
+		distances
+	}
+
+	/// All branches of the tree, ordered by their lower node. 
+	/// 
+	/// Nodes are numbered with the individuals first, from 0 to (n - 1), followed by 
+	/// the common ancestor created at each event, from n to (2n - 2), so that the root 
+	/// is the last node. This is also the order of nodes in the exported graph. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let branches = genealogy.branches();
+	/// 
+	/// assert_eq!(branches.len(), 2 * group_size - 2);
+	/// let length: f64 = branches.iter().map(|branch| branch.length()).sum();
+	/// assert!((length - genealogy.length()).abs() < 1e-9);
+	/// ```
+	pub fn branches(&self) -> Vec<Branch> {
+		let number_of_branches = self.parents.len().saturating_sub(1);
+		self.all_leaves()
+			.into_iter()
+			.take(number_of_branches)
+			.enumerate()
+			.map(|(child, leaves)| self.branch_of_node(child, leaves))
+			.collect()
+	}
+
+	/// Branch with the given identifier, if it is in the genealogy. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let mut genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let branch = genealogy.branches()[3].clone();
+	/// genealogy.rescale(2.0);
+	/// let rescaled = genealogy.branch(branch.id).unwrap();
+	/// 
+	/// assert_eq!(rescaled.leaves, branch.leaves);
+	/// assert!((rescaled.length() - 2.0 * branch.length()).abs() < 1e-9);
+	/// ```
+	pub fn branch(&self, id: BranchId) -> Option<Branch> {
+		self.branch_ids
+			.iter()
+			.take(self.parents.len().saturating_sub(1))
+			.position(|&branch_id| branch_id == id)
+			.map(|child| self.branch_of_node(child, self.leaves_below(child)))
+	}
+
+	/// Length of each branch, in the order of ``branches``. 
+	pub fn branch_lengths(&self) -> Vec<f64> {
+		// From the flat arrays of parents and times, without the individuals below each branch
+		let (parents, times) = self.nodes();
+		parents
+			.iter()
+			.zip(times)
+			.take(parents.len().saturating_sub(1))
+			.map(|(&parent, time)| times[parent] - time)
+			.collect()
+	}
+
+	/// Total length of branches by number of individuals below them: the entry ``j`` 
+	/// is the length of branches ancestral to exactly ``j + 1`` individuals, as in 
+	/// ``clade_statistics`` of the root, but in linear time. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.branch_spectrum(), vec![6.0, 5.0, 0.0]);
+	/// ```
+	pub fn branch_spectrum(&self) -> Vec<f64> {
+		// Children come before their parents
+		let (parents, times) = self.nodes();
+		let mut sizes: Vec<usize> = (0..parents.len()).map(|node| (node < self.group_size()) as usize).collect();
+		let mut spectrum = vec![0.0; self.group_size().saturating_sub(1)];
+		for (node, &parent) in parents.iter().enumerate().take(parents.len().saturating_sub(1)) {
+			spectrum[sizes[node] - 1] += times[parent] - times[node];
+			sizes[parent] += sizes[node];
+		}
+
+		spectrum
+	}
+
+	/// Minimum, maximum, mean and Gini coefficient of the lengths of the branches, 
+	/// or ``None`` if there are no branches, i.e. there is only one individual. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let summary = genealogy.branch_length_summary().unwrap();
+	/// 
+	/// assert_eq!(genealogy.branch_lengths(), vec![1.0, 1.0, 2.0, 2.0, 3.0, 2.0]);
+	/// assert_eq!((summary.min, summary.max), (1.0, 3.0));
+	/// assert_eq!(summary.mean, genealogy.length() / 6.0);
+	/// assert!(summary.gini > 0.0 && summary.gini < 1.0);
+	/// ```
+	pub fn branch_length_summary(&self) -> Option<BranchLengthSummary> {
+		let mut lengths = self.branch_lengths();
+		if lengths.is_empty() {
+			return None;
+		}
+		lengths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+		// Gini coefficient from sorted values: Σ (2i - m - 1) x_i / (m Σ x_i)
+		let count = lengths.len() as f64;
+		let total: f64 = lengths.iter().sum();
+		let weighted: f64 = lengths
+			.iter()
+			.enumerate()
+			.map(|(index, length)| (2.0 * (index + 1) as f64 - count - 1.0) * length)
+			.sum();
+		let gini = match total > 0.0 {
+			true => weighted / (count * total),
+			false => 0.0,
+		};
+
+		Some(BranchLengthSummary {
+			min: lengths[0],
+			max: lengths[lengths.len() - 1],
+			mean: total / count,
+			gini,
+		})
+	}
+
+	fn branch_of_node(&self, child: usize, leaves: Vec<usize>) -> Branch {
+		let parent = self.parents[child];
+
+		Branch { 
+			id: self.branch_ids[child], 
+			child, 
+			parent, 
+			start: self.times[child], 
+			end: self.times[parent], 
+			leaves,
+		}
+	}
+
+	/// Individuals below a node, in increasing order, replaying events until the node. 
+	fn leaves_below(&self, node: usize) -> Vec<usize> {
+		let group_size = self.group_size();
+		if node < group_size {
+			return vec![node];
+		}
+
+		let event = node - group_size;
+		let mut sets = crate::union_find::UnionFind::new(group_size);
+		for indexes in &self.steps[..=event] {
+			for &index in &indexes[1..] {
+				sets.union(indexes[0], index);
+			}
+		}
+		let representative = sets.find(self.steps[event][0]);
+		(0..group_size).filter(|&individual| sets.find(individual) == representative).collect()
+	}
+
+	/// Individuals below every node, in increasing order, built bottom-up 
+	/// since children are created before their parents. 
+	fn all_leaves(&self) -> Vec<Vec<usize>> {
+		let group_size = self.group_size();
+		let mut leaves: Vec<Vec<usize>> = (0..self.parents.len())
+			.map(|node| match node < group_size {
+				true => vec![node],
+				false => Vec::new(),
+			})
+			.collect();
+		for (node, &parent) in self.parents.iter().enumerate() {
+			leaves[node].sort_unstable();
+			if parent != node {
+				let below = leaves[node].clone();
+				leaves[parent].extend(below);
+			}
+		}
+		leaves
+	}
+
+	/// Parent and time of every node, the root being its own parent. 
+	pub(crate) fn nodes(&self) -> (&[usize], &[f64]) {
+		(&self.parents, &self.times)
+	}
+
+	/// Most recent common ancestor of some individuals: going up from each one to the 
+	/// first ancestor of the first individual, the highest of the nodes reached, 
+	/// or ``None`` if some individual does not reach one. 
+	fn common_ancestor(&self, leaves: &[usize]) -> Option<usize> {
+		let mut ancestors = vec![false; self.parents.len()];
+		let mut node = leaves[0];
+		ancestors[node] = true;
+		while self.parents[node] != node {
+			node = self.parents[node];
+			ancestors[node] = true;
+		}
+
+		let mut mrca = leaves[0];
+		for &leaf in leaves {
+			let mut node = leaf;
+			while !ancestors[node] {
+				if self.parents[node] == node {
+					return None;
+				}
+				node = self.parents[node];
+			}
+			mrca = mrca.max(node);
+		}
+		Some(mrca)
+	}
+
+	/// Number of individuals in the group. 
+	pub fn group_size(&self) -> usize {
+		self.sampling_times.len()
+	}
+
+	/// Number of individuals and common ancestors, i.e. (2n - 1) unless some event 
+	/// merges more than two lineages. 
+	pub fn number_of_nodes(&self) -> usize {
+		self.parents.len()
+	}
+
+	/// Node with the given index. 
+	/// 
+	/// # Panics
+	/// 
+	/// If there is no such node, i.e. ``index`` is at least ``number_of_nodes``. 
+	pub fn node(&self, index: usize) -> Node {
+		let group_size = self.group_size();
+		assert!(index < group_size + self.steps.len(), "There is no such node.");
+
+		match index < group_size {
+			true => Node { index, age: self.sampling_times[index], leaves: vec![index] },
+			false => Node { index, age: self.times[index], leaves: self.leaves_below(index) },
+		}
+	}
+
+	/// Same genealogy with individual ``i`` renamed ``permutation[i]``, keeping times, 
+	/// sampling times and identifiers of branches. 
+	/// 
+	/// # Panics
+	/// 
+	/// If ``permutation`` is not a permutation of the individuals. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let relabeled = genealogy.relabel(&[3, 2, 1, 0]);
+	/// 
+	/// assert_eq!(relabeled.divergence(3, 2).unwrap(), genealogy.divergence(0, 1).unwrap());
+	/// assert_eq!(relabeled.depth(), genealogy.depth());
+	/// ```
+	pub fn relabel(&self, permutation: &[usize]) -> Genealogy {
+		let group_size = self.group_size();
+		let mut seen = vec![false; group_size];
+		assert!(
+			permutation.len() == group_size 
+				&& permutation.iter().all(|&label| label < group_size && !std::mem::replace(&mut seen[label], true)),
+			"Labels must be a permutation of the individuals."
+		);
+
+		let steps = self.steps
+			.iter()
+			.map(|indexes| indexes.iter().map(|&index| permutation[index]).collect())
+			.collect();
+		let mut sampling_times = vec![0.0; group_size];
+		let mut branch_ids = self.branch_ids.clone();
+		for (individual, &label) in permutation.iter().enumerate() {
+			sampling_times[label] = self.sampling_times[individual];
+			branch_ids[label] = self.branch_ids[individual];
+		}
+
+		let mut genealogy = Genealogy::from_merger_steps(group_size, steps, self.time_steps.clone())
+			.with_sampling_times(sampling_times);
+		genealogy.branch_ids = branch_ids;
+		genealogy
+	}
+
+	/// Shape of the tree, forgetting labels and times, as nested parentheses with 
+	/// the children of each node in a canonical order, e.g. ``((,),(,))`` for a 
+	/// balanced tree of four individuals. Two genealogies have the same unlabeled 
+	/// topology if and only if they have the same shape. 
+	/// 
+	/// Takes time and memory O(n²) in the worst case, since shapes of all clades are kept. 
+	pub fn shape(&self) -> String {
+		self.canonical_order().0
+	}
+
+	/// Permutation relabeling individuals in the canonical order of the tree, see ``shape``: 
+	/// individuals are numbered as they are reached going down the tree, children in 
+	/// canonical order. Genealogies with the same shape then have the same clades, up 
+	/// to relabeling of individuals within identical subtrees. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::fixtures;
+	/// 
+	/// let genealogy = fixtures::caterpillar(4);
+	/// let other = genealogy.relabel(&[2, 0, 3, 1]);
+	/// 
+	/// let canonical = genealogy.relabel(&genealogy.canonical_permutation());
+	/// let other_canonical = other.relabel(&other.canonical_permutation());
+	/// assert_eq!(genealogy.shape(), other.shape());
+	/// assert_eq!(canonical.divergence(1, 2).unwrap(), other_canonical.divergence(1, 2).unwrap());
+	/// ```
+	pub fn canonical_permutation(&self) -> Vec<usize> {
+		let order = self.canonical_order().1;
+		let mut permutation = vec![0; order.len()];
+		for (label, &individual) in order.iter().enumerate() {
+			permutation[individual] = label;
+		}
+		permutation
+	}
+
+	/// Shape of the root and individuals in canonical order, built bottom-up 
+	/// since children are created before their parents. 
+	fn canonical_order(&self) -> (String, Vec<usize>) {
+		let group_size = self.group_size();
+		let mut sets = crate::union_find::UnionFind::new(group_size);
+		let mut clades: Vec<Option<(String, Vec<usize>)>> = (0..group_size)
+			.map(|individual| Some((String::new(), vec![individual])))
+			.collect();
+		let mut nodes: Vec<usize> = (0..group_size).collect(); // of the lineage of each representative
+
+		for (event, indexes) in self.steps.iter().enumerate() {
+			let mut children: Vec<(String, Vec<usize>)> = indexes
+				.iter()
+				.map(|&index| clades[nodes[sets.find(index)]].take().unwrap())
+				.collect();
+			children.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+			let shapes: Vec<&str> = children.iter().map(|child| child.0.as_str()).collect();
+			let shape = format!("({})", shapes.join(","));
+			let leaves = children.into_iter().flat_map(|child| child.1).collect();
+			clades.push(Some((shape, leaves)));
+
+			let representative = indexes.iter().fold(indexes[0], |representative, &index| sets.union(representative, index));
+			nodes[representative] = group_size + event;
+		}
+
+		clades.pop().unwrap().unwrap()
+	}
+
+	/// Most recent common ancestor of some individuals. 
+	/// 
+	/// # Panics
+	/// 
+	/// If there are no individuals, or some of them is not in the group. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let mrca = genealogy.mrca_node(&[2, 5]);
+	/// 
+	/// assert_eq!(2.0 * mrca.age, genealogy.divergence(2, 5).unwrap());
+	/// assert!(mrca.leaves.contains(&2) && mrca.leaves.contains(&5));
+	/// let clade = genealogy.clade(mrca.index);
+	/// assert_eq!(clade.group_size(), mrca.leaves.len());
+	/// ```
+	pub fn mrca_node(&self, leaves: &[usize]) -> Node {
+		assert!(!leaves.is_empty(), "There must be at least one individual.");
+		assert!(leaves.iter().all(|&leaf| leaf < self.group_size()), "Individuals must be in the group.");
+
+		self.node(self.common_ancestor(leaves).expect("Individuals of a genealogy have a common ancestor."))
+	}
+
+	/// Parent of a node, or ``None`` for the root. 
+	pub fn parent(&self, index: usize) -> Option<usize> {
+		let (parents, _) = self.nodes();
+		match parents[index] == index {
+			true => None,
+			false => Some(parents[index]),
+		}
+	}
+
+	/// Children of a node, none for individuals and two for common ancestors, 
+	/// or more for those of multiple mergers. 
+	pub fn children(&self, index: usize) -> Vec<usize> {
+		let (parents, _) = self.nodes();
+		(0..parents.len())
+			.filter(|&child| child != index && parents[child] == index)
+			.collect()
+	}
+
+	/// Ancestor of an individual at time ``t``, i.e. the node whose branch to its parent 
+	/// contains ``t``: the node itself at its own time, and the root after the common 
+	/// ancestor of the group. 
+	/// 
+	/// # Panics
+	/// 
+	/// If the individual is not in the group, or if ``t`` is before its sampling time. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.ancestor_index_at(0, 0.5), 0);
+	/// assert_eq!(genealogy.ancestor_index_at(0, 1.0), 4);
+	/// assert_eq!(genealogy.ancestor_index_at(3, 3.0), 5);
+	/// assert_eq!(genealogy.ancestor_index_at(3, 10.0), 6);
+	/// ```
+	pub fn ancestor_index_at(&self, leaf: usize, t: f64) -> usize {
+		assert!(leaf < self.group_size(), "The individual must be in the group.");
+		assert!(t >= self.sampling_times[leaf], "Ancestors are only defined after the sampling time.");
+
+		let (parents, times) = self.nodes();
+		let mut node = leaf;
+		while parents[node] != node && times[parents[node]] <= t {
+			node = parents[node];
+		}
+		node
+	}
+
+	/// Time of the common ancestor of two individuals, which is half their divergence
+	/// when both are sampled at time zero, see ``divergence``. 
+	/// 
+	/// # Errors
+	/// 
+	/// As ``divergence``. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.coancestry_time(0, 1), Ok(1.0));
+	/// assert_eq!(genealogy.coancestry_time(0, 3), Ok(4.0));
+	/// ```
+	pub fn coancestry_time(&self, index_1: usize, index_2: usize) -> Result<f64, GenealogyError> {
+		let divergence = self.divergence(index_1, index_2)?;
+		Ok((divergence + self.sampling_times[index_1] + self.sampling_times[index_2]) / 2.0)
+	}
+
+	/// Genealogy of the individuals below a node, relabeled from zero in increasing order. 
+	/// Branches keep their identifiers. 
+	pub fn clade(&self, index: usize) -> Genealogy {
+		let node = self.node(index);
+		let forest = self.truncate_at(node.age);
+		let tree = (0..forest.len())
+			.find(|&tree| forest.individuals(tree) == &node.leaves[..])
+			.unwrap();
+
+		forest.trees()[tree].clone()
+	}
+
+	/// Total length of the branches below a node, i.e. the length of its clade. 
+	pub fn total_length_of_clade(&self, node: &Node) -> f64 {
+		self.clade_branches(node).map(|branch| branch.length()).sum()
+	}
+
+	/// Statistics of the clade below a node, to compare clades within a genealogy. 
+	/// 
+	/// Under the infinite sites model with mutation rate θ, the expected contribution 
+	/// of the clade to the site frequency spectrum of the group is θ / 2 times 
+	/// its ``branch_spectrum``. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let node = genealogy.mrca_node(&[2, 3]);
+	/// let statistics = genealogy.clade_statistics(&node);
+	/// 
+	/// assert_eq!(statistics.leaves, 2);
+	/// assert_eq!(statistics.length, 4.0);
+	/// assert_eq!(statistics.depth, 2.0);
+	/// assert_eq!(statistics.branch_spectrum, vec![4.0, 0.0, 0.0]);
+	/// ```
+	pub fn clade_statistics(&self, node: &Node) -> CladeStatistics {
+		let mut branch_spectrum = vec![0.0; self.group_size().saturating_sub(1)];
+		for branch in self.clade_branches(node) {
+			branch_spectrum[branch.leaves.len() - 1] += branch.length();
+		}
+		let most_recent = node.leaves
+			.iter()
+			.map(|&leaf| self.sampling_times[leaf])
+			.fold(f64::INFINITY, f64::min);
+
+		CladeStatistics {
+			leaves: node.leaves.len(),
+			length: branch_spectrum.iter().sum(),
+			depth: node.age - most_recent,
+			branch_spectrum,
+		}
+	}
+
+	/// Branches strictly below a node. 
+	fn clade_branches<'a>(&self, node: &'a Node) -> impl Iterator<Item = Branch> + 'a {
+		// Clades are nested: a smaller clade sharing an individual is below the node
+		self.branches()
+			.into_iter()
+			.filter(move |branch| {
+				branch.leaves.len() < node.leaves.len() && node.leaves.binary_search(&branch.leaves[0]).is_ok()
+			})
+	}
+
+	/// Time in the past at which each individual was sampled, 
+	/// zero for individuals sampled at present. 
+	pub fn sampling_times(&self) -> &[f64] {
+		&self.sampling_times
 	}
 
 	/// Total depth of the tree, i.e. the distance from the first common ancestor
@@ -39,143 +1064,861 @@ impl Genealogy {
 		self.time_steps.iter().sum()
 	}
 
+	/// Depth of the tree in a unit of time, see ``TimeScale``. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::{Generations, TimeScale};
+	/// 
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let scale = TimeScale::diploid(500.0, 1.0);
+	/// 
+	/// assert_eq!(genealogy.depth_in::<Generations>(&scale), Generations(4000.0));
+	/// ```
+	pub fn depth_in<T: TimeUnit>(&self, scale: &TimeScale) -> T {
+		CoalescentTime(self.depth()).convert(scale)
+	}
+
+	/// Number of common ancestors by time bin: entry ``k`` counts nodes with ages 
+	/// in [k w, (k + 1) w) for bins of width ``w``, up to the bin of the root. 
+	/// 
+	/// # Panics
+	/// 
+	/// If the bin width is not positive and finite. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.depth_profile(1.5), vec![1, 1, 1]);
+	/// assert_eq!(genealogy.depth_profile(10.0), vec![3]);
+	/// ```
+	pub fn depth_profile(&self, bin_width: f64) -> Vec<usize> {
+		assert!(bin_width > 0.0 && bin_width.is_finite(), "The bin width must be positive and finite.");
+
+		let bins = (self.depth() / bin_width).floor() as usize + 1;
+		let mut profile = vec![0; bins];
+		let mut cummulative_time = 0.0;
+		for time_step in &self.time_steps {
+			cummulative_time += time_step;
+			let bin = ((cummulative_time / bin_width).floor() as usize).min(bins - 1);
+			profile[bin] += 1;
+		}
+
+		profile
+	}
+
+	/// Ancestral partition of the group at time ``t`` in the past. 
+	/// 
+	/// Two individuals are in the same set if they already met in a common 
+	/// ancestor at time ``t``. Coalescences happening exactly at time ``t`` 
+	/// are included. 
+	/// 
+	/// # Panics
+	/// 
+	/// If ``t`` is negative. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// 
+	/// assert_eq!(genealogy.partition_at(0.0).amount_of_sets(), group_size);
+	/// assert_eq!(genealogy.partition_at(genealogy.depth()).amount_of_sets(), 1);
+	/// ```
+	pub fn partition_at(&self, t: f64) -> Partition {
+		self.partition_after(self.events_until(t))
+	}
+
+	/// Number of events happening at time ``t`` or before. 
+	fn events_until(&self, t: f64) -> usize {
+		assert!(t >= 0.0, "Time must be non-negative.");
+
+		let mut cummulative_time = 0.0;
+		let mut counter = 0;
+		for time_step in &self.time_steps {
+			cummulative_time += time_step;
+			if cummulative_time > t {
+				break;
+			} else {
+				counter += 1;
+			}
+		}
+
+		counter
+	}
+
+	/// Ancestral partition of the group after ``step`` events, 
+	/// from the initial partition of singletons until a single set. 
+	/// Each event joins two sets, or more for multiple mergers. 
+	/// 
+	/// See trait ``RandomPartition`` for statistics of partitions. 
+	/// 
+	/// # Panics
+	/// 
+	/// If there are less than ``step`` events. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::traits::RandomPartition;
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// 
+	/// assert_eq!(genealogy.partition_after(3).number_of_blocks(), group_size - 3);
+	/// assert_eq!(genealogy.partition_after(group_size - 1).block_sizes(), vec![group_size]);
+	/// ```
+	pub fn partition_after(&self, step: usize) -> Partition {
+		assert!(step <= self.steps.len(), "There are not that many events.");
+
+		let mut partition = Partition::singletons(self.group_size());
+		for indexes in &self.steps[..step] {
+			for &index in &indexes[1..] {
+				partition.union(indexes[0], index);
+			}
+		}
+		partition
+	}
+
+	/// Cut the genealogy at time ``t`` in the past, keeping the forest of subtrees 
+	/// more recent than ``t``, one for each set of ``partition_at(t)``. 
+	/// Individuals sampled further in the past than ``t`` are left out. 
+	/// 
+	/// # Panics
+	/// 
+	/// If ``t`` is negative. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let t = genealogy.depth() / 4.0;
+	/// let forest = genealogy.truncate_at(t);
+	/// 
+	/// assert_eq!(forest.len(), genealogy.partition_at(t).amount_of_sets());
+	/// assert!(forest.trees().iter().all(|tree| tree.depth() <= t));
+	/// ```
+	pub fn truncate_at(&self, t: f64) -> Forest {
+		let events = self.events_until(t);
+		let partition = self.partition_after(events);
+		let group_size = partition.len();
+
+		// Individuals of each tree, and their new labels
+
+		let mut individuals: Vec<Vec<usize>> = partition
+			.all_sets()
+			.map(|set| {
+				let mut set: Vec<usize> = set.collect();
+				set.sort_unstable();
+				set
+			})
+			.filter(|set| set.len() > 1 || self.sampling_times[set[0]] <= t)
+			.collect();
+		individuals.sort_unstable();
+		let mut tree_of = vec![0; group_size];
+		let mut label_of = vec![0; group_size];
+		for (tree, set) in individuals.iter().enumerate() {
+			for (label, &index) in set.iter().enumerate() {
+				tree_of[index] = tree;
+				label_of[index] = label;
+			}
+		}
+
+		// Replay events in each tree
+
+		let mut steps: Vec<Vec<Vec<usize>>> = vec![Vec::new(); individuals.len()];
+		let mut time_steps: Vec<Vec<f64>> = vec![Vec::new(); individuals.len()];
+		let mut branch_ids: Vec<Vec<BranchId>> = individuals
+			.iter()
+			.map(|set| set.iter().map(|&index| self.branch_ids[index]).collect())
+			.collect();
+		let mut last_times = vec![0.0; individuals.len()];
+
+		let mut cummulative_time = 0.0;
+		for event in 0..events {
+			cummulative_time += self.time_steps[event];
+			let indexes = &self.steps[event];
+			let tree = tree_of[indexes[0]];
+			let labels = indexes.iter().map(|&index| label_of[index]).collect();
+
+			steps[tree].push(labels);
+			time_steps[tree].push(cummulative_time - last_times[tree]);
+			branch_ids[tree].push(self.branch_ids[group_size + event]);
+			last_times[tree] = cummulative_time;
+		}
+
+		let trees = individuals
+			.iter()
+			.zip(steps)
+			.zip(time_steps)
+			.zip(branch_ids)
+			.map(|(((set, steps), time_steps), branch_ids)| {
+				Genealogy::from_merger_steps(set.len(), steps, time_steps)
+					.with_sampling_times(set.iter().map(|&index| self.sampling_times[index]).collect())
+					.with_branch_ids(branch_ids)
+			})
+			.collect();
+
+		Forest::new(trees, individuals, t)
+	}
+
+	/// Number of ancestral lineages through time. 
+	/// 
+	/// Returns the times at which the number of lineages changes, together with 
+	/// the number of lineages from that time on, starting with ``(0.0, n)``, 
+	/// where n is the number of individuals sampled at present. Lineages are added 
+	/// at the times individuals are sampled in the past. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let lineages = genealogy.lineages_through_time();
+	/// 
+	/// assert_eq!(lineages.len(), group_size);
+	/// assert_eq!(lineages[0], (0.0, group_size));
+	/// assert_eq!(lineages[group_size - 1].1, 1);
+	/// ```
+	pub fn lineages_through_time(&self) -> Vec<(f64, usize)> {
+		// Changes in the number of lineages: samples in the past and coalescences
+
+		// and the lineages merged by each coalescence
+
+		let mut changes: Vec<(f64, Option<usize>)> = self.sampling_times
+			.iter()
+			.filter(|&&time| time > 0.0)
+			.map(|&time| (time, None))
+			.collect();
+		changes.extend(self.mergers().map(|(time, indexes)| (time, Some(indexes.len()))));
+		changes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+		let mut current = self.sampling_times.iter().filter(|&&time| time == 0.0).count();
+		let mut lineages = vec![(0.0, current)];
+		for (time, merged) in changes {
+			match merged {
+				None => current += 1,
+				Some(merged) => current -= merged - 1,
+			}
+			lineages.push((time, current));
+		}
+
+		lineages
+	}
+
+	/// Replay the events of the genealogy, from the present into the past. 
+	/// 
+	/// See ``EventStream`` for adapters over events. 
+	/// 
+	/// # Panics
+	/// 
+	/// When reaching an event that merges more than two lineages, 
+	/// see ``mergers`` for those. 
+	pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+		self.steps
+			.iter()
+			.zip(&self.time_steps)
+			.enumerate()
+			.scan(0.0, move |cummulative_time, (event, (indexes, &time_step))| {
+				assert_eq!(indexes.len(), 2, "Event {} merges more than two lineages.", event);
+				*cummulative_time += time_step;
+				let sampled = self.sampling_times.iter().filter(|&&time| time <= *cummulative_time).count();
+				Some(Event { 
+					time_step, 
+					indexes: [indexes[0], indexes[1]], 
+					lineages: sampled - event - 1,
+				})
+			})
+	}
+
+	/// Replay the events of the genealogy, from the present into the past, 
+	/// as the time of each event with an individual of each lineage it merges. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::caterpillar(3);
+	/// let mergers: Vec<(f64, &[usize])> = genealogy.mergers().collect();
+	/// 
+	/// assert_eq!(mergers, vec![(1.0, &[0, 1][..]), (2.0, &[0, 2][..])]);
+	/// ```
+	pub fn mergers(&self) -> impl Iterator<Item = (f64, &[usize])> + '_ {
+		self.steps
+			.iter()
+			.zip(&self.time_steps)
+			.scan(0.0, |cummulative_time, (indexes, &time_step)| {
+				*cummulative_time += time_step;
+				Some((*cummulative_time, indexes.as_slice()))
+			})
+	}
+
 	/// Sum of all the time represented in the edges of the genealogy. 
 	pub fn length(&self) -> f64 {
-		let group_size = self.path[0].len();
-		self.time_steps
+		let mut lineages = self.group_size();
+		let length: f64 = self.time_steps
 			.iter()
-			.enumerate()
-			.map(|(i, time_step)| (group_size - i) as f64 * time_step )
-			.sum()
+			.zip(&self.steps)
+			.map(|(time_step, indexes)| {
+				let length = lineages as f64 * time_step;
+				lineages -= indexes.len() - 1;
+				length
+			})
+			.sum();
+
+		// Individuals sampled in the past are not there before being sampled
+		length - self.sampling_times.iter().sum::<f64>()
+	}
+
+	/// Length of the genealogy in a unit of time, see ``TimeScale``. 
+	pub fn length_in<T: TimeUnit>(&self, scale: &TimeScale) -> T {
+		CoalescentTime(self.length()).convert(scale)
 	}
 
-	/// Distance between two individuals in the genealogic tree. 
+	/// Distance between two individuals in the genealogic tree, zero from an individual to itself. 
+	/// 
+	/// # Errors
+	/// 
+	/// If some individual is not in the group, or if both individuals have no common ancestor. 
 	/// 
 	/// # Remarks
 	/// 
 	/// To compute mean divergence over all pairs, prefer the method
 	/// ``mean_pairwise_divergence``. 
-	pub fn divergence(&self, index_1: usize, index_2: usize) -> f64 {
-		let mut counter = 0;
-		for state in &self.path {
-			if state.same_set(index_1, index_2) {
-				break;
-			} else {
-				counter += 1;
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::GenealogyError;
+	/// 
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// 
+	/// assert_eq!(genealogy.divergence(0, 1), Ok(2.0));
+	/// assert_eq!(genealogy.divergence(1, 1), Ok(0.0));
+	/// assert_eq!(genealogy.divergence(0, 4), Err(GenealogyError::IndexOutOfBounds { index: 4, group_size: 4 }));
+	/// ```
+	pub fn divergence(&self, index_1: usize, index_2: usize) -> Result<f64, GenealogyError> {
+		let group_size = self.group_size();
+		for &index in &[index_1, index_2] {
+			if index >= group_size {
+				return Err(GenealogyError::IndexOutOfBounds { index, group_size });
 			}
 		}
+		if index_1 == index_2 {
+			return Ok(0.0);
+		}
+
+		let mrca = self.common_ancestor(&[index_1, index_2])
+			.ok_or(GenealogyError::NotCoalescing { indexes: [index_1, index_2] })?;
+
+		Ok(self.divergence_at(index_1, index_2, mrca))
+	}
+
+	/// Distance between two distinct individuals of the group. 
+	pub(crate) fn pair_divergence(&self, index_1: usize, index_2: usize) -> f64 {
+		let mrca = self.common_ancestor(&[index_1, index_2]).unwrap();
+		self.divergence_at(index_1, index_2, mrca)
+	}
+
+	/// Distance between two individuals through their common ancestor ``mrca``. 
+	fn divergence_at(&self, index_1: usize, index_2: usize, mrca: usize) -> f64 {
+		2.0 * self.times[mrca] - self.sampling_times[index_1] - self.sampling_times[index_2]
+	}
+
+	/// Divergence between two individuals in a unit of time, see ``TimeScale``. 
+	pub fn divergence_in<T: TimeUnit>(&self, index_1: usize, index_2: usize, scale: &TimeScale) -> Result<T, GenealogyError> {
+		Ok(CoalescentTime(self.divergence(index_1, index_2)?).convert(scale))
+	}
+
+	/// Divergence between a pair of distinct individuals chosen uniformly at random, 
+	/// for Monte Carlo estimators over pairs. 
+	/// 
+	/// # Panics
+	/// 
+	/// If there are less than two individuals. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let divergence = genealogy.random_pair_divergence(&mut rand::thread_rng());
+	/// 
+	/// assert!([2.0, 4.0, 8.0].contains(&divergence));
+	/// ```
+	pub fn random_pair_divergence<R>(&self, rng: &mut R) -> f64 
+	where
+		R: Rng + ?Sized,
+	{
+		assert!(self.group_size() > 1, "There must be at least two individuals.");
+		let pair = rand::seq::index::sample(rng, self.group_size(), 2);
+		self.pair_divergence(pair.index(0), pair.index(1))
+	}
 
-		2.0 * self.time_steps.iter().take(counter).sum::<f64>()
+	/// Common ancestor chosen uniformly at random among those of the genealogy, 
+	/// ``n - 1`` unless some event merges more than two lineages, 
+	/// with the clade of individuals below it. 
+	/// 
+	/// # Panics
+	/// 
+	/// If there is a single individual. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// let genealogy = coalescence::fixtures::caterpillar(5);
+	/// let clade = genealogy.random_clade(&mut rand::thread_rng());
+	/// 
+	/// assert!(clade.leaves.len() >= 2);
+	/// assert_eq!(clade.age, (clade.leaves.len() - 1) as f64);
+	/// ```
+	pub fn random_clade<R>(&self, rng: &mut R) -> Node 
+	where
+		R: Rng + ?Sized,
+	{
+		assert!(!self.steps.is_empty(), "There must be at least two individuals.");
+		self.node(self.group_size() + rng.gen_range(0, self.steps.len()))
 	}
 
 	/// Mean distance of all pairs of individual through their first common ancestor, i.e. 
 	/// mean distance of all pairs of leaves in the tree. 
 	pub fn mean_pairwise_divergence(&self) -> f64 {
-		let group_size = self.steps.len() + 1;
+		let group_size = self.group_size();
+		let mut sets = crate::union_find::UnionFind::new(group_size);
 		let mut cummulative_time = 0.0;
 		let mut cummulative_divergence = 0.0;
 
-		for iteration in 0..(group_size - 1) {
+		for iteration in 0..self.steps.len() {
 			// Retrieve values
 
-			let value_indexes = self.steps[iteration];
-			let state = &self.path[iteration];
+			let value_indexes = &self.steps[iteration];
 			cummulative_time += self.time_steps[iteration];
 
 			// Identify the lengths of sets joints
 
-			let set_size_1 = state.len_of_set(value_indexes[0]);
-			let set_size_2 = state.len_of_set(value_indexes[1]); 
+			let set_sizes: Vec<usize> = value_indexes.iter().map(|&index| sets.size(index)).collect();
+			for &index in &value_indexes[1..] {
+				sets.union(value_indexes[0], index);
+			}
 			
-			// Count number of pairs
+			// Count number of pairs across different sets
 
-			let number_of_pairs = set_size_1 * set_size_2;
+			let total: usize = set_sizes.iter().sum();
+			let number_of_pairs = (total * total - set_sizes.iter().map(|size| size * size).sum::<usize>()) / 2;
 
 			// Add to the counter the respective time
 
 			cummulative_divergence += (2.0 * cummulative_time) * number_of_pairs as f64;
 		}
 
-		// Take mean
+		// Take mean, removing the time before individuals are sampled
 
-		cummulative_divergence * 2.0 / (group_size * (group_size - 1)) as f64
+		let sampling_time: f64 = self.sampling_times.iter().sum();
+		cummulative_divergence * 2.0 / (group_size * (group_size - 1)) as f64 
+			- 2.0 * sampling_time / group_size as f64
 	}
 
-	fn compute_graph(&mut self) -> &Graph<(usize, usize), f64, petgraph::Undirected, u32> { 
-		let group_size = self.steps.len() + 1;
-		let mut graph = Graph::new_undirected();
-		let mut node_indexes = HashMap::new();
-		let mut representatives_generation = HashMap::new();
+	/// Drop neutral mutations on the genealogy and return the resulting 
+	/// haplotypes of the group. 
+	/// 
+	/// Mutations are placed uniformly over the total length of the tree: 
+	/// each one falls on a branch with probability proportional to its length. 
+	/// The number of mutations is either Poisson distributed, with mean 
+	/// θ/2 times the length, or fixed, depending on the ``MutationModel``. 
+	/// 
+	/// # Panics
+	/// 
+	/// If some mutation is required but the genealogy has no branches, 
+	/// i.e. it has only one individual. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::MutationModel;
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites: 5 }, &mut rng);
+	/// 
+	/// assert_eq!(haplotypes.segregating_sites(), 5);
+	/// ```
+	pub fn sample_haplotypes<R>(&self, model: MutationModel, rng: &mut R) -> Haplotypes 
+	where
+		R: Rng + ?Sized,
+	{
+		self.sample_mutations(model, rng).haplotypes()
+	}
 
-		match self.steps.is_empty() {
-			true => {
-				graph.add_node((0, 0));
-			},
-			false => {
-				let mut state: PartitionVec<()> =
-            		PartitionVec::from_iter((0..group_size).map(|_| ()));
-				for index in 0..group_size {
-					let node_index = graph.add_node((0, index));
-					node_indexes.insert((0, index), node_index);
-					representatives_generation.insert(index, 0);
-				}
+	/// Drop neutral mutations on the genealogy, keeping track of the branch, 
+	/// age and position of each one. 
+	/// 
+	/// See ``sample_haplotypes`` for how mutations are placed. 
+	/// 
+	/// # Panics
+	/// 
+	/// If some mutation is required but the genealogy has no branches. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::MutationModel;
+	/// let group_size = 10;
+	/// let rng = rand::thread_rng();
+	/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+	///
+	/// let mut rng = rand::thread_rng();
+	/// let genealogy = coalescent.sample_genealogy(&mut rng);
+	/// let mutated = genealogy.sample_mutations(MutationModel::FixedSegregatingSites { sites: 5 }, &mut rng);
+	/// 
+	/// assert_eq!(mutated.mutations().len(), 5);
+	/// assert!(mutated.mutations().iter().all(|mutation| mutation.age < genealogy.depth()));
+	/// ```
+	pub fn sample_mutations<R>(&self, model: MutationModel, rng: &mut R) -> MutatedGenealogy 
+	where
+		R: Rng + ?Sized,
+	{
+		// Cummulative length of the tree, branch by branch
 
-				for generation in 0..self.steps.len() {
-					let time_step = self.time_steps[generation];
-					let value_indexes = self.steps[generation];
+		let branches = self.branches();
+		let cummulative_lengths: Vec<f64> = branches
+			.iter()
+			.scan(0.0, |cummulative, branch| {
+				*cummulative += branch.length();
+				Some(*cummulative)
+			})
+			.collect();
+		let length = cummulative_lengths.last().cloned().unwrap_or(0.0);
 
-					// Retrieve representatives
+		// Number of mutations
 
-					let representatives: Vec<usize> = value_indexes.iter()
-						.map(|&i| (0..group_size).find(|&j| state.same_set(i, j))
-							.expect("Could not retrieve previous state of the genealogy.")
-						).collect();
+		let number_of_mutations = match model {
+			MutationModel::Poisson { theta } => {
+				let mean = theta / 2.0 * length;
+				match mean > 0.0 {
+					true => {
+						let poisson = Poisson::new(mean).expect("Invalid mutation rate.");
+						let number: u64 = poisson.sample(rng);
+						number as usize
+					},
+					false => 0,
+				}
+			},
+			MutationModel::FixedSegregatingSites { sites } => sites,
+		};
+		assert!(number_of_mutations == 0 || length > 0.0, "There are no branches to place mutations on.");
 
-					// Add parent node
+		// Place mutations
 
-					let new_representative = core::cmp::min(representatives[0], representatives[1]);
-					let node_index = graph.add_node((generation + 1, new_representative));
-					node_indexes.insert((generation + 1, new_representative), node_index);
-					
-					// Add edges
+		let mutations = (0..number_of_mutations)
+			.map(|_| {
+				let point = rng.gen::<f64>() * length;
+				let index = cummulative_lengths
+					.iter()
+					.position(|&cummulative| point < cummulative)
+					.unwrap_or(cummulative_lengths.len() - 1);
+				let branch = &branches[index];
+				let age = (branch.end - (cummulative_lengths[index] - point)).max(branch.start);
+				let mutation = Mutation { branch: branch.id, age, position: rng.gen::<f64>() };
 
-					graph.add_edge(
-						node_indexes[&(generation + 1, new_representative)], 
-						node_indexes[&(representatives_generation[&representatives[0]], representatives[0])], 
-						time_step
-					);
-					graph.add_edge(
-						node_indexes[&(generation + 1, new_representative)], 
-						node_indexes[&(representatives_generation[&representatives[1]], representatives[1])], 
-						time_step
-					);
+				(mutation, branch.leaves.clone())
+			})
+			.collect();
 
-					// Update
+		MutatedGenealogy::new(self.clone(), mutations)
+	}
 
-					representatives_generation.insert(new_representative, generation + 1);
-					state.union(value_indexes[0], value_indexes[1]);
+	/// Drop neutral mutations on the genealogy as ``sample_mutations``, but deterministically: 
+	/// mutations only depend on the seed, the identifiers and lengths of branches, and 
+	/// the model. The same genealogy, e.g. read back from a file, always gets the 
+	/// same mutations, on every run and platform, without carrying the state of a 
+	/// random number generator. 
+	/// 
+	/// Under the Poisson model, mutations on each branch are the points of a Poisson 
+	/// process driven by a splitmix64 stream keyed by the seed and the branch, so 
+	/// that derived genealogies keeping the identifiers of branches keep the mutations 
+	/// of the branches they share, if their lengths are the same. Counts and ages are 
+	/// computed from the stream with basic arithmetic only, which is rounded the same 
+	/// on every platform, never with the logarithm or exponential of math libraries. 
+	/// 
+	/// # Panics
+	/// 
+	/// If θ is negative or not finite, or if some mutation is required but the 
+	/// genealogy has no branches. 
+	/// 
+	/// # Examples
+	/// 
+	/// ```
+	/// use coalescence::MutationModel;
+	/// 
+	/// let genealogy = coalescence::fixtures::balanced();
+	/// let model = MutationModel::Poisson { theta: 2.0 };
+	/// let mutated = genealogy.hash_mutations(model, 42);
+	/// 
+	/// assert_eq!(mutated.mutations(), genealogy.hash_mutations(model, 42).mutations());
+	/// ```
+	pub fn hash_mutations(&self, model: MutationModel, seed: u64) -> MutatedGenealogy {
+		let branches = self.branches();
+		let mutations = match model {
+			MutationModel::Poisson { theta } => {
+				assert!(theta >= 0.0 && theta.is_finite(), "Invalid mutation rate.");
+				let rate = theta / 2.0;
+				let mut mutations = Vec::new();
+				for branch in branches.iter().filter(|_| rate > 0.0) {
+					// Poisson number of mutations, then uniform ages along the branch
+					let mut stream = HashStream::new(seed, branch.id.0 as u64);
+					let count = stream.poisson(rate * branch.length());
+					let mut on_branch: Vec<Mutation> = (0..count)
+						.map(|_| {
+							let age = branch.start + stream.uniform() * branch.length();
+							Mutation { branch: branch.id, age, position: stream.uniform() }
+						})
+						.collect();
+					on_branch.sort_by(|a, b| a.age.partial_cmp(&b.age).unwrap());
+					mutations.extend(on_branch.into_iter().map(|mutation| (mutation, branch.leaves.clone())));
 				}
+				mutations
 			},
+			MutationModel::FixedSegregatingSites { sites } => {
+				let cummulative_lengths: Vec<f64> = branches
+					.iter()
+					.scan(0.0, |cummulative, branch| {
+						*cummulative += branch.length();
+						Some(*cummulative)
+					})
+					.collect();
+				let length = cummulative_lengths.last().cloned().unwrap_or(0.0);
+				assert!(sites == 0 || length > 0.0, "There are no branches to place mutations on.");
+
+				// One stream per site
+				(0..sites as u64)
+					.map(|site| {
+						let mut stream = HashStream::new(seed, site);
+						let point = stream.uniform() * length;
+						let index = cummulative_lengths
+							.iter()
+							.position(|&cummulative| point < cummulative)
+							.unwrap_or(cummulative_lengths.len() - 1);
+						let branch = &branches[index];
+						let age = (branch.end - (cummulative_lengths[index] - point)).max(branch.start);
+						let mutation = Mutation { branch: branch.id, age, position: stream.uniform() };
+
+						(mutation, branch.leaves.clone())
+					})
+					.collect()
+			},
+		};
+
+		MutatedGenealogy::new(self.clone(), mutations)
+	}
+
+	fn compute_graph(&mut self) -> &Graph<(usize, usize), f64, petgraph::Undirected, u32> { 
+		self.graph = Some(self.build_graph());
+		self.graph.as_ref().unwrap()
+	}
+
+	fn build_graph(&self) -> Graph<(usize, usize), f64, petgraph::Undirected, u32> { 
+		let group_size = self.group_size();
+		let mut graph = Graph::new_undirected();
+
+		// Nodes are labeled by their generation and the smallest individual below them, 
+		// and children always come before their parent
+
+		let mut representatives: Vec<usize> = (0..self.parents.len()).collect();
+		for node in 0..self.parents.len() {
+			let parent = self.parents[node];
+			representatives[parent] = representatives[parent].min(representatives[node]);
+		}
+		for (node, &representative) in representatives.iter().enumerate() {
+			let generation = match node < group_size {
+				true => 0,
+				false => node - group_size + 1,
+			};
+			graph.add_node((generation, representative));
+		}
+
+		// Edges, weighted by the length of the branch
+
+		for (node, &parent) in self.parents.iter().enumerate().filter(|&(node, &parent)| parent != node) {
+			graph.add_edge(
+				petgraph::graph::NodeIndex::new(parent), 
+				petgraph::graph::NodeIndex::new(node), 
+				self.times[parent] - self.times[node]
+			);
 		}
 		
-		self.graph = Some(graph);
-		&self.graph.as_ref().unwrap()
+		graph
 	}
 }
 
-impl Into<Graph<(usize, usize), f64, petgraph::Undirected, u32>> for Genealogy 
+impl From<Genealogy> for Graph<(usize, usize), f64, petgraph::Undirected, u32> 
 {
-	fn into(mut self) -> Graph<(usize, usize), f64, petgraph::Undirected, u32> { 
-		match self.graph {
+	fn from(mut genealogy: Genealogy) -> Self { 
+		match genealogy.graph {
 		 	Some(graph) => graph,
-		 	None => self.compute_graph().clone(),
+		 	None => genealogy.compute_graph().clone(),
 		 } 
 	}
 }
 
+/// Genealogy of a tree, e.g. a graph exported with ``From<Genealogy>`` and then edited. 
+/// 
+/// Individuals are the leaves, labeled by the second entry of their weight. The root 
+/// is the only node with two neighbours, and times are distances from the root, the 
+/// most recent individual being at time zero; older individuals are sampled in the past. 
+/// 
+/// # Errors
+/// 
+/// If the graph is empty, if it is not a binary tree with non-negative and finite 
+/// branch lengths whose leaves are labeled from 0 to (n - 1), or if the events of the 
+/// tree violate some invariant, e.g. two events at the same time. 
+/// 
+/// # Examples
+/// 
+/// ```
+/// use coalescence::Genealogy;
+/// use petgraph::visit::EdgeRef;
+/// use std::convert::TryFrom;
+/// 
+/// let genealogy = coalescence::fixtures::balanced();
+/// let mut graph: petgraph::Graph<(usize, usize), f64, petgraph::Undirected> = genealogy.clone().into();
+/// // Lengthen both branches below the root, which is the last node
+/// let root = graph.node_indices().last().unwrap();
+/// let edges: Vec<_> = graph.edges(root).map(|edge| edge.id()).collect();
+/// for edge in edges {
+///     graph[edge] += 1.0;
+/// }
+/// 
+/// let edited = Genealogy::try_from(graph).unwrap();
+/// assert_eq!(edited.depth(), genealogy.depth() + 1.0);
+/// assert_eq!(edited.divergence(0, 1).unwrap(), genealogy.divergence(0, 1).unwrap());
+/// ```
+impl std::convert::TryFrom<Graph<(usize, usize), f64, petgraph::Undirected, u32>> for Genealogy {
+	type Error = GenealogyError;
+
+	fn try_from(graph: Graph<(usize, usize), f64, petgraph::Undirected, u32>) -> Result<Self, Self::Error> {
+		let nodes = graph.node_count();
+		if nodes == 0 {
+			return Err(GenealogyError::EmptyGroup);
+		}
+		if nodes.is_multiple_of(2) || graph.edge_count() != nodes - 1 {
+			return Err(GenealogyError::NotATree);
+		}
+		let group_size = nodes.div_ceil(2);
+		if group_size == 1 {
+			return Genealogy::from_events(1, &[]);
+		}
+
+		// Root, and the lengths of edges
+
+		let degrees: Vec<usize> = graph.node_indices().map(|node| graph.neighbors(node).count()).collect();
+		let roots: Vec<usize> = (0..nodes).filter(|&node| degrees[node] == 2).collect();
+		if roots.len() != 1 || degrees.iter().any(|&degree| degree != 1 && degree != 2 && degree != 3) {
+			return Err(GenealogyError::NotATree);
+		}
+		if graph.raw_edges().iter().any(|edge| edge.weight < 0.0 || !edge.weight.is_finite()) {
+			return Err(GenealogyError::NotATree);
+		}
+
+		// Distances from the root, in depth-first order
+
+		let root = petgraph::graph::NodeIndex::new(roots[0]);
+		let mut distances = vec![f64::NAN; nodes];
+		let mut parents = vec![None; nodes];
+		let mut order = Vec::with_capacity(nodes);
+		let mut stack = vec![root];
+		distances[root.index()] = 0.0;
+		while let Some(node) = stack.pop() {
+			order.push(node);
+			for edge in graph.edges(node) {
+				let other = match edge.source() == node {
+					true => edge.target(),
+					false => edge.source(),
+				};
+				if Some(other) == parents[node.index()] {
+					continue;
+				}
+				if !distances[other.index()].is_nan() {
+					return Err(GenealogyError::NotATree);
+				}
+				distances[other.index()] = distances[node.index()] + edge.weight();
+				parents[other.index()] = Some(node);
+				stack.push(other);
+			}
+		}
+		if order.len() != nodes {
+			return Err(GenealogyError::NotATree);
+		}
+
+		// Labels of individuals, and the smallest individual below each node, children first
+
+		let mut labels = vec![None; nodes];
+		let mut seen = vec![false; group_size];
+		for node in (0..nodes).filter(|&node| degrees[node] == 1) {
+			let label = graph[petgraph::graph::NodeIndex::new(node)].1;
+			if label >= group_size || std::mem::replace(&mut seen[label], true) {
+				return Err(GenealogyError::NotATree);
+			}
+			labels[node] = Some(label);
+		}
+		let mut internal = Vec::with_capacity(group_size - 1);
+		let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes];
+		for node in order.iter().rev() {
+			let node = node.index();
+			if degrees[node] > 1 {
+				let representatives: Vec<usize> = children[node].iter().map(|&child| labels[child].unwrap()).collect();
+				labels[node] = representatives.iter().min().cloned();
+				internal.push((node, [representatives[0], representatives[1]]));
+			}
+			if let Some(parent) = parents[node] {
+				children[parent.index()].push(node);
+			}
+		}
+
+		// Events by age, individuals by their sampling times
+
+		let depth = (0..nodes).filter(|&node| degrees[node] == 1).map(|node| distances[node]).fold(0.0, f64::max);
+		let age = |node: usize| match depth - distances[node] <= ULTRAMETRIC_TOLERANCE * depth {
+			true => 0.0,
+			false => depth - distances[node],
+		};
+		let mut events: Vec<(f64, [usize; 2])> = internal.iter().map(|&(node, indexes)| (age(node), indexes)).collect();
+		events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+		let mut sampling_times = vec![0.0; group_size];
+		for node in (0..nodes).filter(|&node| degrees[node] == 1) {
+			sampling_times[labels[node].unwrap()] = age(node);
+		}
+
+		let genealogy = Genealogy::from_events(group_size, &events)?.with_sampling_times(sampling_times);
+		genealogy.validate().map_err(GenealogyError::Invalid)?;
+		Ok(genealogy)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::EventStream;
 
 	#[test]
 	fn construction() {
@@ -186,9 +1929,385 @@ mod tests {
 		let mut rng = rand::thread_rng();
 		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
 
-		assert_eq!(genealogy.path.len(), group_size);
+		assert_eq!(genealogy.parents.len(), 2 * group_size - 1);
+		assert_eq!(genealogy.times.len(), 2 * group_size - 1);
 		assert_eq!(genealogy.steps.len(), group_size - 1);
 		assert_eq!(genealogy.time_steps.len(), group_size - 1);
 		assert!(genealogy.graph.is_none());
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn partition_at() {
+		let group_size = 20;
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(group_size, rng);
+		
+		let mut rng = rand::thread_rng();
+		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
+
+		let mut cummulative_time = 0.0;
+		for i in 0..(group_size - 1) {
+			let midpoint = cummulative_time + genealogy.time_steps[i] / 2.0;
+			assert_eq!(genealogy.partition_at(midpoint).amount_of_sets(), group_size - i);
+			cummulative_time += genealogy.time_steps[i];
+		}
+		assert_eq!(genealogy.partition_at(2.0 * cummulative_time).amount_of_sets(), 1);
+	}
+
+	#[test]
+	fn validation() {
+		let group_size = 30;
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(group_size, rng);
+		
+		let mut rng = rand::thread_rng();
+		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
+		assert_eq!(genealogy.validate(), Ok(()));
+
+		// Graph edges are branch lengths
+		let graph: Graph<(usize, usize), f64, petgraph::Undirected, u32> = genealogy.clone().into();
+		let total: f64 = graph.raw_edges().iter().map(|edge| edge.weight).sum();
+		assert!((total - genealogy.length()).abs() < 1e-9 * genealogy.length());
+
+		let mut broken = genealogy.clone();
+		broken.time_steps[3] = -1.0;
+		broken.steps[5] = broken.steps[4].clone();
+		broken.steps[6].truncate(1);
+		let diagnostics = broken.validate().unwrap_err();
+		assert!(diagnostics.contains(&Diagnostic::InvalidTimeStep { event: 3, time_step: -1.0 }));
+		assert!(diagnostics.iter().any(|diagnostic| matches!(diagnostic, Diagnostic::AlreadyJoined { event: 5, .. })));
+		assert!(diagnostics.contains(&Diagnostic::TooFewLineages { event: 6 }));
+
+		let mut broken = genealogy.clone();
+		broken.times[group_size + 2] += 1e-3;
+		assert_eq!(broken.validate(), Err(vec![Diagnostic::InconsistentState { event: 2 }]));
+
+		let mut broken = genealogy;
+		broken.parents.pop();
+		assert_eq!(broken.validate().unwrap_err().len(), 1);
+	}
+
+	#[test]
+	fn branches() {
+		let group_size = 15;
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(group_size, rng);
+		
+		let mut rng = rand::thread_rng();
+		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
+		let branches = genealogy.branches();
+
+		// Every internal node has two children, whose leaves it joins
+		for node in group_size..(2 * group_size - 1) {
+			let children: Vec<&Branch> = branches.iter().filter(|branch| branch.parent == node).collect();
+			assert_eq!(children.len(), 2);
+			let mut leaves: Vec<usize> = children.iter().flat_map(|branch| branch.leaves.clone()).collect();
+			leaves.sort_unstable();
+			if node < 2 * group_size - 2 {
+				assert_eq!(branches[node].leaves, leaves);
+			} else {
+				assert_eq!(leaves, (0..group_size).collect::<Vec<usize>>());
+			}
+		}
+		for branch in &branches {
+			assert!(branch.length() > 0.0);
+			assert_eq!(genealogy.branch(branch.id).as_ref(), Some(branch));
+		}
+	}
+
+	#[test]
+	fn truncate_at() {
+		let group_size = 25;
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(group_size, rng);
+		
+		let mut rng = rand::thread_rng();
+		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
+		let t = genealogy.depth() / 3.0;
+		let forest = genealogy.truncate_at(t);
+
+		let mut individuals: Vec<usize> = (0..forest.len()).flat_map(|tree| forest.individuals(tree).to_vec()).collect();
+		individuals.sort_unstable();
+		assert_eq!(individuals, (0..group_size).collect::<Vec<usize>>());
+
+		// Divergences within trees are preserved
+		for (index, tree) in forest.trees().iter().enumerate() {
+			assert_eq!(tree.validate(), Ok(()));
+			let original = forest.individuals(index);
+			for i in 0..original.len() {
+				for j in 0..i {
+					assert!((tree.divergence(i, j).unwrap() - genealogy.divergence(original[i], original[j]).unwrap()).abs() < 1e-9);
+				}
+			}
+		}
+
+		// Total length below the cut
+		let length: f64 = genealogy.lineages_through_time()
+			.windows(2)
+			.map(|pair| pair[0].1 as f64 * (pair[1].0.min(t) - pair[0].0.min(t)))
+			.sum();
+		assert!((forest.length() - length).abs() < 1e-9);
+
+		// Cutting above the root leaves the whole tree
+		let forest = genealogy.truncate_at(2.0 * genealogy.depth());
+		assert_eq!(forest.len(), 1);
+		assert_eq!(forest.trees()[0].branches(), genealogy.branches());
+	}
+
+	#[test]
+	fn traversal() {
+		let group_size = 12;
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(group_size, rng);
+		
+		let mut rng = rand::thread_rng();
+		let genealogy: Genealogy = coalescent.sample_genealogy(&mut rng);
+		let root = 2 * group_size - 2;
+
+		assert_eq!(genealogy.mrca_node(&(0..group_size).collect::<Vec<usize>>()).index, root);
+		assert_eq!(genealogy.mrca_node(&[3]).index, 3);
+		assert_eq!(genealogy.parent(root), None);
+		for node in group_size..=root {
+			let children = genealogy.children(node);
+			assert_eq!(children.len(), 2);
+			assert!(children.iter().all(|&child| genealogy.parent(child) == Some(node)));
+
+			let clade = genealogy.clade(node);
+			assert_eq!(clade.validate(), Ok(()));
+			assert!((clade.depth() - genealogy.node(node).age).abs() < 1e-9);
+		}
+	}
+
+	#[test]
+	fn ancestry_paths() {
+		let mut rng = rand::thread_rng();
+		let mut coalescent = crate::Coalescent::sampler(5);
+		coalescent.schedule_sample(0.3, 2);
+		for _ in 0..20 {
+			let genealogy = coalescent.sample_genealogy(&mut rng);
+			let (parents, times) = genealogy.nodes();
+			for leaf in 0..genealogy.group_size() {
+				// Each node up the path is the ancestor over its own branch
+				let mut node = leaf;
+				while parents[node] != node {
+					let middle = (times[node] + times[parents[node]]) / 2.0;
+					assert_eq!(genealogy.ancestor_index_at(leaf, middle), node);
+					node = parents[node];
+				}
+				assert_eq!(genealogy.ancestor_index_at(leaf, times[node] + 1.0), node);
+			}
+
+			// Two individuals share their ancestors from their common ancestor on
+			let time = genealogy.coancestry_time(0, 5).unwrap();
+			assert!(time >= 0.3);
+			assert_eq!(genealogy.ancestor_index_at(0, time), genealogy.ancestor_index_at(5, time));
+			assert_ne!(genealogy.ancestor_index_at(0, time - 1e-9), genealogy.ancestor_index_at(5, time - 1e-9));
+		}
+	}
+
+	#[test]
+	fn from_events() {
+		assert_eq!(Genealogy::from_events(0, &[]).unwrap_err(), GenealogyError::EmptyGroup);
+		assert_eq!(Genealogy::from_events(1, &[]).unwrap().depth(), 0.0);
+		assert_eq!(
+			Genealogy::from_events(3, &[(1.0, [0, 1])]).unwrap_err(),
+			GenealogyError::WrongNumberOfEvents { expected: 2, found: 1 }
+		);
+		assert_eq!(
+			Genealogy::from_events(2, &[(1.0, [0, 2])]).unwrap_err(),
+			GenealogyError::Invalid(vec![Diagnostic::IndexOutOfRange { event: 0, index: 2 }])
+		);
+		assert_eq!(
+			Genealogy::from_events(3, &[(1.0, [0, 1]), (0.5, [1, 2])]).unwrap_err(),
+			GenealogyError::Invalid(vec![Diagnostic::InvalidTimeStep { event: 1, time_step: -0.5 }])
+		);
+
+		// Round trip of a sampled genealogy
+		let rng = rand::thread_rng();
+		let coalescent = crate::Coalescent::new(8, rng);
+		let mut rng = rand::thread_rng();
+		let genealogy = coalescent.sample_genealogy(&mut rng);
+		let events: Vec<(f64, [usize; 2])> = genealogy.events()
+			.scan_times()
+			.map(|(time, event)| (time, event.indexes))
+			.collect();
+		let rebuilt = Genealogy::from_events(8, &events).unwrap();
+		assert!((rebuilt.mean_pairwise_divergence() - genealogy.mean_pairwise_divergence()).abs() < 1e-9);
+	}
+
+	#[test]
+	fn heterochronous() {
+		let rng = rand::thread_rng();
+		let mut coalescent = crate::Coalescent::new(4, rng);
+		coalescent.schedule_sample(0.5, 3);
+
+		let mut rng = rand::thread_rng();
+		for _ in 0..20 {
+			let genealogy = coalescent.sample_genealogy(&mut rng);
+			assert_eq!(genealogy.validate(), Ok(()));
+			assert_eq!(genealogy.group_size(), 7);
+			assert!(!genealogy.is_ultrametric());
+
+			// Ancient samples only join lineages at their sampling time
+			assert_eq!(genealogy.lineages_through_time()[0], (0.0, 4));
+			assert!(genealogy.divergence(4, 5).unwrap() >= 0.0);
+			assert!(genealogy.node(6).age == 0.5);
+			assert!(genealogy.parent(6).map(|parent| genealogy.node(parent).age > 0.5).unwrap());
+			assert!(genealogy.partition_at(0.25).amount_of_sets() >= 3);
+		}
+	}
+
+	#[test]
+	fn clade_statistics() {
+		let genealogy = crate::fixtures::balanced();
+
+		let root = genealogy.node(6);
+		let statistics = genealogy.clade_statistics(&root);
+		assert_eq!(statistics.leaves, 4);
+		assert_eq!(statistics.length, genealogy.length());
+		assert_eq!(statistics.depth, genealogy.depth());
+		assert_eq!(statistics.branch_spectrum, vec![6.0, 5.0, 0.0]);
+
+		// Flat computations agree with those through branches
+		let sampled = crate::Coalescent::sampler(40).sample_genealogy(&mut rand::thread_rng());
+		let expected = sampled.clade_statistics(&sampled.node(78)).branch_spectrum;
+		for (flat, length) in sampled.branch_spectrum().iter().zip(expected) {
+			assert!((flat - length).abs() < 1e-9);
+		}
+		let lengths: Vec<f64> = sampled.branches().iter().map(|branch| branch.length()).collect();
+		assert_eq!(sampled.branch_lengths(), lengths);
+
+		let first = genealogy.node(4);
+		assert_eq!(genealogy.total_length_of_clade(&first), 2.0);
+		assert_eq!(genealogy.clade_statistics(&first).depth, 1.0);
+
+		// Individuals are clades without branches
+		let leaf = genealogy.node(3);
+		assert_eq!(genealogy.clade_statistics(&leaf).length, 0.0);
+		assert_eq!(genealogy.clade_statistics(&leaf).depth, 0.0);
+	}
+
+	#[test]
+	fn depths() {
+		let rng = rand::thread_rng();
+		let mut coalescent = crate::Coalescent::new(5, rng);
+		coalescent.schedule_sample(0.3, 2);
+		let mut rng = rand::thread_rng();
+		let genealogy = coalescent.sample_genealogy(&mut rng);
+
+		// Depths of individuals sampled in the past are shorter
+		let depths = genealogy.leaf_depths();
+		assert!((depths[0] - genealogy.depth()).abs() < 1e-9);
+		assert!((depths[6] - (genealogy.depth() - 0.3)).abs() < 1e-9);
+
+		let profile = genealogy.depth_profile(0.1);
+		assert_eq!(profile.iter().sum::<usize>(), 6);
+		assert!(profile[profile.len() - 1] >= 1);
+		assert_eq!(crate::fixtures::caterpillar(4).depth_profile(1.0), vec![0, 1, 1, 1]);
+	}
+
+	#[test]
+	fn branch_lengths() {
+		let genealogy = crate::fixtures::caterpillar(1);
+		assert!(genealogy.branch_length_summary().is_none());
+
+		// Branches of a cherry have equal lengths
+		let summary = crate::fixtures::cherry().branch_length_summary().unwrap();
+		assert_eq!(summary.gini, 0.0);
+		assert_eq!(summary.mean, 1.0);
+
+		let genealogy = crate::fixtures::almost_star(10, 1e-6);
+		let summary = genealogy.branch_length_summary().unwrap();
+		assert!((summary.max - 1.0).abs() < 1e-5);
+		assert!(summary.min < 1e-5);
+		assert!(summary.gini > 0.4);
+	}
+
+	#[test]
+	fn random_queries() {
+		let genealogy = crate::fixtures::balanced();
+		let mut rng = rand::thread_rng();
+		let samples = 20_000;
+		let mean = (0..samples).map(|_| genealogy.random_pair_divergence(&mut rng)).sum::<f64>() / samples as f64;
+		assert!((mean - genealogy.mean_pairwise_divergence()).abs() < 0.1);
+
+		// The three common ancestors are equally likely
+		let mut counts = [0; 3];
+		for _ in 0..3000 {
+			counts[genealogy.random_clade(&mut rng).index - 4] += 1;
+		}
+		assert!(counts.iter().all(|&count| count > 850 && count < 1150));
+	}
+
+	#[test]
+	fn relabeling() {
+		let genealogy = crate::fixtures::balanced().with_sampling_times(vec![0.0, 0.5, 0.0, 0.0]);
+		let permutation = [2, 0, 3, 1];
+		let relabeled = genealogy.relabel(&permutation);
+		assert_eq!(relabeled.validate(), Ok(()));
+		assert_eq!(relabeled.sampling_times(), &[0.5, 0.0, 0.0, 0.0]);
+		for i in 0..4 {
+			for j in 0..4 {
+				assert_eq!(relabeled.divergence(permutation[i], permutation[j]).unwrap(), genealogy.divergence(i, j).unwrap());
+			}
+		}
+		let ids = |genealogy: &Genealogy, leaf: usize| genealogy.branches().iter().find(|branch| branch.child == leaf).unwrap().id;
+		assert_eq!(ids(&relabeled, 2), ids(&genealogy, 0));
+
+		// Shapes of random genealogies of four individuals: caterpillars or balanced
+		let coalescent = crate::Coalescent::sampler(4);
+		let mut rng = rand::thread_rng();
+		for _ in 0..20 {
+			let genealogy = coalescent.sample_genealogy(&mut rng);
+			let shape = genealogy.shape();
+			assert!(shape == "(,(,(,)))" || shape == "((,),(,))");
+			assert_eq!(genealogy.relabel(&[1, 3, 0, 2]).shape(), shape);
+		}
+		assert_eq!(crate::fixtures::balanced().canonical_permutation(), vec![0, 1, 2, 3]);
+	}
+
+	#[test]
+	fn graph_round_trip() {
+		use std::convert::TryFrom;
+
+		let coalescent = crate::Coalescent::sampler(12);
+		let mut rng = rand::thread_rng();
+		let genealogy = coalescent.sample_genealogy(&mut rng).with_sampling_times(vec![0.0; 12]);
+		let other = Genealogy::try_from(Graph::from(genealogy.clone())).unwrap();
+		assert!((other.depth() - genealogy.depth()).abs() < 1e-9);
+		for i in 0..12 {
+			for j in 0..12 {
+				assert!((other.divergence(i, j).unwrap() - genealogy.divergence(i, j).unwrap()).abs() < 1e-9);
+			}
+		}
+
+		// Individuals sampled in the past
+		let ancient = Genealogy::from_events(3, &[(1.0, [0, 1]), (3.0, [0, 2])]).unwrap().with_sampling_times(vec![0.0, 0.5, 2.0]);
+		let other = Genealogy::try_from(Graph::from(ancient.clone())).unwrap();
+		assert_eq!(other.sampling_times(), ancient.sampling_times());
+		assert_eq!(other.length(), ancient.length());
+
+		// Not trees
+		let mut graph = Graph::from(crate::fixtures::cherry());
+		let extra = graph.add_node((0, 2));
+		assert_eq!(Genealogy::try_from(graph.clone()).unwrap_err(), GenealogyError::NotATree);
+		graph.add_edge(extra, petgraph::graph::NodeIndex::new(0), 1.0);
+		graph.add_node((0, 3));
+		assert_eq!(Genealogy::try_from(graph).unwrap_err(), GenealogyError::NotATree);
+		assert_eq!(Genealogy::try_from(Graph::new_undirected()).unwrap_err(), GenealogyError::EmptyGroup);
+	}
+
+	#[test]
+	fn divergence_errors() {
+		let genealogy = crate::fixtures::caterpillar(5);
+		assert_eq!(genealogy.divergence(3, 4), Ok(8.0));
+		assert_eq!(genealogy.divergence(2, 2), Ok(0.0));
+		assert_eq!(genealogy.divergence(7, 1), Err(GenealogyError::IndexOutOfBounds { index: 7, group_size: 5 }));
+
+		// Two individuals not joined yet
+		let unjoined = Genealogy::from_steps(2, Vec::new(), Vec::new());
+		let error = unjoined.divergence(0, 1).unwrap_err();
+		assert_eq!(error, GenealogyError::NotCoalescing { indexes: [0, 1] });
+		assert_eq!(error.to_string(), "individuals 0 and 1 have no common ancestor");
+	}
+}
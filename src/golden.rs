@@ -0,0 +1,164 @@
+//! Golden seeds, to detect changes of the sampled genealogies across versions.
+//!
+//! Each ``GoldenCase`` samples a genealogy from a fixed seed of ``Pcg32`` and compares
+//! the hash of its events with the one recorded for this version of the crate. A change
+//! of the hash means that the same seed no longer gives the same genealogy: simulations
+//! of downstream crates are then not reproducible across the upgrade, even if they are
+//! still correct in distribution.
+//!
+//! Times are rounded to ``1e-9`` before hashing, so that differences of the last digits
+//! of floating-point functions between platforms do not change hashes.
+//!
+//! # Examples
+//!
+//! In a test of a downstream crate:
+//! ```
+//! for case in coalescence::golden::CASES {
+//!     assert_eq!(case.check(), Ok(()));
+//! }
+//! ```
+//!
+
+// Types
+use crate::{Coalescent, Demography, EventStream, Genealogy, StructuredCoalescent};
+use rand_pcg::Pcg32;
+
+// Traits
+use rand::SeedableRng;
+
+/// Model sampled by a golden case.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GoldenModel {
+    /// ``Coalescent`` of a group of constant size one.
+    Kingman { group_size: usize },
+    /// ``Coalescent`` of a group growing exponentially, see ``Demography::exponential``.
+    Exponential { group_size: usize, growth_rate: f64 },
+    /// ``StructuredCoalescent::secondary_contact`` with ``samples`` individuals of each deme.
+    SecondaryContact { samples: [usize; 2], split_time: f64, contact_time: f64, migration_rate: f64 },
+}
+
+/// Seed of a model with the hash of the events it gives, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub model: GoldenModel,
+    pub seed: u64,
+    /// Hash of the events, see ``event_hash``.
+    pub hash: u64,
+}
+
+/// Hash of a sampled genealogy that differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenMismatch {
+    pub name: &'static str,
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl std::fmt::Display for GoldenMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "golden case {} gives events of hash {:#018x} instead of {:#018x}", self.name, self.found, self.expected)
+    }
+}
+
+impl std::error::Error for GoldenMismatch {}
+
+/// Golden cases of this version of the crate.
+pub const CASES: &[GoldenCase] = &[
+    GoldenCase { name: "kingman-2", model: GoldenModel::Kingman { group_size: 2 }, seed: 1, hash: 0x0859_291a_d295_413a },
+    GoldenCase { name: "kingman-10", model: GoldenModel::Kingman { group_size: 10 }, seed: 42, hash: 0xe4ba_ed4b_3e3f_8af7 },
+    GoldenCase { name: "kingman-100", model: GoldenModel::Kingman { group_size: 100 }, seed: 2020, hash: 0x8d50_f89d_87db_ef73 },
+    GoldenCase {
+        name: "exponential-20",
+        model: GoldenModel::Exponential { group_size: 20, growth_rate: 5.0 },
+        seed: 7,
+        hash: 0x7f5d_d31d_9938_4f5f,
+    },
+    GoldenCase {
+        name: "secondary-contact-6",
+        model: GoldenModel::SecondaryContact { samples: [3, 3], split_time: 2.0, contact_time: 0.5, migration_rate: 1.0 },
+        seed: 11,
+        hash: 0x6af3_695e_7e4b_05ee,
+    },
+];
+
+impl GoldenCase {
+    /// Genealogy sampled from the seed.
+    pub fn sample(&self) -> Genealogy {
+        let mut rng = Pcg32::seed_from_u64(self.seed);
+        match self.model {
+            GoldenModel::Kingman { group_size } => Coalescent::sampler(group_size).sample_genealogy(&mut rng),
+            GoldenModel::Exponential { group_size, growth_rate } => {
+                let mut coalescent = Coalescent::sampler(group_size);
+                coalescent.set_demography(Demography::exponential(growth_rate));
+                coalescent.sample_genealogy(&mut rng)
+            },
+            GoldenModel::SecondaryContact { samples, split_time, contact_time, migration_rate } => {
+                StructuredCoalescent::secondary_contact(split_time, contact_time, migration_rate)
+                    .sample_genealogy(&samples, &mut rng)
+            },
+        }
+    }
+
+    /// Whether the sampled genealogy has the recorded hash.
+    ///
+    /// # Errors
+    ///
+    /// If the hash differs, with both hashes.
+    pub fn check(&self) -> Result<(), GoldenMismatch> {
+        let found = event_hash(&self.sample());
+        match found == self.hash {
+            true => Ok(()),
+            false => Err(GoldenMismatch { name: self.name, expected: self.hash, found }),
+        }
+    }
+}
+
+/// FNV-1a hash of the group size and of the events of a genealogy: the time of each event,
+/// rounded to ``1e-9``, and the individuals it joins.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::golden::event_hash;
+///
+/// let genealogy = coalescence::fixtures::balanced();
+/// let mut rescaled = genealogy.clone();
+/// rescaled.rescale(1.0 + 1e-12);
+///
+/// assert_eq!(event_hash(&genealogy), event_hash(&rescaled));
+/// assert_ne!(event_hash(&genealogy), event_hash(&coalescence::fixtures::caterpillar(4)));
+/// ```
+pub fn event_hash(genealogy: &Genealogy) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut words = vec![genealogy.group_size() as u64];
+    for (time, event) in genealogy.events().scan_times() {
+        words.push((time * 1e9).round() as u64);
+        words.extend(event.indexes.iter().map(|&index| index as u64));
+    }
+
+    words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .fold(OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_cases() {
+        for case in CASES {
+            assert_eq!(case.check(), Ok(()), "{:#018x}", event_hash(&case.sample()));
+        }
+
+        let mut changed = CASES[1];
+        changed.hash ^= 1;
+        let mismatch = changed.check().unwrap_err();
+        assert_eq!(mismatch.found, CASES[1].hash);
+        assert!(mismatch.to_string().starts_with("golden case kingman-10 gives events of hash"));
+    }
+}
@@ -0,0 +1,608 @@
+//! Likelihood of the mutation rate by importance sampling.
+//!
+//! The probability of haplotypes under the infinite sites model is a sum over
+//! all ancestral histories: sequences of coalescences of identical lineages and
+//! removals of mutations, going backwards until a single lineage without mutations.
+//! Going back one event from a configuration with ``n`` lineages, the probability
+//! p of the configuration satisfies (Griffiths and Tavaré, 1994)
+//!
+//! p(n) = Σ (n_i - 1) / (n - 1 + θ) p(n - e_i) + Σ θ / (n - 1 + θ) (n_j + 1) / n p(n - e_i + e_j),
+//!
+//! where the first sum is over haplotypes ``i`` carried by ``n_i`` ≥ 2 lineages,
+//! and the second one over mutations carried by a single lineage, of haplotype ``i``,
+//! which becomes haplotype ``j`` without the mutation (with ``n_j = 0`` if it is new).
+//!
+//! Histories are simulated backwards choosing events with a proposal distribution,
+//! and the product of the terms of the recursion divided by the probabilities of the
+//! proposal is an unbiased estimate of the likelihood.
+//!
+//! Under other demographies, ``sfs_likelihood_surface`` estimates the likelihood of
+//! the site frequency spectrum alone by Monte Carlo integration over genealogies:
+//! given a genealogy, the number of sites with ``j`` derived copies is Poisson with
+//! mean θ/2 times the length of branches ancestral to ``j`` individuals, independently
+//! over ``j``, and these probabilities are averaged over genealogies sampled from the
+//! coalescent, over a grid of mutation rates θ and exponential growth rates. Averages
+//! are taken in log space, as probabilities of many sites underflow.
+//!
+
+// Types
+use crate::observers::BranchSpectrum;
+use crate::{Coalescent, Demography, Haplotypes};
+
+// Traits
+use rand::Rng;
+use std::io::Write;
+
+/// How backward events are chosen when simulating ancestral histories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Proposal {
+    /// Each event with probability proportional to its term in the recursion
+    /// (Griffiths and Tavaré, 1994).
+    GriffithsTavare,
+    /// A lineage uniformly among those that can coalesce or lose a mutation,
+    /// then one of its events uniformly (Stephens and Donnelly, 2000).
+    StephensDonnelly,
+}
+
+/// Estimate of the likelihood of some haplotypes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LikelihoodEstimate {
+    /// Scaled mutation rate θ.
+    pub theta: f64,
+    /// Mean of the importance weights.
+    pub likelihood: f64,
+    /// Logarithm of the mean of the importance weights, which stays finite when
+    /// the likelihood underflows to zero, e.g. for many segregating sites.
+    pub log_likelihood: f64,
+    /// Standard error of the mean.
+    pub standard_error: f64,
+    /// Effective number of histories, (Σ w)² / Σ w².
+    pub effective_sample_size: f64,
+}
+
+/// Importance sampler of ancestral histories under the infinite sites model.
+///
+/// Ancestral alleles must be coded as ``0`` and derived ones as ``1``.
+/// Data incompatible with the infinite sites model, i.e. without a rooted perfect
+/// phylogeny, have likelihood zero.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{ImportanceSampler, MutationModel, Proposal};
+/// let group_size = 6;
+/// let rng = rand::thread_rng();
+/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = coalescent.sample_genealogy(&mut rng);
+/// let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta: 2.0 }, &mut rng);
+///
+/// let sampler = ImportanceSampler::new(2.0, Proposal::StephensDonnelly);
+/// let curve = sampler.likelihood_curve(&haplotypes, &[1.0, 2.0, 4.0], 100, &mut rng);
+///
+/// assert_eq!(curve.len(), 3);
+/// assert!(curve.iter().all(|estimate| estimate.likelihood > 0.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImportanceSampler {
+    driving_theta: f64,
+    proposal: Proposal,
+}
+
+/// Event of a history, going backwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    lineages: usize, // before the event
+    factor: f64, // term of the recursion without θ
+    mutation: bool,
+    proposal_probability: f64,
+}
+
+/// Haplotypes, as sorted sites with the derived allele, and number of lineages carrying them.
+type Configuration = Vec<(Vec<usize>, usize)>;
+
+impl ImportanceSampler {
+    /// Creates a new sampler, whose proposal is tuned for the mutation rate ``driving_theta``.
+    ///
+    /// # Panics
+    ///
+    /// If the mutation rate is not positive and finite.
+    pub fn new(driving_theta: f64, proposal: Proposal) -> Self {
+        assert!(
+            driving_theta > 0.0 && driving_theta.is_finite(),
+            "The driving mutation rate must be positive and finite."
+        );
+
+        ImportanceSampler { driving_theta, proposal }
+    }
+
+    /// Estimate the likelihood at the driving mutation rate from ``replicates`` histories.
+    pub fn likelihood<R>(&self, haplotypes: &Haplotypes, replicates: usize, rng: &mut R) -> LikelihoodEstimate
+    where
+        R: Rng + ?Sized,
+    {
+        self.likelihood_curve(haplotypes, &[self.driving_theta], replicates, rng)[0]
+    }
+
+    /// Estimate the likelihood at each mutation rate, reusing the same ``replicates``
+    /// histories, simulated with the driving mutation rate.
+    ///
+    /// Estimates are most precise near the driving mutation rate.
+    pub fn likelihood_curve<R>(
+        &self,
+        haplotypes: &Haplotypes,
+        thetas: &[f64],
+        replicates: usize,
+        rng: &mut R,
+    ) -> Vec<LikelihoodEstimate>
+    where
+        R: Rng + ?Sized,
+    {
+        let histories: Vec<Option<Vec<Step>>> =
+            (0..replicates).map(|_| self.sample_history(haplotypes, rng)).collect();
+
+        thetas
+            .iter()
+            .map(|&theta| {
+                let weights: Vec<f64> = histories
+                    .iter()
+                    .map(|history| match history {
+                        Some(steps) => weight(steps, theta),
+                        None => 0.0,
+                    })
+                    .collect();
+                estimate(theta, &weights)
+            })
+            .collect()
+    }
+
+    /// Simulate a history backwards, or ``None`` if it gets stuck.
+    fn sample_history<R>(&self, haplotypes: &Haplotypes, rng: &mut R) -> Option<Vec<Step>>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut configuration = configuration(haplotypes);
+        let mut steps = Vec::new();
+
+        loop {
+            let lineages: usize = configuration.iter().map(|(_, count)| count).sum();
+            if lineages <= 1 && configuration.iter().all(|(sites, _)| sites.is_empty()) {
+                return Some(steps);
+            }
+
+            // Possible events: coalescences within a haplotype, or removals of a mutation
+
+            let mut events = Vec::new();
+            for (haplotype, (sites, count)) in configuration.iter().enumerate() {
+                if *count >= 2 {
+                    events.push((haplotype, None));
+                }
+                if *count == 1 {
+                    for &site in sites {
+                        let shared = configuration
+                            .iter()
+                            .enumerate()
+                            .any(|(other, (other_sites, _))| other != haplotype && other_sites.binary_search(&site).is_ok());
+                        if !shared {
+                            events.push((haplotype, Some(site)));
+                        }
+                    }
+                }
+            }
+            if events.is_empty() {
+                return None;
+            }
+
+            // Terms of the recursion, without θ, and proposal
+
+            let n = lineages as f64;
+            let factors: Vec<f64> = events
+                .iter()
+                .map(|&(haplotype, site)| match site {
+                    None => (configuration[haplotype].1 - 1) as f64,
+                    Some(site) => {
+                        let (target, _) = without(&configuration, haplotype, site);
+                        let target_count = target.map(|other| configuration[other].1).unwrap_or(0);
+                        (target_count + 1) as f64 / n
+                    },
+                })
+                .collect();
+            let probabilities = self.proposal_probabilities(&configuration, &events, &factors);
+
+            let mut point = rng.gen::<f64>();
+            let chosen = probabilities
+                .iter()
+                .position(|&probability| {
+                    point -= probability;
+                    point < 0.0
+                })
+                .unwrap_or(events.len() - 1);
+
+            steps.push(Step {
+                lineages,
+                factor: factors[chosen],
+                mutation: events[chosen].1.is_some(),
+                proposal_probability: probabilities[chosen],
+            });
+
+            // Apply the event
+
+            match events[chosen] {
+                (haplotype, None) => configuration[haplotype].1 -= 1,
+                (haplotype, Some(site)) => {
+                    let (target, sites) = without(&configuration, haplotype, site);
+                    match target {
+                        Some(other) => {
+                            configuration[other].1 += 1;
+                            configuration.swap_remove(haplotype);
+                        },
+                        None => configuration[haplotype].0 = sites,
+                    }
+                },
+            }
+        }
+    }
+
+    fn proposal_probabilities(
+        &self,
+        configuration: &[(Vec<usize>, usize)],
+        events: &[(usize, Option<usize>)],
+        factors: &[f64],
+    ) -> Vec<f64> {
+        match self.proposal {
+            Proposal::GriffithsTavare => {
+                let terms: Vec<f64> = events
+                    .iter()
+                    .zip(factors)
+                    .map(|(&(_, site), &factor)| match site {
+                        None => factor,
+                        Some(_) => self.driving_theta * factor,
+                    })
+                    .collect();
+                let total: f64 = terms.iter().sum();
+                terms.iter().map(|term| term / total).collect()
+            },
+            Proposal::StephensDonnelly => {
+                // Lineages that can be involved in an event, and events of each one
+
+                let mutations = |haplotype: usize| {
+                    events.iter().filter(|&&(other, site)| other == haplotype && site.is_some()).count()
+                };
+                let active: usize = configuration
+                    .iter()
+                    .enumerate()
+                    .map(|(haplotype, &(_, count))| match count >= 2 {
+                        true => count,
+                        false => (mutations(haplotype) > 0) as usize,
+                    })
+                    .sum();
+                events
+                    .iter()
+                    .map(|&(haplotype, site)| match site {
+                        None => configuration[haplotype].1 as f64 / active as f64,
+                        Some(_) => 1.0 / (active * mutations(haplotype)) as f64,
+                    })
+                    .collect()
+            },
+        }
+    }
+}
+
+/// Distinct haplotypes of the group, with the number of individuals carrying each one.
+fn configuration(haplotypes: &Haplotypes) -> Configuration {
+    let mut configuration: Configuration = Vec::new();
+    for index in 0..haplotypes.group_size() {
+        let sites: Vec<usize> = haplotypes
+            .haplotype(index)
+            .iter()
+            .enumerate()
+            .filter(|(_, &allele)| allele == 1)
+            .map(|(site, _)| site)
+            .collect();
+        match configuration.iter_mut().find(|(other, _)| other == &sites) {
+            Some((_, count)) => *count += 1,
+            None => configuration.push((sites, 1)),
+        }
+    }
+    configuration
+}
+
+/// Sites of a haplotype without a mutation, and the haplotype already present with these sites, if any.
+fn without(configuration: &[(Vec<usize>, usize)], haplotype: usize, site: usize) -> (Option<usize>, Vec<usize>) {
+    let sites: Vec<usize> = configuration[haplotype].0.iter().cloned().filter(|&other| other != site).collect();
+    let target = configuration.iter().position(|(other, _)| other == &sites);
+    (target, sites)
+}
+
+/// Estimates of a likelihood over a grid of mutation rates and growth rates, e.g. for
+/// contour plots: ``estimates[i][j]`` is at growth rate ``growth_rates[i]`` and mutation
+/// rate ``thetas[j]``.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LikelihoodSurface {
+    pub thetas: Vec<f64>,
+    pub growth_rates: Vec<f64>,
+    pub estimates: Vec<Vec<LikelihoodEstimate>>,
+}
+
+impl LikelihoodSurface {
+    /// Point of the grid with the largest estimate, as growth rate and estimate.
+    pub fn maximum(&self) -> (f64, LikelihoodEstimate) {
+        self.growth_rates
+            .iter()
+            .zip(&self.estimates)
+            .flat_map(|(&growth_rate, row)| row.iter().map(move |&estimate| (growth_rate, estimate)))
+            .fold(None, |best: Option<(f64, LikelihoodEstimate)>, point| match best {
+                Some(best) if best.1.log_likelihood >= point.1.log_likelihood => Some(best),
+                _ => Some(point),
+            })
+            .expect("The grid is not empty.")
+    }
+
+    /// Writes the grid as tidy CSV, with one line per point of the grid and the columns
+    /// ``growth_rate``, ``theta``, ``likelihood``, ``log_likelihood`` and ``standard_error``.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "growth_rate,theta,likelihood,log_likelihood,standard_error")?;
+        for (growth_rate, row) in self.growth_rates.iter().zip(&self.estimates) {
+            for estimate in row {
+                let LikelihoodEstimate { theta, likelihood, log_likelihood, standard_error, .. } = estimate;
+                writeln!(writer, "{},{},{},{},{}", growth_rate, theta, likelihood, log_likelihood, standard_error)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Likelihood of a site frequency spectrum over a grid of mutation rates and exponential
+/// growth rates, see the module documentation, from ``replicates`` genealogies for each
+/// growth rate, which are reused for all mutation rates.
+///
+/// The entry ``j`` of the spectrum is the number of sites with ``j + 1`` derived copies,
+/// as given by ``site_frequency_spectrum`` on Haplotypes.
+///
+/// # Panics
+///
+/// If the grid is empty, if some mutation rate is not positive and finite, or if some
+/// growth rate is not finite.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{sfs_likelihood_surface, Coalescent, MutationModel};
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = Coalescent::sampler(10).sample_genealogy(&mut rng);
+/// let spectrum = genealogy.sample_haplotypes(MutationModel::Poisson { theta: 3.0 }, &mut rng).site_frequency_spectrum();
+///
+/// let surface = sfs_likelihood_surface(&spectrum, &[1.0, 3.0, 9.0], &[0.0, 2.0], 200, &mut rng);
+///
+/// assert_eq!(surface.estimates.len(), 2);
+/// assert!(surface.estimates.iter().flatten().all(|estimate| estimate.likelihood >= 0.0));
+/// ```
+pub fn sfs_likelihood_surface<R>(
+    spectrum: &[usize],
+    thetas: &[f64],
+    growth_rates: &[f64],
+    replicates: usize,
+    rng: &mut R,
+) -> LikelihoodSurface
+where
+    R: Rng + ?Sized,
+{
+    assert!(!thetas.is_empty() && !growth_rates.is_empty(), "The grid must not be empty.");
+    assert!(thetas.iter().all(|&theta| theta > 0.0 && theta.is_finite()), "Mutation rates must be positive and finite.");
+    assert!(growth_rates.iter().all(|rate| rate.is_finite()), "Growth rates must be finite.");
+
+    let group_size = spectrum.len() + 1;
+    let log_factorials: f64 = spectrum
+        .iter()
+        .map(|&count| (1..=count).map(|k| (k as f64).ln()).sum::<f64>())
+        .sum();
+    let estimates = growth_rates
+        .iter()
+        .map(|&growth_rate| {
+            let mut coalescent = Coalescent::sampler(group_size);
+            coalescent.set_demography(Demography::exponential(growth_rate));
+            let mut observer = BranchSpectrum::default();
+            let lengths: Vec<Vec<f64>> = (0..replicates)
+                .map(|_| {
+                    coalescent.sample_observed(&mut observer, rng);
+                    observer.lengths.clone()
+                })
+                .collect();
+
+            thetas
+                .iter()
+                .map(|&theta| {
+                    let log_weights: Vec<f64> = lengths
+                        .iter()
+                        .map(|lengths| {
+                            let log_probability: f64 = spectrum
+                                .iter()
+                                .zip(lengths)
+                                .map(|(&count, &length)| {
+                                    let mean = theta * length / 2.0;
+                                    match count {
+                                        0 => -mean,
+                                        _ => count as f64 * mean.ln() - mean,
+                                    }
+                                })
+                                .sum();
+                            log_probability - log_factorials
+                        })
+                        .collect();
+                    log_estimate(theta, &log_weights)
+                })
+                .collect()
+        })
+        .collect();
+
+    LikelihoodSurface { thetas: thetas.to_vec(), growth_rates: growth_rates.to_vec(), estimates }
+}
+
+/// Importance weight of a history at mutation rate θ.
+fn weight(steps: &[Step], theta: f64) -> f64 {
+    steps
+        .iter()
+        .map(|step| {
+            let denominator = step.lineages as f64 - 1.0 + theta;
+            let term = match step.mutation {
+                true => theta * step.factor / denominator,
+                false => step.factor / denominator,
+            };
+            term / step.proposal_probability
+        })
+        .product()
+}
+
+fn estimate(theta: f64, weights: &[f64]) -> LikelihoodEstimate {
+    let log_weights: Vec<f64> = weights.iter().map(|weight| weight.ln()).collect();
+    log_estimate(theta, &log_weights)
+}
+
+/// Estimate from the logarithms of the weights, which are scaled by the largest one
+/// before averaging so that none underflows (log-sum-exp).
+fn log_estimate(theta: f64, log_weights: &[f64]) -> LikelihoodEstimate {
+    let replicates = log_weights.len() as f64;
+    let largest = log_weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if largest == f64::NEG_INFINITY {
+        return LikelihoodEstimate {
+            theta,
+            likelihood: 0.0,
+            log_likelihood: f64::NEG_INFINITY,
+            standard_error: 0.0,
+            effective_sample_size: 0.0,
+        };
+    }
+
+    let scaled: Vec<f64> = log_weights.iter().map(|log_weight| (log_weight - largest).exp()).collect();
+    let sum: f64 = scaled.iter().sum();
+    let sum_of_squares: f64 = scaled.iter().map(|weight| weight * weight).sum();
+    let mean = sum / replicates;
+    let variance = (sum_of_squares / replicates - mean * mean).max(0.0);
+    let log_likelihood = largest + sum.ln() - replicates.ln();
+
+    LikelihoodEstimate {
+        theta,
+        likelihood: log_likelihood.exp(),
+        log_likelihood,
+        standard_error: (largest + 0.5 * (variance / replicates).ln()).exp(),
+        effective_sample_size: sum * sum / sum_of_squares,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exact likelihood by the recursion, for small configurations.
+    fn exact(configuration: &Configuration, theta: f64) -> f64 {
+        let lineages: usize = configuration.iter().map(|(_, count)| count).sum();
+        if lineages <= 1 && configuration.iter().all(|(sites, _)| sites.is_empty()) {
+            return 1.0;
+        }
+        let n = lineages as f64;
+        let denominator = n - 1.0 + theta;
+        let mut total = 0.0;
+        for (haplotype, (sites, count)) in configuration.iter().enumerate() {
+            if *count >= 2 {
+                let mut next = configuration.clone();
+                next[haplotype].1 -= 1;
+                total += (count - 1) as f64 / denominator * exact(&next, theta);
+            }
+            if *count == 1 {
+                for &site in sites {
+                    let shared = configuration
+                        .iter()
+                        .enumerate()
+                        .any(|(other, (other_sites, _))| other != haplotype && other_sites.contains(&site));
+                    if shared {
+                        continue;
+                    }
+                    let (target, without_site) = without(configuration, haplotype, site);
+                    let mut next = configuration.clone();
+                    let target_count = match target {
+                        Some(other) => {
+                            let target_count = next[other].1;
+                            next[other].1 += 1;
+                            next.swap_remove(haplotype);
+                            target_count
+                        },
+                        None => {
+                            next[haplotype].0 = without_site;
+                            0
+                        },
+                    };
+                    total += theta / denominator * (target_count + 1) as f64 / n * exact(&next, theta);
+                }
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn no_mutations() {
+        // Every history gives the probability of no mutations, 2 / ((1 + θ) (2 + θ))
+        let haplotypes = Haplotypes::new(3, Vec::new());
+        let mut rng = rand::thread_rng();
+        for &proposal in &[Proposal::GriffithsTavare, Proposal::StephensDonnelly] {
+            let estimate = ImportanceSampler::new(1.0, proposal).likelihood(&haplotypes, 10, &mut rng);
+            assert!((estimate.likelihood - 2.0 / (2.0 * 3.0)).abs() < 1e-12);
+            assert!(estimate.standard_error < 1e-12);
+        }
+    }
+
+    #[test]
+    fn matches_exact_likelihood() {
+        let haplotypes = Haplotypes::new(5, vec![(0.1, vec![0, 1]), (0.4, vec![0]), (0.7, vec![2, 3, 4]), (0.9, vec![4])]);
+        let theta = 1.5;
+        let exact = exact(&configuration(&haplotypes), theta);
+
+        let mut rng = rand::thread_rng();
+        for &proposal in &[Proposal::GriffithsTavare, Proposal::StephensDonnelly] {
+            let estimate = ImportanceSampler::new(theta, proposal).likelihood(&haplotypes, 4000, &mut rng);
+            assert!((estimate.likelihood - exact).abs() < 0.05 * exact);
+        }
+
+        // Incompatible sites
+        let incompatible = Haplotypes::new(4, vec![(0.1, vec![0, 1]), (0.5, vec![1, 2])]);
+        let estimate = ImportanceSampler::new(theta, Proposal::GriffithsTavare).likelihood(&incompatible, 10, &mut rng);
+        assert_eq!(estimate.likelihood, 0.0);
+    }
+
+    #[test]
+    fn likelihood_surfaces() {
+        // Two individuals: the number of sites is geometric, P(k) = θ^k / (1 + θ)^(k + 1)
+        let mut rng = rand::thread_rng();
+        let thetas = [0.5, 2.0];
+        let surface = sfs_likelihood_surface(&[3], &thetas, &[0.0], 20_000, &mut rng);
+        for (estimate, &theta) in surface.estimates[0].iter().zip(&thetas) {
+            let exact = theta.powi(3) / (1.0 + theta).powi(4);
+            assert!((estimate.likelihood - exact).abs() < 4.0 * estimate.standard_error + 1e-3, "{:?}", estimate);
+        }
+        assert_eq!(surface.maximum().1.theta, 2.0);
+
+        // Rare variants in excess favour growth
+        let spectrum = [12, 1, 1, 0, 0, 0, 0, 0, 0];
+        let surface = sfs_likelihood_surface(&spectrum, &[2.0, 4.0, 8.0], &[0.0, 5.0], 2000, &mut rng);
+        assert_eq!(surface.maximum().0, 5.0);
+
+        let mut csv = Vec::new();
+        surface.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 7);
+        assert!(csv.starts_with("growth_rate,theta,likelihood,log_likelihood,standard_error\n0,2,"));
+
+        // Thousands of sites: every likelihood underflows, but not its logarithm
+        let mut spectrum = vec![0; 9];
+        spectrum[0] = 5000;
+        let surface = sfs_likelihood_surface(&spectrum, &[175.0, 1750.0, 17500.0], &[0.0], 200, &mut rng);
+        for estimate in &surface.estimates[0] {
+            assert_eq!(estimate.likelihood, 0.0);
+            assert!(estimate.log_likelihood.is_finite(), "{:?}", estimate);
+            assert!(estimate.effective_sample_size >= 1.0);
+        }
+        assert_eq!(surface.maximum().1.theta, 1750.0);
+    }
+}
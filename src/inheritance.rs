@@ -0,0 +1,342 @@
+//! Inheritance modes and populations with two sexes.
+//!
+//! The rate of coalescence of a locus depends on how many copies of it are
+//! carried by males and females and how they are transmitted. With ``Nm``
+//! males and ``Nf`` females, the effective size of the population for each
+//! inheritance mode is (Caballero, 1995)
+//! - autosomal: 4 Nm Nf / (Nm + Nf),
+//! - X-linked: 9 Nm Nf / (4 Nm + 2 Nf),
+//! - haplodiploid: 9 Nm Nf / (4 Nm + 2 Nf), with haploid males as in bees and ants,
+//! - Y-linked: Nm / 2,
+//! - mitochondrial: Nf / 2,
+//!
+//! all measured in diploid individuals, so that two copies of the locus meet
+//! in a common ancestor after 2 Ne generations on average.
+//!
+//! These effective sizes average over the sex of the carriers of lineages. To keep
+//! track of it, e.g. to sample males and females separately, lineages can also move
+//! between two demes, males and females, each generation going to the sex of the
+//! parent they come from, and coalescing at the rate of each sex, see ``sex_structured``.
+//!
+
+// Types
+use crate::{AncestryPath, Coalescent, Demography, Genealogy, StructuredCoalescent};
+
+// Traits
+use rand::Rng;
+
+/// How a locus is transmitted from parents to offspring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inheritance {
+    /// Two copies in every individual, one from each parent.
+    Autosomal,
+    /// Two copies in females and one in males, which receive it from their mother.
+    XLinked,
+    /// Two copies in females, one from each parent, and one in males, which hatch
+    /// from unfertilized eggs and receive it from their mother. Transmitted like an X chromosome.
+    Haplodiploid,
+    /// One copy in males, received from their father.
+    YLinked,
+    /// One copy in every individual, received from their mother.
+    Mitochondrial,
+}
+
+/// Population with ``Nm`` males and ``Nf`` females, and a locus with a given
+/// inheritance mode.
+///
+/// # Examples
+///
+/// Genealogies of the Y chromosome are shorter when there are fewer males.
+/// ```
+/// use coalescence::{Inheritance, TwoSex};
+///
+/// let population = TwoSex::new(100.0, 1000.0, Inheritance::YLinked);
+/// assert_eq!(population.effective_size(), 50.0);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = population.sample_genealogy(10, &mut rng);
+/// assert!(genealogy.depth() > 0.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoSex {
+    males: f64,
+    females: f64,
+    inheritance: Inheritance,
+    growth_rate: f64, // per generation
+}
+
+impl TwoSex {
+    /// Creates a new population with two sexes.
+    ///
+    /// # Panics
+    ///
+    /// If the number of males or females is not positive.
+    pub fn new(males: f64, females: f64, inheritance: Inheritance) -> Self {
+        assert!(males > 0.0 && females > 0.0, "The number of males and females must be positive.");
+
+        TwoSex { males, females, inheritance, growth_rate: 0.0 }
+    }
+
+    /// Population with equal numbers of males and females, of total size ``size``
+    /// at present, that has been growing exponentially at rate ``growth_rate``
+    /// per generation, with a locus inherited from only one parent.
+    ///
+    /// Uniparental loci have small effective sizes, so that even moderate growth
+    /// per generation is strong in the units of the coalescent process, and
+    /// genealogies are star-like: most of their length is in external branches.
+    /// For example, a population of 10000 individuals and growth rate 0.01.
+    ///
+    /// # Panics
+    ///
+    /// If the inheritance mode is not Y-linked or mitochondrial, or in the same cases
+    /// as ``new`` and ``with_growth_rate``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Inheritance, TwoSex};
+    ///
+    /// let population = TwoSex::uniparental_expansion(Inheritance::Mitochondrial, 10_000.0, 0.01);
+    /// // 2 Ne = 5000 generations per unit of time
+    /// assert!((population.demography().growth_rate_at(0.0) - 50.0).abs() < 1e-9);
+    ///
+    /// // Most of the length is on external branches, against about 30% without growth
+    /// let mut rng = rand::thread_rng();
+    /// let external_fraction = (0..100)
+    ///     .map(|_| {
+    ///         let genealogy = population.sample_genealogy(20, &mut rng);
+    ///         let external: f64 = genealogy.branches()
+    ///             .iter()
+    ///             .filter(|branch| branch.child < 20)
+    ///             .map(|branch| branch.length())
+    ///             .sum();
+    ///         external / genealogy.length()
+    ///     })
+    ///     .sum::<f64>() / 100.0;
+    /// assert!(external_fraction > 0.5);
+    /// ```
+    pub fn uniparental_expansion(inheritance: Inheritance, size: f64, growth_rate: f64) -> Self {
+        assert!(
+            inheritance == Inheritance::YLinked || inheritance == Inheritance::Mitochondrial,
+            "The locus must be inherited from only one parent."
+        );
+
+        TwoSex::new(size / 2.0, size / 2.0, inheritance).with_growth_rate(growth_rate)
+    }
+
+    /// Population that has been growing exponentially at rate ``growth_rate`` per generation,
+    /// with the present numbers of males and females.
+    ///
+    /// # Panics
+    ///
+    /// If the growth rate is not finite.
+    pub fn with_growth_rate(mut self, growth_rate: f64) -> Self {
+        assert!(growth_rate.is_finite(), "The growth rate must be finite.");
+
+        self.growth_rate = growth_rate;
+        self
+    }
+
+    /// Growth rate per generation.
+    pub fn growth_rate(&self) -> f64 {
+        self.growth_rate
+    }
+
+    /// Number of males.
+    pub fn males(&self) -> f64 {
+        self.males
+    }
+
+    /// Number of females.
+    pub fn females(&self) -> f64 {
+        self.females
+    }
+
+    /// Inheritance mode of the locus.
+    pub fn inheritance(&self) -> Inheritance {
+        self.inheritance
+    }
+
+    /// Effective size of the population for the locus, measured in diploid individuals.
+    pub fn effective_size(&self) -> f64 {
+        let (males, females) = (self.males, self.females);
+        match self.inheritance {
+            Inheritance::Autosomal => 4.0 * males * females / (males + females),
+            Inheritance::XLinked | Inheritance::Haplodiploid => 9.0 * males * females / (4.0 * males + 2.0 * females),
+            Inheritance::YLinked => males / 2.0,
+            Inheritance::Mitochondrial => females / 2.0,
+        }
+    }
+
+    /// Number of generations in a unit of time of the coalescent process,
+    /// i.e. the mean time for two copies of the locus to meet in a common ancestor.
+    pub fn generations_per_unit(&self) -> f64 {
+        2.0 * self.effective_size()
+    }
+
+    /// Demography of the population in the units of the coalescent process,
+    /// with growth rate per unit of time equal to the growth rate per generation
+    /// times the number of generations per unit.
+    pub fn demography(&self) -> Demography {
+        Demography::exponential(self.growth_rate * self.generations_per_unit())
+    }
+
+    /// Sample a genealogy of ``group_size`` copies of the locus,
+    /// with time measured in generations.
+    pub fn sample_genealogy<R>(&self, group_size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        let mut coalescent = Coalescent::sampler(group_size);
+        coalescent.set_demography(self.demography());
+        let mut genealogy = coalescent.sample_genealogy(rng);
+        genealogy.rescale(self.generations_per_unit());
+
+        genealogy
+    }
+}
+
+impl TwoSex {
+    /// Structured coalescent of the locus with two demes, males then females, and time
+    /// measured in generations. Each generation, a lineage in a deme moves to the sex
+    /// of the parent it comes from, and two lineages in the same deme coalesce with
+    /// the probability that they come from the same copy in a parent of that sex:
+    /// ``1 / (2 Nm)`` or ``1 / Nm`` in males, depending on their number of copies,
+    /// and ``1 / (2 Nf)`` in females.
+    ///
+    /// Over many generations, it has the same rate of coalescence as ``effective_size``,
+    /// but it takes about one migration per lineage and generation, so it is only
+    /// practical for small populations.
+    ///
+    /// # Panics
+    ///
+    /// If the locus is inherited from only one parent, since then all the parents of its
+    /// lineages have the same sex, or if the population grows.
+    pub fn sex_structured(&self) -> StructuredCoalescent {
+        assert!(self.growth_rate == 0.0, "The population must have constant size.");
+        let (males, females) = (self.males, self.females);
+
+        // Relative sizes are inverse probabilities of coalescence per generation
+        let (sizes, migration) = match self.inheritance {
+            Inheritance::Autosomal => (vec![2.0 * males, 2.0 * females], vec![vec![0.0, 0.5], vec![0.5, 0.0]]),
+            Inheritance::XLinked | Inheritance::Haplodiploid => {
+                (vec![males, 2.0 * females], vec![vec![0.0, 1.0], vec![0.5, 0.0]])
+            },
+            Inheritance::YLinked | Inheritance::Mitochondrial => {
+                panic!("The locus must be inherited from both parents.")
+            },
+        };
+
+        StructuredCoalescent::new(sizes, migration)
+    }
+
+    /// Sample a genealogy of copies of the locus in ``males`` males and ``females`` females,
+    /// with time measured in generations, with the sex of the carriers of the ancestors
+    /// of each copy: deme 0 for males and deme 1 for females. Copies in males come first.
+    ///
+    /// # Panics
+    ///
+    /// In the same cases as ``sex_structured``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Inheritance, TwoSex};
+    ///
+    /// let population = TwoSex::new(20.0, 20.0, Inheritance::XLinked);
+    /// let mut rng = rand::thread_rng();
+    /// let (genealogy, ancestry) = population.sample_genealogy_by_sex(1, 2, &mut rng);
+    ///
+    /// assert_eq!(genealogy.group_size(), 3);
+    /// // The X chromosome of a male comes from his mother
+    /// let moves = &ancestry[0].moves;
+    /// assert_eq!(moves[0], (0.0, 0));
+    /// assert!(moves.len() == 1 || moves[1].1 == 1);
+    /// ```
+    pub fn sample_genealogy_by_sex<R>(&self, males: usize, females: usize, rng: &mut R) -> (Genealogy, Vec<AncestryPath>)
+    where
+        R: Rng + ?Sized,
+    {
+        self.sex_structured().sample_genealogy_with_ancestry(&[males, females], rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_sizes() {
+        // With equal numbers of males and females, there are 3/4 as many X chromosomes as autosomes.
+        let autosomal = TwoSex::new(500.0, 500.0, Inheritance::Autosomal);
+        let x_linked = TwoSex::new(500.0, 500.0, Inheritance::XLinked);
+
+        assert_eq!(autosomal.effective_size(), 1000.0);
+        assert_eq!(x_linked.effective_size(), 750.0);
+        assert_eq!(TwoSex::new(500.0, 500.0, Inheritance::Mitochondrial).effective_size(), 250.0);
+    }
+
+    #[test]
+    fn pairwise_coalescence_in_generations() {
+        let population = TwoSex::new(10.0, 30.0, Inheritance::Autosomal);
+        let samples = 4000;
+
+        let mut rng = rand::thread_rng();
+        let mean_depth = (0..samples)
+            .map(|_| population.sample_genealogy(2, &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+
+        // 2 Ne = 2 * 4 * 10 * 30 / 40 = 60 generations
+        assert!((mean_depth - 60.0).abs() < 6.0);
+    }
+
+    #[test]
+    fn star_like_under_growth() {
+        let group_size = 20;
+        let samples = 200;
+        let mut rng = rand::thread_rng();
+        let mut external_fraction = |population: TwoSex| {
+            (0..samples)
+                .map(|_| {
+                    let genealogy = population.sample_genealogy(group_size, &mut rng);
+                    let external: f64 = genealogy.branches()
+                        .iter()
+                        .filter(|branch| branch.child < group_size)
+                        .map(|branch| branch.length())
+                        .sum();
+                    external / genealogy.length()
+                })
+                .sum::<f64>() / samples as f64
+        };
+
+        // Without growth, about 2 / (2 H(n - 1)) of the length is external
+        let constant = external_fraction(TwoSex::new(5000.0, 5000.0, Inheritance::YLinked));
+        let growing = external_fraction(TwoSex::uniparental_expansion(Inheritance::YLinked, 10_000.0, 0.01));
+
+        assert!(constant < 0.4);
+        assert!(growing > 0.5);
+    }
+
+    #[test]
+    fn sex_structured_scaling() {
+        // 2 Ne = 2 * 9 * 10 * 30 / (40 + 60) = 54 generations, whatever the sexes sampled
+        let mut rng = rand::thread_rng();
+        let samples = 3000;
+        for &inheritance in &[Inheritance::XLinked, Inheritance::Haplodiploid] {
+            let population = TwoSex::new(10.0, 30.0, inheritance);
+            assert_eq!(population.generations_per_unit(), 54.0);
+            for &(males, females) in &[(2, 0), (0, 2), (1, 1)] {
+                let mean_depth = (0..samples)
+                    .map(|_| population.sample_genealogy_by_sex(males, females, &mut rng).0.depth())
+                    .sum::<f64>() / samples as f64;
+                assert!((mean_depth - 54.0).abs() < 6.0, "{}", mean_depth);
+            }
+        }
+
+        let autosomal = TwoSex::new(10.0, 30.0, Inheritance::Autosomal);
+        let mean_depth = (0..samples)
+            .map(|_| autosomal.sample_genealogy_by_sex(1, 1, &mut rng).0.depth())
+            .sum::<f64>() / samples as f64;
+        assert!((mean_depth - 60.0).abs() < 6.0, "{}", mean_depth);
+    }
+}
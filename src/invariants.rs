@@ -0,0 +1,103 @@
+//! Invariants of genealogies.
+//!
+//! Every model producing a ``Genealogy`` should satisfy these predicates, which
+//! only use its public methods. They are meant for tests of new models, see
+//! also method ``validate`` on Genealogy for diagnostics of malformed genealogies.
+//!
+
+// Types
+use crate::Genealogy;
+
+/// Times of events are finite and strictly increasing from the present.
+pub fn monotone_times(genealogy: &Genealogy) -> bool {
+    genealogy.events().all(|event| event.time_step > 0.0 && event.time_step.is_finite())
+}
+
+/// Each ancestral partition along the path refines the next one,
+/// which has exactly one set less.
+pub fn refining_partitions(genealogy: &Genealogy) -> bool {
+    let events = genealogy.events().count();
+    (0..events).all(|step| {
+        let (state, next_state) = (genealogy.partition_after(step), genealogy.partition_after(step + 1));
+        next_state.amount_of_sets() + 1 == state.amount_of_sets()
+            && state.all_sets().all(|mut set| {
+                let first = set.next().unwrap();
+                set.all(|other| next_state.same_set(first, other))
+            })
+    })
+}
+
+/// Every ancestral partition covers all individuals, from singletons at present
+/// to a single set at the root, after one event less than individuals.
+pub fn conserved_leaves(genealogy: &Genealogy) -> bool {
+    let group_size = genealogy.group_size();
+    let events = genealogy.events().count();
+
+    events + 1 == group_size.max(1)
+        && genealogy.partition_after(0).amount_of_sets() == group_size
+        && genealogy.partition_after(events).amount_of_sets() == group_size.min(1)
+        && (0..=events).all(|step| {
+            let state = genealogy.partition_after(step);
+            state.len() == group_size && state.all_sets().map(|set| set.count()).sum::<usize>() == group_size
+        })
+}
+
+/// All the invariants of this module.
+///
+/// # Examples
+///
+/// ```
+/// let rng = rand::thread_rng();
+/// let coalescent = coalescence::Coalescent::new(10, rng);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = coalescent.sample_genealogy(&mut rng);
+///
+/// assert!(coalescence::invariants::all(&genealogy));
+/// ```
+pub fn all(genealogy: &Genealogy) -> bool {
+    monotone_times(genealogy) && refining_partitions(genealogy) && conserved_leaves(genealogy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BirthDeath, Coalescent, Demography, StructuredCoalescent};
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    proptest! {
+        #[test]
+        fn kingman(group_size in 1usize..40, growth_rate in 0.0f64..5.0, seed: u64) {
+            let mut rng = Pcg32::seed_from_u64(seed);
+            let mut coalescent = Coalescent::new(group_size, rng.clone());
+            coalescent.set_demography(Demography::exponential(growth_rate));
+            let mut genealogy = coalescent.sample_genealogy(&mut rng);
+
+            prop_assert!(all(&genealogy));
+            genealogy.rescale(0.5);
+            prop_assert!(all(&genealogy));
+        }
+
+        #[test]
+        fn other_models(samples in proptest::collection::vec(0usize..6, 2), seed: u64) {
+            prop_assume!(samples.iter().sum::<usize>() > 0);
+            let mut rng = Pcg32::seed_from_u64(seed);
+
+            let migration = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+            let structured = StructuredCoalescent::new(vec![1.0, 0.5], migration);
+            prop_assert!(all(&structured.sample_genealogy(&samples, &mut rng)));
+
+            let birth_death = BirthDeath::new(2.0, 1.0, 0.5);
+            prop_assert!(all(&birth_death.sample_genealogy(samples[0] + samples[1], &mut rng)));
+        }
+    }
+
+    #[test]
+    fn fixtures() {
+        assert!(all(&crate::fixtures::balanced()));
+        assert!(all(&crate::fixtures::caterpillar(6)));
+        assert!(all(&crate::fixtures::almost_star(5, 1e-3)));
+    }
+}
@@ -0,0 +1,315 @@
+//! Genealogies in JSON, with the ``serde_json`` feature.
+//!
+//! The format is versioned and does not depend on how genealogies are stored,
+//! so that files stay readable by later versions of the crate and by other tools.
+//! Version 1 is an object with the fields
+//!
+//! - ``"format"``: the string ``"coalescence/genealogy"``,
+//! - ``"version"``: the integer 1,
+//! - ``"group_size"``: the number of individuals n,
+//! - ``"sampling_times"``: the time in the past at which each individual was sampled,
+//! - ``"branch_ids"``: optionally, the identifier of the branch above each node,
+//!   (2n - 1) of them unless some event merges more than two lineages, numbered as in
+//!   ``branches``, see ``BranchId``,
+//! - ``"events"``: the coalescence events, in order, each one an object with the
+//!   ``"time"`` in the past at which it happens and its ``"individuals"``, one of each
+//!   lineage it joins: two of them, or more for multiple mergers, see ``from_mergers``.
+//!
+//! Other fields are ignored when reading, so that later minor versions can add some.
+//!
+//! ```text
+//! {
+//!   "format": "coalescence/genealogy",
+//!   "version": 1,
+//!   "group_size": 3,
+//!   "sampling_times": [0.0, 0.0, 0.0],
+//!   "branch_ids": [0, 1, 2, 3, 4],
+//!   "events": [
+//!     {"time": 0.5, "individuals": [0, 1]},
+//!     {"time": 2.0, "individuals": [2, 0]}
+//!   ]
+//! }
+//! ```
+//!
+
+// Types
+use crate::{BranchId, Genealogy, GenealogyError};
+use serde_json::{Map, Value};
+
+/// Name of the format, in the field ``"format"``.
+pub const FORMAT: &str = "coalescence/genealogy";
+
+/// Latest version of the format, in the field ``"version"``.
+pub const VERSION: u64 = 1;
+
+/// Error when reading a genealogy from JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// The text is not valid JSON, from the given line and column onwards.
+    Syntax { line: usize, column: usize, message: String },
+    /// The JSON value does not follow the schema.
+    Schema(String),
+    /// The version of the format is not supported.
+    UnsupportedVersion(u64),
+    /// The events do not give a valid genealogy.
+    Genealogy(GenealogyError),
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax { line, column, message } => {
+                write!(f, "invalid JSON at line {}, column {}: {}", line, column, message)
+            },
+            JsonError::Schema(message) => write!(f, "invalid genealogy file: {}", message),
+            JsonError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {} of the format, the latest is {}", version, VERSION)
+            },
+            JsonError::Genealogy(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonError::Genealogy(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Genealogy {
+    /// Genealogy in the latest version of the JSON format, see the module documentation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Genealogy;
+    ///
+    /// let genealogy = Genealogy::from_events(3, &[(0.5, [0, 1]), (2.0, [2, 0])]).unwrap();
+    /// let json = genealogy.to_json();
+    ///
+    /// assert!(json.contains("\"version\": 1"));
+    /// let other = Genealogy::from_json(&json).unwrap();
+    /// assert_eq!(other.depth(), 2.0);
+    /// assert_eq!(other.divergence(0, 1).unwrap(), 1.0);
+    /// ```
+    pub fn to_json(&self) -> String {
+        let sampling_times: Vec<String> = self.sampling_times().iter().map(|time| number(*time)).collect();
+        let branch_ids: Vec<String> = self.branch_ids().iter().map(|id| id.index().to_string()).collect();
+        let events: Vec<String> = self
+            .mergers()
+            .map(|(time, indexes)| {
+                let individuals: Vec<String> = indexes.iter().map(|index| index.to_string()).collect();
+                format!("    {{\"time\": {}, \"individuals\": [{}]}}", number(time), individuals.join(", "))
+            })
+            .collect();
+
+        let mut json = String::from("{\n");
+        json += &format!("  \"format\": \"{}\",\n", FORMAT);
+        json += &format!("  \"version\": {},\n", VERSION);
+        json += &format!("  \"group_size\": {},\n", self.group_size());
+        json += &format!("  \"sampling_times\": [{}],\n", sampling_times.join(", "));
+        json += &format!("  \"branch_ids\": [{}],\n", branch_ids.join(", "));
+        match events.is_empty() {
+            true => json += "  \"events\": []\n",
+            false => json += &format!("  \"events\": [\n{}\n  ]\n", events.join(",\n")),
+        }
+        json += "}\n";
+
+        json
+    }
+
+    /// Read a genealogy in any supported version of the JSON format, see the module documentation.
+    ///
+    /// # Errors
+    ///
+    /// If the text is not JSON, if it does not follow the schema, if its version is
+    /// more recent than this crate supports, or if events do not give a valid genealogy,
+    /// see ``from_events``, or ``from_mergers`` when some event merges more than two
+    /// lineages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Genealogy;
+    /// use coalescence::json::JsonError;
+    ///
+    /// let json = r#"{"format": "coalescence/genealogy", "version": 2, "group_size": 1}"#;
+    ///
+    /// assert_eq!(Genealogy::from_json(json).unwrap_err(), JsonError::UnsupportedVersion(2));
+    /// ```
+    pub fn from_json(json: &str) -> Result<Genealogy, JsonError> {
+        let value: Value = serde_json::from_str(json).map_err(|error| JsonError::Syntax {
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+        })?;
+        let object = value.as_object().ok_or_else(|| schema("expected an object"))?;
+
+        if get(object, "format")?.as_str() != Some(FORMAT) {
+            return Err(schema(&format!("the format must be \"{}\"", FORMAT)));
+        }
+        let version = integer(get(object, "version")?, "version")?;
+        if version == 0 || version > VERSION {
+            return Err(JsonError::UnsupportedVersion(version));
+        }
+
+        let group_size = integer(get(object, "group_size")?, "group_size")? as usize;
+        let sampling_times: Vec<f64> = match object.get("sampling_times") {
+            Some(value) => array(value, "sampling_times")?
+                .iter()
+                .map(|time| time.as_f64().ok_or_else(|| schema("sampling times must be numbers")))
+                .collect::<Result<_, _>>()?,
+            None => vec![0.0; group_size],
+        };
+        if sampling_times.len() != group_size {
+            return Err(schema("there must be one sampling time per individual"));
+        }
+
+        let mergers: Vec<(f64, Vec<usize>)> = array(get(object, "events")?, "events")?
+            .iter()
+            .map(|event| {
+                let event = event.as_object().ok_or_else(|| schema("events must be objects"))?;
+                let time = get(event, "time")?.as_f64().ok_or_else(|| schema("times must be numbers"))?;
+                let individuals = array(get(event, "individuals")?, "individuals")?
+                    .iter()
+                    .map(|individual| integer(individual, "individuals").map(|individual| individual as usize))
+                    .collect::<Result<Vec<usize>, _>>()?;
+                match individuals.len() >= 2 {
+                    true => Ok((time, individuals)),
+                    false => Err(schema("each event must join at least two individuals")),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let branch_ids: Option<Vec<BranchId>> = match object.get("branch_ids") {
+            Some(value) => Some(
+                array(value, "branch_ids")?
+                    .iter()
+                    .map(|id| integer(id, "branch_ids").map(|id| BranchId::new(id as usize)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            None => None,
+        };
+
+        // Binary genealogies are checked as such, e.g. for their number of events
+        let genealogy = match mergers.iter().all(|(_, individuals)| individuals.len() == 2) {
+            true => {
+                let events: Vec<(f64, [usize; 2])> = mergers
+                    .iter()
+                    .map(|(time, individuals)| (*time, [individuals[0], individuals[1]]))
+                    .collect();
+                Genealogy::from_events(group_size, &events)
+            },
+            false => Genealogy::from_mergers(group_size, &mergers),
+        };
+        let mut genealogy = genealogy.map_err(JsonError::Genealogy)?;
+        if branch_ids.as_ref().is_some_and(|branch_ids| branch_ids.len() != genealogy.number_of_nodes()) {
+            return Err(schema("there must be one branch identifier per node"));
+        }
+        if let Some(branch_ids) = branch_ids {
+            genealogy = genealogy.with_branch_ids(branch_ids);
+        }
+        match sampling_times.iter().all(|&time| time == 0.0) {
+            true => Ok(genealogy),
+            false => {
+                let genealogy = genealogy.with_sampling_times(sampling_times);
+                genealogy
+                    .validate()
+                    .map_err(|diagnostics| JsonError::Genealogy(GenealogyError::Invalid(diagnostics)))?;
+                Ok(genealogy)
+            },
+        }
+    }
+}
+
+/// Shortest representation that reads back as the same number.
+fn number(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+fn schema(message: &str) -> JsonError {
+    JsonError::Schema(message.to_string())
+}
+
+fn get<'a>(object: &'a Map<String, Value>, key: &str) -> Result<&'a Value, JsonError> {
+    object.get(key).ok_or_else(|| schema(&format!("missing field \"{}\"", key)))
+}
+
+fn integer(value: &Value, key: &str) -> Result<u64, JsonError> {
+    match value.as_f64() {
+        Some(number) if number >= 0.0 && number.fract() == 0.0 && number < 2f64.powi(53) => Ok(number as u64),
+        _ => Err(schema(&format!("\"{}\" must be non-negative integers", key))),
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], JsonError> {
+    match value {
+        Value::Array(values) => Ok(values),
+        _ => Err(schema(&format!("\"{}\" must be an array", key))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coalescent;
+
+    #[test]
+    fn round_trip() {
+        let mut coalescent = Coalescent::sampler(6);
+        coalescent.schedule_sample(0.3, 2);
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let genealogy = coalescent.sample_genealogy(&mut rng);
+            let other = Genealogy::from_json(&genealogy.to_json()).unwrap();
+            assert_eq!(other.group_size(), 8);
+            assert_eq!(other.sampling_times(), genealogy.sampling_times());
+            assert!((other.depth() - genealogy.depth()).abs() < 1e-12);
+            assert!((other.length() - genealogy.length()).abs() < 1e-9);
+            let events = genealogy.events().zip(other.events());
+            assert!(events.into_iter().all(|(event, other)| event.indexes == other.indexes));
+        }
+
+        // Identifiers of branches are kept, e.g. those of an induced genealogy
+        let induced = coalescent.sample_genealogy(&mut rng).induced(&[5, 1, 7, 2]);
+        let other = Genealogy::from_json(&induced.to_json()).unwrap();
+        for (branch, other) in induced.branches().iter().zip(other.branches()) {
+            assert_eq!(branch.id, other.id);
+        }
+        assert_eq!(other.branches()[0].id, BranchId::new(5));
+        let single = Genealogy::from_events(1, &[]).unwrap();
+        assert_eq!(Genealogy::from_json(&single.to_json()).unwrap().group_size(), 1);
+
+        // Multiple mergers list all their individuals
+        let collapsed = coalescent.sample_genealogy(&mut rng).collapse_short_branches(0.2);
+        let other = Genealogy::from_json(&collapsed.to_json()).unwrap();
+        assert_eq!(other.degrees(), collapsed.degrees());
+        assert_eq!(other.sampling_times(), collapsed.sampling_times());
+        assert!((other.length() - collapsed.length()).abs() < 1e-9);
+        let star = Genealogy::from_mergers(3, &[(1.0, vec![0, 1, 2])]).unwrap();
+        assert!(star.to_json().contains("{\"time\": 1.0, \"individuals\": [0, 1, 2]}"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Genealogy::from_json("{\"format\": "), Err(JsonError::Syntax { .. })));
+        assert!(matches!(Genealogy::from_json("[1, 2]"), Err(JsonError::Schema(_))));
+
+        // Unknown fields are ignored, but events must be valid
+        let json = r#"{
+            "format": "coalescence/genealogy", "version": 1, "group_size": 2,
+            "comment": {"nested": [true, null, "é"]},
+            "events": [{"time": -1.0, "individuals": [0, 1]}]
+        }"#;
+        assert!(matches!(Genealogy::from_json(json), Err(JsonError::Genealogy(_))));
+        let json = json.replace("-1.0", "1e-1");
+        assert_eq!(Genealogy::from_json(&json).unwrap().depth(), 0.1);
+
+        // Branch identifiers are optional, but one per node
+        let json = json.replace("\"version\"", "\"branch_ids\": [0, 1], \"version\"");
+        assert!(matches!(Genealogy::from_json(&json), Err(JsonError::Schema(_))));
+    }
+}
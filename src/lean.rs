@@ -0,0 +1,161 @@
+//! Genealogies of very large groups.
+//!
+//! A ``Genealogy`` stores the parent, the time and the branch identifier of each
+//! node next to its events, which is about 80 bytes per individual on 64-bit
+//! platforms. A ``LeanGenealogy`` only stores the events, i.e. one pair of
+//! individuals and one time step per event, which is 24 bytes per individual:
+//! groups of 10⁷ individuals fit in a few hundred megabytes. Summaries are computed
+//! on the fly with a union–find over individuals.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+use crate::Genealogy;
+
+/// Genealogy stored as its events only, see the module documentation.
+///
+/// Use ``sample_lean_genealogy`` on Coalescent to build them, and ``to_genealogy``
+/// to recover a full genealogy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeanGenealogy {
+    group_size: usize,
+    steps: Vec<[usize; 2]>,
+    time_steps: Vec<f64>,
+}
+
+impl LeanGenealogy {
+    pub(crate) fn new(group_size: usize, steps: Vec<[usize; 2]>, time_steps: Vec<f64>) -> Self {
+        LeanGenealogy { group_size, steps, time_steps }
+    }
+
+    /// Number of individuals.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// An individual of each of the two lineages joined by each event.
+    pub fn steps(&self) -> &[[usize; 2]] {
+        &self.steps
+    }
+
+    /// Waiting time before each event.
+    pub fn time_steps(&self) -> &[f64] {
+        &self.time_steps
+    }
+
+    /// Time to the most recent common ancestor.
+    pub fn depth(&self) -> f64 {
+        self.time_steps.iter().sum()
+    }
+
+    /// Total length of branches.
+    pub fn length(&self) -> f64 {
+        self.time_steps
+            .iter()
+            .enumerate()
+            .map(|(i, time_step)| (self.group_size - i) as f64 * time_step)
+            .sum()
+    }
+
+    /// Mean time to the common ancestor of two individuals, times two,
+    /// over all pairs of individuals.
+    pub fn mean_pairwise_divergence(&self) -> f64 {
+        let mut sets = UnionFind::new(self.group_size);
+        let mut time = 0.0;
+        let mut divergence = 0.0;
+        for (indexes, time_step) in self.steps.iter().zip(&self.time_steps) {
+            time += time_step;
+            let pairs = (sets.size(indexes[0]) * sets.size(indexes[1])) as f64;
+            divergence += 2.0 * time * pairs;
+            sets.union(indexes[0], indexes[1]);
+        }
+
+        let pairs = (self.group_size * self.group_size.saturating_sub(1) / 2) as f64;
+        match pairs > 0.0 {
+            true => divergence / pairs,
+            false => 0.0,
+        }
+    }
+
+    /// Total length of branches by number of individuals below them: the entry ``j``
+    /// is the length of branches ancestral to exactly ``j + 1`` individuals.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::Coalescent;
+    ///
+    /// let coalescent = Coalescent::new(1000, rand::thread_rng());
+    /// let genealogy = coalescent.sample_lean_genealogy(&mut rand::thread_rng());
+    /// let spectrum = genealogy.branch_spectrum();
+    ///
+    /// assert_eq!(spectrum.len(), 999);
+    /// assert!((spectrum.iter().sum::<f64>() - genealogy.length()).abs() < 1e-9 * genealogy.length());
+    /// ```
+    pub fn branch_spectrum(&self) -> Vec<f64> {
+        let mut sets = UnionFind::new(self.group_size);
+        let mut starts = vec![0.0; self.group_size]; // of the branch above each representative
+        let mut spectrum = vec![0.0; self.group_size.saturating_sub(1)];
+        let mut time = 0.0;
+        for (indexes, time_step) in self.steps.iter().zip(&self.time_steps) {
+            time += time_step;
+            for &individual in indexes {
+                let representative = sets.find(individual);
+                spectrum[sets.size(representative) - 1] += time - starts[representative];
+            }
+            let representative = sets.union(indexes[0], indexes[1]);
+            starts[representative] = time;
+        }
+
+        spectrum
+    }
+
+    /// Bytes used on the heap by the events.
+    pub fn heap_size(&self) -> usize {
+        self.steps.capacity() * std::mem::size_of::<[usize; 2]>()
+            + self.time_steps.capacity() * std::mem::size_of::<f64>()
+    }
+
+    /// Full genealogy with the same events, with the nodes of its tree.
+    pub fn to_genealogy(&self) -> Genealogy {
+        Genealogy::from_steps(self.group_size, self.steps.clone(), self.time_steps.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Coalescent;
+    use rand::SeedableRng;
+
+    #[test]
+    fn agrees_with_genealogy() {
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8);
+        let coalescent = Coalescent::new(30, rng.clone());
+        for _ in 0..20 {
+            let lean = coalescent.sample_lean_genealogy(&mut rng);
+            let genealogy = lean.to_genealogy();
+            assert_eq!(lean.group_size(), 30);
+            assert!((lean.depth() - genealogy.depth()).abs() < 1e-12);
+            assert!((lean.length() - genealogy.length()).abs() < 1e-9);
+            assert!((lean.mean_pairwise_divergence() - genealogy.mean_pairwise_divergence()).abs() < 1e-9);
+
+            let root = genealogy.node(2 * 30 - 2);
+            let expected = genealogy.clade_statistics(&root).branch_spectrum;
+            for (lean, full) in lean.branch_spectrum().iter().zip(expected) {
+                assert!((lean - full).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn large_groups() {
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(1);
+        let group_size = 100_000;
+        let coalescent = Coalescent::new(group_size, rng.clone());
+        let lean = coalescent.sample_lean_genealogy(&mut rng);
+        assert_eq!(lean.steps().len(), group_size - 1);
+        assert_eq!(lean.heap_size(), 24 * (group_size - 1));
+        // Expected depth: 2 (1 - 1 / n)
+        assert!(lean.depth() > 0.1 && lean.depth() < 10.0);
+    }
+}
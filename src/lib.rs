@@ -1,10 +1,85 @@
 //! Coalescent process as described in [Coalescent Theory](https://en.wikipedia.org/wiki/Coalescent_theory)
 
+pub use annotated::*;
+pub use background::*;
+pub use balancing::*;
 pub use coalescent::*;
+pub use demography::*;
+pub use events::*;
+pub use fit::*;
+pub use fluctuating::*;
+pub use forest::*;
 pub use genealogy::*;
+pub use inference::*;
+pub use inheritance::*;
+pub use lean::*;
+pub use multilocus::*;
+pub use mutations::*;
+pub use pairwise::*;
+pub use partition::*;
+pub use phylodynamics::*;
+pub use prior::*;
+pub use quantitative::*;
+pub use structured::*;
+pub use study::*;
+pub use sweep::*;
+pub use trajectory::*;
+pub use units::*;
 
+pub mod annotated;
+pub mod background;
+pub mod balance;
+pub mod balancing;
 pub mod coalescent;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod demographies;
+pub mod demography;
+pub mod differentiation;
+pub mod discretization;
+pub mod distances;
+pub mod estimators;
+pub mod events;
+pub mod ewens;
+pub mod exact;
+pub mod fit;
+pub mod fixtures;
+pub mod fluctuating;
+pub mod forest;
 pub mod genealogy;
+pub mod golden;
+pub mod inference;
+pub mod inheritance;
+pub mod invariants;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod lean;
+pub mod models;
+pub mod multifurcating;
+pub mod multilocus;
+pub mod mutations;
+pub mod observers;
+pub mod pairwise;
+pub mod partition;
+pub mod phylodynamics;
+#[cfg(feature = "plotters")]
+pub mod plot;
+pub mod prior;
+pub mod quantitative;
+pub mod species;
+pub mod statistics;
+pub mod structured;
+pub mod study;
+pub mod subsample;
+pub mod sweep;
+pub mod tables;
+pub mod theory;
+pub mod topologies;
+pub mod trajectory;
+pub mod units;
+mod union_find;
 
 pub mod traits;
 
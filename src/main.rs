@@ -1,8 +1,227 @@
+//! Command line interface.
+//!
+//! ```text
+//! coalescence simulate -n <group size> [-r <replicates>] [-t <theta> | -s <segregating sites>] [--seed <seed>] [--ms] [--model <name>] [--statistics <names>]
+//! ```
+//!
+//! By default, prints a table with statistics of each replicate. With ``--ms``,
+//! prints the haplotypes of each replicate in the output format of Hudson's ``ms``.
+//! Models are named as in ``ModelKind``, and only those without parameters,
+//! ``kingman`` by default and ``yule`` with birth rate one, can be simulated.
+//! With ``--statistics``, followed by names separated by commas, e.g. ``depth,length``,
+//! the table has a column per statistic of ``StatisticRegistry`` instead.
+//!
+
+// Types
+use coalescence::models::ModelKind;
+use coalescence::{BirthDeath, Coalescent, MutationModel, StatisticRegistry};
+use rand_pcg::Pcg32;
+
+// Traits
+use rand::{Rng, SeedableRng};
+
+const USAGE: &str = "Usage: coalescence simulate -n <group size> [-r <replicates>] [-t <theta> | -s <segregating sites>] [--seed <seed>] [--ms] [--model <name>] [--statistics <names>]";
+
+/// Options of the ``simulate`` subcommand.
+#[derive(Debug, Clone, PartialEq)]
+struct SimulateOptions {
+    group_size: usize,
+    replicates: usize,
+    mutations: Option<MutationModel>,
+    seed: Option<u64>,
+    ms: bool,
+    model: ModelKind,
+    statistics: Option<Vec<String>>,
+}
+
+impl SimulateOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut group_size = None;
+        let mut replicates = 1;
+        let mut mutations = None;
+        let mut seed = None;
+        let mut ms = false;
+        let mut model = ModelKind::Kingman;
+        let mut statistics = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-n" => group_size = Some(parse_value(arg, args.next())?),
+                "-r" => replicates = parse_value(arg, args.next())?,
+                "-t" => mutations = Some(MutationModel::Poisson { theta: parse_value(arg, args.next())? }),
+                "-s" => mutations = Some(MutationModel::FixedSegregatingSites { sites: parse_value(arg, args.next())? }),
+                "--seed" => seed = Some(parse_value(arg, args.next())?),
+                "--ms" => ms = true,
+                "--model" => {
+                    let name = args.next().ok_or(format!("Missing value for {}", arg))?;
+                    model = name.parse().map_err(|error: coalescence::models::ModelError| error.to_string())?;
+                },
+                "--statistics" => {
+                    let names: Vec<String> = args
+                        .next()
+                        .ok_or(format!("Missing value for {}", arg))?
+                        .split(',')
+                        .map(|name| name.to_string())
+                        .collect();
+                    StatisticRegistry::default().select(&names).map_err(|error| error.to_string())?;
+                    statistics = Some(names);
+                },
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        let group_size = group_size.ok_or("Missing group size, given by -n")?;
+        if group_size < 2 {
+            return Err("Group size must be at least 2".to_string());
+        }
+        if ms && mutations.is_none() {
+            return Err("ms output requires mutations, given by -t or -s".to_string());
+        }
+        if ms && statistics.is_some() {
+            return Err("ms output has no statistics, given by --statistics".to_string());
+        }
+
+        if !matches!(model, ModelKind::Kingman | ModelKind::Yule) {
+            return Err(format!("Model {} has parameters, simulate it with the library", model));
+        }
+
+        Ok(SimulateOptions { group_size, replicates, mutations, seed, ms, model, statistics })
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    value
+        .ok_or(format!("Missing value for {}", option))?
+        .parse()
+        .map_err(|_| format!("Invalid value for {}", option))
+}
+
+fn simulate(options: &SimulateOptions) {
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = Pcg32::seed_from_u64(seed);
+    let coalescent = Coalescent::new(options.group_size, rng.clone());
+    let statistics = options
+        .statistics
+        .as_ref()
+        .map(|names| StatisticRegistry::default().select(names).expect("Names are checked when parsing."));
+
+    // Header
+
+    if options.ms {
+        let arguments: Vec<String> = std::env::args().skip(1).collect();
+        println!("coalescence {}", arguments.join(" "));
+        println!("{}", seed);
+    } else if let Some(names) = &options.statistics {
+        println!("replicate\t{}", names.join("\t"));
+    } else {
+        let mut header = "replicate\tdepth\tlength\tpairwise_divergence".to_string();
+        if options.mutations.is_some() {
+            header.push_str("\tsegregating_sites");
+        }
+        println!("{}", header);
+    }
+
+    // Replicates
+
+    for replicate in 0..options.replicates {
+        let genealogy = match options.model {
+            ModelKind::Yule => BirthDeath::yule(1.0).sample_genealogy(options.group_size, &mut rng),
+            _ => coalescent.sample_genealogy(&mut rng),
+        };
+        let haplotypes = options.mutations.map(|model| genealogy.sample_haplotypes(model, &mut rng));
+
+        match (options.ms, haplotypes) {
+            (true, Some(haplotypes)) => print!("\n{}", haplotypes.to_ms()),
+            (_, haplotypes) if statistics.is_some() => {
+                // Without mutations, as a simulation study of theta zero
+                let model = MutationModel::Poisson { theta: 0.0 };
+                let haplotypes = haplotypes.unwrap_or_else(|| genealogy.sample_haplotypes(model, &mut rng));
+                let values: Vec<String> = statistics
+                    .iter()
+                    .flatten()
+                    .map(|statistic| statistic.compute(&genealogy, &haplotypes).to_string())
+                    .collect();
+                println!("{}\t{}", replicate, values.join("\t"));
+            },
+            (_, haplotypes) => {
+                let mut line = format!(
+                    "{}\t{}\t{}\t{}",
+                    replicate,
+                    genealogy.depth(),
+                    genealogy.length(),
+                    genealogy.mean_pairwise_divergence()
+                );
+                if let Some(haplotypes) = haplotypes {
+                    line.push_str(&format!("\t{}", haplotypes.segregating_sites()));
+                }
+                println!("{}", line);
+            }
+        }
+    }
+}
+
 fn main() {
-    let group_size = 5;
-    let rng = rand::thread_rng();
-    let coalescent = coalescence::Coalescent::new(group_size, rng);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(|command| command.as_str()) {
+        Some("simulate") => match SimulateOptions::parse(&args[1..]) {
+            Ok(options) => simulate(&options),
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                std::process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let options = SimulateOptions::parse(&args("-n 10 -r 5 -s 3 --seed 7 --ms")).unwrap();
+
+        assert_eq!(options, SimulateOptions {
+            group_size: 10,
+            replicates: 5,
+            mutations: Some(MutationModel::FixedSegregatingSites { sites: 3 }),
+            seed: Some(7),
+            ms: true,
+            model: ModelKind::Kingman,
+            statistics: None,
+        });
+        assert_eq!(SimulateOptions::parse(&args("-n 10 --model yule")).unwrap().model, ModelKind::Yule);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(SimulateOptions::parse(&args("-r 5")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 1")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 -t")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 --ms")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 --unknown")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 --model moran")).unwrap_err().starts_with("unsupported model moran"));
+        assert!(SimulateOptions::parse(&args("-n 10 --model sweep")).is_err());
+    }
+
+    #[test]
+    fn parse_statistics() {
+        let options = SimulateOptions::parse(&args("-n 10 -t 2 --statistics depth,length,segregating_sites")).unwrap();
+        let names = ["depth", "length", "segregating_sites"].iter().map(|name| name.to_string()).collect();
+        assert_eq!(options.statistics, Some(names));
 
-    let mut rng = rand::thread_rng();
-    println!("{:?}", coalescent.sample_genealogy(&mut rng) );
-}
\ No newline at end of file
+        assert!(SimulateOptions::parse(&args("-n 10 --statistics")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 --statistics depth,sackin")).is_err());
+        assert!(SimulateOptions::parse(&args("-n 10 -t 2 --ms --statistics depth")).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Models of genealogies, grouped by kind and selected by name.
+//!
+//! Each submodule gathers the types of a family of models, and ``ModelKind``
+//! names every model, so that command line tools and builders can choose
+//! one at runtime. Names of models that are planned but not implemented yet,
+//! see ``UNSUPPORTED``, are rejected with the reason.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::models::{ModelError, ModelKind};
+//!
+//! let kind: ModelKind = "birth-death".parse().unwrap();
+//!
+//! assert_eq!(kind, ModelKind::BirthDeath);
+//! assert_eq!(kind.name(), "birth-death");
+//! assert!(matches!("lambda".parse::<ModelKind>(), Err(ModelError::Unsupported { .. })));
+//! ```
+//!
+
+/// The standard coalescent of Kingman, with a demography.
+pub mod kingman {
+    pub use crate::coalescent::Coalescent;
+    pub use crate::demography::Demography;
+}
+
+/// Coalescent in a population divided into demes or classes.
+pub mod structured {
+    pub use crate::structured::StructuredCoalescent;
+}
+
+/// Genealogies at loci under selection or linked to a selected site.
+pub mod selection {
+    pub use crate::background::BackgroundSelection;
+    pub use crate::balancing::BalancingSelection;
+    pub use crate::sweep::Sweep;
+    pub use crate::trajectory::Trajectory;
+}
+
+/// Birth–death trees of phylodynamics.
+pub mod birth_death {
+    pub use crate::phylodynamics::BirthDeath;
+}
+
+/// Coalescent where each pair of individuals has its own rate, e.g. from kinship.
+pub mod pairwise {
+    pub use crate::pairwise::{Merge, PairwiseCoalescent};
+}
+
+/// Genealogies of uniparental loci in populations with two sexes.
+pub mod inheritance {
+    pub use crate::inheritance::{Inheritance, TwoSex};
+}
+
+/// Name of a model of genealogies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModelKind {
+    /// ``Coalescent``.
+    Kingman,
+    /// ``StructuredCoalescent``.
+    Structured,
+    /// ``Sweep``.
+    Sweep,
+    /// ``BalancingSelection``.
+    BalancingSelection,
+    /// ``BirthDeath``.
+    BirthDeath,
+    /// Pure birth process, see ``BirthDeath::yule``.
+    Yule,
+    /// ``TwoSex``.
+    TwoSex,
+    /// ``PairwiseCoalescent``.
+    Pairwise,
+    /// ``BackgroundSelection``.
+    BackgroundSelection,
+}
+
+impl ModelKind {
+    /// All models, in the order of their declaration.
+    pub const ALL: [ModelKind; 9] = [
+        ModelKind::Kingman,
+        ModelKind::Structured,
+        ModelKind::Sweep,
+        ModelKind::BalancingSelection,
+        ModelKind::BirthDeath,
+        ModelKind::Yule,
+        ModelKind::TwoSex,
+        ModelKind::Pairwise,
+        ModelKind::BackgroundSelection,
+    ];
+
+    /// Name of the model, in kebab case.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModelKind::Kingman => "kingman",
+            ModelKind::Structured => "structured",
+            ModelKind::Sweep => "sweep",
+            ModelKind::BalancingSelection => "balancing-selection",
+            ModelKind::BirthDeath => "birth-death",
+            ModelKind::Yule => "yule",
+            ModelKind::TwoSex => "two-sex",
+            ModelKind::Pairwise => "pairwise",
+            ModelKind::BackgroundSelection => "background-selection",
+        }
+    }
+}
+
+impl std::fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Names of models that are not implemented, with the reason.
+pub const UNSUPPORTED: [(&str, &str); 4] = [
+    ("lambda", "Λ-coalescents are not implemented, build their genealogies with Genealogy::from_mergers"),
+    ("smc", "the sequentially Markov coalescent needs recombination, which is not implemented"),
+    ("wf", "the Wright–Fisher model in discrete time is not implemented"),
+    ("moran", "the Moran model in discrete time is not implemented"),
+];
+
+/// Error when no model of a given name can be chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelError {
+    /// No model has this name.
+    Unknown(String),
+    /// The model is planned but not implemented, see ``UNSUPPORTED``.
+    Unsupported { name: &'static str, reason: &'static str },
+}
+
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ModelError::Unknown(name) => {
+                let names: Vec<&str> = ModelKind::ALL.iter().map(|kind| kind.name()).collect();
+                write!(f, "unknown model {}, expected one of {}", name, names.join(", "))
+            },
+            ModelError::Unsupported { name, reason } => write!(f, "unsupported model {}: {}", name, reason),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl std::str::FromStr for ModelKind {
+    type Err = ModelError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(&(name, reason)) = UNSUPPORTED.iter().find(|(unsupported, _)| *unsupported == name) {
+            return Err(ModelError::Unsupported { name, reason });
+        }
+        ModelKind::ALL
+            .iter()
+            .find(|kind| kind.name() == name)
+            .cloned()
+            .ok_or_else(|| ModelError::Unknown(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for kind in ModelKind::ALL.iter() {
+            assert_eq!(kind.name().parse::<ModelKind>(), Ok(*kind));
+            assert_eq!(kind.to_string(), kind.name());
+        }
+        assert_eq!("moran-model".parse::<ModelKind>(), Err(ModelError::Unknown("moran-model".to_string())));
+
+        // Planned models are rejected with their reason
+        for &(name, reason) in UNSUPPORTED.iter() {
+            assert_eq!(name.parse::<ModelKind>(), Err(ModelError::Unsupported { name, reason }));
+            assert!(!ModelKind::ALL.iter().any(|kind| kind.name() == name));
+        }
+        assert!("smc".parse::<ModelKind>().unwrap_err().to_string().starts_with("unsupported model smc: "));
+    }
+}
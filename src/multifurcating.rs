@@ -0,0 +1,248 @@
+//! Genealogies with polytomies, where common ancestors may have more than two children.
+//!
+//! Reconstructed trees cannot resolve branches much shorter than the time to the
+//! next mutation, so they are compared to simulated genealogies once short inner
+//! branches of the latter are collapsed into polytomies, see ``collapse_short_branches``.
+//! Models with multiple mergers, like Λ- and Ξ-coalescents, give such genealogies too,
+//! built from their mergers by ``from_mergers``.
+//!
+//! Both are plain ``Genealogy`` values, whose events may merge more than two lineages:
+//! lengths, depths, divergences and spectra are computed as for binary genealogies.
+//! Their ``events`` are binary by definition, so replay them with ``mergers`` instead.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+use crate::{Genealogy, GenealogyError};
+
+impl Genealogy {
+    /// Build the genealogy of ``group_size`` individuals sampled at present from a list of
+    /// mergers, each one given by its time in the past and an individual of each of the
+    /// lineages it joins.
+    ///
+    /// # Errors
+    ///
+    /// If there are no individuals, if some individual is not in the group, if a merger
+    /// does not join at least two lineages, or not after the previous one, or if
+    /// mergers do not join all individuals.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Genealogy, GenealogyError};
+    ///
+    /// let mergers = [(0.5, vec![0, 2, 3]), (1.5, vec![1, 3])];
+    /// let genealogy = Genealogy::from_mergers(4, &mergers).unwrap();
+    /// assert_eq!(genealogy.degrees(), vec![3, 2]);
+    /// assert_eq!(genealogy.divergence(0, 2), Ok(1.0));
+    ///
+    /// let error = Genealogy::from_mergers(4, &mergers[..1]).unwrap_err();
+    /// assert_eq!(error, GenealogyError::NotCoalescing { indexes: [0, 1] });
+    /// ```
+    pub fn from_mergers(group_size: usize, mergers: &[(f64, Vec<usize>)]) -> Result<Genealogy, GenealogyError> {
+        if group_size == 0 {
+            return Err(GenealogyError::EmptyGroup);
+        }
+
+        let mut sets = UnionFind::new(group_size);
+        let mut steps = Vec::with_capacity(mergers.len());
+        let mut time_steps = Vec::with_capacity(mergers.len());
+        let mut previous = 0.0;
+        for (event, (time, individuals)) in mergers.iter().enumerate() {
+            if let Some(&index) = individuals.iter().find(|&&index| index >= group_size) {
+                return Err(GenealogyError::IndexOutOfBounds { index, group_size });
+            }
+            // Representatives are individuals, one of each lineage
+            let mut lineages: Vec<usize> = individuals.iter().map(|&index| sets.find(index)).collect();
+            lineages.sort_unstable();
+            lineages.dedup();
+            if lineages.len() < 2 || !(*time > previous && time.is_finite()) {
+                return Err(GenealogyError::InvalidMerger { event });
+            }
+
+            for &lineage in &lineages[1..] {
+                sets.union(lineages[0], lineage);
+            }
+            steps.push(lineages);
+            time_steps.push(time - previous);
+            previous = *time;
+        }
+        if let Some(unjoined) = (1..group_size).find(|&index| sets.find(index) != sets.find(0)) {
+            return Err(GenealogyError::NotCoalescing { indexes: [0, unjoined] });
+        }
+
+        Ok(Genealogy::from_merger_steps(group_size, steps, time_steps))
+    }
+
+    /// Genealogy where inner branches shorter than ``epsilon`` are collapsed, their
+    /// children being joined to their parent. Branches above individuals are kept,
+    /// so that individuals stay leaves, and so are sampling times and identifiers
+    /// of the branches left.
+    ///
+    /// # Panics
+    ///
+    /// If ``epsilon`` is negative or not a number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // The balanced tree, with inner branches of length three and two below the root
+    /// let genealogy = coalescence::fixtures::balanced();
+    ///
+    /// let collapsed = genealogy.collapse_short_branches(2.5);
+    /// assert_eq!(collapsed.number_of_nodes(), 6);
+    /// assert_eq!(collapsed.children(5), vec![2, 3, 4]);
+    ///
+    /// let star = genealogy.collapse_short_branches(3.5);
+    /// assert_eq!(star.children(4), vec![0, 1, 2, 3]);
+    /// assert_eq!(collapsed.depth(), genealogy.depth());
+    /// ```
+    pub fn collapse_short_branches(&self, epsilon: f64) -> Genealogy {
+        assert!(epsilon >= 0.0, "The threshold must be non-negative.");
+
+        let group_size = self.group_size();
+        let (parents, times) = self.nodes();
+
+        // Node that each common ancestor is merged into, from the root down
+        let mut merged: Vec<usize> = (0..parents.len()).collect();
+        for node in (group_size..parents.len()).rev() {
+            let parent = parents[node];
+            if parent != node && times[parent] - times[node] < epsilon {
+                merged[node] = merged[parent];
+            }
+        }
+
+        // Smallest individual below each node, children coming before their parents
+        let mut representatives: Vec<usize> = (0..parents.len()).collect();
+        for (node, &parent) in parents.iter().enumerate() {
+            representatives[parent] = representatives[parent].min(representatives[node]);
+        }
+
+        // An individual of each kept child of the kept common ancestors, in order
+        let mut lineages: Vec<Vec<usize>> = vec![Vec::new(); parents.len()];
+        for node in (0..parents.len() - 1).filter(|&node| merged[node] == node) {
+            lineages[merged[parents[node]]].push(representatives[node]);
+        }
+        let kept: Vec<usize> = (0..parents.len()).filter(|&node| merged[node] == node).collect();
+        let mut steps = Vec::with_capacity(kept.len() - group_size);
+        let mut time_steps = Vec::with_capacity(kept.len() - group_size);
+        let mut previous = 0.0;
+        for &node in &kept[group_size..] {
+            steps.push(std::mem::take(&mut lineages[node]));
+            time_steps.push(times[node] - previous);
+            previous = times[node];
+        }
+
+        let branch_ids = kept.iter().map(|&node| self.branch_ids()[node]).collect();
+        Genealogy::from_merger_steps(group_size, steps, time_steps)
+            .with_sampling_times(self.sampling_times().to_vec())
+            .with_branch_ids(branch_ids)
+    }
+
+    /// Number of children of each common ancestor, in the order of nodes.
+    pub fn degrees(&self) -> Vec<usize> {
+        let group_size = self.group_size();
+        let (parents, _) = self.nodes();
+        let mut degrees = vec![0; parents.len() - group_size];
+        for (node, &parent) in parents.iter().enumerate() {
+            if parent != node {
+                degrees[parent - group_size] += 1;
+            }
+        }
+
+        degrees
+    }
+
+    /// Whether every common ancestor has exactly two children.
+    pub fn is_binary(&self) -> bool {
+        self.degrees().iter().all(|&degree| degree == 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::RandomPartition;
+
+    #[test]
+    fn collapsing() {
+        let genealogy = crate::Coalescent::sampler(30).sample_genealogy(&mut rand::thread_rng());
+
+        // Nothing is collapsed below the shortest branch
+        let binary = genealogy.collapse_short_branches(0.0);
+        assert!(binary.is_binary());
+        assert_eq!(binary.number_of_nodes(), 59);
+        for (collapsed, length) in binary.branch_lengths().iter().zip(genealogy.branch_lengths()) {
+            assert!((collapsed - length).abs() < 1e-9);
+        }
+
+        // Everything is collapsed into a star above the longest branch
+        let star = genealogy.collapse_short_branches(f64::INFINITY);
+        assert_eq!(star.degrees(), vec![30]);
+        assert_eq!(star.depth(), genealogy.depth());
+        assert!((star.length() - 30.0 * genealogy.depth()).abs() < 1e-9);
+        assert_eq!(star.lineages_through_time().last(), Some(&(genealogy.depth(), 1)));
+
+        // Degrees add up to the number of branches, and inner branches left are long enough
+        let collapsed = genealogy.collapse_short_branches(0.05);
+        assert_eq!(collapsed.validate(), Ok(()));
+        let degrees = collapsed.degrees();
+        assert_eq!(degrees.iter().sum::<usize>(), collapsed.number_of_nodes() - 1);
+        assert!(degrees.iter().all(|&degree| degree >= 2));
+        for node in 0..collapsed.number_of_nodes() - 1 {
+            let parent = collapsed.parent(node).unwrap();
+            assert!(parent > node);
+            if node >= 30 {
+                assert!(collapsed.node(parent).age - collapsed.node(node).age >= 0.05);
+            }
+        }
+        assert_eq!(collapsed.parent(collapsed.number_of_nodes() - 1), None);
+        assert_eq!(collapsed.branches().len(), collapsed.number_of_nodes() - 1);
+    }
+
+    #[test]
+    fn statistics_of_mergers() {
+        // Individuals 0, 2 and 3 merge at time 1, then with individual 1 at time 3
+        let genealogy = Genealogy::from_mergers(4, &[(1.0, vec![0, 2, 3]), (3.0, vec![1, 2])]).unwrap();
+        assert_eq!(genealogy.validate(), Ok(()));
+        assert_eq!(genealogy.depth(), 3.0);
+        assert_eq!(genealogy.length(), 3.0 + 3.0 + 2.0);
+        assert_eq!(genealogy.branch_spectrum(), vec![6.0, 0.0, 2.0]);
+        assert_eq!(genealogy.lineages_through_time(), vec![(0.0, 4), (1.0, 2), (3.0, 1)]);
+        assert_eq!(genealogy.children(4), vec![0, 2, 3]);
+        assert_eq!(genealogy.node(4).leaves, vec![0, 2, 3]);
+        assert_eq!(genealogy.partition_after(1).block_sizes(), vec![3, 1]);
+
+        // Three pairs at distance 2, and three at distance 6
+        assert_eq!(genealogy.divergence(0, 3), Ok(2.0));
+        assert_eq!(genealogy.divergence(1, 3), Ok(6.0));
+        assert!((genealogy.mean_pairwise_divergence() - 4.0).abs() < 1e-12);
+
+        // Mutations fall on the branches left
+        let mutated = genealogy.sample_mutations(crate::MutationModel::FixedSegregatingSites { sites: 20 }, &mut rand::thread_rng());
+        let sfs = mutated.haplotypes().site_frequency_spectrum();
+        assert_eq!(sfs.len(), 3);
+        assert_eq!(sfs[1], 0);
+        assert_eq!(sfs.iter().sum::<usize>(), 20);
+    }
+
+    #[test]
+    fn mergers() {
+        let genealogy = Genealogy::from_mergers(5, &[(1.0, vec![0, 1, 2, 3, 4])]).unwrap();
+        assert_eq!(genealogy.branch_spectrum(), vec![5.0, 0.0, 0.0, 0.0]);
+        assert_eq!(genealogy.divergence(1, 1), Ok(0.0));
+        assert_eq!(genealogy.shape(), "(,,,,)");
+        let genealogy = Genealogy::from_mergers(1, &[]).unwrap();
+        assert_eq!((genealogy.depth(), genealogy.length()), (0.0, 0.0));
+
+        let errors = [
+            (vec![(1.0, vec![0, 0])], GenealogyError::InvalidMerger { event: 0 }),
+            (vec![(1.0, vec![0, 1]), (1.0, vec![1, 2])], GenealogyError::InvalidMerger { event: 1 }),
+            (vec![(1.0, vec![0, 3])], GenealogyError::IndexOutOfBounds { index: 3, group_size: 3 }),
+        ];
+        for (mergers, error) in &errors {
+            assert_eq!(&Genealogy::from_mergers(3, mergers).unwrap_err(), error);
+        }
+        assert_eq!(Genealogy::from_mergers(0, &[]).unwrap_err(), GenealogyError::EmptyGroup);
+    }
+}
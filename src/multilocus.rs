@@ -0,0 +1,159 @@
+//! Genealogies of several loci.
+//!
+//! Unlinked loci, e.g. on different chromosomes, have independent genealogies
+//! of the same group of individuals. Averaging over loci reduces the variance
+//! of statistics coming from the randomness of genealogies.
+//!
+
+// Types
+use crate::{Coalescent, Genealogy, Haplotypes, MutationModel};
+
+// Traits
+use rand::Rng;
+
+/// Independent genealogies of a group of individuals at unlinked loci.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{MultiLocusGenealogies, MutationModel};
+/// let group_size = 10;
+/// let rng = rand::thread_rng();
+/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogies = MultiLocusGenealogies::sample(&coalescent, 100, &mut rng);
+/// let haplotypes = genealogies.sample_haplotypes(MutationModel::Poisson { theta: 2.0 }, &mut rng);
+///
+/// assert_eq!(genealogies.loci(), 100);
+/// assert_eq!(haplotypes.len(), 100);
+/// assert_eq!(genealogies.tmrcas().len(), 100);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiLocusGenealogies {
+    genealogies: Vec<Genealogy>, // all of the same group size
+}
+
+impl MultiLocusGenealogies {
+    /// Creates a new container from the genealogy of each locus.
+    ///
+    /// # Panics
+    ///
+    /// If the genealogies are not all of the same group of individuals.
+    pub fn new(genealogies: Vec<Genealogy>) -> Self {
+        assert!(
+            genealogies.windows(2).all(|pair| pair[0].group_size() == pair[1].group_size()),
+            "All genealogies must have the same group size."
+        );
+
+        MultiLocusGenealogies { genealogies }
+    }
+
+    /// Sample independent genealogies of ``loci`` unlinked loci.
+    pub fn sample<R, S>(coalescent: &Coalescent<R>, loci: usize, rng: &mut S) -> Self
+    where
+        R: Rng,
+        S: Rng + ?Sized,
+    {
+        let genealogies = (0..loci).map(|_| coalescent.sample_genealogy(rng)).collect();
+
+        MultiLocusGenealogies { genealogies }
+    }
+
+    /// Number of loci.
+    pub fn loci(&self) -> usize {
+        self.genealogies.len()
+    }
+
+    /// Genealogy of each locus.
+    pub fn genealogies(&self) -> &[Genealogy] {
+        &self.genealogies
+    }
+
+    /// Time to the most recent common ancestor at each locus, i.e. their depths.
+    pub fn tmrcas(&self) -> Vec<f64> {
+        self.genealogies.iter().map(|genealogy| genealogy.depth()).collect()
+    }
+
+    /// Mean time to the most recent common ancestor over loci.
+    pub fn mean_tmrca(&self) -> f64 {
+        mean(self.tmrcas())
+    }
+
+    /// Mean over loci of the mean pairwise divergence.
+    pub fn mean_pairwise_divergence(&self) -> f64 {
+        mean(self.genealogies.iter().map(|genealogy| genealogy.mean_pairwise_divergence()))
+    }
+
+    /// Drop neutral mutations on the genealogy of each locus.
+    ///
+    /// See method ``sample_haplotypes`` on Genealogy.
+    pub fn sample_haplotypes<R>(&self, model: MutationModel, rng: &mut R) -> Vec<Haplotypes>
+    where
+        R: Rng + ?Sized,
+    {
+        self.genealogies
+            .iter()
+            .map(|genealogy| genealogy.sample_haplotypes(model, rng))
+            .collect()
+    }
+
+    /// Mean over loci of the mean number of differences between pairs of individuals π,
+    /// dropping neutral mutations on each genealogy.
+    pub fn mean_pairwise_differences<R>(&self, model: MutationModel, rng: &mut R) -> f64
+    where
+        R: Rng + ?Sized,
+    {
+        mean(self.sample_haplotypes(model, rng).iter().map(|haplotypes| haplotypes.mean_pairwise_differences()))
+    }
+
+    /// All loci in the output format of Hudson's ``ms``, one replicate per locus,
+    /// dropping neutral mutations on each genealogy.
+    pub fn to_ms<R>(&self, model: MutationModel, rng: &mut R) -> String
+    where
+        R: Rng + ?Sized,
+    {
+        self.sample_haplotypes(model, rng)
+            .iter()
+            .map(|haplotypes| format!("\n{}", haplotypes.to_ms()))
+            .collect()
+    }
+}
+
+/// Mean of the values, zero if there are none.
+fn mean<I>(values: I) -> f64
+where
+    I: IntoIterator<Item = f64>,
+{
+    let (sum, count) = values
+        .into_iter()
+        .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+
+    match count > 0 {
+        true => sum / count as f64,
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_over_loci() {
+        let rng = rand::thread_rng();
+        let coalescent = Coalescent::new(2, rng);
+
+        let mut rng = rand::thread_rng();
+        let genealogies = MultiLocusGenealogies::sample(&coalescent, 4000, &mut rng);
+
+        // For two individuals, E[TMRCA] = 1 and E[π] = θ
+        assert!((genealogies.mean_tmrca() - 1.0).abs() < 0.1);
+        assert!((genealogies.mean_pairwise_divergence() - 2.0 * genealogies.mean_tmrca()).abs() < 1e-9);
+        let pi = genealogies.mean_pairwise_differences(MutationModel::Poisson { theta: 3.0 }, &mut rng);
+        assert!((pi - 3.0).abs() < 0.3);
+
+        let ms = genealogies.to_ms(MutationModel::FixedSegregatingSites { sites: 1 }, &mut rng);
+        assert_eq!(ms.matches("//").count(), 4000);
+    }
+}
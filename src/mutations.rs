@@ -0,0 +1,510 @@
+//! Neutral mutations on genealogies.
+//!
+//! Mutations follow the infinite sites model: each mutation hits a new site,
+//! so every mutation is a segregating site of the sample. Mutations fall on
+//! the branches of a genealogy uniformly over its total length, either as a
+//! Poisson process of rate θ/2 or conditioning on the total number S of
+//! segregating sites (the "fixed-S" convention of the ``-s`` option of ``ms``).
+//!
+
+// Types
+use crate::{BranchId, Genealogy};
+
+/// How mutations are dropped on a genealogy.
+///
+/// See method [sample_haplotypes](../genealogy/struct.Genealogy.html#method.sample_haplotypes).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MutationModel {
+    /// Mutations arrive at rate θ/2 along every branch, so that the expected
+    /// number of differences between two individuals is θ.
+    Poisson { theta: f64 },
+    /// Exactly this many mutations, placed uniformly over the tree,
+    /// i.e. proportionally to branch lengths.
+    FixedSegregatingSites { sites: usize },
+}
+
+/// Stream of uniform numbers derived from a seed and a key, e.g. a branch, by
+/// splitmix64 hashing, so that it is the same on every run and platform.
+pub(crate) struct HashStream {
+    state: u64,
+}
+
+impl HashStream {
+    pub(crate) fn new(seed: u64, key: u64) -> Self {
+        HashStream { state: splitmix64(seed ^ splitmix64(key)) }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        splitmix64(self.state)
+    }
+
+    /// Uniform number in [0, 1), with 53 random bits.
+    pub(crate) fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Poisson number of the given mean, by inversion of its distribution function,
+    /// as a sum of Poisson numbers of means at most ``POISSON_CHUNK``.
+    ///
+    /// Only additions, multiplications and divisions are used, which IEEE 754 rounds
+    /// the same on every platform, unlike ``exp`` or ``ln`` of math libraries.
+    pub(crate) fn poisson(&mut self, mean: f64) -> usize {
+        let chunks = (mean / POISSON_CHUNK).ceil() as usize;
+        let chunk = mean / chunks.max(1) as f64;
+        let zero = exp_neg(chunk);
+
+        (0..chunks)
+            .map(|_| {
+                let uniform = self.uniform();
+                let (mut count, mut probability, mut cumulative) = (0, zero, zero);
+                while uniform >= cumulative && probability > 0.0 {
+                    count += 1;
+                    probability *= chunk / count as f64;
+                    cumulative += probability;
+                }
+                count
+            })
+            .sum()
+    }
+}
+
+/// Largest mean of the Poisson numbers drawn at once by ``HashStream::poisson``.
+const POISSON_CHUNK: f64 = 16.0;
+
+/// e^{-x} for 0 ≤ x ≤ ``POISSON_CHUNK``, with basic arithmetic only: the Taylor series
+/// at ``x / 2^k`` ≤ 1/8, squared ``k`` times.
+fn exp_neg(x: f64) -> f64 {
+    let (mut y, mut halvings) = (x, 0);
+    while y > 0.125 {
+        y /= 2.0;
+        halvings += 1;
+    }
+    let mut result = (1..=16).rev().fold(1.0, |sum, k| 1.0 - y / k as f64 * sum);
+    for _ in 0..halvings {
+        result *= result;
+    }
+    result
+}
+
+/// Finalizer of splitmix64.
+fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Haplotypes of a group of individuals under the infinite sites model.
+///
+/// Every site has an ancestral allele, coded as ``0``, and a derived allele,
+/// coded as ``1``, carried by the individuals below the mutation.
+/// Sites are sorted by their position in the unit interval.
+///
+/// This struct is created by the ``sample_haplotypes`` method on Genealogy.
+/// See its documentation for more.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Haplotypes {
+    group_size: usize,
+    positions: Vec<f64>,
+    carriers: Vec<Vec<usize>>, // sorted indexes of individuals with derived allele
+}
+
+impl Haplotypes {
+    pub(crate) fn new(group_size: usize, mut sites: Vec<(f64, Vec<usize>)>) -> Self {
+        sites.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let (positions, carriers) = sites.into_iter().unzip();
+
+        Haplotypes { group_size, positions, carriers }
+    }
+
+    /// Number of individuals.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// Number of segregating sites.
+    pub fn segregating_sites(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Positions of the sites in the unit interval, in increasing order.
+    pub fn positions(&self) -> &[f64] {
+        &self.positions
+    }
+
+    /// Individuals carrying the derived allele at a site, in increasing order.
+    pub fn carriers(&self, site: usize) -> &[usize] {
+        &self.carriers[site]
+    }
+
+    /// Alleles of one individual at every site.
+    pub fn haplotype(&self, index: usize) -> Vec<u8> {
+        self.carriers
+            .iter()
+            .map(|carriers| carriers.binary_search(&index).is_ok() as u8)
+            .collect()
+    }
+
+    /// Matrix of alleles, with one row per individual and one column per site.
+    pub fn matrix(&self) -> Vec<Vec<u8>> {
+        (0..self.group_size).map(|index| self.haplotype(index)).collect()
+    }
+
+    /// Mean number of differences between pairs of individuals,
+    /// an estimator of θ sometimes denoted by π.
+    pub fn mean_pairwise_differences(&self) -> f64 {
+        let n = self.group_size;
+        let pairs = (n * n.saturating_sub(1) / 2) as f64;
+        let differences: usize = self.carriers
+            .iter()
+            .map(|carriers| carriers.len() * (n - carriers.len()))
+            .sum();
+
+        match pairs > 0.0 {
+            true => differences as f64 / pairs,
+            false => 0.0,
+        }
+    }
+
+    /// Replicate in the output format of Hudson's ``ms``.
+    ///
+    /// Writes the ``//`` separator, the number of segregating sites,
+    /// their positions and one line of ``0``s and ``1``s per individual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::MutationModel;
+    /// let group_size = 4;
+    /// let rng = rand::thread_rng();
+    /// let coalescent = coalescence::Coalescent::new(group_size, rng);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let genealogy = coalescent.sample_genealogy(&mut rng);
+    /// let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites: 3 }, &mut rng);
+    /// let ms = haplotypes.to_ms();
+    /// let lines: Vec<&str> = ms.lines().collect();
+    ///
+    /// assert_eq!(lines[0], "//");
+    /// assert_eq!(lines[1], "segsites: 3");
+    /// assert!(lines[2].starts_with("positions:"));
+    /// assert_eq!(lines.len(), 3 + group_size);
+    /// assert!(lines[3..].iter().all(|line| line.len() == 3));
+    /// ```
+    pub fn to_ms(&self) -> String {
+        let mut output = format!("//\nsegsites: {}\n", self.segregating_sites());
+        if self.segregating_sites() > 0 {
+            output.push_str("positions:");
+            for position in &self.positions {
+                output.push_str(&format!(" {:.4}", position));
+            }
+            output.push('\n');
+            for haplotype in self.matrix() {
+                let line: String = haplotype.iter().map(|&allele| if allele == 1 { '1' } else { '0' }).collect();
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+        output
+    }
+
+    /// Unfolded site frequency spectrum.
+    ///
+    /// The entry ``i`` is the number of sites where exactly ``i + 1``
+    /// individuals carry the derived allele, for ``i`` from 0 to (n - 2).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::MutationModel;
+    /// let group_size = 10;
+    /// let rng = rand::thread_rng();
+    /// let coalescent = coalescence::Coalescent::new(group_size, rng);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let genealogy = coalescent.sample_genealogy(&mut rng);
+    /// let model = MutationModel::FixedSegregatingSites { sites: 20 };
+    /// let haplotypes = genealogy.sample_haplotypes(model, &mut rng);
+    /// let sfs = haplotypes.site_frequency_spectrum();
+    ///
+    /// assert_eq!(sfs.len(), group_size - 1);
+    /// assert_eq!(sfs.iter().sum::<usize>(), 20);
+    /// ```
+    pub fn site_frequency_spectrum(&self) -> Vec<usize> {
+        let mut sfs = vec![0; self.group_size.saturating_sub(1)];
+        for carriers in &self.carriers {
+            sfs[carriers.len() - 1] += 1;
+        }
+        sfs
+    }
+}
+
+/// Mutation on a branch of a genealogy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mutation {
+    /// Branch hit by the mutation.
+    pub branch: BranchId,
+    /// Time of the mutation in the past.
+    pub age: f64,
+    /// Position of the site in the unit interval.
+    pub position: f64,
+}
+
+/// Genealogy together with the mutations dropped on it.
+///
+/// This struct is created by the ``sample_mutations`` method on Genealogy.
+/// See its documentation for more.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::MutationModel;
+/// let group_size = 10;
+/// let rng = rand::thread_rng();
+/// let coalescent = coalescence::Coalescent::new(group_size, rng);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = coalescent.sample_genealogy(&mut rng);
+/// let mutated = genealogy.sample_mutations(MutationModel::Poisson { theta: 5.0 }, &mut rng);
+///
+/// let first_half = mutated.in_range(0.0, 0.5);
+/// assert!(first_half.iter().all(|mutation| mutation.position < 0.5));
+/// for branch in mutated.genealogy().branches() {
+///     let on_branch = mutated.on_branch(branch.id);
+///     assert!(on_branch.iter().all(|mutation| branch.start <= mutation.age && mutation.age <= branch.end));
+/// }
+/// assert_eq!(mutated.haplotypes().segregating_sites(), mutated.mutations().len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MutatedGenealogy {
+    genealogy: Genealogy,
+    mutations: Vec<Mutation>, // sorted by position
+    carriers: Vec<Vec<usize>>, // sorted indexes of individuals below each mutation
+}
+
+impl MutatedGenealogy {
+    pub(crate) fn new(genealogy: Genealogy, mut mutations: Vec<(Mutation, Vec<usize>)>) -> Self {
+        mutations.sort_by(|a, b| a.0.position.partial_cmp(&b.0.position).unwrap());
+        let (mutations, carriers) = mutations.into_iter().unzip();
+
+        MutatedGenealogy { genealogy, mutations, carriers }
+    }
+
+    /// Genealogy the mutations were dropped on.
+    pub fn genealogy(&self) -> &Genealogy {
+        &self.genealogy
+    }
+
+    /// All mutations, in increasing order of position.
+    pub fn mutations(&self) -> &[Mutation] {
+        &self.mutations
+    }
+
+    /// Individuals carrying the derived allele of a mutation, in increasing order,
+    /// where ``site`` indexes ``mutations``.
+    pub fn carriers(&self, site: usize) -> &[usize] {
+        &self.carriers[site]
+    }
+
+    /// Mutations with positions in the interval [``start``, ``end``).
+    pub fn in_range(&self, start: f64, end: f64) -> &[Mutation] {
+        let first = self.mutations.partition_point(|mutation| mutation.position < start);
+        let last = self.mutations.partition_point(|mutation| mutation.position < end).max(first);
+        &self.mutations[first..last]
+    }
+
+    /// Mutations on a branch, in increasing order of position.
+    pub fn on_branch(&self, branch: BranchId) -> Vec<&Mutation> {
+        self.mutations.iter().filter(|mutation| mutation.branch == branch).collect()
+    }
+
+    /// Mutations carried by exactly the given individuals, in increasing order of position.
+    pub fn carried_by(&self, individuals: &[usize]) -> Vec<&Mutation> {
+        let mut individuals = individuals.to_vec();
+        individuals.sort_unstable();
+        individuals.dedup();
+
+        self.mutations
+            .iter()
+            .zip(&self.carriers)
+            .filter(|(_, carriers)| carriers == &&individuals)
+            .map(|(mutation, _)| mutation)
+            .collect()
+    }
+
+    /// Matrix of derived alleles, with one row per individual and one column per mutation.
+    pub fn genotypes(&self) -> Vec<Vec<u8>> {
+        self.haplotypes().matrix()
+    }
+
+    /// Haplotypes of the group, forgetting where mutations happened.
+    pub fn haplotypes(&self) -> Haplotypes {
+        let sites = self.mutations
+            .iter()
+            .zip(&self.carriers)
+            .map(|(mutation, carriers)| (mutation.position, carriers.clone()))
+            .collect();
+
+        Haplotypes::new(self.genealogy.group_size(), sites)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl MutatedGenealogy {
+    /// Matrix of derived alleles, with one row per individual and one column per mutation,
+    /// for linear algebra with ``ndarray``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::MutationModel;
+    /// let genealogy = coalescence::fixtures::balanced();
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let mutated = genealogy.sample_mutations(MutationModel::FixedSegregatingSites { sites: 6 }, &mut rng);
+    /// let matrix = mutated.genotype_matrix();
+    ///
+    /// assert_eq!(matrix.dim(), (4, 6));
+    /// assert_eq!(matrix.sum_axis(ndarray::Axis(0)).mapv(|count| count as f64 / 4.0), mutated.allele_frequencies());
+    /// ```
+    pub fn genotype_matrix(&self) -> ndarray::Array2<u8> {
+        let group_size = self.genealogy.group_size();
+        let mut matrix = ndarray::Array2::zeros((group_size, self.mutations.len()));
+        for (site, carriers) in self.carriers.iter().enumerate() {
+            for &individual in carriers {
+                matrix[[individual, site]] = 1;
+            }
+        }
+        matrix
+    }
+
+    /// Frequency of the derived allele of each mutation in the group.
+    pub fn allele_frequencies(&self) -> ndarray::Array1<f64> {
+        let group_size = self.genealogy.group_size() as f64;
+        self.carriers.iter().map(|carriers| carriers.len() as f64 / group_size).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_segregating_sites() {
+        let group_size = 20;
+        let sites = 50;
+        let rng = rand::thread_rng();
+        let coalescent = crate::Coalescent::new(group_size, rng);
+
+        let mut rng = rand::thread_rng();
+        let genealogy = coalescent.sample_genealogy(&mut rng);
+        let haplotypes = genealogy.sample_haplotypes(MutationModel::FixedSegregatingSites { sites }, &mut rng);
+
+        assert_eq!(haplotypes.segregating_sites(), sites);
+        assert!(haplotypes.positions().windows(2).all(|w| w[0] <= w[1]));
+        for site in 0..sites {
+            let carriers = haplotypes.carriers(site);
+            assert!(!carriers.is_empty() && carriers.len() < group_size);
+        }
+
+        let matrix = haplotypes.matrix();
+        assert_eq!(matrix.len(), group_size);
+        let derived: usize = matrix.iter().flatten().map(|&allele| allele as usize).sum();
+        let weighted_sfs: usize = haplotypes.site_frequency_spectrum()
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (i + 1) * count)
+            .sum();
+        assert_eq!(derived, weighted_sfs);
+    }
+
+    #[test]
+    fn no_mutations() {
+        let rng = rand::thread_rng();
+        let coalescent = crate::Coalescent::new(10, rng);
+
+        let mut rng = rand::thread_rng();
+        let genealogy = coalescent.sample_genealogy(&mut rng);
+        let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta: 0.0 }, &mut rng);
+
+        assert_eq!(haplotypes.segregating_sites(), 0);
+        assert_eq!(haplotypes.site_frequency_spectrum(), vec![0; 9]);
+    }
+
+    #[test]
+    fn mutation_queries() {
+        let genealogy = crate::fixtures::balanced();
+        let mut rng = rand::thread_rng();
+        let mutated = genealogy.sample_mutations(MutationModel::FixedSegregatingSites { sites: 50 }, &mut rng);
+
+        let branches = genealogy.branches();
+        for (site, mutation) in mutated.mutations().iter().enumerate() {
+            let branch = branches.iter().find(|branch| branch.id == mutation.branch).unwrap();
+            assert!(branch.start <= mutation.age && mutation.age <= branch.end);
+            assert_eq!(mutated.carriers(site), &branch.leaves[..]);
+        }
+        let on_branches: usize = branches.iter().map(|branch| mutated.on_branch(branch.id).len()).sum();
+        assert_eq!(on_branches, 50);
+
+        // Queries by carriers
+        let by_carriers: usize = branches.iter().map(|branch| mutated.carried_by(&branch.leaves).len()).sum();
+        assert_eq!(by_carriers, 50);
+        assert!(mutated.carried_by(&[0, 2]).is_empty());
+
+        // Queries by position
+        assert_eq!(mutated.in_range(0.0, 1.0).len(), 50);
+        assert_eq!(mutated.in_range(0.5, 0.5).len(), 0);
+        let split = mutated.in_range(0.0, 0.3).len() + mutated.in_range(0.3, 1.0).len();
+        assert_eq!(split, 50);
+
+        assert_eq!(mutated.genotypes(), mutated.haplotypes().matrix());
+    }
+
+    #[test]
+    fn hashed_mutations() {
+        let genealogy = crate::fixtures::balanced();
+        let model = MutationModel::Poisson { theta: 4.0 };
+        let mutated = genealogy.hash_mutations(model, 7);
+        assert_eq!(mutated.mutations(), genealogy.clone().hash_mutations(model, 7).mutations());
+        assert_ne!(mutated.mutations(), genealogy.hash_mutations(model, 8).mutations());
+
+        // Expected number of mutations: θ/2 times the length
+        let seeds = 2000;
+        let total: usize = (0..seeds).map(|seed| genealogy.hash_mutations(model, seed).mutations().len()).sum();
+        assert!((total as f64 / seeds as f64 - 2.0 * genealogy.length()).abs() < 0.5);
+
+        let model = MutationModel::FixedSegregatingSites { sites: 10 };
+        let mutated = genealogy.hash_mutations(model, 3);
+        assert_eq!(mutated.mutations().len(), 10);
+        assert_eq!(mutated.genotypes(), genealogy.hash_mutations(model, 3).genotypes());
+    }
+
+    #[test]
+    fn golden_hashed_mutations() {
+        // FNV-1a hash of the bits of mutations, the same on every platform
+        let hash = |mutated: &MutatedGenealogy| {
+            mutated
+                .mutations()
+                .iter()
+                .flat_map(|mutation| vec![mutation.age.to_bits(), mutation.position.to_bits()])
+                .flat_map(|word| word.to_le_bytes())
+                .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+        };
+        let genealogy = crate::fixtures::balanced();
+
+        let mutated = genealogy.hash_mutations(MutationModel::Poisson { theta: 4.0 }, 7);
+        assert_eq!((mutated.mutations().len(), hash(&mutated)), (19, 0x5375_34c9_64cf_b4aa));
+        let mutated = genealogy.hash_mutations(MutationModel::Poisson { theta: 100.0 }, 11);
+        assert_eq!((mutated.mutations().len(), hash(&mutated)), (582, 0xca87_4e8a_0aea_a77b));
+        let mutated = genealogy.hash_mutations(MutationModel::FixedSegregatingSites { sites: 10 }, 3);
+        assert_eq!((mutated.mutations().len(), hash(&mutated)), (10, 0x3973_7de1_a960_48f5));
+    }
+
+    #[test]
+    fn exact_exponentials() {
+        for &x in &[0.0f64, 0.1, 1.0, 5.5, 16.0] {
+            let expected = (-x).exp();
+            assert!((exp_neg(x) - expected).abs() < 1e-13 * expected, "{}", x);
+        }
+    }
+}
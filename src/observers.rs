@@ -0,0 +1,187 @@
+//! Statistics accumulated online while sampling, see ``StatisticsObserver``.
+//!
+//! Sampling with an observer only keeps the sets of individuals below each lineage,
+//! in a union–find, so that statistics of groups of millions of individuals take
+//! linear memory and almost linear time, without storing the genealogy.
+//!
+
+// Types
+use crate::coalescent::choose_two;
+use crate::union_find::UnionFind;
+use crate::{Coalescent, Event};
+
+// Traits
+use crate::traits::StatisticsObserver;
+use markovian::traits::CMarkovChainTrait;
+use rand::Rng;
+
+/// Total length of the genealogy, see ``length`` on Genealogy.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TreeLength {
+    pub length: f64,
+}
+
+impl StatisticsObserver for TreeLength {
+    fn start(&mut self, _group_size: usize) {
+        self.length = 0.0;
+    }
+
+    fn observe(&mut self, event: &Event, _sizes: [usize; 2]) {
+        self.length += (event.lineages + 1) as f64 * event.time_step;
+    }
+}
+
+/// Depth of the genealogy, see ``depth`` on Genealogy.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TreeDepth {
+    pub depth: f64,
+}
+
+impl StatisticsObserver for TreeDepth {
+    fn start(&mut self, _group_size: usize) {
+        self.depth = 0.0;
+    }
+
+    fn observe(&mut self, event: &Event, _sizes: [usize; 2]) {
+        self.depth += event.time_step;
+    }
+}
+
+/// Total length of branches by the number of individuals below them: the k-th entry
+/// is the length of branches with (k + 1) individuals below, whose expectation times
+/// θ / 2 is the expected site frequency spectrum.
+///
+/// Lengths are only complete after the last event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchSpectrum {
+    pub lengths: Vec<f64>,
+    counts: Vec<usize>, // number of lineages of each size
+    changes: Vec<f64>,  // time of the last change of each count
+    time: f64,
+}
+
+impl BranchSpectrum {
+    /// Adds the length of lineages of ``size`` individuals since their count last changed.
+    fn update(&mut self, size: usize) {
+        if let Some(length) = self.lengths.get_mut(size - 1) {
+            *length += self.counts[size - 1] as f64 * (self.time - self.changes[size - 1]);
+        }
+        self.changes[size - 1] = self.time;
+    }
+}
+
+impl StatisticsObserver for BranchSpectrum {
+    fn start(&mut self, group_size: usize) {
+        self.lengths = vec![0.0; group_size.saturating_sub(1)];
+        self.counts = vec![0; group_size];
+        self.changes = vec![0.0; group_size];
+        self.time = 0.0;
+        if let Some(singletons) = self.counts.first_mut() {
+            *singletons = group_size;
+        }
+    }
+
+    fn observe(&mut self, event: &Event, sizes: [usize; 2]) {
+        // Constant time per event, as only the counts of three sizes change
+        self.time += event.time_step;
+        let joined = sizes[0] + sizes[1];
+        for &size in &[sizes[0], sizes[1], joined] {
+            self.update(size);
+        }
+        self.counts[sizes[0] - 1] -= 1;
+        self.counts[sizes[1] - 1] -= 1;
+        self.counts[joined - 1] += 1;
+
+        if event.lineages == 1 {
+            for size in 1..self.counts.len() {
+                self.update(size);
+            }
+        }
+    }
+}
+
+impl<R> Coalescent<R>
+where
+    R: Rng,
+{
+    /// Sample the events of a genealogy, calling the observer on each one, in the same
+    /// distribution as ``sample_genealogy`` but without storing the genealogy.
+    ///
+    /// # Panics
+    ///
+    /// If there are samples scheduled in the past.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::observers::{BranchSpectrum, TreeLength};
+    ///
+    /// let coalescent = coalescence::Coalescent::sampler(100_000);
+    /// let mut observer = (TreeLength::default(), BranchSpectrum::default());
+    ///
+    /// coalescent.sample_observed(&mut observer, &mut rand::thread_rng());
+    ///
+    /// let (length, spectrum) = observer;
+    /// let total: f64 = spectrum.lengths.iter().sum();
+    /// assert!((total - length.length).abs() < 1e-9 * length.length);
+    /// ```
+    pub fn sample_observed<O, S>(&self, observer: &mut O, rng: &mut S)
+    where
+        O: StatisticsObserver,
+        S: Rng + ?Sized,
+    {
+        let group_size = self.state().len();
+        let waiting_times = self.sample_waiting_times(rng);
+        observer.start(group_size);
+
+        // One individual of each lineage
+        let mut lineages: Vec<usize> = (0..group_size).collect();
+        let mut sets = UnionFind::new(group_size);
+        for time_step in waiting_times {
+            let [first, second] = choose_two(lineages.len(), rng);
+            let indexes = [lineages[first], lineages[second]];
+            let sizes = [sets.size(indexes[0]), sets.size(indexes[1])];
+            lineages[first] = sets.union(indexes[0], indexes[1]);
+            lineages.swap_remove(second);
+
+            let event = Event { time_step, indexes, lineages: lineages.len() };
+            observer.observe(&event, sizes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn online_statistics() {
+        // Expectations: length 2 Σ 1 / k, and 2 / k for branches below k individuals
+        let (group_size, samples) = (8, 20_000);
+        let coalescent = Coalescent::sampler(group_size);
+        let mut rng = rand::thread_rng();
+        let mut observer = ((TreeLength::default(), TreeDepth::default()), BranchSpectrum::default());
+        let (mut length, mut depth) = (0.0, 0.0);
+        let mut spectrum = vec![0.0; group_size - 1];
+        for _ in 0..samples {
+            coalescent.sample_observed(&mut observer, &mut rng);
+            length += (observer.0).0.length / samples as f64;
+            depth += (observer.0).1.depth / samples as f64;
+            for (mean, length) in spectrum.iter_mut().zip(&observer.1.lengths) {
+                *mean += length / samples as f64;
+            }
+        }
+
+        let expected: f64 = (1..group_size).map(|k| 2.0 / k as f64).sum();
+        assert!((length - expected).abs() < 0.03 * expected, "{}", length);
+        assert!((depth - 2.0 * (1.0 - 1.0 / group_size as f64)).abs() < 0.03, "{}", depth);
+        for (k, mean) in spectrum.iter().enumerate() {
+            assert!((mean - 2.0 / (k + 1) as f64).abs() < 0.05, "{} {}", k, mean);
+        }
+
+        // A single individual has no events
+        Coalescent::sampler(1).sample_observed(&mut observer, &mut rng);
+        assert_eq!((observer.0).0.length, 0.0);
+        assert!(observer.1.lengths.is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! Coalescent with pair-specific rates.
+//!
+//! Instead of every pair of lineages coalescing at rate one, each pair of
+//! individuals ``i`` and ``j`` has its own rate ``r[i][j]``, e.g. from a kinship
+//! or relatedness matrix, so that individuals are no longer exchangeable.
+//! Once lineages join, the rate between two ancestral lineages combines the
+//! rates between their individuals, see ``Merge``.
+//!
+//! Events are simulated by the Gillespie algorithm: the waiting time is exponential
+//! with the sum of the rates of all pairs of lineages, and the pair that coalesces
+//! is chosen with probability proportional to its rate, see ``GillespieStep``.
+//!
+
+// Types
+use crate::Genealogy;
+
+// Traits
+use crate::traits::GillespieStep;
+use rand::Rng;
+
+/// How the rate between two lineages is obtained from the rates of their individuals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Merge {
+    /// Mean of the rates over all pairs of individuals, one from each lineage.
+    Average,
+    /// Smallest rate over all pairs of individuals, one from each lineage.
+    Minimum,
+    /// Largest rate over all pairs of individuals, one from each lineage.
+    Maximum,
+}
+
+/// Coalescent process where each pair of lineages has its own rate of coalescence.
+///
+/// # Examples
+///
+/// Two pairs of relatives coalesce first.
+/// ```
+/// use coalescence::{Merge, PairwiseCoalescent};
+///
+/// let rates = vec![
+///     vec![0.0, 100.0, 0.1, 0.1],
+///     vec![100.0, 0.0, 0.1, 0.1],
+///     vec![0.1, 0.1, 0.0, 100.0],
+///     vec![0.1, 0.1, 100.0, 0.0],
+/// ];
+/// let pairwise = PairwiseCoalescent::new(rates, Merge::Average);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = pairwise.sample_genealogy(&mut rng);
+///
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// assert!(genealogy.divergence(0, 1).unwrap() < genealogy.divergence(0, 2).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairwiseCoalescent {
+    rates: Vec<Vec<f64>>,
+    merge: Merge,
+}
+
+impl PairwiseCoalescent {
+    /// Creates a new coalescent with rates ``rates[i][j]`` between individuals ``i`` and ``j``.
+    /// Diagonal entries are ignored.
+    ///
+    /// # Panics
+    ///
+    /// If the matrix is not square, not symmetric, or if some rate is negative or not finite.
+    pub fn new(rates: Vec<Vec<f64>>, merge: Merge) -> Self {
+        let group_size = rates.len();
+        assert!(rates.iter().all(|row| row.len() == group_size), "The matrix of rates must be square.");
+        assert!(
+            rates.iter().flatten().all(|&rate| rate >= 0.0 && rate.is_finite()),
+            "Rates must be non-negative and finite."
+        );
+        assert!(
+            (0..group_size).all(|i| (0..i).all(|j| rates[i][j] == rates[j][i])),
+            "The matrix of rates must be symmetric."
+        );
+
+        PairwiseCoalescent { rates, merge }
+    }
+
+    /// Exchangeable model of ``group_size`` individuals, where all pairs have rate one,
+    /// i.e. the standard coalescent.
+    pub fn exchangeable(group_size: usize) -> Self {
+        PairwiseCoalescent::new(vec![vec![1.0; group_size]; group_size], Merge::Average)
+    }
+
+    /// Number of individuals.
+    pub fn group_size(&self) -> usize {
+        self.rates.len()
+    }
+
+    /// Rate between two individuals.
+    pub fn rate(&self, first: usize, second: usize) -> f64 {
+        self.rates[first][second]
+    }
+
+    /// Rule to combine rates of joined lineages.
+    pub fn merge(&self) -> Merge {
+        self.merge
+    }
+
+    /// Sample a genealogy of the group.
+    ///
+    /// Takes time O(n³): each event updates the rates between the new lineage
+    /// and the others, and chooses among all pairs.
+    ///
+    /// # Panics
+    ///
+    /// If the group is empty, or if some lineages never meet because all rates between them are zero.
+    pub fn sample_genealogy<R>(&self, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        let group_size = self.group_size();
+        assert!(group_size > 0, "The group must be non-empty.");
+
+        // Lineages with an individual and the number of individuals of each one,
+        // and rates between lineages
+
+        let mut lineages: Vec<(usize, usize)> = (0..group_size).map(|individual| (individual, 1)).collect();
+        let mut rates = self.rates.clone();
+        let mut steps = Vec::with_capacity(group_size - 1);
+        let mut time_steps = Vec::with_capacity(group_size - 1);
+
+        while lineages.len() > 1 {
+            let (waiting_time, (first, second)) = PairRates(&rates)
+                .gillespie_step(rng)
+                .expect("Lineages never meet: all rates between them are zero.");
+            time_steps.push(waiting_time);
+            steps.push([lineages[first].0, lineages[second].0]);
+
+            // Rates of the new lineage, in place of the first one
+
+            let (size_1, size_2) = (lineages[first].1 as f64, lineages[second].1 as f64);
+            let merged: Vec<f64> = rates[first]
+                .iter()
+                .zip(&rates[second])
+                .map(|(&rate_1, &rate_2)| match self.merge {
+                    Merge::Average => (size_1 * rate_1 + size_2 * rate_2) / (size_1 + size_2),
+                    Merge::Minimum => rate_1.min(rate_2),
+                    Merge::Maximum => rate_1.max(rate_2),
+                })
+                .collect();
+            for (row, &rate) in rates.iter_mut().zip(&merged) {
+                row[first] = rate;
+            }
+            rates[first] = merged;
+            lineages[first].1 += lineages[second].1;
+
+            // Remove the second lineage, moving the last one into its place
+
+            lineages.swap_remove(second);
+            rates.swap_remove(second);
+            for row in rates.iter_mut() {
+                row.swap_remove(second);
+            }
+        }
+
+        Genealogy::from_steps(group_size, steps, time_steps)
+    }
+}
+
+/// Rates between current lineages, with one event per pair ``first < second``.
+struct PairRates<'a>(&'a [Vec<f64>]);
+
+impl<'a> GillespieStep for PairRates<'a> {
+    type Event = (usize, usize);
+
+    fn event_rates(&self, rates: &mut Vec<f64>) {
+        for (second, row) in self.0.iter().enumerate() {
+            rates.extend_from_slice(&row[..second]);
+        }
+    }
+
+    fn event(&self, mut index: usize) -> (usize, usize) {
+        let mut second = 1;
+        while index >= second {
+            index -= second;
+            second += 1;
+        }
+        (index, second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchangeable_is_kingman() {
+        // E[TMRCA] = 2 (1 - 1 / n) and E[length] = 2 (1 + 1/2 + 1/3)
+        let pairwise = PairwiseCoalescent::exchangeable(4);
+        let mut rng = rand::thread_rng();
+        let samples = 4000;
+        let (mut depth, mut length) = (0.0, 0.0);
+        for _ in 0..samples {
+            let genealogy = pairwise.sample_genealogy(&mut rng);
+            depth += genealogy.depth();
+            length += genealogy.length();
+        }
+        assert!((depth / samples as f64 - 1.5).abs() < 0.1);
+        assert!((length / samples as f64 - 2.0 * (1.0 + 0.5 + 1.0 / 3.0)).abs() < 0.15);
+    }
+
+    #[test]
+    fn merging_rules() {
+        // Individual 0 is related to 1 and 2, which are not related
+        let rates = vec![vec![0.0, 1.0, 1.0], vec![1.0, 0.0, 0.0], vec![1.0, 0.0, 0.0]];
+        let mut rng = rand::thread_rng();
+
+        let maximum = PairwiseCoalescent::new(rates.clone(), Merge::Maximum);
+        assert_eq!(maximum.sample_genealogy(&mut rng).validate(), Ok(()));
+        let average = PairwiseCoalescent::new(rates, Merge::Average);
+        assert_eq!(average.rate(1, 2), 0.0);
+        let genealogy = average.sample_genealogy(&mut rng);
+        assert_eq!(genealogy.validate(), Ok(()));
+        assert!(genealogy.divergence(1, 2).unwrap() >= genealogy.divergence(0, 1).unwrap().min(genealogy.divergence(0, 2).unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Lineages never meet")]
+    fn unrelated_lineages() {
+        // Whichever pair coalesces first, the remaining rate is the one between 1 and 2
+        let rates = vec![vec![0.0, 1.0, 1.0], vec![1.0, 0.0, 0.0], vec![1.0, 0.0, 0.0]];
+        PairwiseCoalescent::new(rates, Merge::Minimum).sample_genealogy(&mut rand::thread_rng());
+    }
+}
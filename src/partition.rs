@@ -0,0 +1,235 @@
+//! Partitions of a group of individuals.
+//!
+//! States of the coalescent process are partitions of the group, where each set
+//! is a lineage. They are stored as a union–find, together with a list of one
+//! representative per set and a circular list of the members of each set, so that
+//! the operations of the process take almost constant time: joining two sets,
+//! telling whether two individuals are in the same set, and choosing the k-th set.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+
+/// Partition of the individuals ``0..n``.
+///
+/// Sets are indexed from 0 to the number of sets minus one, by their position in
+/// ``representatives``. Indexes only change when sets are joined: the last set
+/// takes the index of the set that disappears.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::Partition;
+///
+/// let mut partition = Partition::singletons(4);
+/// partition.union(0, 2);
+///
+/// assert_eq!(partition.amount_of_sets(), 3);
+/// assert!(partition.same_set(2, 0));
+/// let mut set: Vec<usize> = partition.set(2).collect();
+/// set.sort_unstable();
+/// assert_eq!(set, vec![0, 2]);
+/// ```
+#[derive(Clone)]
+pub struct Partition {
+    sets: UnionFind,
+    next: Vec<usize>, // next member of the set, in a circular list
+    representatives: Vec<usize>, // one per set
+    positions: Vec<usize>, // in representatives, only meaningful for representatives
+}
+
+impl Partition {
+    /// Partition of ``n`` individuals in singletons.
+    pub fn singletons(n: usize) -> Self {
+        Partition {
+            sets: UnionFind::new(n),
+            next: (0..n).collect(),
+            representatives: (0..n).collect(),
+            positions: (0..n).collect(),
+        }
+    }
+
+    /// Partition of no individuals, with space for ``capacity`` of them.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Partition {
+            sets: UnionFind::with_capacity(capacity),
+            next: Vec::with_capacity(capacity),
+            representatives: Vec::with_capacity(capacity),
+            positions: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a new individual in a set of its own, returning the individual.
+    pub fn push(&mut self) -> usize {
+        let individual = self.sets.push();
+        self.next.push(individual);
+        self.positions.push(self.representatives.len());
+        self.representatives.push(individual);
+        individual
+    }
+
+    /// Number of individuals.
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Whether there are no individuals.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of sets.
+    pub fn amount_of_sets(&self) -> usize {
+        self.representatives.len()
+    }
+
+    /// One individual of each set, the k-th one representing the set of index k.
+    pub fn representatives(&self) -> &[usize] {
+        &self.representatives
+    }
+
+    /// Representative of the set of an individual.
+    pub fn find(&self, individual: usize) -> usize {
+        self.sets.find(individual)
+    }
+
+    /// Whether two individuals are in the same set.
+    pub fn same_set(&self, first: usize, second: usize) -> bool {
+        self.find(first) == self.find(second)
+    }
+
+    /// Number of individuals in the set of an individual.
+    pub fn len_of_set(&self, individual: usize) -> usize {
+        self.sets.size(individual)
+    }
+
+    /// Joins the sets of two individuals.
+    pub fn union(&mut self, first: usize, second: usize) {
+        let (first_root, second_root) = (self.find(first), self.find(second));
+        if first_root == second_root {
+            return;
+        }
+        let root = self.sets.union(first_root, second_root);
+        let removed = match root == first_root {
+            true => second_root,
+            false => first_root,
+        };
+
+        // Splice circular lists and forget the removed representative
+
+        self.next.swap(first_root, second_root);
+        let position = self.positions[removed];
+        self.representatives.swap_remove(position);
+        if let Some(&moved) = self.representatives.get(position) {
+            self.positions[moved] = position;
+        }
+    }
+
+    /// Individuals in the set of an individual, starting with it.
+    pub fn set(&self, individual: usize) -> Set<'_> {
+        Set { partition: self, start: individual, current: Some(individual) }
+    }
+
+    /// Individuals of each set, by index of the set.
+    pub fn all_sets(&self) -> impl Iterator<Item = Set<'_>> + '_ {
+        self.representatives.iter().map(move |&representative| self.set(representative))
+    }
+}
+
+/// Same sets, regardless of how they are stored.
+impl PartialEq for Partition {
+    fn eq(&self, other: &Partition) -> bool {
+        // Label each set by its smallest individual
+        let labels = |partition: &Partition| {
+            let mut labels = vec![usize::MAX; partition.len()];
+            (0..partition.len())
+                .map(|individual| {
+                    let root = partition.find(individual);
+                    if labels[root] == usize::MAX {
+                        labels[root] = individual;
+                    }
+                    labels[root]
+                })
+                .collect::<Vec<usize>>()
+        };
+
+        self.len() == other.len() && self.amount_of_sets() == other.amount_of_sets() && labels(self) == labels(other)
+    }
+}
+
+impl Eq for Partition {}
+
+impl std::fmt::Debug for Partition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut sets: Vec<Vec<usize>> = self
+            .all_sets()
+            .map(|set| {
+                let mut set: Vec<usize> = set.collect();
+                set.sort_unstable();
+                set
+            })
+            .collect();
+        sets.sort_unstable();
+        f.debug_set().entries(sets).finish()
+    }
+}
+
+/// Iterator over the individuals of a set of a ``Partition``.
+///
+/// This struct is created by the ``set`` method on Partition.
+#[derive(Debug, Clone)]
+pub struct Set<'a> {
+    partition: &'a Partition,
+    start: usize,
+    current: Option<usize>,
+}
+
+impl<'a> Iterator for Set<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let current = self.current?;
+        let next = self.partition.next[current];
+        self.current = match next == self.start {
+            true => None,
+            false => Some(next),
+        };
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets() {
+        let mut partition = Partition::with_capacity(5);
+        for _ in 0..5 {
+            partition.push();
+        }
+        partition.union(0, 1);
+        partition.union(3, 4);
+        partition.union(4, 1);
+        partition.union(0, 3);
+        assert_eq!(partition.amount_of_sets(), 2);
+        assert_eq!(partition.len_of_set(1), 4);
+        for (index, &representative) in partition.representatives().iter().enumerate() {
+            assert_eq!(partition.positions[representative], index);
+            assert_eq!(partition.find(representative), representative);
+        }
+        let mut set: Vec<usize> = partition.set(4).collect();
+        set.sort_unstable();
+        assert_eq!(set, vec![0, 1, 3, 4]);
+        assert_eq!(partition.all_sets().map(|set| set.count()).sum::<usize>(), 5);
+
+        // Equality of sets, whatever the order of unions
+        let mut other = Partition::singletons(5);
+        other.union(3, 0);
+        other.union(1, 4);
+        assert_ne!(partition, other);
+        other.union(1, 3);
+        assert_eq!(partition, other);
+        assert_eq!(format!("{:?}", other), "{[0, 1, 3, 4], [2]}");
+    }
+}
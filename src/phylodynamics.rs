@@ -0,0 +1,181 @@
+//! Birth–death trees, for comparison with the coalescent.
+//!
+//! In phylodynamics, genealogies of samples often come from a birth–death process:
+//! each lineage splits at rate λ and dies at rate μ, and each lineage alive at
+//! present is sampled with probability ρ. Conditioning on ``n`` sampled tips,
+//! with a uniform prior on the time of origin, the ``n - 1`` times of the nodes
+//! of the reconstructed tree are independent with cumulative distribution
+//! F(t) = λ (1 - e^{-(λ - μ) t}) / (λ - μ e^{-(λ - μ) t}) (Gernhard, 2008),
+//! where incomplete sampling amounts to rates ρ λ and μ - λ (1 - ρ) (Stadler, 2009).
+//! Going backwards in time, each node joins a pair of lineages chosen uniformly,
+//! as in the coalescent.
+//!
+//! Times are in units of the rates, not in coalescent units.
+//!
+
+// Types
+use crate::Genealogy;
+
+// Traits
+use rand::Rng;
+
+/// Constant-rate birth–death process with incomplete sampling at present.
+///
+/// # Examples
+///
+/// Compare the shape of birth–death and coalescent genealogies.
+/// ```
+/// use coalescence::BirthDeath;
+///
+/// let birth_death = BirthDeath::new(2.0, 1.0, 0.5);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = birth_death.sample_genealogy(10, &mut rng);
+/// let coalescent = coalescence::Coalescent::new(10, rand::thread_rng());
+/// let other = coalescent.sample_genealogy(&mut rng);
+///
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// assert_eq!(genealogy.group_size(), other.group_size());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BirthDeath {
+    birth_rate: f64,
+    death_rate: f64,
+    sampling_fraction: f64,
+}
+
+impl BirthDeath {
+    /// Creates a new birth–death process with birth rate λ, death rate μ, and
+    /// probability ρ of sampling each lineage alive at present.
+    ///
+    /// # Panics
+    ///
+    /// If the birth rate is not positive, if the death rate is negative or larger
+    /// than the birth rate, i.e. the process is subcritical, or if the sampling
+    /// fraction is not positive and at most one.
+    pub fn new(birth_rate: f64, death_rate: f64, sampling_fraction: f64) -> Self {
+        assert!(birth_rate > 0.0 && birth_rate.is_finite(), "The birth rate must be positive and finite.");
+        assert!(
+            death_rate >= 0.0 && death_rate <= birth_rate,
+            "The death rate must be non-negative and at most the birth rate."
+        );
+        assert!(
+            sampling_fraction > 0.0 && sampling_fraction <= 1.0,
+            "The sampling fraction must be positive and at most one."
+        );
+
+        BirthDeath { birth_rate, death_rate, sampling_fraction }
+    }
+
+    /// Pure birth process, or Yule process, with every lineage sampled.
+    pub fn yule(birth_rate: f64) -> Self {
+        BirthDeath::new(birth_rate, 0.0, 1.0)
+    }
+
+    /// Birth rate λ.
+    pub fn birth_rate(&self) -> f64 {
+        self.birth_rate
+    }
+
+    /// Death rate μ.
+    pub fn death_rate(&self) -> f64 {
+        self.death_rate
+    }
+
+    /// Sampling fraction ρ.
+    pub fn sampling_fraction(&self) -> f64 {
+        self.sampling_fraction
+    }
+
+    /// Net diversification rate λ - μ.
+    pub fn net_diversification_rate(&self) -> f64 {
+        self.birth_rate - self.death_rate
+    }
+
+    /// Sample the reconstructed tree of ``group_size`` sampled tips.
+    pub fn sample_genealogy<R>(&self, group_size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        // Times of nodes
+
+        let mut times: Vec<f64> = (1..group_size).map(|_| self.node_time(rng.gen())).collect();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut last_time = 0.0;
+        let time_steps = times
+            .iter()
+            .map(|&time| {
+                let time_step = time - last_time;
+                last_time = time;
+                time_step
+            })
+            .collect();
+
+        // Pairs of lineages, represented by an individual
+
+        let mut lineages: Vec<usize> = (0..group_size).collect();
+        let steps = (1..group_size)
+            .map(|_| {
+                let pair = rand::seq::index::sample(rng, lineages.len(), 2);
+                let (first, second) = (pair.index(0), pair.index(1));
+                let indexes = [lineages[first], lineages[second]];
+                lineages.swap_remove(second);
+                indexes
+            })
+            .collect();
+
+        Genealogy::from_steps(group_size, steps, time_steps)
+    }
+
+    /// Inverse of the cumulative distribution of the time of a node, at ``u`` in [0, 1).
+    fn node_time(&self, u: f64) -> f64 {
+        let birth_rate = self.sampling_fraction * self.birth_rate;
+        let death_rate = self.death_rate - self.birth_rate * (1.0 - self.sampling_fraction);
+        let net_rate = birth_rate - death_rate;
+
+        match net_rate > 0.0 {
+            true => ((birth_rate - u * death_rate) / (birth_rate * (1.0 - u))).ln() / net_rate,
+            false => u / (birth_rate * (1.0 - u)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yule_node_times() {
+        // Under the Yule process, the node of two tips is exponential with rate λ
+        let yule = BirthDeath::yule(2.0);
+        let samples = 4000;
+
+        let mut rng = rand::thread_rng();
+        let mean_depth = (0..samples)
+            .map(|_| yule.sample_genealogy(2, &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+
+        assert!((mean_depth - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn node_time_distribution() {
+        let birth_death = BirthDeath::new(3.0, 1.0, 0.25);
+
+        // Median of F(t) = λ' (1 - x) / (λ' - μ' x), with x = e^{-(λ - μ) t}
+        let (birth_rate, death_rate) = (0.75, -1.25);
+        let median = birth_death.node_time(0.5);
+        let x = (-2.0 * median).exp();
+        assert!((birth_rate * (1.0 - x) / (birth_rate - death_rate * x) - 0.5).abs() < 1e-12);
+
+        // Critical process
+        let critical = BirthDeath::new(1.0, 1.0, 1.0);
+        assert!((critical.node_time(0.5) - 1.0).abs() < 1e-12);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            assert_eq!(birth_death.sample_genealogy(6, &mut rng).validate(), Ok(()));
+        }
+    }
+}
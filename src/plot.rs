@@ -0,0 +1,367 @@
+//! Plots of genealogies, with the ``plotters`` feature.
+//!
+//! Trees are drawn either rectangular, with time before present on the horizontal
+//! axis and individuals on the vertical one, or circular, with the root at the
+//! center and individuals on the unit circle. Individuals are ordered so that
+//! branches never cross. Clades can be colored, and mutations drawn as tick marks
+//! on their branches, at their age.
+//!
+//! ``Genealogy::plot`` writes SVG and PNG files. Any other backend of ``plotters``
+//! can be drawn on with ``Genealogy::plot_on``.
+//!
+
+// Types
+use crate::{BranchId, Genealogy, Mutation, MutatedGenealogy};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::collections::HashMap;
+
+/// Layout of a tree plot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Time on the horizontal axis, the root on the left.
+    Rectangular,
+    /// Time along the radius, the root at the center.
+    Circular,
+}
+
+/// Error when plotting a genealogy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlotError {
+    /// The extension of the file is neither ``svg`` nor ``png``, see ``Genealogy::plot_on`` for other formats.
+    UnsupportedFormat(String),
+    /// The backend failed to draw.
+    Drawing(String),
+}
+
+impl std::fmt::Display for PlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PlotError::UnsupportedFormat(extension) => {
+                write!(f, "unsupported format \"{}\", only svg and png files are written directly", extension)
+            },
+            PlotError::Drawing(message) => write!(f, "failed to draw: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PlotError {}
+
+/// Options of a tree plot.
+///
+/// By default, the plot is rectangular, of 800 by 600 pixels, with black branches,
+/// labels of individuals, and no mutations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotOptions {
+    layout: Layout,
+    size: (u32, u32),
+    clade_colors: Vec<(usize, RGBColor)>,
+    mutations: Vec<Mutation>,
+    labels: bool,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        PlotOptions { layout: Layout::Rectangular, size: (800, 600), clade_colors: Vec::new(), mutations: Vec::new(), labels: true }
+    }
+}
+
+impl PlotOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        PlotOptions::default()
+    }
+
+    /// Layout of the tree.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Width and height of the plot, in pixels.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    /// Color of the branches below the node of the given index, see ``node`` on Genealogy.
+    /// Colors of nested clades take precedence over the colors of the clades containing them.
+    pub fn color_clade(mut self, node: usize, color: RGBColor) -> Self {
+        self.clade_colors.push((node, color));
+        self
+    }
+
+    /// Mutations to draw as tick marks, e.g. from ``sample_mutations`` on Genealogy.
+    pub fn mutations(mut self, mutated: &MutatedGenealogy) -> Self {
+        self.mutations = mutated.mutations().to_vec();
+        self
+    }
+
+    /// Whether to write the index of each individual next to it.
+    pub fn labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+}
+
+impl Genealogy {
+    /// Plot the genealogy into an SVG or PNG file, by the extension of the path.
+    ///
+    /// # Errors
+    ///
+    /// If the extension of the path is neither ``svg`` nor ``png``, or if the file can not be written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::plot::{Layout, PlotOptions};
+    /// use coalescence::MutationModel;
+    /// use plotters::style::RED;
+    ///
+    /// let genealogy = coalescence::fixtures::balanced();
+    /// let mutated = genealogy.sample_mutations(MutationModel::Poisson { theta: 2.0 }, &mut rand::thread_rng());
+    /// let clade = genealogy.mrca_node(&[2, 3]);
+    /// let options = PlotOptions::new().layout(Layout::Circular).color_clade(clade.index, RED).mutations(&mutated);
+    ///
+    /// let path = std::env::temp_dir().join("coalescence-balanced.svg");
+    /// genealogy.plot(&path, &options).unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().starts_with("<svg"));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn plot<P>(&self, path: P, options: &PlotOptions) -> Result<(), PlotError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "svg" => self.plot_file(SVGBackend::new(path, options.size).into_drawing_area(), options),
+            "png" => self.plot_file(BitMapBackend::new(path, options.size).into_drawing_area(), options),
+            _ => Err(PlotError::UnsupportedFormat(extension.to_string())),
+        }
+    }
+
+    fn plot_file<DB>(&self, area: DrawingArea<DB, Shift>, options: &PlotOptions) -> Result<(), PlotError>
+    where
+        DB: DrawingBackend,
+    {
+        area.fill(&WHITE).map_err(drawing)?;
+        self.plot_on(&area, options)?;
+        area.present().map_err(drawing)
+    }
+
+    /// Plot the genealogy on a drawing area of any backend of ``plotters``.
+    /// The size of the options is ignored, since the area has its own.
+    ///
+    /// # Errors
+    ///
+    /// If the backend fails to draw.
+    pub fn plot_on<DB>(&self, area: &DrawingArea<DB, Shift>, options: &PlotOptions) -> Result<(), PlotError>
+    where
+        DB: DrawingBackend,
+    {
+        let tree = TreeLayout::new(self, options);
+
+        match options.layout {
+            Layout::Rectangular => {
+                let depth = tree.depth;
+                let margin = depth.max(f64::MIN_POSITIVE) * 0.05;
+                let mut chart = ChartBuilder::on(area)
+                    .margin(20)
+                    .x_label_area_size(40)
+                    .build_cartesian_2d(-margin..depth + margin * if options.labels { 3.0 } else { 1.0 }, -0.5..tree.group_size as f64 - 0.5)
+                    .map_err(drawing)?;
+                let formatter = |x: &f64| format!("{:.2}", depth - x);
+                chart
+                    .configure_mesh()
+                    .disable_mesh()
+                    .disable_y_axis()
+                    .x_desc("Time before present")
+                    .x_label_formatter(&formatter)
+                    .draw()
+                    .map_err(drawing)?;
+
+                let x = |node: usize| depth - tree.ages[node];
+                for node in 0..tree.parents.len() {
+                    let style = tree.colors[node].stroke_width(2);
+                    if let Some(parent) = tree.parent(node) {
+                        let points = vec![(x(parent), tree.positions[node]), (x(node), tree.positions[node])];
+                        chart.draw_series(std::iter::once(PathElement::new(points, style))).map_err(drawing)?;
+                    }
+                    if let [first, second] = tree.children[node][..] {
+                        let points = vec![(x(node), tree.positions[first]), (x(node), tree.positions[second])];
+                        chart.draw_series(std::iter::once(PathElement::new(points, style))).map_err(drawing)?;
+                    }
+                }
+                for (y, age) in tree.ticks(&options.mutations) {
+                    let points = vec![(depth - age, y - 0.15), (depth - age, y + 0.15)];
+                    chart.draw_series(std::iter::once(PathElement::new(points, RED.stroke_width(2)))).map_err(drawing)?;
+                }
+                if options.labels {
+                    let labels = (0..tree.group_size)
+                        .map(|leaf| Text::new(format!("{}", leaf), (x(leaf) + margin * 0.5, tree.positions[leaf]), ("sans-serif", 14)));
+                    chart.draw_series(labels).map_err(drawing)?;
+                }
+            },
+            Layout::Circular => {
+                let mut chart = ChartBuilder::on(area)
+                    .margin(20)
+                    .build_cartesian_2d(-1.2..1.2, -1.2..1.2)
+                    .map_err(drawing)?;
+
+                let radius = |node: usize| radius_at(tree.depth, tree.ages[node]);
+                let angle = |position: f64| 2.0 * std::f64::consts::PI * (position + 0.5) / tree.group_size as f64;
+                let point = |radius: f64, position: f64| (radius * angle(position).cos(), radius * angle(position).sin());
+                for node in 0..tree.parents.len() {
+                    let style = tree.colors[node].stroke_width(2);
+                    if let Some(parent) = tree.parent(node) {
+                        let position = tree.positions[node];
+                        let points = vec![point(radius(parent), position), point(radius(node), position)];
+                        chart.draw_series(std::iter::once(PathElement::new(points, style))).map_err(drawing)?;
+                    }
+                    if let [first, second] = tree.children[node][..] {
+                        let (start, end) = (tree.positions[first], tree.positions[second]);
+                        let points: Vec<(f64, f64)> = (0..=32)
+                            .map(|step| point(radius(node), start + (end - start) * step as f64 / 32.0))
+                            .collect();
+                        chart.draw_series(std::iter::once(PathElement::new(points, style))).map_err(drawing)?;
+                    }
+                }
+                for (position, age) in tree.ticks(&options.mutations) {
+                    let tick = radius_at(tree.depth, age);
+                    let points = vec![point(tick, position - 0.15), point(tick, position + 0.15)];
+                    chart.draw_series(std::iter::once(PathElement::new(points, RED.stroke_width(2)))).map_err(drawing)?;
+                }
+                if options.labels {
+                    let labels = (0..tree.group_size)
+                        .map(|leaf| Text::new(format!("{}", leaf), point(radius(leaf) + 0.08, tree.positions[leaf]), ("sans-serif", 14)));
+                    chart.draw_series(labels).map_err(drawing)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+fn drawing<E>(error: E) -> PlotError
+where
+    E: std::fmt::Display,
+{
+    PlotError::Drawing(error.to_string())
+}
+
+/// Distance to the center of a node of the given age, in the circular layout.
+fn radius_at(depth: f64, age: f64) -> f64 {
+    match depth > 0.0 {
+        true => 1.0 - age / depth,
+        false => 1.0,
+    }
+}
+
+/// Nodes of a genealogy with their position across the plot and their color.
+struct TreeLayout {
+    group_size: usize,
+    depth: f64,
+    parents: Vec<usize>,
+    ages: Vec<f64>,
+    children: Vec<Vec<usize>>,
+    positions: Vec<f64>,
+    colors: Vec<RGBColor>,
+    branches: HashMap<BranchId, usize>,
+}
+
+impl TreeLayout {
+    fn new(genealogy: &Genealogy, options: &PlotOptions) -> Self {
+        let group_size = genealogy.group_size();
+        let (parents, ages) = genealogy.nodes();
+        let root = parents.len() - 1;
+        let mut children = vec![Vec::new(); parents.len()];
+        for node in 0..parents.len() {
+            if parents[node] != node {
+                children[parents[node]].push(node);
+            }
+        }
+
+        // Individuals in depth-first order, and common ancestors between their children
+        let mut positions = vec![0.0; parents.len()];
+        let mut next = 0.0;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            match children[node].is_empty() {
+                true => {
+                    positions[node] = next;
+                    next += 1.0;
+                },
+                false => stack.extend(children[node].iter().rev()),
+            }
+        }
+        for node in group_size..parents.len() {
+            positions[node] = children[node].iter().map(|&child| positions[child]).sum::<f64>() / children[node].len() as f64;
+        }
+
+        // Colors from the root down, nested clades last
+        let mut colors = vec![BLACK; parents.len()];
+        for node in (0..parents.len()).rev() {
+            if parents[node] != node {
+                colors[node] = colors[parents[node]];
+            }
+            if let Some(&(_, color)) = options.clade_colors.iter().rev().find(|(clade, _)| *clade == node) {
+                colors[node] = color;
+            }
+        }
+
+        let branches = genealogy.branches().into_iter().map(|branch| (branch.id, branch.child)).collect();
+
+        TreeLayout { group_size, depth: ages[root], parents: parents.to_vec(), ages: ages.to_vec(), children, positions, colors, branches }
+    }
+
+    fn parent(&self, node: usize) -> Option<usize> {
+        match self.parents[node] == node {
+            true => None,
+            false => Some(self.parents[node]),
+        }
+    }
+
+    /// Position and age of each mutation on a branch of the genealogy.
+    fn ticks<'a>(&'a self, mutations: &'a [Mutation]) -> impl Iterator<Item = (f64, f64)> + 'a {
+        mutations.iter().filter_map(move |mutation| {
+            let node = *self.branches.get(&mutation.branch)?;
+            Some((self.positions[node], mutation.age))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg_files() {
+        let genealogy = crate::fixtures::caterpillar(6);
+        let mutated = genealogy.sample_mutations(crate::MutationModel::FixedSegregatingSites { sites: 5 }, &mut rand::thread_rng());
+        let path = std::env::temp_dir().join("coalescence-plot-test.svg");
+        for &layout in &[Layout::Rectangular, Layout::Circular] {
+            let options = PlotOptions::new().layout(layout).color_clade(8, BLUE).mutations(&mutated);
+            genealogy.plot(&path, &options).unwrap();
+            let svg = std::fs::read_to_string(&path).unwrap();
+            assert!(svg.contains("<polyline"));
+            assert!(svg.contains("\n5\n</text>"));
+        }
+        std::fs::remove_file(path).unwrap();
+
+        let error = genealogy.plot("tree.gif", &PlotOptions::new()).unwrap_err();
+        assert_eq!(error, PlotError::UnsupportedFormat("gif".to_string()));
+    }
+
+    #[test]
+    fn png_files() {
+        let genealogy = crate::fixtures::balanced();
+        let path = std::env::temp_dir().join("coalescence-plot-test.png");
+        let options = PlotOptions::new().size(120, 80).color_clade(5, RED);
+        genealogy.plot(&path, &options).unwrap();
+        let png = std::fs::read(&path).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! The coalescent as a prior on genealogies, for Bayesian phylogenetics.
+//!
+//! Given a ranked topology, i.e. the order in which lineages are joined, the
+//! coalescent under a demography only determines the ages of common ancestors:
+//! while ``k`` lineages remain, the next event happens after an intensity of
+//! coalescence exponential of rate ``C(k, 2)``, intensity being the integral of
+//! the inverse of the size of the population. ``CoalescentPrior`` samples ages
+//! conditional on a topology and evaluates their density, which are the moves and
+//! the prior of samplers over trees, with skyline demographies built by
+//! ``Demography::skyline``.
+//!
+
+// Types
+use crate::{Demography, Genealogy};
+use rand_distr::Exp1;
+
+// Traits
+use rand::Rng;
+use rand_distr::Distribution;
+
+/// Coalescent prior on the ages of common ancestors of isochronous samples.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{CoalescentPrior, Demography};
+///
+/// let prior = CoalescentPrior::new(Demography::skyline(&[1.0, 0.2], &[0.5]));
+/// let topology = coalescence::fixtures::balanced();
+///
+/// let genealogy = prior.sample_ages(&topology, &mut rand::thread_rng());
+///
+/// assert_eq!(genealogy.shape(), topology.shape());
+/// assert!(prior.log_density(&genealogy).is_finite());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoalescentPrior {
+    demography: Demography,
+}
+
+impl CoalescentPrior {
+    pub fn new(demography: Demography) -> Self {
+        CoalescentPrior { demography }
+    }
+
+    pub fn demography(&self) -> &Demography {
+        &self.demography
+    }
+
+    /// Genealogy with the ranked topology of ``topology`` and new ages of its common
+    /// ancestors, sampled from the coalescent conditional on the topology.
+    ///
+    /// # Panics
+    ///
+    /// If individuals are not all sampled at present.
+    pub fn sample_ages<S>(&self, topology: &Genealogy, rng: &mut S) -> Genealogy
+    where
+        S: Rng + ?Sized,
+    {
+        assert_isochronous(topology);
+
+        let group_size = topology.group_size();
+        let mut time = 0.0;
+        let (steps, time_steps) = topology
+            .events()
+            .enumerate()
+            .map(|(event, step)| {
+                let lineages = group_size - event;
+                let intensity: f64 = Exp1.sample(rng);
+                let time_step = self.demography.waiting_time(time, intensity / pairs(lineages));
+                time += time_step;
+                (step.indexes, time_step)
+            })
+            .unzip();
+
+        Genealogy::from_steps(group_size, steps, time_steps)
+    }
+
+    /// Logarithm of the density of the ages of common ancestors of ``genealogy``,
+    /// conditional on its ranked topology.
+    ///
+    /// # Panics
+    ///
+    /// If individuals are not all sampled at present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{CoalescentPrior, Demography};
+    ///
+    /// // Two individuals in a population of size one: the depth is exponential
+    /// let prior = CoalescentPrior::new(Demography::constant());
+    /// let genealogy = coalescence::Genealogy::from_events(2, &[(0.7, [0, 1])]).unwrap();
+    ///
+    /// assert!((prior.log_density(&genealogy) + 0.7).abs() < 1e-12);
+    /// ```
+    pub fn log_density(&self, genealogy: &Genealogy) -> f64 {
+        assert_isochronous(genealogy);
+
+        let group_size = genealogy.group_size();
+        let mut time = 0.0;
+        genealogy
+            .events()
+            .enumerate()
+            .map(|(event, step)| {
+                let rate = pairs(group_size - event);
+                let end = time + step.time_step;
+                let log_density = (rate / self.demography.size_at(end)).ln() - rate * self.demography.intensity(time, end);
+                time = end;
+                log_density
+            })
+            .sum()
+    }
+}
+
+/// Number of pairs among ``lineages`` lineages.
+fn pairs(lineages: usize) -> f64 {
+    (lineages * (lineages - 1) / 2) as f64
+}
+
+fn assert_isochronous(genealogy: &Genealogy) {
+    assert!(
+        genealogy.sampling_times().iter().all(|&time| time == 0.0),
+        "Individuals must all be sampled at present."
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coalescent;
+
+    #[test]
+    fn skyline_depths() {
+        let demography = Demography::skyline(&[0.5, 2.0, 1.0], &[0.2, 1.0]);
+        let prior = CoalescentPrior::new(demography.clone());
+        let (group_size, samples) = (6, 20_000);
+        let mut coalescent = Coalescent::sampler(group_size);
+        coalescent.set_demography(demography);
+        let topology = coalescent.sample_genealogy(&mut rand::thread_rng());
+
+        let mut rng = rand::thread_rng();
+        let (mut prior_depth, mut coalescent_depth) = (0.0, 0.0);
+        for _ in 0..samples {
+            let genealogy = prior.sample_ages(&topology, &mut rng);
+            assert_eq!(genealogy.shape(), topology.shape());
+            prior_depth += genealogy.depth() / samples as f64;
+            coalescent_depth += coalescent.sample_genealogy(&mut rng).depth() / samples as f64;
+        }
+        assert!((prior_depth - coalescent_depth).abs() < 0.05 * coalescent_depth, "{} {}", prior_depth, coalescent_depth);
+    }
+
+    #[test]
+    fn densities() {
+        // Three individuals in a population of size one: 3 e^{-3 t₁} e^{-(t₂ - t₁)}
+        let prior = CoalescentPrior::new(Demography::constant());
+        let genealogy = Genealogy::from_events(3, &[(0.2, [0, 1]), (0.9, [0, 2])]).unwrap();
+        let expected = 3f64.ln() - 3.0 * 0.2 - 0.7;
+        assert!((prior.log_density(&genealogy) - expected).abs() < 1e-12);
+
+        // Halving the size doubles rates
+        let prior = CoalescentPrior::new(Demography::skyline(&[0.5], &[]));
+        let expected = 6f64.ln() - 6.0 * 0.2 + 2f64.ln() - 2.0 * 0.7;
+        assert!((prior.log_density(&genealogy) - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,198 @@
+//! Continuous traits evolving along genealogies.
+//!
+//! A trait starts at a given value at the first common ancestor and evolves down
+//! each branch independently, either by Brownian motion of rate ``σ²``, or by an
+//! Ornstein–Uhlenbeck process pulled towards an optimum ``θ`` with strength ``α``.
+//! Over a branch of length ``t``, the value of the child is normal with
+//! ``E = θ + (x - θ) e^{-α t}`` and ``Var = σ² (1 - e^{-2 α t}) / (2 α)``, which tends to
+//! ``σ² t`` as ``α`` goes to zero.
+//!
+//! Individuals then share part of their trait through their common ancestry: under
+//! Brownian motion, the covariance of two individuals is ``σ²`` times the time from
+//! the first common ancestor of the group to their own, which is the usual kinship
+//! matrix of quantitative genetics under a given genealogy.
+//!
+
+// Types
+use crate::Genealogy;
+use rand_distr::StandardNormal;
+
+// Traits
+use rand::Rng;
+use rand_distr::Distribution;
+
+/// Model of evolution of a continuous trait along branches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraitModel {
+    /// Brownian motion, whose variance grows at rate ``rate``.
+    Brownian { rate: f64 },
+    /// Ornstein–Uhlenbeck process of variance rate ``rate``, pulled towards ``optimum``
+    /// with strength ``strength``.
+    OrnsteinUhlenbeck { rate: f64, strength: f64, optimum: f64 },
+}
+
+impl TraitModel {
+    /// Mean and variance of the value after ``time``, starting at ``value``.
+    fn transition(&self, value: f64, time: f64) -> (f64, f64) {
+        match *self {
+            TraitModel::Brownian { rate } => (value, rate * time),
+            TraitModel::OrnsteinUhlenbeck { rate, strength, optimum } => {
+                let mean = optimum + (value - optimum) * (-strength * time).exp();
+                (mean, rate * -(-2.0 * strength * time).exp_m1() / (2.0 * strength))
+            },
+        }
+    }
+
+    fn validate(&self) {
+        let (rate, strength) = match *self {
+            TraitModel::Brownian { rate } => (rate, 1.0),
+            TraitModel::OrnsteinUhlenbeck { rate, strength, optimum } => {
+                assert!(optimum.is_finite(), "The optimum must be finite.");
+                (rate, strength)
+            },
+        };
+        assert!(rate >= 0.0 && rate.is_finite(), "The rate must be non-negative and finite.");
+        assert!(strength > 0.0 && strength.is_finite(), "The strength must be positive and finite.");
+    }
+}
+
+impl Genealogy {
+    /// Values of a trait at each individual, the first common ancestor having value
+    /// ``root_value``, see the module documentation.
+    ///
+    /// # Panics
+    ///
+    /// If the rate is negative, or the strength of an Ornstein–Uhlenbeck process not
+    /// positive, or if some parameter is not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::TraitModel;
+    ///
+    /// let genealogy = coalescence::fixtures::balanced();
+    /// let model = TraitModel::OrnsteinUhlenbeck { rate: 1.0, strength: 0.5, optimum: 2.0 };
+    ///
+    /// let traits = genealogy.sample_traits(&model, 0.0, &mut rand::thread_rng());
+    ///
+    /// assert_eq!(traits.len(), 4);
+    /// ```
+    pub fn sample_traits<S>(&self, model: &TraitModel, root_value: f64, rng: &mut S) -> Vec<f64>
+    where
+        S: Rng + ?Sized,
+    {
+        model.validate();
+        assert!(root_value.is_finite(), "The value at the root must be finite.");
+
+        // Parents come after their children
+        let (parents, times) = self.nodes();
+        let mut values = vec![root_value; parents.len()];
+        for node in (0..parents.len()).rev() {
+            let parent = parents[node];
+            if parent != node {
+                let (mean, variance) = model.transition(values[parent], times[parent] - times[node]);
+                let normal: f64 = StandardNormal.sample(rng);
+                values[node] = mean + variance.sqrt() * normal;
+            }
+        }
+
+        values.truncate(self.group_size());
+        values
+    }
+
+    /// Covariance matrix of the traits of individuals under the given model, given the
+    /// value at the first common ancestor.
+    ///
+    /// # Panics
+    ///
+    /// As ``sample_traits``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::TraitModel;
+    ///
+    /// // Individuals 0 and 1 join at time 1, three units of time below the root
+    /// let genealogy = coalescence::fixtures::balanced();
+    /// let covariance = genealogy.trait_covariance(&TraitModel::Brownian { rate: 0.5 });
+    ///
+    /// assert_eq!(covariance[0][0], 2.0);
+    /// assert_eq!(covariance[0][1], 1.5);
+    /// assert_eq!(covariance[0][2], 0.0);
+    /// ```
+    pub fn trait_covariance(&self, model: &TraitModel) -> Vec<Vec<f64>> {
+        model.validate();
+
+        let group_size = self.group_size();
+        let depth = self.depth();
+        let sampling_times = self.sampling_times();
+        let mut covariance = vec![vec![0.0; group_size]; group_size];
+        for first in 0..group_size {
+            for second in first..group_size {
+                let ancestor = match first == second {
+                    true => sampling_times[first],
+                    false => (self.pair_divergence(first, second) + sampling_times[first] + sampling_times[second]) / 2.0,
+                };
+                let shared = depth - ancestor;
+                let value = match *model {
+                    TraitModel::Brownian { rate } => rate * shared,
+                    TraitModel::OrnsteinUhlenbeck { strength, .. } => {
+                        // Variance at the common ancestor, decayed along both paths below it
+                        let (_, variance) = model.transition(0.0, shared);
+                        let below = 2.0 * ancestor - sampling_times[first] - sampling_times[second];
+                        variance * (-strength * below).exp()
+                    },
+                };
+                covariance[first][second] = value;
+                covariance[second][first] = value;
+            }
+        }
+
+        covariance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covariances() {
+        let genealogy = crate::Coalescent::sampler(5).sample_genealogy(&mut rand::thread_rng());
+        let models = [
+            TraitModel::Brownian { rate: 2.0 },
+            TraitModel::OrnsteinUhlenbeck { rate: 1.5, strength: 0.8, optimum: -1.0 },
+        ];
+        let samples = 40_000;
+        let mut rng = rand::thread_rng();
+        for model in &models {
+            let expected = genealogy.trait_covariance(model);
+            let mut sums = [0.0; 5];
+            let mut products = [[0.0; 5]; 5];
+            for _ in 0..samples {
+                let traits = genealogy.sample_traits(model, 1.0, &mut rng);
+                for i in 0..5 {
+                    sums[i] += traits[i] / samples as f64;
+                    for j in 0..5 {
+                        products[i][j] += traits[i] * traits[j] / samples as f64;
+                    }
+                }
+            }
+            for i in 0..5 {
+                for j in 0..5 {
+                    let covariance = products[i][j] - sums[i] * sums[j];
+                    let scale = (expected[i][i] * expected[j][j]).sqrt();
+                    assert!((covariance - expected[i][j]).abs() < 0.05 * scale, "{:?}", model);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pulled_towards_optimum() {
+        let genealogy = Genealogy::from_events(2, &[(50.0, [0, 1])]).unwrap();
+        let model = TraitModel::OrnsteinUhlenbeck { rate: 0.0, strength: 1.0, optimum: 3.0 };
+        let traits = genealogy.sample_traits(&model, -10.0, &mut rand::thread_rng());
+        assert!(traits.iter().all(|value| (value - 3.0).abs() < 1e-12));
+    }
+}
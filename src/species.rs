@@ -0,0 +1,176 @@
+//! Gene trees within a species tree, under the multispecies coalescent.
+//!
+//! Each species of a species tree is a deme, and at each divergence, backwards in time,
+//! the lineages of a species all move into its sister species, which then stands for their
+//! common ancestor. Gene trees of unlinked loci then differ from the species tree through
+//! incomplete lineage sorting: lineages of a clade of species may not coalesce before its
+//! divergence, so that a focal group is not monophyletic in the gene tree.
+//!
+//! The concordance factor of a clade of the species tree is the fraction of loci whose
+//! gene tree has the individuals of the species of the clade as a monophyletic group.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+use crate::{EventStream, Genealogy, MultiLocusGenealogies, StructuredCoalescent};
+
+impl StructuredCoalescent {
+    /// Multispecies coalescent along a species tree, whose individuals are the species, with
+    /// all species of relative size ``size`` and no migration, see the module documentation.
+    ///
+    /// # Panics
+    ///
+    /// If the size is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Genealogy, StructuredCoalescent};
+    ///
+    /// let species_tree = Genealogy::from_events(3, &[(1.0, [0, 1]), (3.0, [1, 2])]).unwrap();
+    /// let multispecies = StructuredCoalescent::multispecies(&species_tree, 1.0);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let gene_tree = multispecies.sample_genealogy(&[2, 2, 1], &mut rng);
+    ///
+    /// assert_eq!(multispecies.demes(), 3);
+    /// assert!(gene_tree.divergence(0, 4).unwrap() > 6.0);
+    /// ```
+    pub fn multispecies(species_tree: &Genealogy, size: f64) -> Self {
+        assert!(size > 0.0 && size.is_finite(), "The size must be positive and finite.");
+
+        let species = species_tree.group_size();
+        let mut structured = StructuredCoalescent::new(vec![size; species], vec![vec![0.0; species]; species]);
+
+        // Deme of each lineage of species, kept at its representative
+        let mut lineages = UnionFind::new(species);
+        let mut demes: Vec<usize> = (0..species).collect();
+        for (time, event) in species_tree.events().scan_times() {
+            let [first, second] = [lineages.find(event.indexes[0]), lineages.find(event.indexes[1])];
+            structured = structured.add_pulse(time, demes[second], demes[first], 1.0);
+            let representative = lineages.union(first, second);
+            demes[representative] = demes[first];
+        }
+
+        structured
+    }
+}
+
+impl Genealogy {
+    /// Whether the given individuals are exactly those below some node.
+    ///
+    /// # Panics
+    ///
+    /// If there are no individuals, or if some is not in the group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let genealogy = coalescence::fixtures::balanced();
+    ///
+    /// assert!(genealogy.is_monophyletic(&[1, 0]));
+    /// assert!(!genealogy.is_monophyletic(&[1, 2]));
+    /// ```
+    pub fn is_monophyletic(&self, individuals: &[usize]) -> bool {
+        let mut individuals = individuals.to_vec();
+        individuals.sort_unstable();
+        individuals.dedup();
+
+        self.mrca_node(&individuals).leaves == individuals
+    }
+}
+
+impl MultiLocusGenealogies {
+    /// Fraction of loci where the given individuals are monophyletic.
+    ///
+    /// # Panics
+    ///
+    /// If there are no loci, or as ``is_monophyletic`` on Genealogy.
+    pub fn monophyly(&self, individuals: &[usize]) -> f64 {
+        assert!(self.loci() > 0, "There must be some loci.");
+
+        let monophyletic = self.genealogies().iter().filter(|genealogy| genealogy.is_monophyletic(individuals)).count();
+        monophyletic as f64 / self.loci() as f64
+    }
+
+    /// Concordance factor of each clade of the species tree, in the order of ``branches``
+    /// on the species tree, where individuals are numbered species by species, with
+    /// ``samples[i]`` individuals of species ``i``, as in ``sample_genealogy`` on
+    /// StructuredCoalescent.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one number of samples per species, if they do not add up to the
+    /// group size, if some species is not sampled, or if there are no loci.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{Genealogy, MultiLocusGenealogies, StructuredCoalescent};
+    ///
+    /// // Species 0 and 1 diverged long after their ancestor and species 2
+    /// let species_tree = Genealogy::from_events(3, &[(1.0, [0, 1]), (10.0, [0, 2])]).unwrap();
+    /// let multispecies = StructuredCoalescent::multispecies(&species_tree, 1.0);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let samples = [2, 2, 2];
+    /// let loci = (0..200).map(|_| multispecies.sample_genealogy(&samples, &mut rng)).collect();
+    /// let factors = MultiLocusGenealogies::new(loci).concordance_factors(&species_tree, &samples);
+    ///
+    /// assert_eq!(factors.len(), 4);
+    /// assert_eq!(factors[3].0, vec![0, 1]);
+    /// assert!(factors[3].1 > 0.95);
+    /// ```
+    pub fn concordance_factors(&self, species_tree: &Genealogy, samples: &[usize]) -> Vec<(Vec<usize>, f64)> {
+        assert_eq!(samples.len(), species_tree.group_size(), "There must be one number of samples per species.");
+        assert!(samples.iter().all(|&size| size > 0), "All species must be sampled.");
+        assert!(
+            self.genealogies().iter().all(|genealogy| genealogy.group_size() == samples.iter().sum::<usize>()),
+            "Samples must add up to the group size."
+        );
+
+        // Individuals of each species
+        let mut first = 0;
+        let individuals: Vec<Vec<usize>> = samples
+            .iter()
+            .map(|&size| {
+                first += size;
+                ((first - size)..first).collect()
+            })
+            .collect();
+
+        species_tree
+            .branches()
+            .into_iter()
+            .map(|branch| {
+                let group: Vec<usize> = branch.leaves.iter().flat_map(|&species| individuals[species].clone()).collect();
+                let factor = self.monophyly(&group);
+                (branch.leaves, factor)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incomplete_lineage_sorting() {
+        // With one individual per species, gene trees match ((0, 1), 2) with probability
+        // 1 - 2/3 e^{-T}, T being the time between divergences
+        let species_tree = Genealogy::from_events(3, &[(0.5, [0, 1]), (1.0, [1, 2])]).unwrap();
+        let multispecies = StructuredCoalescent::multispecies(&species_tree, 1.0);
+        let mut rng = rand::thread_rng();
+        let loci: Vec<Genealogy> = (0..10_000).map(|_| multispecies.sample_genealogy(&[1, 1, 1], &mut rng)).collect();
+        assert!(loci.iter().all(|genealogy| genealogy.divergence(0, 1).unwrap() > 1.0));
+        assert!(loci.iter().all(|genealogy| genealogy.divergence(1, 2).unwrap() > 2.0));
+
+        let genealogies = MultiLocusGenealogies::new(loci);
+        let expected = 1.0 - 2.0 / 3.0 * (-0.5f64).exp();
+        assert!((genealogies.monophyly(&[0, 1]) - expected).abs() < 0.02);
+        let factors = genealogies.concordance_factors(&species_tree, &[1, 1, 1]);
+        assert_eq!(factors[0], (vec![0], 1.0));
+        assert!(factors.iter().any(|(clade, factor)| clade == &vec![0, 1] && (factor - expected).abs() < 0.02));
+    }
+}
@@ -0,0 +1,386 @@
+//! Statistical tests against simulations of the coalescent.
+//!
+//! Expected values under a model are estimated by simulating genealogies,
+//! and the distribution of test statistics by a parametric bootstrap:
+//! simulating data under the model and computing the statistic on each replicate.
+//!
+//! Neutrality tests like Tajima's D are calibrated the same way: their null distribution
+//! under the standard neutral model, for a given number of individuals and of segregating
+//! sites, is estimated from simulations, and kept in a ``NullCache`` so that many loci with
+//! the same numbers share it.
+//!
+
+// Types
+use crate::estimators::{fay_wu_h, harmonics, pi};
+use crate::{Coalescent, Demography, MutationModel};
+use std::collections::HashMap;
+
+// Traits
+use rand::Rng;
+
+/// Statistic comparing observed and expected counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitStatistic {
+    /// Pearson's statistic Σ (O - E)² / E.
+    ChiSquare,
+    /// Likelihood ratio statistic G = 2 Σ O ln(O / E).
+    LikelihoodRatio,
+}
+
+impl FitStatistic {
+    fn compute(&self, observed: &[usize], expected: &[f64]) -> f64 {
+        observed
+            .iter()
+            .zip(expected)
+            .filter(|(_, &expected)| expected > 0.0)
+            .map(|(&observed, &expected)| {
+                let observed = observed as f64;
+                match self {
+                    FitStatistic::ChiSquare => (observed - expected).powi(2) / expected,
+                    FitStatistic::LikelihoodRatio => match observed > 0.0 {
+                        true => 2.0 * observed * (observed / expected).ln(),
+                        false => 0.0,
+                    },
+                }
+            })
+            .sum()
+    }
+}
+
+/// Result of a goodness-of-fit test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoodnessOfFit {
+    /// Value of the statistic for the observed data.
+    pub statistic: f64,
+    /// Proportion of simulated replicates with a statistic at least as large,
+    /// counting the observed data as one of them.
+    pub p_value: f64,
+    /// Expected counts under the model, given the number of segregating sites.
+    pub expected: Vec<f64>,
+}
+
+/// Test whether an observed unfolded site frequency spectrum, see method
+/// ``site_frequency_spectrum`` on Haplotypes, fits the coalescent with a demography.
+///
+/// Conditioning on the observed number of segregating sites S, the expected spectrum
+/// is S times the expected proportion of the length of the genealogy below each number
+/// of individuals, estimated from ``replicates`` genealogies. The p-value comes from
+/// ``replicates`` spectra simulated with S segregating sites, each one on a new genealogy,
+/// which accounts for the correlation between sites sharing a genealogy.
+///
+/// # Panics
+///
+/// If the spectrum is empty, i.e. there is only one individual.
+///
+/// # Remarks
+///
+/// As the site frequency spectrum of a single locus varies a lot between genealogies,
+/// the test has little power unless the misfit is large.
+///
+/// # Examples
+///
+/// Only singletons fit a growing population better than one of constant size.
+/// ```
+/// use coalescence::Demography;
+/// use coalescence::statistics::{sfs_goodness_of_fit, FitStatistic};
+///
+/// let observed = vec![30, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let mut rng = rand::thread_rng();
+/// let constant = sfs_goodness_of_fit(&observed, &Demography::constant(), FitStatistic::LikelihoodRatio, 200, &mut rng);
+/// let growing = sfs_goodness_of_fit(&observed, &Demography::exponential(100.0), FitStatistic::LikelihoodRatio, 200, &mut rng);
+///
+/// assert!(growing.statistic < constant.statistic);
+/// assert!(constant.p_value > 0.0 && constant.p_value <= 1.0);
+/// assert!((constant.expected.iter().sum::<f64>() - 30.0).abs() < 1e-9);
+/// ```
+pub fn sfs_goodness_of_fit<R>(
+    observed: &[usize],
+    demography: &Demography,
+    statistic: FitStatistic,
+    replicates: usize,
+    rng: &mut R,
+) -> GoodnessOfFit
+where
+    R: Rng + ?Sized,
+{
+    assert!(!observed.is_empty(), "The spectrum must have at least one entry.");
+    let group_size = observed.len() + 1;
+    let sites: usize = observed.iter().sum();
+
+    let mut coalescent = Coalescent::sampler(group_size);
+    coalescent.set_demography(demography.clone());
+
+    // Expected spectrum
+
+    let mut branch_spectrum = vec![0.0; observed.len()];
+    for _ in 0..replicates {
+        let genealogy = coalescent.sample_genealogy(rng);
+        for (total, length) in branch_spectrum.iter_mut().zip(genealogy.branch_spectrum()) {
+            *total += length;
+        }
+    }
+    let length: f64 = branch_spectrum.iter().sum();
+    let expected: Vec<f64> = branch_spectrum
+        .iter()
+        .map(|total| match length > 0.0 {
+            true => sites as f64 * total / length,
+            false => 0.0,
+        })
+        .collect();
+
+    // Parametric bootstrap
+
+    let observed_statistic = statistic.compute(observed, &expected);
+    let model = MutationModel::FixedSegregatingSites { sites };
+    let as_large = (0..replicates)
+        .filter(|_| {
+            let genealogy = coalescent.sample_genealogy(rng);
+            let simulated = genealogy.sample_haplotypes(model, rng).site_frequency_spectrum();
+            statistic.compute(&simulated, &expected) >= observed_statistic
+        })
+        .count();
+
+    GoodnessOfFit {
+        statistic: observed_statistic,
+        p_value: (as_large + 1) as f64 / (replicates + 1) as f64,
+        expected,
+    }
+}
+
+/// Statistic of a neutrality test, computed from an unfolded site frequency spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeutralityStatistic {
+    /// Tajima's D, the difference between π and Watterson's estimator over its standard deviation.
+    TajimaD,
+    /// Fay and Wu's H, the difference between π and θ_H.
+    FayWuH,
+}
+
+impl NeutralityStatistic {
+    /// Value of the statistic, ``NaN`` for Tajima's D without segregating sites.
+    ///
+    /// # Panics
+    ///
+    /// If the spectrum is empty, i.e. there is only one individual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::statistics::NeutralityStatistic;
+    ///
+    /// // An excess of singletons, as after an expansion
+    /// let d = NeutralityStatistic::TajimaD.compute(&[10, 1, 0, 0, 0, 1, 0, 0, 0]);
+    /// assert!(d < -1.0);
+    /// ```
+    pub fn compute(&self, sfs: &[usize]) -> f64 {
+        assert!(!sfs.is_empty(), "The spectrum must have at least one entry.");
+        let differences = pi(sfs).value;
+        match self {
+            NeutralityStatistic::TajimaD => {
+                // Tajima (1989)
+                let n = (sfs.len() + 1) as f64;
+                let (a1, a2) = harmonics(sfs.len() + 1);
+                let sites = sfs.iter().sum::<usize>() as f64;
+                let b1 = (n + 1.0) / (3.0 * (n - 1.0));
+                let b2 = 2.0 * (n * n + n + 3.0) / (9.0 * n * (n - 1.0));
+                let c1 = b1 - 1.0 / a1;
+                let c2 = b2 - (n + 2.0) / (a1 * n) + a2 / (a1 * a1);
+                let (e1, e2) = (c1 / a1, c2 / (a1 * a1 + a2));
+
+                (differences - sites / a1) / (e1 * sites + e2 * sites * (sites - 1.0)).sqrt()
+            },
+            NeutralityStatistic::FayWuH => differences - fay_wu_h(sfs).value,
+        }
+    }
+}
+
+/// Distribution of a neutrality statistic under the standard neutral model, given the
+/// numbers of individuals and of segregating sites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullDistribution {
+    values: Vec<f64>, // sorted, without NaN
+}
+
+impl NullDistribution {
+    /// Null distribution of ``statistic`` estimated from ``replicates`` spectra of ``sites``
+    /// segregating sites in ``group_size`` individuals, each one on a new genealogy.
+    ///
+    /// # Panics
+    ///
+    /// If there are less than two individuals or no replicates.
+    pub fn simulate<R>(statistic: NeutralityStatistic, group_size: usize, sites: usize, replicates: usize, rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(group_size > 1, "There must be at least two individuals.");
+        assert!(replicates > 0, "There must be some replicates.");
+
+        let coalescent = Coalescent::sampler(group_size);
+        let model = MutationModel::FixedSegregatingSites { sites };
+        let mut values: Vec<f64> = (0..replicates)
+            .map(|_| {
+                let genealogy = coalescent.sample_genealogy(rng);
+                statistic.compute(&genealogy.sample_haplotypes(model, rng).site_frequency_spectrum())
+            })
+            .filter(|value| !value.is_nan())
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        NullDistribution { values }
+    }
+
+    /// Simulated values of the statistic, in increasing order.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Proportion of values at most ``value``, counting it as one of them.
+    pub fn lower_p_value(&self, value: f64) -> f64 {
+        let below = self.values.partition_point(|&other| other <= value);
+        (below + 1) as f64 / (self.values.len() + 1) as f64
+    }
+
+    /// Proportion of values at least ``value``, counting it as one of them.
+    pub fn upper_p_value(&self, value: f64) -> f64 {
+        let above = self.values.len() - self.values.partition_point(|&other| other < value);
+        (above + 1) as f64 / (self.values.len() + 1) as f64
+    }
+
+    /// Two-sided p-value, twice the smaller one-sided one, at most one.
+    pub fn p_value(&self, value: f64) -> f64 {
+        (2.0 * self.lower_p_value(value).min(self.upper_p_value(value))).min(1.0)
+    }
+
+    /// Critical values of a two-sided test of level ``level``: the quantiles of the null
+    /// distribution at ``level / 2`` and ``1 - level / 2``.
+    ///
+    /// # Panics
+    ///
+    /// If the level is not in (0, 1), or if there are no values, e.g. without segregating sites.
+    pub fn critical_values(&self, level: f64) -> (f64, f64) {
+        assert!(0.0 < level && level < 1.0, "The level must be in (0, 1).");
+        assert!(!self.values.is_empty(), "There must be some values.");
+
+        let quantile = |probability: f64| {
+            let index = (probability * self.values.len() as f64).floor() as usize;
+            self.values[index.min(self.values.len() - 1)]
+        };
+        (quantile(level / 2.0), quantile(1.0 - level / 2.0))
+    }
+}
+
+/// Null distributions already simulated, by statistic and numbers of individuals and sites.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::statistics::{NeutralityStatistic, NullCache};
+///
+/// let mut cache = NullCache::new(2000);
+/// let mut rng = rand::thread_rng();
+///
+/// let sfs = [12, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let d = NeutralityStatistic::TajimaD.compute(&sfs);
+/// let null = cache.get(NeutralityStatistic::TajimaD, 10, 12, &mut rng);
+///
+/// assert!(null.lower_p_value(d) < 0.05);
+/// let (lower, upper) = null.critical_values(0.05);
+/// assert!(lower < 0.0 && 0.0 < upper);
+/// assert_eq!(cache.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullCache {
+    replicates: usize,
+    distributions: HashMap<(NeutralityStatistic, usize, usize), NullDistribution>,
+}
+
+impl NullCache {
+    /// Empty cache, whose distributions are simulated from ``replicates`` spectra each.
+    ///
+    /// # Panics
+    ///
+    /// If there are no replicates.
+    pub fn new(replicates: usize) -> Self {
+        assert!(replicates > 0, "There must be some replicates.");
+
+        NullCache { replicates, distributions: HashMap::new() }
+    }
+
+    /// Null distribution of ``statistic`` for ``sites`` segregating sites in ``group_size``
+    /// individuals, simulated on the first call only.
+    ///
+    /// # Panics
+    ///
+    /// As ``simulate`` on NullDistribution.
+    pub fn get<R>(&mut self, statistic: NeutralityStatistic, group_size: usize, sites: usize, rng: &mut R) -> &NullDistribution
+    where
+        R: Rng + ?Sized,
+    {
+        let replicates = self.replicates;
+        self.distributions
+            .entry((statistic, group_size, sites))
+            .or_insert_with(|| NullDistribution::simulate(statistic, group_size, sites, replicates, rng))
+    }
+
+    /// Number of distributions in the cache.
+    pub fn len(&self) -> usize {
+        self.distributions.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.distributions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goodness_of_fit() {
+        let mut rng = rand::thread_rng();
+
+        // Under a constant size, the expected spectrum is proportional to 1 / i
+        let observed: Vec<usize> = (1..10).map(|i| (60.0 / i as f64).round() as usize).collect();
+        let fit = sfs_goodness_of_fit(&observed, &Demography::constant(), FitStatistic::ChiSquare, 500, &mut rng);
+        let harmonic: f64 = (1..10).map(|i| 1.0 / i as f64).sum();
+        let sites: usize = observed.iter().sum();
+        assert!((fit.expected[0] - sites as f64 / harmonic).abs() < 0.1 * fit.expected[0]);
+        assert!(fit.p_value > 0.3);
+
+        // Under strong growth, genealogies are star-like
+        let star = vec![40, 0, 0, 0, 0, 0, 0, 0, 0];
+        let growing = sfs_goodness_of_fit(&star, &Demography::exponential(100.0), FitStatistic::ChiSquare, 200, &mut rng);
+        let constant = sfs_goodness_of_fit(&star, &Demography::constant(), FitStatistic::ChiSquare, 200, &mut rng);
+        assert!(growing.expected[0] > constant.expected[0]);
+        assert!(growing.statistic < constant.statistic);
+    }
+
+    #[test]
+    fn neutrality_tests() {
+        // D is about centered under the null, and p-values are about uniform
+        let mut rng = rand::thread_rng();
+        let null = NullDistribution::simulate(NeutralityStatistic::TajimaD, 20, 15, 4000, &mut rng);
+        let mean: f64 = null.values().iter().sum::<f64>() / null.values().len() as f64;
+        assert!(mean.abs() < 0.15, "{}", mean);
+        let (lower, upper) = null.critical_values(0.1);
+        assert!(lower < upper);
+        assert!((null.lower_p_value(lower) - 0.05).abs() < 0.01);
+        assert!((null.upper_p_value(upper) - 0.05).abs() < 0.02);
+        assert!(null.p_value(0.0) > 0.5);
+
+        // Without sites, D is not defined
+        assert!(NeutralityStatistic::TajimaD.compute(&[0, 0, 0]).is_nan());
+        assert_eq!(NullDistribution::simulate(NeutralityStatistic::TajimaD, 4, 0, 10, &mut rng).values().len(), 0);
+
+        // Fay and Wu's H is negative with many high-frequency derived alleles
+        assert!(NeutralityStatistic::FayWuH.compute(&[0, 0, 0, 0, 5]) < 0.0);
+
+        let mut cache = NullCache::new(50);
+        cache.get(NeutralityStatistic::FayWuH, 5, 3, &mut rng);
+        cache.get(NeutralityStatistic::FayWuH, 5, 3, &mut rng);
+        cache.get(NeutralityStatistic::TajimaD, 5, 3, &mut rng);
+        assert_eq!(cache.len(), 2);
+    }
+}
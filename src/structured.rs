@@ -0,0 +1,803 @@
+//! Structured coalescent.
+//!
+//! The population is divided into demes, each one with a relative size,
+//! and lineages move between demes backwards in time. With ``k`` lineages in a
+//! deme of relative size ν, each pair of them coalesces at rate 1 / ν, and
+//! each lineage moves from deme ``i`` to deme ``j`` at rate ``m[i][j]``.
+//! Migration rates are constant within epochs, while the size of each deme is
+//! either constant or changes exponentially at its own growth rate, as in
+//! [Demography](../demography/struct.Demography.html), and lineages can also move
+//! at once at given times.
+//!
+//! Moving at once models admixture: if, forwards in time, a fraction ``f`` of
+//! deme ``A`` comes from deme ``B`` at time ``T``, then backwards in time each
+//! lineage in ``A`` jumps to ``B`` with probability ``f`` at time ``T``, see ``add_pulse``.
+//! The demes visited by the ancestors of each individual are its ``AncestryPath``.
+//!
+//! Demes can be geographic, but also classes of individuals, like the carriers
+//! of each allele at a selected site, see [Trajectory](../trajectory/struct.Trajectory.html).
+//!
+
+// Types
+use crate::{AnnotatedGenealogy, Genealogy};
+use rand_distr::Exp1;
+
+// Traits
+use crate::traits::{gillespie, GillespieStep};
+use rand::Rng;
+
+/// Coalescent process in a population divided into demes.
+///
+/// # Examples
+///
+/// Two demes exchanging migrants: individuals of the same deme tend
+/// to be more closely related.
+/// ```
+/// use coalescence::StructuredCoalescent;
+///
+/// let migration = vec![vec![0.0, 0.1], vec![0.1, 0.0]];
+/// let structured = StructuredCoalescent::new(vec![0.5, 0.5], migration);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = structured.sample_genealogy(&[5, 5], &mut rng);
+///
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredCoalescent {
+    epochs: Vec<StructuredEpoch>, // sorted by start, the first one starting at zero
+    pulses: Vec<Pulse>, // sorted by time
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct StructuredEpoch {
+    start: f64,
+    sizes: Vec<f64>, // at the start of the epoch
+    growth_rates: Vec<f64>,
+    migration: Vec<Vec<f64>>,
+}
+
+/// Lineages moving at once from a deme to another one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Pulse {
+    time: f64,
+    from: usize,
+    to: usize,
+    proportion: f64, // probability for each lineage to move
+}
+
+impl StructuredCoalescent {
+    /// Creates a new structured coalescent with constant relative sizes of demes
+    /// and migration rates, where ``migration[i][j]`` is the rate at which each
+    /// lineage in deme ``i`` moves to deme ``j``. Diagonal entries are ignored.
+    ///
+    /// Demes of size zero are empty: no lineage can move into them.
+    ///
+    /// # Panics
+    ///
+    /// If some size is negative or not finite, if the migration matrix is not
+    /// square with one row per deme, if some migration rate is negative or not finite,
+    /// or if there is migration into an empty deme.
+    pub fn new(sizes: Vec<f64>, migration: Vec<Vec<f64>>) -> Self {
+        let epoch = StructuredEpoch::new(0.0, sizes, migration);
+
+        StructuredCoalescent { epochs: vec![epoch], pulses: Vec::new() }
+    }
+
+    /// Adds an epoch starting at time ``start`` in the past, with new sizes and migration rates.
+    ///
+    /// # Panics
+    ///
+    /// If ``start`` is not after the start of the last epoch, if the number of demes
+    /// changes, or in the same cases as ``new``.
+    pub fn add_epoch(mut self, start: f64, sizes: Vec<f64>, migration: Vec<Vec<f64>>) -> Self {
+        let last = self.epochs.last().unwrap();
+        assert!(start > last.start && start.is_finite(), "Epochs must start after the previous one.");
+        assert_eq!(sizes.len(), self.demes(), "The number of demes must not change.");
+
+        self.epochs.push(StructuredEpoch::new(start, sizes, migration));
+        self
+    }
+
+    /// Moves each lineage in deme ``from`` to deme ``to`` with probability ``proportion``, at time ``time``,
+    /// i.e. a pulse of admixture where a fraction ``proportion`` of deme ``from`` descends from deme ``to``.
+    ///
+    /// # Panics
+    ///
+    /// If the time is negative or not finite, if some deme does not exist,
+    /// or if the proportion is not between zero and one.
+    ///
+    /// # Examples
+    ///
+    /// Deme 1 is a mix of deme 0 and an unsampled ghost deme 2, which split from deme 0 long ago.
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// let isolated = vec![vec![0.0; 3]; 3];
+    /// let merging = vec![vec![0.0, 0.0, 0.0], vec![0.0, 0.0, 0.0], vec![1e6, 0.0, 0.0]];
+    /// let structured = StructuredCoalescent::new(vec![1.0, 1.0, 1.0], isolated)
+    ///     .add_epoch(10.0, vec![1.0, 1.0, 1.0], merging)
+    ///     .add_pulse(0.1, 1, 0, 0.7)
+    ///     .add_pulse(0.1, 1, 2, 1.0);
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let (genealogy, ancestry) = structured.sample_genealogy_with_ancestry(&[2, 3, 0], &mut rng);
+    ///
+    /// assert_eq!(genealogy.group_size(), 5);
+    /// assert_eq!(ancestry[2].deme_at(0.0), 1);
+    /// assert_ne!(ancestry[2].deme_at(1.0), 1);
+    /// ```
+    pub fn add_pulse(mut self, time: f64, from: usize, to: usize, proportion: f64) -> Self {
+        assert!(time >= 0.0 && time.is_finite(), "Pulses must happen at a non-negative finite time.");
+        assert!(from < self.demes() && to < self.demes(), "Pulses must be between existing demes.");
+        assert!((0.0..=1.0).contains(&proportion), "The proportion must be between zero and one.");
+
+        let index = self.pulses.iter().position(|pulse| pulse.time > time).unwrap_or(self.pulses.len());
+        self.pulses.insert(index, Pulse { time, from, to, proportion });
+        self
+    }
+
+    /// Sets the growth rates of the demes during the last epoch, so that the size of deme ``i``
+    /// at time ``t`` in the past is ``sizes[i] e^{-growth_rates[i] (t - start)}``.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one growth rate per deme, or if some is not finite.
+    ///
+    /// # Examples
+    ///
+    /// A simplified out-of-Africa history: Eurasians split from Africans at time 0.5 in the
+    /// past, through a bottleneck, and then grew, exchanging migrants with Africans.
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// let migration = vec![vec![0.0, 0.5], vec![0.5, 0.0]];
+    /// let merging = vec![vec![0.0, 0.0], vec![1e6, 0.0]];
+    /// let structured = StructuredCoalescent::new(vec![1.0, 2.0], migration)
+    ///     .with_growth_rates(vec![0.0, 6.0])
+    ///     .add_epoch(0.5, vec![1.0, 0.1], merging);
+    ///
+    /// assert!((structured.size_at(1, 0.25) - 2.0 * (-1.5f64).exp()).abs() < 1e-12);
+    ///
+    /// let genealogy = structured.sample_genealogy(&[5, 5], &mut rand::thread_rng());
+    /// assert_eq!(genealogy.validate(), Ok(()));
+    /// ```
+    pub fn with_growth_rates(mut self, growth_rates: Vec<f64>) -> Self {
+        assert_eq!(growth_rates.len(), self.demes(), "There must be one growth rate per deme.");
+        assert!(growth_rates.iter().all(|rate| rate.is_finite()), "Growth rates must be finite.");
+
+        self.epochs.last_mut().unwrap().growth_rates = growth_rates;
+        self
+    }
+
+    /// Relative size of deme ``deme`` at time ``t`` in the past.
+    ///
+    /// # Panics
+    ///
+    /// If the deme does not exist.
+    pub fn size_at(&self, deme: usize, t: f64) -> f64 {
+        let epoch = self.epochs.iter().rev().find(|epoch| epoch.start <= t).unwrap_or(&self.epochs[0]);
+        epoch.size_at(deme, t)
+    }
+
+    /// Number of demes.
+    pub fn demes(&self) -> usize {
+        self.epochs[0].sizes.len()
+    }
+
+    /// Sample a genealogy of a group with ``samples[i]`` individuals from deme ``i``.
+    ///
+    /// Individuals are numbered deme by deme: the first ``samples[0]`` individuals
+    /// are from the first deme, and so on.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one number of samples per deme, if there are lineages in an empty
+    /// deme, or if lineages never meet because some demes are not connected by migration
+    /// in the last epoch.
+    pub fn sample_genealogy<R>(&self, samples: &[usize], rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        self.sample_genealogy_with_ancestry(samples, rng).0
+    }
+
+    /// Sample a genealogy as ``sample_genealogy``, together with the ancestry path
+    /// of each individual.
+    ///
+    /// # Panics
+    ///
+    /// In the same cases as ``sample_genealogy``.
+    pub fn sample_genealogy_with_ancestry<R>(&self, samples: &[usize], rng: &mut R) -> (Genealogy, Vec<AncestryPath>)
+    where
+        R: Rng + ?Sized,
+    {
+        assert_eq!(samples.len(), self.demes(), "There must be one number of samples per deme.");
+        let group_size: usize = samples.iter().sum();
+
+        // Lineages, represented by an individual, and their demes
+
+        let mut lineages: Vec<(usize, usize)> = samples
+            .iter()
+            .enumerate()
+            .flat_map(|(deme, &size)| std::iter::repeat_n(deme, size))
+            .enumerate()
+            .collect();
+
+        let mut steps = Vec::with_capacity(group_size.saturating_sub(1));
+        let mut time_steps = Vec::with_capacity(group_size.saturating_sub(1));
+
+        // Moves of each lineage while its individual represents it, and the time
+        // and representative of the lineage it joins, if any
+
+        let mut moves: Vec<Vec<(f64, usize)>> = lineages.iter().map(|&(_, deme)| vec![(0.0, deme)]).collect();
+        let mut joined: Vec<Option<(f64, usize)>> = vec![None; group_size];
+
+        let mut time = 0.0;
+        let mut last_event = 0.0;
+        let mut epoch_index = 0;
+        let mut pulse_index = 0;
+
+        while lineages.len() > 1 {
+            let epoch = &self.epochs[epoch_index];
+            let demes = self.demes();
+
+            // Rates of events
+
+            let mut counts = vec![0; demes];
+            for &(_, deme) in &lineages {
+                counts[deme] += 1;
+            }
+            assert!(
+                (0..demes).all(|deme| counts[deme] == 0 || epoch.sizes[deme] > 0.0),
+                "There are lineages in an empty deme."
+            );
+            let step = match epoch.growth_rates.iter().all(|&rate| rate == 0.0) {
+                true => DemeRates { counts, epoch }.gillespie_step(rng),
+                false => epoch.growing_step(&counts, time, rng),
+            };
+
+            // Next change of parameters
+
+            let next_epoch = self.epochs.get(epoch_index + 1).map(|epoch| epoch.start).unwrap_or(f64::INFINITY);
+            let next_pulse = self.pulses.get(pulse_index).map(|pulse| pulse.time).unwrap_or(f64::INFINITY);
+            let next_change = next_epoch.min(next_pulse);
+
+            let waiting_time = step.map(|(waiting_time, _)| waiting_time).unwrap_or(f64::INFINITY);
+
+            if time + waiting_time >= next_change {
+                assert!(next_change.is_finite(), "Lineages never meet: some demes are not connected.");
+                time = next_change;
+                if next_pulse <= next_epoch {
+                    let pulse = self.pulses[pulse_index];
+                    for (individual, deme) in lineages.iter_mut() {
+                        if *deme == pulse.from && rng.gen::<f64>() < pulse.proportion {
+                            *deme = pulse.to;
+                            moves[*individual].push((time, pulse.to));
+                        }
+                    }
+                    pulse_index += 1;
+                } else {
+                    epoch_index += 1;
+                }
+                continue;
+            }
+            time += waiting_time;
+
+            // Apply the event
+
+            let (from, to) = step.unwrap().1;
+
+            let in_deme: Vec<usize> = (0..lineages.len()).filter(|&i| lineages[i].1 == from).collect();
+            match from == to {
+                true => {
+                    let pair = rand::seq::index::sample(rng, in_deme.len(), 2);
+                    let (first, second) = (in_deme[pair.index(0)], in_deme[pair.index(1)]);
+                    let indexes = [lineages[first].0, lineages[second].0];
+
+                    steps.push(indexes);
+                    time_steps.push(time - last_event);
+                    last_event = time;
+
+                    joined[indexes[1]] = Some((time, indexes[0]));
+                    lineages.swap_remove(second);
+                },
+                false => {
+                    let lineage = in_deme[rng.gen_range(0, in_deme.len())];
+                    lineages[lineage].1 = to;
+                    moves[lineages[lineage].0].push((time, to));
+                },
+            }
+        }
+
+        let ancestry = (0..group_size).map(|individual| AncestryPath::follow(individual, &moves, &joined)).collect();
+
+        (Genealogy::from_steps(group_size, steps, time_steps), ancestry)
+    }
+
+    /// Sample a genealogy as ``sample_genealogy``, with the ``"deme"`` of each node in its
+    /// annotations, and a unary node at each move of a lineage, annotated with the deme it
+    /// moves to backwards in time.
+    ///
+    /// # Panics
+    ///
+    /// In the same cases as ``sample_genealogy``.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// let structured = StructuredCoalescent::secondary_contact(1.0, 0.5, 2.0);
+    /// let annotated = structured.sample_annotated_genealogy(&[2, 2], &mut rand::thread_rng());
+    ///
+    /// assert_eq!(annotated.annotations(3), &[("deme".to_string(), "1".to_string())]);
+    /// // Lineages of deme 1 move into deme 0 at the split
+    /// assert!(annotated.to_newick().contains("[&deme=0]"));
+    /// ```
+    pub fn sample_annotated_genealogy<R>(&self, samples: &[usize], rng: &mut R) -> AnnotatedGenealogy
+    where
+        R: Rng + ?Sized,
+    {
+        let (genealogy, ancestry) = self.sample_genealogy_with_ancestry(samples, rng);
+        let (parents, times) = (genealogy.nodes().0.to_vec(), genealogy.nodes().1.to_vec());
+        let mut annotated = AnnotatedGenealogy::from(genealogy);
+
+        // Moves are on the branch above the last node of the path of the individual before them
+        for (individual, path) in ancestry.iter().enumerate() {
+            let mut node = individual;
+            for &(time, deme) in path.moves.iter().skip(1) {
+                while parents[node] != node && times[parents[node]] < time {
+                    node = parents[node];
+                }
+                if annotated.unary_nodes(node).iter().all(|unary_node| unary_node.time != time) {
+                    annotated.add_unary_node(node, time, vec![("deme".to_string(), deme.to_string())]);
+                }
+            }
+        }
+
+        // Deme of each node, from the path of an individual below it
+        let mut below: Vec<Option<usize>> = (0..parents.len()).map(|node| Some(node).filter(|&node| node < ancestry.len())).collect();
+        for (node, &parent) in parents.iter().enumerate() {
+            let individual = below[node].unwrap();
+            if below[parent].is_none() {
+                below[parent] = Some(individual);
+            }
+            annotated.annotate(node, "deme", &ancestry[individual].deme_at(times[node]).to_string());
+        }
+
+        annotated
+    }
+}
+
+/// Two demes that split from an ancestral population, evolved in isolation, and then
+/// came back into contact, exchanging migrants, like the two sides of a hybrid zone.
+///
+/// Backwards in time, lineages migrate between the demes until the start of the contact,
+/// at time ``contact_time`` in the past, then stay in their deme until the split, at time
+/// ``split_time``, when all lineages of deme 1 move into deme 0, which then has the size of
+/// the ancestral population. Admixture pulses can be added at any time before the split.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::SecondaryContact;
+///
+/// let structured = SecondaryContact::new(5.0, 1.0)
+///     .sizes([1.0, 0.5])
+///     .ancestral_size(2.0)
+///     .migration([0.5, 0.2])
+///     .add_pulse(2.0, 0, 1, 0.1)
+///     .build();
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = structured.sample_genealogy(&[4, 3], &mut rng);
+///
+/// assert_eq!(structured.demes(), 2);
+/// assert_eq!(genealogy.validate(), Ok(()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryContact {
+    split_time: f64,
+    contact_time: f64,
+    sizes: [f64; 2],
+    ancestral_size: f64,
+    migration: [f64; 2], // rate of moves out of each deme, backwards in time
+    pulses: Vec<Pulse>,
+}
+
+impl SecondaryContact {
+    /// Scenario of demes of size one, split at ``split_time`` from an ancestral population of
+    /// size one, and in contact since ``contact_time``, without migration until it is set.
+    ///
+    /// # Panics
+    ///
+    /// If ``contact_time`` is negative, or not before ``split_time``, or if ``split_time`` is not finite.
+    pub fn new(split_time: f64, contact_time: f64) -> Self {
+        assert!(split_time.is_finite(), "The split time must be finite.");
+        assert!(0.0 <= contact_time && contact_time < split_time, "The contact must start after the split.");
+
+        SecondaryContact {
+            split_time,
+            contact_time,
+            sizes: [1.0, 1.0],
+            ancestral_size: 1.0,
+            migration: [0.0, 0.0],
+            pulses: Vec::new(),
+        }
+    }
+
+    /// Sets the relative sizes of both demes after the split.
+    ///
+    /// # Panics
+    ///
+    /// If some size is not positive and finite.
+    pub fn sizes(mut self, sizes: [f64; 2]) -> Self {
+        assert!(sizes.iter().all(|&size| size > 0.0 && size.is_finite()), "Sizes must be positive and finite.");
+
+        self.sizes = sizes;
+        self
+    }
+
+    /// Sets the relative size of the ancestral population, before the split.
+    ///
+    /// # Panics
+    ///
+    /// If the size is not positive and finite.
+    pub fn ancestral_size(mut self, size: f64) -> Self {
+        assert!(size > 0.0 && size.is_finite(), "Sizes must be positive and finite.");
+
+        self.ancestral_size = size;
+        self
+    }
+
+    /// Sets the migration rates during the contact, where ``rates[i]`` is the rate at which
+    /// each lineage in deme ``i`` moves to the other deme.
+    ///
+    /// # Panics
+    ///
+    /// If some rate is negative or not finite.
+    pub fn migration(mut self, rates: [f64; 2]) -> Self {
+        assert!(rates.iter().all(|&rate| rate >= 0.0 && rate.is_finite()), "Migration rates must be non-negative and finite.");
+
+        self.migration = rates;
+        self
+    }
+
+    /// Adds a pulse of admixture, as ``add_pulse`` on StructuredCoalescent.
+    ///
+    /// # Panics
+    ///
+    /// If the pulse is not before the split, or in the same cases as ``add_pulse``.
+    pub fn add_pulse(mut self, time: f64, from: usize, to: usize, proportion: f64) -> Self {
+        assert!(time < self.split_time, "Pulses must happen before the split.");
+        assert!(from < 2 && to < 2, "Pulses must be between existing demes.");
+        assert!((0.0..=1.0).contains(&proportion), "The proportion must be between zero and one.");
+
+        self.pulses.push(Pulse { time, from, to, proportion });
+        self
+    }
+
+    /// Structured coalescent of the scenario, with the two demes after the split.
+    pub fn build(&self) -> StructuredCoalescent {
+        let isolated = vec![vec![0.0; 2]; 2];
+        let contact = vec![vec![0.0, self.migration[0]], vec![self.migration[1], 0.0]];
+        let sizes = self.sizes.to_vec();
+
+        let mut structured = match self.contact_time > 0.0 {
+            true => StructuredCoalescent::new(sizes.clone(), contact).add_epoch(self.contact_time, sizes, isolated.clone()),
+            false => StructuredCoalescent::new(sizes, isolated.clone()),
+        };
+        structured = structured
+            .add_epoch(self.split_time, vec![self.ancestral_size, self.sizes[1]], isolated)
+            .add_pulse(self.split_time, 1, 0, 1.0);
+        for pulse in &self.pulses {
+            structured = structured.add_pulse(pulse.time, pulse.from, pulse.to, pulse.proportion);
+        }
+
+        structured
+    }
+}
+
+impl StructuredCoalescent {
+    /// Structured coalescent of two demes of size one, split at ``split_time`` from an ancestral
+    /// population of size one and exchanging migrants at rate ``migration_rate`` since
+    /// ``contact_time``, see ``SecondaryContact`` to set other parameters.
+    ///
+    /// # Panics
+    ///
+    /// As ``new`` and ``migration`` on SecondaryContact.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// // Without contact, individuals of different demes only meet before the split
+    /// let structured = StructuredCoalescent::secondary_contact(3.0, 0.0, 1.0);
+    /// let genealogy = structured.sample_genealogy(&[1, 1], &mut rand::thread_rng());
+    ///
+    /// assert!(genealogy.depth() > 3.0);
+    /// ```
+    pub fn secondary_contact(split_time: f64, contact_time: f64, migration_rate: f64) -> Self {
+        SecondaryContact::new(split_time, contact_time)
+            .migration([migration_rate, migration_rate])
+            .build()
+    }
+}
+
+/// Lineages in each deme during an epoch, with one event per pair of demes:
+/// a coalescence within a deme, or a move from one deme to another.
+struct DemeRates<'a> {
+    counts: Vec<usize>,
+    epoch: &'a StructuredEpoch,
+}
+
+impl<'a> GillespieStep for DemeRates<'a> {
+    type Event = (usize, usize);
+
+    fn event_rates(&self, rates: &mut Vec<f64>) {
+        for (from, &count) in self.counts.iter().enumerate() {
+            let k = count as f64;
+            for to in 0..self.counts.len() {
+                let rate = match (from == to, count) {
+                    (true, 0..=1) => 0.0,
+                    (true, _) => k * (k - 1.0) / 2.0 / self.epoch.sizes[from],
+                    (false, _) => k * self.epoch.migration[from][to],
+                };
+                rates.push(rate);
+            }
+        }
+    }
+
+    fn event(&self, index: usize) -> (usize, usize) {
+        (index / self.counts.len(), index % self.counts.len())
+    }
+}
+
+/// Demes visited by the ancestors of an individual, backwards in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AncestryPath {
+    /// Time of each move and the deme reached, sorted by time, starting with
+    /// the deme of the individual at time zero.
+    pub moves: Vec<(f64, usize)>,
+}
+
+impl AncestryPath {
+    /// Path of an individual: the moves of its lineage and then, once it joins another
+    /// lineage, the moves of that one, up to the most recent common ancestor of the group.
+    fn follow(individual: usize, moves: &[Vec<(f64, usize)>], joined: &[Option<(f64, usize)>]) -> Self {
+        let mut path = vec![moves[individual][0]];
+        let (mut current, mut start) = (individual, 0.0);
+        loop {
+            let end = joined[current].map(|(time, _)| time).unwrap_or(f64::INFINITY);
+            path.extend(moves[current].iter().skip(1).filter(|&&(time, _)| start <= time && time < end));
+            match joined[current] {
+                Some((time, other)) => {
+                    start = time;
+                    current = other;
+                },
+                None => break,
+            }
+        }
+
+        AncestryPath { moves: path }
+    }
+
+    /// Deme of the ancestor at the given time in the past.
+    pub fn deme_at(&self, time: f64) -> usize {
+        let index = self.moves.partition_point(|&(move_time, _)| move_time <= time);
+        self.moves[index.saturating_sub(1)].1
+    }
+
+    /// Whether the ancestor was ever in the given deme.
+    pub fn visits(&self, deme: usize) -> bool {
+        self.moves.iter().any(|&(_, other)| other == deme)
+    }
+}
+
+impl StructuredEpoch {
+    fn new(start: f64, sizes: Vec<f64>, migration: Vec<Vec<f64>>) -> Self {
+        assert!(!sizes.is_empty(), "There must be at least one deme.");
+        assert!(sizes.iter().all(|&size| size >= 0.0 && size.is_finite()), "Sizes must be non-negative and finite.");
+        assert!(
+            migration.len() == sizes.len() && migration.iter().all(|row| row.len() == sizes.len()),
+            "The migration matrix must have one row and one column per deme."
+        );
+        assert!(
+            migration.iter().flatten().all(|&rate| rate >= 0.0 && rate.is_finite()),
+            "Migration rates must be non-negative and finite."
+        );
+        assert!(
+            migration.iter().all(|row| (0..sizes.len()).all(|to| sizes[to] > 0.0 || row[to] == 0.0)),
+            "There must be no migration into empty demes."
+        );
+
+        let growth_rates = vec![0.0; sizes.len()];
+        StructuredEpoch { start, sizes, growth_rates, migration }
+    }
+
+    fn size_at(&self, deme: usize, t: f64) -> f64 {
+        self.sizes[deme] * (-self.growth_rates[deme] * (t - self.start)).exp()
+    }
+
+    /// Next event from time ``t``, as ``DemeRates`` but with sizes changing within the epoch:
+    /// the first coalescence in each deme is drawn by inverting its intensity, and competes
+    /// with migrations, whose rates stay constant.
+    fn growing_step<R>(&self, counts: &[usize], t: f64, rng: &mut R) -> Option<(f64, (usize, usize))>
+    where
+        R: Rng + ?Sized,
+    {
+        let demes = counts.len();
+        let mut rates = Vec::with_capacity(demes * demes);
+        for (from, &count) in counts.iter().enumerate() {
+            rates.extend(self.migration[from].iter().enumerate().map(|(to, &rate)| match from == to {
+                true => 0.0,
+                false => count as f64 * rate,
+            }));
+        }
+        let mut step = gillespie(&rates, rng).map(|(waiting_time, index)| (waiting_time, (index / demes, index % demes)));
+
+        for (deme, &count) in counts.iter().enumerate().filter(|&(_, &count)| count > 1) {
+            let k = count as f64;
+            let intensity = rng.sample::<f64, _>(Exp1) / (k * (k - 1.0) / 2.0);
+            let (size, growth_rate) = (self.size_at(deme, t), self.growth_rates[deme]);
+            let waiting_time = match growth_rate == 0.0 {
+                true => intensity * size,
+                false => {
+                    let argument = intensity * growth_rate * size;
+                    match argument > -1.0 {
+                        true => argument.ln_1p() / growth_rate,
+                        false => f64::INFINITY,
+                    }
+                },
+            };
+            if waiting_time.is_finite() && step.map(|(first, _)| waiting_time < first).unwrap_or(true) {
+                step = Some((waiting_time, (deme, deme)));
+            }
+        }
+
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_deme_is_kingman() {
+        let structured = StructuredCoalescent::new(vec![1.0], vec![vec![0.0]]);
+        let samples = 4000;
+
+        let mut rng = rand::thread_rng();
+        let mean_depth = (0..samples)
+            .map(|_| structured.sample_genealogy(&[2], &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+
+        assert!((mean_depth - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn isolated_demes_meet_after_merging() {
+        // Demes are isolated until they merge into a single population by migration
+        let isolated = vec![vec![0.0, 0.0], vec![0.0, 0.0]];
+        let merging = vec![vec![0.0, 0.0], vec![1e6, 0.0]];
+        let structured = StructuredCoalescent::new(vec![1.0, 1.0], isolated)
+            .add_epoch(5.0, vec![1.0, 1.0], merging);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let genealogy = structured.sample_genealogy(&[3, 2], &mut rng);
+            assert_eq!(genealogy.validate(), Ok(()));
+            assert!(genealogy.divergence(0, 4).unwrap() > 10.0);
+        }
+    }
+
+    #[test]
+    fn pulses() {
+        let isolated = vec![vec![0.0, 0.0], vec![0.0, 0.0]];
+        let structured = StructuredCoalescent::new(vec![1.0, 1.0], isolated)
+            .add_pulse(1.0, 1, 0, 1.0);
+
+        let mut rng = rand::thread_rng();
+        let genealogy = structured.sample_genealogy(&[1, 1], &mut rng);
+        assert!(genealogy.depth() > 1.0);
+    }
+
+    #[test]
+    fn admixture() {
+        // Large demes, so that lineages rarely meet before the pulse
+        let isolated = vec![vec![0.0, 0.0], vec![0.0, 0.0]];
+        let merging = vec![vec![0.0, 0.0], vec![1e6, 0.0]];
+        let structured = StructuredCoalescent::new(vec![1000.0, 1.0], isolated)
+            .add_epoch(5.0, vec![1.0, 1.0], merging)
+            .add_pulse(0.5, 0, 1, 0.3);
+
+        let mut rng = rand::thread_rng();
+        let replicates = 2000;
+        let mut admixed = 0;
+        for _ in 0..replicates {
+            let (genealogy, ancestry) = structured.sample_genealogy_with_ancestry(&[2, 0], &mut rng);
+            assert_eq!(genealogy.validate(), Ok(()));
+            for path in &ancestry {
+                assert_eq!(path.deme_at(0.4), 0);
+                if path.deme_at(1.0) == 1 {
+                    admixed += 1;
+                    assert_eq!(path.moves[1].0, 0.5);
+                }
+            }
+            // Ancestors are the same after the common ancestor
+            let depth = genealogy.depth();
+            assert_eq!(ancestry[0].deme_at(depth + 1.0), ancestry[1].deme_at(depth + 1.0));
+        }
+        assert!((admixed as f64 / (2 * replicates) as f64 - 0.3).abs() < 0.03);
+    }
+
+    #[test]
+    fn growing_demes() {
+        // A pair in a deme growing at rate one meets after e E1(1) on average
+        let structured = StructuredCoalescent::new(vec![1.0, 1.0], vec![vec![0.0; 2]; 2])
+            .with_growth_rates(vec![1.0, -1.0]);
+        let samples = 10_000;
+        let mut rng = rand::thread_rng();
+        let depth = (0..samples)
+            .map(|_| structured.sample_genealogy(&[2, 0], &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+        assert!((depth - 0.596_35).abs() < 0.02, "{}", depth);
+
+        // Sizes follow each epoch
+        let structured = structured.add_epoch(2.0, vec![3.0, 1.0], vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+        assert!((structured.size_at(0, 1.0) - (-1.0f64).exp()).abs() < 1e-12);
+        assert!((structured.size_at(1, 1.0) - 1f64.exp()).abs() < 1e-12);
+        assert_eq!(structured.size_at(0, 5.0), 3.0);
+        let genealogy = structured.sample_genealogy(&[3, 3], &mut rng);
+        assert_eq!(genealogy.validate(), Ok(()));
+    }
+
+    #[test]
+    fn annotated_moves() {
+        let structured = StructuredCoalescent::secondary_contact(2.0, 1.0, 1.0);
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let annotated = structured.sample_annotated_genealogy(&[3, 2], &mut rng);
+            let (parents, _) = annotated.genealogy().nodes();
+            let deme = |annotations: &[(String, String)]| annotations[0].1.clone();
+            for leaf in 0..5 {
+                assert_eq!(deme(annotated.annotations(leaf)), if leaf < 3 { "0" } else { "1" });
+            }
+
+            // The deme of a parent is the one reached by the last move below it
+            for (node, &parent) in parents.iter().enumerate().filter(|&(node, &parent)| node != parent) {
+                let last = annotated.unary_nodes(node).last().map(|unary_node| &unary_node.annotations[..]);
+                assert_eq!(deme(last.unwrap_or(annotated.annotations(node))), deme(annotated.annotations(parent)));
+            }
+        }
+    }
+
+    #[test]
+    fn secondary_contact() {
+        // Without contact, individuals of different demes meet on average ν after the split
+        let mut rng = rand::thread_rng();
+        let samples = 4000;
+        let isolation = SecondaryContact::new(2.0, 0.0).ancestral_size(0.5).build();
+        let depth = (0..samples)
+            .map(|_| isolation.sample_genealogy(&[1, 1], &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+        assert!((depth - 2.5).abs() < 0.05, "{}", depth);
+
+        // Contact brings them together earlier, lineages only moving during the contact and at the split
+        let contact = SecondaryContact::new(2.0, 1.0).ancestral_size(0.5).migration([1.0, 1.0]).build();
+        let (mut depth, mut admixed) = (0.0, 0);
+        for _ in 0..samples {
+            let (genealogy, ancestry) = contact.sample_genealogy_with_ancestry(&[1, 1], &mut rng);
+            depth += genealogy.depth() / samples as f64;
+            assert!(ancestry.iter().all(|path| path.moves.iter().all(|&(time, _)| time < 1.0 || time == 2.0)));
+            admixed += (ancestry[0].deme_at(1.5) == 1) as usize;
+        }
+        assert!(depth < 2.3, "{}", depth);
+        assert!(admixed > 0);
+        let symmetric = SecondaryContact::new(2.0, 1.0).migration([1.0, 1.0]).build();
+        assert_eq!(StructuredCoalescent::secondary_contact(2.0, 1.0, 1.0), symmetric);
+    }
+}
@@ -0,0 +1,585 @@
+//! Simulation studies over a grid of parameters.
+//!
+//! Runs many replicates for every combination of group size, mutation rate θ
+//! and growth rate, and computes chosen statistics of each replicate.
+//! Results are tidy: one record, or one row of CSV, per replicate.
+//!
+//! Statistics are chosen among the built-in ``Statistic`` or any implementation of
+//! ``SummaryStatistic``, and can be looked up by name in a ``StatisticRegistry``,
+//! e.g. to select them from a configuration file, see the ``config`` module.
+//!
+//! Large studies can also be swept in parallel, one cell of the grid per task, keeping
+//! only the mean and variance of each statistic in each cell. Each cell draws from its
+//! own stream of ``Pcg32``, so that summaries only depend on the seed, not on the
+//! number of threads.
+//!
+
+// Types
+use crate::{Coalescent, Demography, Genealogy, Haplotypes, MutationModel};
+use rand_pcg::Pcg32;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Traits
+use rand::Rng;
+use std::io::Write;
+
+/// Statistic computed on each replicate of a simulation study.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Statistic {
+    /// Depth of the genealogy.
+    Depth,
+    /// Total length of the genealogy.
+    Length,
+    /// Mean divergence between pairs of individuals.
+    MeanPairwiseDivergence,
+    /// Number of segregating sites.
+    SegregatingSites,
+    /// Mean number of differences between pairs of individuals.
+    PairwiseDifferences,
+}
+
+impl Statistic {
+    /// All statistics, in the order of their declaration.
+    pub const ALL: [Statistic; 5] = [
+        Statistic::Depth,
+        Statistic::Length,
+        Statistic::MeanPairwiseDivergence,
+        Statistic::SegregatingSites,
+        Statistic::PairwiseDifferences,
+    ];
+
+    /// Name of the statistic, used as column name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Statistic::Depth => "depth",
+            Statistic::Length => "length",
+            Statistic::MeanPairwiseDivergence => "mean_pairwise_divergence",
+            Statistic::SegregatingSites => "segregating_sites",
+            Statistic::PairwiseDifferences => "pairwise_differences",
+        }
+    }
+
+    pub(crate) fn compute(&self, genealogy: &Genealogy, haplotypes: &Haplotypes) -> f64 {
+        match self {
+            Statistic::Depth => genealogy.depth(),
+            Statistic::Length => genealogy.length(),
+            Statistic::MeanPairwiseDivergence => genealogy.mean_pairwise_divergence(),
+            Statistic::SegregatingSites => haplotypes.segregating_sites() as f64,
+            Statistic::PairwiseDifferences => haplotypes.mean_pairwise_differences(),
+        }
+    }
+}
+
+/// Statistic of a replicate, computed from its genealogy and the haplotypes of
+/// the mutations dropped on it.
+///
+/// # Examples
+///
+/// A custom statistic, computed in a study by name.
+/// ```
+/// use coalescence::{Genealogy, Haplotypes, SimulationStudy, StatisticRegistry, SummaryStatistic};
+///
+/// struct Sackin;
+///
+/// impl SummaryStatistic for Sackin {
+///     fn name(&self) -> &str {
+///         "sackin"
+///     }
+///
+///     fn compute(&self, genealogy: &Genealogy, _haplotypes: &Haplotypes) -> f64 {
+///         genealogy.sackin() as f64
+///     }
+/// }
+///
+/// let mut registry = StatisticRegistry::new();
+/// registry.register(Sackin).unwrap();
+/// let statistics = registry.select(&["depth", "sackin"]).unwrap();
+///
+/// let study = SimulationStudy::new(vec![4], 10).summary_statistics(statistics);
+/// let records = study.run(&mut rand::thread_rng());
+/// assert!(records.iter().all(|record| record.values[1] == 8.0 || record.values[1] == 9.0));
+/// ```
+pub trait SummaryStatistic: Send + Sync {
+    /// Name of the statistic, used as column name.
+    fn name(&self) -> &str;
+
+    /// Value of the statistic on a replicate.
+    fn compute(&self, genealogy: &Genealogy, haplotypes: &Haplotypes) -> f64;
+}
+
+impl SummaryStatistic for Statistic {
+    fn name(&self) -> &str {
+        Statistic::name(self)
+    }
+
+    fn compute(&self, genealogy: &Genealogy, haplotypes: &Haplotypes) -> f64 {
+        Statistic::compute(self, genealogy, haplotypes)
+    }
+}
+
+/// Error when looking up statistics by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// No statistic has the name.
+    Unknown(String),
+    /// Some statistic already has the name.
+    Duplicate(String),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RegistryError::Unknown(name) => write!(f, "unknown statistic {}", name),
+            RegistryError::Duplicate(name) => write!(f, "a statistic named {} is already registered", name),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Statistics by name, starting with the built-in ones, see ``Statistic``.
+#[derive(Clone)]
+pub struct StatisticRegistry {
+    statistics: Vec<Arc<dyn SummaryStatistic>>,
+}
+
+impl Default for StatisticRegistry {
+    fn default() -> Self {
+        let statistics = Statistic::ALL.iter().map(|&statistic| Arc::new(statistic) as Arc<dyn SummaryStatistic>).collect();
+        StatisticRegistry { statistics }
+    }
+}
+
+impl std::fmt::Debug for StatisticRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("StatisticRegistry").field("names", &self.names()).finish()
+    }
+}
+
+impl StatisticRegistry {
+    /// Creates a registry of the built-in statistics.
+    pub fn new() -> Self {
+        StatisticRegistry::default()
+    }
+
+    /// Adds a statistic.
+    ///
+    /// # Errors
+    ///
+    /// If some statistic already has its name.
+    pub fn register<S>(&mut self, statistic: S) -> Result<(), RegistryError>
+    where
+        S: SummaryStatistic + 'static,
+    {
+        if self.get(statistic.name()).is_some() {
+            return Err(RegistryError::Duplicate(statistic.name().to_string()));
+        }
+        self.statistics.push(Arc::new(statistic));
+        Ok(())
+    }
+
+    /// Statistic of the given name.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn SummaryStatistic>> {
+        self.statistics.iter().find(|statistic| statistic.name() == name).cloned()
+    }
+
+    /// Statistics of the given names, in order.
+    ///
+    /// # Errors
+    ///
+    /// If some name is unknown.
+    pub fn select<S>(&self, names: &[S]) -> Result<Vec<Arc<dyn SummaryStatistic>>, RegistryError>
+    where
+        S: AsRef<str>,
+    {
+        names
+            .iter()
+            .map(|name| self.get(name.as_ref()).ok_or_else(|| RegistryError::Unknown(name.as_ref().to_string())))
+            .collect()
+    }
+
+    /// Names of all statistics, in order of registration.
+    pub fn names(&self) -> Vec<&str> {
+        self.statistics.iter().map(|statistic| statistic.name()).collect()
+    }
+}
+
+/// Statistics of one replicate of a simulation study,
+/// in the same order as the statistics of the study.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub group_size: usize,
+    pub theta: f64,
+    pub growth_rate: f64,
+    pub replicate: usize,
+    pub values: Vec<f64>,
+}
+
+/// Mean and variance of each statistic over the replicates of a cell of the grid,
+/// in the same order as the statistics of the study.
+///
+/// Variances are unbiased, and not a number with a single replicate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellSummary {
+    pub group_size: usize,
+    pub theta: f64,
+    pub growth_rate: f64,
+    pub replicates: usize,
+    pub means: Vec<f64>,
+    pub variances: Vec<f64>,
+}
+
+/// Progress of a sweep, reported after each cell, e.g. to advance a progress bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Simulation study over a grid of parameters.
+///
+/// By default, there are no mutations, the population has constant size
+/// and the statistics are depth, length and mean pairwise divergence.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::{SimulationStudy, Statistic};
+///
+/// let study = SimulationStudy::new(vec![5, 10], 3)
+///     .thetas(vec![1.0, 2.0])
+///     .growth_rates(vec![0.0, 1.0])
+///     .statistics(vec![Statistic::Depth, Statistic::SegregatingSites]);
+///
+/// let mut csv = Vec::new();
+/// study.write_csv(&mut csv, &mut rand::thread_rng()).unwrap();
+/// let csv = String::from_utf8(csv).unwrap();
+///
+/// assert_eq!(csv.lines().next(), Some("group_size,theta,growth_rate,replicate,depth,segregating_sites"));
+/// assert_eq!(csv.lines().count(), 1 + 2 * 2 * 2 * 3);
+/// ```
+#[derive(Clone)]
+pub struct SimulationStudy {
+    group_sizes: Vec<usize>,
+    replicates: usize,
+    thetas: Vec<f64>,
+    growth_rates: Vec<f64>,
+    statistics: Vec<Arc<dyn SummaryStatistic>>,
+}
+
+impl std::fmt::Debug for SimulationStudy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SimulationStudy")
+            .field("group_sizes", &self.group_sizes)
+            .field("replicates", &self.replicates)
+            .field("thetas", &self.thetas)
+            .field("growth_rates", &self.growth_rates)
+            .field("statistics", &self.statistic_names())
+            .finish()
+    }
+}
+
+/// Studies are equal if they have the same grid and statistics of the same names.
+impl PartialEq for SimulationStudy {
+    fn eq(&self, other: &Self) -> bool {
+        self.group_sizes == other.group_sizes
+            && self.replicates == other.replicates
+            && self.thetas == other.thetas
+            && self.growth_rates == other.growth_rates
+            && self.statistic_names() == other.statistic_names()
+    }
+}
+
+impl SimulationStudy {
+    /// Creates a new study with ``replicates`` replicates for each group size.
+    pub fn new(group_sizes: Vec<usize>, replicates: usize) -> Self {
+        SimulationStudy {
+            group_sizes,
+            replicates,
+            thetas: vec![0.0],
+            growth_rates: vec![0.0],
+            statistics: vec![Arc::new(Statistic::Depth), Arc::new(Statistic::Length), Arc::new(Statistic::MeanPairwiseDivergence)],
+        }
+    }
+
+    /// Mutation rates θ of the grid.
+    pub fn thetas(mut self, thetas: Vec<f64>) -> Self {
+        self.thetas = thetas;
+        self
+    }
+
+    /// Exponential growth rates of the grid.
+    pub fn growth_rates(mut self, growth_rates: Vec<f64>) -> Self {
+        self.growth_rates = growth_rates;
+        self
+    }
+
+    /// Statistics computed on each replicate.
+    pub fn statistics(mut self, statistics: Vec<Statistic>) -> Self {
+        self.statistics = statistics.into_iter().map(|statistic| Arc::new(statistic) as Arc<dyn SummaryStatistic>).collect();
+        self
+    }
+
+    /// Statistics computed on each replicate, including custom ones, see ``SummaryStatistic``.
+    pub fn summary_statistics(mut self, statistics: Vec<Arc<dyn SummaryStatistic>>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Names of the statistics computed on each replicate, in order.
+    pub fn statistic_names(&self) -> Vec<&str> {
+        self.statistics.iter().map(|statistic| statistic.name()).collect()
+    }
+
+    /// Run the study and collect all records.
+    pub fn run<R>(&self, rng: &mut R) -> Vec<Record>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut records = Vec::new();
+        self.for_each_record(rng, |record| records.push(record));
+        records
+    }
+
+    /// Run the study, writing each record as a row in CSV format, after a header row.
+    pub fn write_csv<W, R>(&self, mut writer: W, rng: &mut R) -> std::io::Result<()>
+    where
+        W: Write,
+        R: Rng + ?Sized,
+    {
+        writeln!(writer, "group_size,theta,growth_rate,replicate,{}", self.statistic_names().join(","))?;
+
+        let mut result = Ok(());
+        self.for_each_record(rng, |record| {
+            if result.is_ok() {
+                let values: Vec<String> = record.values.iter().map(|value| value.to_string()).collect();
+                result = writeln!(
+                    writer,
+                    "{},{},{},{},{}",
+                    record.group_size,
+                    record.theta,
+                    record.growth_rate,
+                    record.replicate,
+                    values.join(",")
+                );
+            }
+        });
+        result
+    }
+
+    /// Run the study, passing each record to ``f`` as soon as it is computed, so that
+    /// large studies can be streamed to any output without keeping their records.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::SimulationStudy;
+    ///
+    /// let study = SimulationStudy::new(vec![10], 100);
+    /// let mut deepest: f64 = 0.0;
+    /// study.for_each_record(&mut rand::thread_rng(), |record| deepest = deepest.max(record.values[0]));
+    ///
+    /// assert!(deepest > 0.0);
+    /// ```
+    pub fn for_each_record<R, F>(&self, rng: &mut R, mut f: F)
+    where
+        R: Rng + ?Sized,
+        F: FnMut(Record),
+    {
+        for cell in self.cells() {
+            self.for_each_record_of(cell, rng, &mut f);
+        }
+    }
+
+    /// Run the study in parallel over the cells of the grid, on as many threads as
+    /// available, and summarize each cell, in the order of ``run``. Each cell draws from
+    /// the stream of ``Pcg32`` of its index, with the given seed, and ``progress`` is
+    /// called from the worker threads after each cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::{SimulationStudy, Statistic};
+    /// use std::sync::Mutex;
+    ///
+    /// let study = SimulationStudy::new(vec![5, 10, 20], 200)
+    ///     .growth_rates(vec![0.0, 10.0])
+    ///     .statistics(vec![Statistic::Depth]);
+    ///
+    /// let reports = Mutex::new(Vec::new());
+    /// let summaries = study.sweep_parameters(7, |progress| reports.lock().unwrap().push(progress));
+    ///
+    /// let reports = reports.into_inner().unwrap();
+    /// assert_eq!(reports.len(), 3 * 2);
+    /// assert!(reports.iter().all(|progress| progress.total == 3 * 2));
+    /// assert!(reports.iter().any(|progress| progress.completed == 3 * 2));
+    /// assert_eq!(summaries.len(), 3 * 2);
+    /// assert_eq!((summaries[1].group_size, summaries[1].growth_rate), (5, 10.0));
+    /// assert!(summaries[1].means[0] < summaries[0].means[0]);
+    /// assert_eq!(summaries, study.sweep_parameters(7, |_| ()));
+    /// ```
+    pub fn sweep_parameters<F>(&self, seed: u64, progress: F) -> Vec<CellSummary>
+    where
+        F: Fn(Progress) + Sync,
+    {
+        let cells = self.cells();
+        let total = cells.len();
+        let threads = std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1).min(total);
+
+        // Workers take the next cell until there are none left
+        let next = AtomicUsize::new(0);
+        let completed = AtomicUsize::new(0);
+        let summaries = Mutex::new(vec![None; total]);
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= total {
+                        break;
+                    }
+
+                    let summary = self.summarize(cells[index], &mut Pcg32::new(seed, index as u64));
+                    summaries.lock().unwrap()[index] = Some(summary);
+                    progress(Progress { completed: completed.fetch_add(1, Ordering::Relaxed) + 1, total });
+                });
+            }
+        });
+
+        summaries.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Cells of the grid, as group size, growth rate and θ, in the order of ``run``.
+    fn cells(&self) -> Vec<(usize, f64, f64)> {
+        let mut cells = Vec::new();
+        for &group_size in &self.group_sizes {
+            for &growth_rate in &self.growth_rates {
+                for &theta in &self.thetas {
+                    cells.push((group_size, growth_rate, theta));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Run the replicates of a cell of the grid.
+    fn for_each_record_of<R, F>(&self, (group_size, growth_rate, theta): (usize, f64, f64), rng: &mut R, f: &mut F)
+    where
+        R: Rng + ?Sized,
+        F: FnMut(Record),
+    {
+        let mut coalescent = Coalescent::sampler(group_size);
+        coalescent.set_demography(Demography::exponential(growth_rate));
+
+        for replicate in 0..self.replicates {
+            let genealogy = coalescent.sample_genealogy(rng);
+            let haplotypes = genealogy.sample_haplotypes(MutationModel::Poisson { theta }, rng);
+            let values = self.statistics
+                .iter()
+                .map(|statistic| statistic.compute(&genealogy, &haplotypes))
+                .collect();
+
+            f(Record { group_size, theta, growth_rate, replicate, values });
+        }
+    }
+
+    /// Mean and variance of each statistic over the replicates of a cell.
+    fn summarize<R>(&self, cell: (usize, f64, f64), rng: &mut R) -> CellSummary
+    where
+        R: Rng + ?Sized,
+    {
+        let (group_size, growth_rate, theta) = cell;
+        let (mut sums, mut squares) = (vec![0.0; self.statistics.len()], vec![0.0; self.statistics.len()]);
+        self.for_each_record_of(cell, rng, &mut |record: Record| {
+            for (index, value) in record.values.iter().enumerate() {
+                sums[index] += value;
+                squares[index] += value * value;
+            }
+        });
+
+        let replicates = self.replicates as f64;
+        let means: Vec<f64> = sums.iter().map(|sum| sum / replicates).collect();
+        let variances = squares
+            .iter()
+            .zip(&means)
+            .map(|(square, mean)| ((square - replicates * mean * mean) / (replicates - 1.0)).max(0.0))
+            .map(|variance| if self.replicates > 1 { variance } else { f64::NAN })
+            .collect();
+
+        CellSummary { group_size, theta, growth_rate, replicates: self.replicates, means, variances }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let study = SimulationStudy::new(vec![4, 8], 5)
+            .thetas(vec![0.0, 3.0])
+            .statistics(vec![Statistic::SegregatingSites, Statistic::Depth]);
+        let records = study.run(&mut rand::thread_rng());
+
+        assert_eq!(records.len(), 2 * 2 * 5);
+        for record in &records {
+            assert_eq!(record.values.len(), 2);
+            if record.theta == 0.0 {
+                assert_eq!(record.values[0], 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_sweep() {
+        let study = SimulationStudy::new(vec![2, 6], 2000)
+            .thetas(vec![0.0, 4.0])
+            .statistics(vec![Statistic::Depth, Statistic::SegregatingSites]);
+        let reports = Mutex::new(Vec::new());
+        let summaries = study.sweep_parameters(1, |progress| reports.lock().unwrap().push(progress));
+
+        // One report per cell, each count once
+        let mut completed: Vec<usize> = reports.into_inner().unwrap().iter().map(|progress| progress.completed).collect();
+        completed.sort_unstable();
+        assert_eq!(completed, vec![1, 2, 3, 4]);
+
+        // The depth of a pair is exponential with mean and variance one
+        assert_eq!((summaries[0].group_size, summaries[0].theta), (2, 0.0));
+        assert!((summaries[0].means[0] - 1.0).abs() < 0.1);
+        assert!((summaries[0].variances[0] - 1.0).abs() < 0.2);
+        assert_eq!(summaries[0].means[1], 0.0);
+        assert!((summaries[1].means[1] - 4.0).abs() < 0.4);
+        assert!(summaries.iter().all(|summary| summary.replicates == 2000));
+
+        let single = SimulationStudy::new(vec![3], 1).sweep_parameters(0, |_| ());
+        assert!(single[0].variances.iter().all(|variance| variance.is_nan()));
+    }
+
+    #[test]
+    fn registry() {
+        struct Leaves;
+
+        impl SummaryStatistic for Leaves {
+            fn name(&self) -> &str {
+                "leaves"
+            }
+
+            fn compute(&self, genealogy: &Genealogy, _haplotypes: &Haplotypes) -> f64 {
+                genealogy.group_size() as f64
+            }
+        }
+
+        let mut registry = StatisticRegistry::new();
+        assert_eq!(registry.names().len(), Statistic::ALL.len());
+        registry.register(Leaves).unwrap();
+        assert_eq!(registry.register(Leaves), Err(RegistryError::Duplicate("leaves".to_string())));
+        assert_eq!(registry.register(Statistic::Depth), Err(RegistryError::Duplicate("depth".to_string())));
+        assert_eq!(registry.select(&["depth", "leafs"]).err(), Some(RegistryError::Unknown("leafs".to_string())));
+
+        let study = SimulationStudy::new(vec![3, 6], 2).summary_statistics(registry.select(&["leaves", "length"]).unwrap());
+        assert_eq!(study.statistic_names(), vec!["leaves", "length"]);
+        let records = study.run(&mut rand::thread_rng());
+        assert!(records.iter().all(|record| record.values[0] == record.group_size as f64));
+    }
+}
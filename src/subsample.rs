@@ -0,0 +1,169 @@
+//! Genealogies of subsamples of a large simulated panel.
+//!
+//! The genealogy of a subsample of individuals is induced by the genealogy of the
+//! whole panel: it keeps the events that merge at least two lineages ancestral to some
+//! individual of the subsample, each one with those lineages only. Simulating a panel once and inducing the genealogies
+//! of many random subsamples, in linear time each, is much faster than simulating
+//! each subsample, and keeps subsamples dependent as they are in a real panel, e.g.
+//! for studies of the variability due to sampling.
+//!
+
+// Types
+use crate::union_find::UnionFind;
+use crate::Genealogy;
+
+// Traits
+use rand::Rng;
+
+impl Genealogy {
+    /// Genealogy of the given individuals, which are labeled by their position in
+    /// ``individuals``, see the module documentation. Each branch keeps the identifier
+    /// of the branch of the panel above the same lower node, see ``branch``.
+    ///
+    /// # Panics
+    ///
+    /// If there are no individuals, or if some is out of bounds or repeated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let genealogy = coalescence::fixtures::balanced();
+    /// let induced = genealogy.induced(&[2, 0, 3]);
+    ///
+    /// assert_eq!(induced.group_size(), 3);
+    /// assert_eq!(induced.divergence(0, 2).unwrap(), genealogy.divergence(2, 3).unwrap());
+    /// assert_eq!(induced.depth(), genealogy.depth());
+    /// ```
+    pub fn induced(&self, individuals: &[usize]) -> Genealogy {
+        let group_size = self.group_size();
+        assert!(!individuals.is_empty(), "There must be some individuals.");
+        let mut labels: Vec<Option<usize>> = vec![None; group_size];
+        for (label, &individual) in individuals.iter().enumerate() {
+            assert!(individual < group_size, "Individuals must be in the group.");
+            assert!(labels[individual].is_none(), "Individuals must not be repeated.");
+            labels[individual] = Some(label);
+        }
+
+        // Label of a kept individual below each lineage, kept at its representative
+        let mut lineages = UnionFind::new(group_size);
+        let mut mergers = Vec::with_capacity(individuals.len() - 1);
+        let mut branch_ids: Vec<_> = individuals.iter().map(|&individual| self.branch_ids()[individual]).collect();
+        for (index, (time, indexes)) in self.mergers().enumerate() {
+            let merged: Vec<usize> = indexes.iter().map(|&index| lineages.find(index)).collect();
+            let kept: Vec<usize> = merged.iter().filter_map(|&lineage| labels[lineage]).collect();
+            let representative = merged.iter().fold(merged[0], |representative, &lineage| lineages.union(representative, lineage));
+            labels[representative] = kept.first().cloned();
+            if kept.len() >= 2 {
+                mergers.push((time, kept));
+                branch_ids.push(self.branch_ids()[group_size + index]);
+            }
+        }
+
+        // Each node keeps the identifier of the node of the panel it comes from
+        let sampling_times = individuals.iter().map(|&individual| self.sampling_times()[individual]).collect();
+        Genealogy::from_mergers(individuals.len(), &mergers)
+            .expect("Mergers of the panel are valid.")
+            .with_sampling_times(sampling_times)
+            .with_branch_ids(branch_ids)
+    }
+
+    /// Genealogy of ``size`` individuals drawn uniformly without replacement,
+    /// labeled in increasing order of their index in the panel.
+    ///
+    /// # Panics
+    ///
+    /// If ``size`` is zero or larger than the group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut rng = rand::thread_rng();
+    /// let panel = coalescence::Coalescent::sampler(1000).sample_genealogy(&mut rng);
+    ///
+    /// let depths: Vec<f64> = (0..100).map(|_| panel.subsample(10, &mut rng).depth()).collect();
+    ///
+    /// assert!(depths.iter().all(|&depth| depth <= panel.depth() + 1e-9));
+    /// ```
+    pub fn subsample<R>(&self, size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(size > 0 && size <= self.group_size(), "The subsample must be nonempty and fit in the group.");
+
+        let mut individuals = rand::seq::index::sample(rng, self.group_size(), size).into_vec();
+        individuals.sort_unstable();
+        self.induced(&individuals)
+    }
+
+    /// Genealogy of the individuals kept independently with probability ``probability``,
+    /// labeled in increasing order of their index in the panel, or ``None`` if none is kept.
+    ///
+    /// # Panics
+    ///
+    /// If ``probability`` is not in [0, 1].
+    pub fn binomial_subsample<R>(&self, probability: f64, rng: &mut R) -> Option<Genealogy>
+    where
+        R: Rng + ?Sized,
+    {
+        assert!((0.0..=1.0).contains(&probability), "The probability must be in [0, 1].");
+
+        let individuals: Vec<usize> = (0..self.group_size()).filter(|_| rng.gen_bool(probability)).collect();
+        match individuals.is_empty() {
+            true => None,
+            false => Some(self.induced(&individuals)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn induced_genealogies() {
+        let mut rng = rand::thread_rng();
+        let panel = crate::Coalescent::sampler(50).sample_genealogy(&mut rng);
+        let individuals = [40, 3, 17, 25, 8];
+        let induced = panel.induced(&individuals);
+        assert!(induced.validate().is_ok());
+        for (first, &individual) in individuals.iter().enumerate() {
+            for (second, &other) in individuals.iter().enumerate().skip(first + 1) {
+                assert!((induced.divergence(first, second).unwrap() - panel.divergence(individual, other).unwrap()).abs() < 1e-12);
+            }
+        }
+        assert_eq!(panel.induced(&[7]).depth(), 0.0);
+
+        // Branches start at the same node as the branch of the panel with their identifier
+        for branch in induced.branches() {
+            let original = panel.branch(branch.id).unwrap();
+            assert!((branch.start - original.start).abs() < 1e-12);
+            assert!(branch.leaves.iter().all(|&leaf| original.leaves.contains(&individuals[leaf])));
+        }
+
+        // Individuals sampled in the past keep their sampling time
+        let heterochronous = crate::fixtures::balanced().with_sampling_times(vec![0.0, 0.5, 0.0, 0.0]);
+        assert_eq!(heterochronous.induced(&[1, 2]).sampling_times(), &[0.5, 0.0]);
+
+        // Mergers keep the lineages of the subsample only
+        let mergers = crate::Genealogy::from_mergers(5, &[(1.0, vec![0, 1, 2]), (2.0, vec![3, 4, 0])]).unwrap();
+        let induced = mergers.induced(&[4, 2, 1]);
+        assert_eq!(induced.degrees(), vec![2, 2]);
+        assert_eq!(induced.divergence(1, 2), Ok(2.0));
+        assert_eq!(mergers.induced(&[0, 3, 2, 4]).degrees(), vec![2, 3]);
+    }
+
+    #[test]
+    fn subsamples_follow_the_coalescent() {
+        // The depth of a subsample of m individuals has mean 2 (1 - 1 / m)
+        let (size, samples) = (5, 4000);
+        let mut rng = rand::thread_rng();
+        let mut depth = 0.0;
+        for _ in 0..samples {
+            let panel = crate::Coalescent::sampler(40).sample_genealogy(&mut rng);
+            depth += panel.subsample(size, &mut rng).depth() / samples as f64;
+        }
+        assert!((depth - 1.6).abs() < 0.08, "{}", depth);
+
+        let panel = crate::Coalescent::sampler(40).sample_genealogy(&mut rng);
+        assert!(panel.binomial_subsample(0.0, &mut rng).is_none());
+        assert_eq!(panel.binomial_subsample(1.0, &mut rng).unwrap().group_size(), 40);
+    }
+}
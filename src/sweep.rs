@@ -0,0 +1,153 @@
+//! Selective sweeps.
+//!
+//! A beneficial allele with selection coefficient s arises in a single copy and
+//! increases in frequency until fixation, following the deterministic logistic
+//! trajectory dx/dt = α x (1 - x) with α = 2 N s, in units of 2N generations.
+//! Neutral loci linked to the selected site hitchhike with it: their genealogies
+//! are shortened, and more so the closer they are to the site.
+//!
+
+// Types
+use crate::{Genealogy, Trajectory};
+
+// Traits
+use rand::Rng;
+
+/// Number of intervals of constant frequency approximating the trajectory of a sweep.
+const TRAJECTORY_STEPS: usize = 1000;
+
+/// Complete selective sweep in a population of constant size.
+///
+/// # Examples
+///
+/// Without recombination, the genealogy of a sample at the end of a strong
+/// sweep is as short as the sweep itself.
+/// ```
+/// use coalescence::Sweep;
+///
+/// let sweep = Sweep::new(10_000.0, 0.01, 0.0, 0.0);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = sweep.sample_genealogy(10, &mut rng);
+///
+/// assert!(genealogy.depth() < 2.0 * sweep.duration());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    population_size: f64,
+    selection_coefficient: f64,
+    end: f64,
+    recombination_rate: f64,
+}
+
+impl Sweep {
+    /// Creates a new sweep in a population of ``population_size`` diploid individuals,
+    /// with a selection coefficient ``s``, that ended ``end`` units of time ago,
+    /// observed at a locus at recombination distance ρ = 4 N r from the selected site.
+    ///
+    /// # Panics
+    ///
+    /// If the population size is not larger than one, if the selection coefficient
+    /// is not positive, or if the end time or the recombination rate are negative.
+    pub fn new(population_size: f64, selection_coefficient: f64, end: f64, recombination_rate: f64) -> Self {
+        assert!(population_size > 1.0 && population_size.is_finite(), "The population size must be larger than one.");
+        assert!(
+            selection_coefficient > 0.0 && selection_coefficient.is_finite(),
+            "The selection coefficient must be positive."
+        );
+        assert!(end >= 0.0 && end.is_finite(), "The end of the sweep must be non-negative.");
+        assert!(
+            recombination_rate >= 0.0 && recombination_rate.is_finite(),
+            "The recombination rate must be non-negative."
+        );
+
+        Sweep { population_size, selection_coefficient, end, recombination_rate }
+    }
+
+    /// Scaled selection coefficient α = 2 N s.
+    pub fn alpha(&self) -> f64 {
+        2.0 * self.population_size * self.selection_coefficient
+    }
+
+    /// Time from the origin of the beneficial allele, at frequency 1 / (2N),
+    /// to its fixation, at frequency 1 - 1 / (2N).
+    pub fn duration(&self) -> f64 {
+        let epsilon = self.epsilon();
+        2.0 * ((1.0 - epsilon) / epsilon).ln() / self.alpha()
+    }
+
+    /// Frequency trajectory of the beneficial allele, backwards in time: fixed
+    /// until the end of the sweep, then logistic until its origin.
+    pub fn trajectory(&self) -> Trajectory {
+        let epsilon = self.epsilon();
+        let alpha = self.alpha();
+        let duration = self.duration();
+        let constant = epsilon / (1.0 - epsilon);
+
+        let mut times = Vec::with_capacity(TRAJECTORY_STEPS + 2);
+        let mut frequencies = Vec::with_capacity(TRAJECTORY_STEPS + 1);
+        if self.end > 0.0 {
+            times.push(0.0);
+            frequencies.push(1.0);
+        }
+
+        // Frequency at the middle of each interval, u units of time before fixation
+
+        let step = duration / TRAJECTORY_STEPS as f64;
+        for index in 0..TRAJECTORY_STEPS {
+            let u = (index as f64 + 0.5) * step;
+            times.push(self.end + index as f64 * step);
+            frequencies.push(1.0 / (1.0 + constant * (alpha * u).exp()));
+        }
+        times.push(self.end + duration);
+
+        Trajectory::new(times, frequencies)
+    }
+
+    /// Sample the genealogy of ``group_size`` individuals at the linked locus.
+    pub fn sample_genealogy<R>(&self, group_size: usize, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        // At present, every individual carries the beneficial allele (with probability 1 - 1 / (2N)
+        // if the sweep just ended)
+
+        self.trajectory().sample_genealogy(group_size, 0, self.recombination_rate, rng)
+    }
+
+    fn epsilon(&self) -> f64 {
+        1.0 / (2.0 * self.population_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hitchhiking() {
+        let samples = 500;
+        let mut rng = rand::thread_rng();
+        let mut mean_depth = |recombination_rate: f64| {
+            let sweep = Sweep::new(10_000.0, 0.01, 0.0, recombination_rate);
+            (0..samples)
+                .map(|_| sweep.sample_genealogy(2, &mut rng).depth())
+                .sum::<f64>() / samples as f64
+        };
+
+        // Close to the selected site, genealogies are as short as the sweep,
+        // and far from it they are not affected.
+        assert!(mean_depth(0.0) < 0.2);
+        assert!(mean_depth(10_000.0) > 0.8);
+    }
+
+    #[test]
+    fn trajectory() {
+        let sweep = Sweep::new(1000.0, 0.05, 0.5, 1.0);
+        let trajectory = sweep.trajectory();
+
+        assert_eq!(trajectory.frequency_at(0.2), 1.0);
+        assert!((trajectory.origin() - 0.5 - sweep.duration()).abs() < 1e-12);
+        assert!(trajectory.frequency_at(0.5 + sweep.duration() * 0.99) < 0.01);
+    }
+}
@@ -0,0 +1,174 @@
+//! Genealogies as tables of nodes and edges, following the table model of tskit.
+//!
+//! Nodes are numbered as in ``branches`` on Genealogy: individuals first, then common
+//! ancestors in the order of their events, so that the root is the last one. Each row
+//! of the node table has the time of the node in the past, whether it is a sampled
+//! individual, and its deme if known. Each row of the edge table joins a parent to one
+//! of its children, over the whole locus, i.e. the interval [0, 1) of the sequence, and
+//! edges are sorted by the time of their parent, as tskit requires.
+//!
+//! Both tables are written as CSV files with a header, with an empty field for unknown
+//! demes, and can be loaded into a ``tskit.TableCollection`` of sequence length one.
+//!
+//! # Examples
+//!
+//! ```
+//! let (nodes, edges) = coalescence::fixtures::balanced().tables();
+//!
+//! let mut csv = Vec::new();
+//! edges.write_csv(&mut csv).unwrap();
+//! let csv = String::from_utf8(csv).unwrap();
+//!
+//! assert_eq!(nodes.len(), 7);
+//! assert_eq!(csv.lines().next(), Some("id,left,right,parent,child"));
+//! assert_eq!(csv.lines().nth(1), Some("0,0,1,4,0"));
+//! ```
+//!
+
+// Types
+use crate::{AnnotatedGenealogy, Genealogy};
+
+// Traits
+use std::io::Write;
+
+/// Nodes of a genealogy, by columns, the identifier of a node being its row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeTable {
+    pub times: Vec<f64>,
+    pub is_sample: Vec<bool>,
+    pub demes: Vec<Option<usize>>,
+}
+
+/// Edges of a genealogy, by columns, over the whole locus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdgeTable {
+    pub parents: Vec<usize>,
+    pub children: Vec<usize>,
+}
+
+impl NodeTable {
+    /// Number of nodes.
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Whether there are no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Write the table in CSV format, with the header ``id,is_sample,time,deme``.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "id,is_sample,time,deme")?;
+        for (id, ((time, is_sample), deme)) in self.times.iter().zip(&self.is_sample).zip(&self.demes).enumerate() {
+            let deme = deme.map(|deme| deme.to_string()).unwrap_or_default();
+            writeln!(writer, "{},{},{},{}", id, *is_sample as u8, time, deme)?;
+        }
+        Ok(())
+    }
+}
+
+impl EdgeTable {
+    /// Number of edges.
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Whether there are no edges.
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// Write the table in CSV format, with the header ``id,left,right,parent,child``.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "id,left,right,parent,child")?;
+        for (id, (parent, child)) in self.parents.iter().zip(&self.children).enumerate() {
+            writeln!(writer, "{},0,1,{},{}", id, parent, child)?;
+        }
+        Ok(())
+    }
+}
+
+impl Genealogy {
+    /// Tables of nodes and edges, without demes, see the module documentation.
+    pub fn tables(&self) -> (NodeTable, EdgeTable) {
+        let (parents, times) = self.nodes();
+        let group_size = self.group_size();
+        let nodes = NodeTable {
+            is_sample: (0..parents.len()).map(|node| node < group_size).collect(),
+            demes: vec![None; parents.len()],
+            times: times.to_vec(),
+        };
+
+        // Parents are numbered in the order of their times
+        let mut pairs: Vec<(usize, usize)> = parents
+            .iter()
+            .enumerate()
+            .filter(|&(child, &parent)| child != parent)
+            .map(|(child, &parent)| (parent, child))
+            .collect();
+        pairs.sort_unstable();
+        let edges = EdgeTable {
+            parents: pairs.iter().map(|&(parent, _)| parent).collect(),
+            children: pairs.iter().map(|&(_, child)| child).collect(),
+        };
+
+        (nodes, edges)
+    }
+}
+
+impl AnnotatedGenealogy {
+    /// Tables of nodes and edges, with the demes of nodes read from their annotation
+    /// ``"deme"``, as in ``sample_annotated_genealogy`` on StructuredCoalescent. Unary
+    /// nodes are left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::StructuredCoalescent;
+    ///
+    /// let structured = StructuredCoalescent::secondary_contact(2.0, 0.5, 1.0);
+    /// let annotated = structured.sample_annotated_genealogy(&[2, 1], &mut rand::thread_rng());
+    /// let (nodes, _) = annotated.tables();
+    ///
+    /// assert_eq!(&nodes.demes[..3], &[Some(0), Some(0), Some(1)]);
+    /// ```
+    pub fn tables(&self) -> (NodeTable, EdgeTable) {
+        let (mut nodes, edges) = self.genealogy().tables();
+        for (node, deme) in nodes.demes.iter_mut().enumerate() {
+            *deme = self
+                .annotations(node)
+                .iter()
+                .find(|(key, _)| key == "deme")
+                .and_then(|(_, value)| value.parse().ok());
+        }
+
+        (nodes, edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables() {
+        let genealogy = crate::Genealogy::from_events(3, &[(0.5, [1, 2]), (2.0, [0, 2])]).unwrap();
+        let (nodes, edges) = genealogy.tables();
+        assert_eq!(nodes.times, vec![0.0, 0.0, 0.0, 0.5, 2.0]);
+        assert_eq!(nodes.is_sample, vec![true, true, true, false, false]);
+        assert_eq!(edges.parents, vec![3, 3, 4, 4]);
+        assert_eq!(edges.children, vec![1, 2, 0, 3]);
+
+        let mut annotated = AnnotatedGenealogy::from(genealogy);
+        annotated.annotate(4, "deme", "2").annotate(0, "deme", "unknown");
+        let (nodes, _) = annotated.tables();
+        let mut csv = Vec::new();
+        nodes.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "id,is_sample,time,deme\n0,1,0,\n1,1,0,\n2,1,0,\n3,0,0.5,\n4,0,2,2\n");
+
+        let (nodes, edges) = crate::Genealogy::from_events(1, &[]).unwrap().tables();
+        assert_eq!(nodes.len(), 1);
+        assert!(edges.is_empty());
+    }
+}
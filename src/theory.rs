@@ -0,0 +1,463 @@
+//! Analytic results for the coalescent process.
+//!
+//! Exact formulas for quantities of the n-coalescent, useful to validate
+//! simulations and for deterministic calculations. Time is measured in the
+//! same units as in the simulations: each pair of lineages coalesces at rate one.
+//!
+
+/// Largest absolute error allowed in the alternating sum of Tavaré's formula.
+const TOLERANCE: f64 = 1e-12;
+
+/// Expected number of jumps of the uniformized chain in each time step.
+const JUMPS_PER_STEP: f64 = 16.0;
+
+/// Probabilities below this value are discarded in the uniformized chain.
+const NEGLIGIBLE: f64 = 1e-30;
+
+/// Half the number of intervals of Simpson's rule in numerical integrals over time.
+const INTEGRATION_INTERVALS: usize = 100;
+
+/// Probability that a group of ``n`` individuals has exactly ``k`` ancestral
+/// lineages at time ``t`` in the past.
+///
+/// # Remarks
+///
+/// Tavaré's formula (1984) is an alternating sum which suffers from
+/// catastrophic cancellation in floating point arithmetic, typically for
+/// large ``n`` and small ``t``. When its error can not be guaranteed to be small,
+/// the probability is computed instead by uniformization of the Markov chain
+/// of the number of lineages, which only adds positive terms and takes time
+/// O(n²).
+///
+/// # Panics
+///
+/// If ``n`` is zero or ``t`` is negative.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::lineages_probability;
+///
+/// // Two lineages have not coalesced after time t with probability exp(-t)
+/// let t = 0.5;
+/// assert!((lineages_probability(2, 2, t) - (-t).exp()).abs() < 1e-12);
+///
+/// // It is stable for large groups
+/// let p = lineages_probability(1000, 20, 0.1);
+/// assert!(0.0 < p && p < 1.0);
+/// ```
+pub fn lineages_probability(n: usize, k: usize, t: f64) -> f64 {
+    assert!(n > 0, "The group must be non-empty.");
+    assert!(t >= 0.0, "Time must be non-negative.");
+
+    if k == 0 || k > n {
+        0.0
+    } else if t == 0.0 || t.is_infinite() {
+        let trivial = if t == 0.0 { n } else { 1 };
+        (k == trivial) as u32 as f64
+    } else {
+        let log_factorials = log_factorials(2 * n);
+        match tavare(&log_factorials, n, k, t) {
+            Some(probability) => probability,
+            None => uniformization(n, t)[k],
+        }
+    }
+}
+
+/// Distribution of the number of ancestral lineages of a group of ``n``
+/// individuals at time ``t`` in the past.
+///
+/// The entry ``k`` is the probability of exactly ``k`` lineages, for ``k``
+/// from 0 to n, so that the first entry is always zero.
+/// See [lineages_probability](fn.lineages_probability.html) for remarks on the computation.
+///
+/// # Panics
+///
+/// If ``n`` is zero or ``t`` is negative.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::lineages_distribution;
+///
+/// let distribution = lineages_distribution(50, 0.1);
+///
+/// assert_eq!(distribution.len(), 51);
+/// assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+/// ```
+pub fn lineages_distribution(n: usize, t: f64) -> Vec<f64> {
+    assert!(n > 0, "The group must be non-empty.");
+    assert!(t >= 0.0, "Time must be non-negative.");
+
+    if t == 0.0 || t.is_infinite() {
+        return (0..=n).map(|k| lineages_probability(n, k, t)).collect();
+    }
+
+    // Exact formula when it is accurate
+
+    let log_factorials = log_factorials(2 * n);
+    let mut distribution: Vec<Option<f64>> = (0..=n)
+        .map(|k| match k {
+            0 => Some(0.0),
+            _ => tavare(&log_factorials, n, k, t),
+        })
+        .collect();
+
+    // Uniformization otherwise
+
+    if distribution.iter().any(|p| p.is_none()) {
+        let uniformized = uniformization(n, t);
+        for k in 1..=n {
+            if distribution[k].is_none() {
+                distribution[k] = Some(uniformized[k]);
+            }
+        }
+    }
+
+    distribution.into_iter().map(|p| p.unwrap()).collect()
+}
+
+/// Expected number of ancestral lineages of a group of ``n`` individuals
+/// at time ``t`` in the past.
+///
+/// All terms in the formula are positive, so it is numerically stable.
+///
+/// # Panics
+///
+/// If ``n`` is zero or ``t`` is negative.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::expected_lineages;
+///
+/// assert_eq!(expected_lineages(10, 0.0), 10.0);
+/// assert!((expected_lineages(2, 1.0) - (1.0 + (-1.0_f64).exp())).abs() < 1e-12);
+/// ```
+pub fn expected_lineages(n: usize, t: f64) -> f64 {
+    assert!(n > 0, "The group must be non-empty.");
+    assert!(t >= 0.0, "Time must be non-negative.");
+
+    if t == 0.0 {
+        return n as f64;
+    }
+
+    let log_factorials = log_factorials(2 * n);
+    (1..=n)
+        .map(|j| {
+            // n_[j] / n_(j), i.e. falling over rising factorial
+            let log_ratio = (log_factorials[n] - log_factorials[n - j])
+                - (log_factorials[n + j - 1] - log_factorials[n - 1]);
+            (2 * j - 1) as f64 * (-rate(j) * t + log_ratio).exp()
+        })
+        .sum()
+}
+
+/// Distribution of the number of ancestral lineages at time ``s`` of a group of ``n``
+/// individuals, conditional on having exactly ``k`` lineages at a later time ``t``,
+/// with 0 ≤ s ≤ t. The entry ``j`` is the probability of exactly ``j`` lineages.
+///
+/// By the Markov property, it is proportional to P_n(j at s) P_j(k at t - s).
+///
+/// # Panics
+///
+/// If ``n`` is zero, if ``s`` is not between zero and ``t``, or if ``k`` lineages
+/// at time ``t`` are impossible.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::conditional_lineages_distribution;
+///
+/// let distribution = conditional_lineages_distribution(10, 3, 0.2, 0.5);
+///
+/// assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+/// assert!(distribution[..3].iter().all(|&p| p == 0.0));
+/// ```
+pub fn conditional_lineages_distribution(n: usize, k: usize, s: f64, t: f64) -> Vec<f64> {
+    assert!(n > 0, "The group must be non-empty.");
+    assert!(0.0 <= s && s <= t, "The intermediate time must be between zero and the time of observation.");
+
+    let before = lineages_distribution(n, s);
+    let mut distribution: Vec<f64> = before
+        .iter()
+        .enumerate()
+        .map(|(j, p)| match j >= k && *p > 0.0 {
+            true => p * lineages_probability(j, k, t - s),
+            false => 0.0,
+        })
+        .collect();
+    let total: f64 = distribution.iter().sum();
+    assert!(total > 0.0, "There can not be {} lineages at time {}.", k, t);
+
+    for p in distribution.iter_mut() {
+        *p /= total;
+    }
+    distribution
+}
+
+/// Expected length of the genealogy of ``n`` individuals, conditional on having
+/// exactly ``k`` ancestral lineages at time ``t``, as observed at a sampling time
+/// in serially sampled or epidemic data.
+///
+/// After time ``t``, the k lineages form a k-coalescent, of expected length
+/// 2 (1 + 1/2 + ... + 1/(k - 1)). Before, the expected number of lineages, without
+/// counting the single lineage above the root, is integrated numerically.
+///
+/// # Panics
+///
+/// If ``n`` is zero, if ``t`` is negative or not finite, or if ``k`` lineages at
+/// time ``t`` are impossible.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::conditional_expected_length;
+///
+/// // Without conditioning on time, two lineages have expected length 2
+/// assert!((conditional_expected_length(2, 2, 0.0) - 2.0).abs() < 1e-12);
+/// // Two lineages that did not coalesce before time t
+/// assert!((conditional_expected_length(2, 2, 1.5) - (2.0 * 1.5 + 2.0)).abs() < 1e-6);
+/// ```
+pub fn conditional_expected_length(n: usize, k: usize, t: f64) -> f64 {
+    assert!(t >= 0.0 && t.is_finite(), "Time must be non-negative and finite.");
+
+    let after: f64 = (1..k).map(|i| 2.0 / i as f64).sum();
+    let before = integrate(t, |s| {
+        let distribution = conditional_lineages_distribution(n, k, s, t);
+        distribution.iter().enumerate().skip(2).map(|(j, p)| j as f64 * p).sum()
+    });
+
+    before + after
+}
+
+/// Expected time to the most recent common ancestor of ``n`` individuals,
+/// conditional on having exactly ``k`` ancestral lineages at time ``t``.
+///
+/// With more than one lineage, it is t + 2 (1 - 1/k). With a single lineage,
+/// the root is before ``t`` and the probability of more lineages is integrated numerically.
+///
+/// # Panics
+///
+/// If ``n`` is zero, if ``t`` is negative or not finite, or if ``k`` lineages at
+/// time ``t`` are impossible.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::theory::conditional_expected_depth;
+///
+/// assert!((conditional_expected_depth(10, 2, 0.5) - 1.5).abs() < 1e-12);
+/// assert!(conditional_expected_depth(10, 1, 0.5) < 0.5);
+/// ```
+pub fn conditional_expected_depth(n: usize, k: usize, t: f64) -> f64 {
+    assert!(t >= 0.0 && t.is_finite(), "Time must be non-negative and finite.");
+
+    match k > 1 {
+        true => {
+            // Only checks that k lineages are possible
+            conditional_lineages_distribution(n, k, t, t);
+            t + 2.0 * (1.0 - 1.0 / k as f64)
+        },
+        false => integrate(t, |s| 1.0 - conditional_lineages_distribution(n, k, s, t)[1]),
+    }
+}
+
+/// Integral of a smooth function over [0, t] by Simpson's rule, after the change of
+/// variables s = t u², which puts more points near zero, where the number of
+/// lineages changes fastest.
+fn integrate<F>(t: f64, mut f: F) -> f64
+where
+    F: FnMut(f64) -> f64,
+{
+    if t == 0.0 {
+        return 0.0;
+    }
+
+    let intervals = 2 * INTEGRATION_INTERVALS;
+    let h = 1.0 / intervals as f64;
+    let mut sum = 0.0;
+    for i in 0..=intervals {
+        let u = i as f64 * h;
+        let weight = match i {
+            0 => 1.0,
+            i if i == intervals => 1.0,
+            i if i % 2 == 1 => 4.0,
+            _ => 2.0,
+        };
+        sum += weight * f(t * u * u) * 2.0 * t * u;
+    }
+
+    sum * h / 3.0
+}
+
+/// Tavaré's formula, if it can be evaluated accurately.
+fn tavare(log_factorials: &[f64], n: usize, k: usize, t: f64) -> Option<f64> {
+    let lf = |m: usize| log_factorials[m];
+
+    let mut sum = 0.0;
+    let mut sum_of_magnitudes = 0.0;
+    for j in k..=n {
+        let log_magnitude = -rate(j) * t
+            + ((2 * j - 1) as f64).ln()
+            + (lf(k + j - 2) - lf(k - 1)) // k_(j-1), rising factorial
+            + (lf(n) - lf(n - j)) // n_[j], falling factorial
+            - (lf(n + j - 1) - lf(n - 1)) // n_(j), rising factorial
+            - lf(k)
+            - lf(j - k);
+        let magnitude = log_magnitude.exp();
+        let sign = if (j - k).is_multiple_of(2) { 1.0 } else { -1.0 };
+
+        sum += sign * magnitude;
+        sum_of_magnitudes += magnitude;
+    }
+
+    let error = 4.0 * (n - k + 1) as f64 * f64::EPSILON * sum_of_magnitudes;
+    match error < TOLERANCE {
+        true => Some(sum.clamp(0.0, 1.0)),
+        false => None,
+    }
+}
+
+/// Distribution of the number of lineages at time ``t`` by uniformization.
+///
+/// Time is split in steps in which the chain, restricted to the numbers of
+/// lineages with non-negligible probability, jumps at most at rate Λ. Then,
+/// in each step, the transition is a Poisson mixture of powers of the
+/// stochastic matrix I + Q / Λ, where Q is the generator of the chain.
+/// The entry ``k`` corresponds to ``k`` lineages.
+fn uniformization(n: usize, t: f64) -> Vec<f64> {
+    let mut distribution = vec![0.0; n + 1];
+    distribution[n] = 1.0;
+    let mut top = n; // largest number of lineages with non-negligible probability
+    let mut elapsed = 0.0;
+
+    while elapsed < t && top > 1 {
+        let uniform_rate = rate(top);
+        let step = (t - elapsed).min(JUMPS_PER_STEP / uniform_rate);
+        let expected_jumps = uniform_rate * step;
+
+        // Poisson mixture of powers of the jump matrix
+
+        let mut power = distribution.clone();
+        let mut weight = (-expected_jumps).exp();
+        let mut mixture: Vec<f64> = power.iter().map(|p| weight * p).collect();
+        let mut jumps = 0;
+        while (jumps as f64) < expected_jumps || weight > f64::EPSILON * 1e-2 {
+            for k in 1..=top {
+                let stay = 1.0 - rate(k) / uniform_rate;
+                let arrive = if k < top { rate(k + 1) / uniform_rate * power[k + 1] } else { 0.0 };
+                power[k] = stay * power[k] + arrive;
+            }
+            jumps += 1;
+            weight *= expected_jumps / jumps as f64;
+            for k in 1..=top {
+                mixture[k] += weight * power[k];
+            }
+        }
+        distribution = mixture;
+        elapsed += step;
+
+        // Restrict to non-negligible probabilities
+
+        while top > 1 && distribution[top] < NEGLIGIBLE {
+            distribution[top] = 0.0;
+            top -= 1;
+        }
+    }
+
+    distribution.into_iter().map(|p| p.clamp(0.0, 1.0)).collect()
+}
+
+/// Rate of coalescence with ``k`` lineages.
+fn rate(k: usize) -> f64 {
+    (k * k.saturating_sub(1) / 2) as f64
+}
+
+/// Logarithm of the factorials from 0! to ``m``!.
+fn log_factorials(m: usize) -> Vec<f64> {
+    let mut log_factorials = Vec::with_capacity(m + 1);
+    let mut cummulative = 0.0;
+    log_factorials.push(cummulative);
+    for i in 1..=m {
+        cummulative += (i as f64).ln();
+        log_factorials.push(cummulative);
+    }
+    log_factorials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniformization_matches_exact_formula() {
+        let n = 15;
+        let log_factorials = log_factorials(2 * n);
+        for &t in &[0.001, 0.01, 0.1, 0.5, 1.0, 3.0] {
+            let uniformized = uniformization(n, t);
+            for (k, &probability) in uniformized.iter().enumerate().skip(1) {
+                if let Some(exact) = tavare(&log_factorials, n, k, t) {
+                    assert!((exact - probability).abs() < 1e-12, "n = {}, k = {}, t = {}: {} vs {}", n, k, t, exact, probability);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distribution_is_consistent() {
+        for &n in &[2, 10, 100, 2000] {
+            for &t in &[0.001, 0.05, 1.0] {
+                let distribution = lineages_distribution(n, t);
+                let total: f64 = distribution.iter().sum();
+                let mean: f64 = distribution.iter().enumerate().map(|(k, p)| k as f64 * p).sum();
+
+                assert!((total - 1.0).abs() < 1e-8, "n = {}, t = {}: total {}", n, t, total);
+                assert!((mean - expected_lineages(n, t)).abs() < 1e-6 * n as f64, "n = {}, t = {}", n, t);
+                assert!((distribution[n / 2 + 1] - lineages_probability(n, n / 2 + 1, t)).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn simulations_agree() {
+        let group_size = 10;
+        let t = 0.3;
+        let samples = 2000;
+        let coalescent = crate::Coalescent::new(group_size, rand::thread_rng());
+
+        let mut rng = rand::thread_rng();
+        let empirical_mean = (0..samples)
+            .map(|_| coalescent.sample_genealogy(&mut rng).partition_at(t).amount_of_sets() as f64)
+            .sum::<f64>() / samples as f64;
+
+        assert!((empirical_mean - expected_lineages(group_size, t)).abs() < 0.2);
+    }
+
+    #[test]
+    fn conditional_expectations() {
+        let (n, t) = (6, 0.4);
+        let mut rng = rand::thread_rng();
+        let coalescent = crate::Coalescent::sampler(n);
+        let (mut lengths, mut depths, mut counts) = (vec![0.0; n + 1], vec![0.0; n + 1], vec![0; n + 1]);
+        for _ in 0..20_000 {
+            let genealogy = coalescent.sample_genealogy(&mut rng);
+            let k = genealogy.partition_at(t).amount_of_sets();
+            lengths[k] += genealogy.length();
+            depths[k] += genealogy.depth();
+            counts[k] += 1;
+        }
+        for k in 1..=3 {
+            let length = lengths[k] / counts[k] as f64;
+            let depth = depths[k] / counts[k] as f64;
+            assert!((length - conditional_expected_length(n, k, t)).abs() < 0.1, "k = {}", k);
+            assert!((depth - conditional_expected_depth(n, k, t)).abs() < 0.05, "k = {}", k);
+        }
+
+        // Conditioning on the distribution at time t gives back the unconditional length
+        let distribution = lineages_distribution(n, t);
+        let length: f64 = (1..=n).map(|k| distribution[k] * conditional_expected_length(n, k, t)).sum();
+        let harmonic: f64 = (1..n).map(|i| 2.0 / i as f64).sum();
+        assert!((length - harmonic).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,260 @@
+//! Frequencies of topologies across many genealogies.
+//!
+//! A ``TopologyCounter`` tallies the topologies of the genealogies it is given, as keys
+//! chosen by ``TopologyKey``: unlabeled shapes, see ``shape`` on Genealogy, or ranked
+//! shapes, which also keep the order of the events. For small numbers of distinct
+//! topologies, counts are exact. For very many replicates of large groups, where most
+//! topologies are seen once, a count-min sketch (Cormode and Muthukrishnan, 2005) keeps
+//! memory bounded: each topology is hashed into one counter of each row, and its count
+//! is estimated by the smallest of them, which never underestimates it. Only a bounded
+//! number of the most frequent topologies are then kept, to report them.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::topologies::{TopologyCounter, TopologyKey};
+//!
+//! let coalescent = coalescence::Coalescent::sampler(4);
+//! let mut rng = rand::thread_rng();
+//! let mut counter = TopologyCounter::exact(TopologyKey::Shape);
+//! counter.extend((0..3000).map(|_| coalescent.sample_genealogy(&mut rng)));
+//!
+//! // Caterpillars are twice as likely as balanced trees
+//! let most_common = counter.most_common(2);
+//! assert_eq!(most_common[0].0, "(,(,(,)))");
+//! assert!((most_common[0].1 as f64 / 3000.0 - 2.0 / 3.0).abs() < 0.05);
+//! ```
+//!
+
+// Types
+use crate::Genealogy;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+
+// Traits
+use std::hash::{Hash, Hasher};
+
+/// Key identifying the topology of a genealogy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TopologyKey {
+    /// Unlabeled topology, see ``shape`` on Genealogy.
+    Shape,
+    /// Unlabeled topology with the rank of each common ancestor, from one for the most
+    /// recent one, e.g. ``((,)1,(,)2)3`` for a balanced tree of four individuals.
+    RankedShape,
+}
+
+impl TopologyKey {
+    /// Key of the topology of a genealogy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use coalescence::topologies::TopologyKey;
+    ///
+    /// let genealogy = coalescence::fixtures::caterpillar(3);
+    ///
+    /// assert_eq!(TopologyKey::Shape.key(&genealogy), "(,(,))");
+    /// assert_eq!(TopologyKey::RankedShape.key(&genealogy), "(,(,)1)2");
+    /// ```
+    pub fn key(&self, genealogy: &Genealogy) -> String {
+        match self {
+            TopologyKey::Shape => genealogy.shape(),
+            TopologyKey::RankedShape => ranked_shape(genealogy),
+        }
+    }
+}
+
+/// Counter of the topologies of genealogies, see the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopologyCounter {
+    key: TopologyKey,
+    total: u64,
+    counts: Counts,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Counts {
+    Exact(HashMap<String, u64>),
+    Sketch {
+        rows: Vec<Vec<u64>>,
+        capacity: usize,
+        candidates: HashMap<String, u64>, // most frequent topologies, with their estimates
+    },
+}
+
+impl TopologyCounter {
+    /// Counter keeping every topology with its exact count.
+    pub fn exact(key: TopologyKey) -> Self {
+        TopologyCounter { key, total: 0, counts: Counts::Exact(HashMap::new()) }
+    }
+
+    /// Counter in memory bounded by a count-min sketch of ``depth`` rows of ``width``
+    /// counters, keeping the ``capacity`` most frequent topologies. Estimates exceed
+    /// counts by at most ``e / width`` times the total with probability ``1 - e^{-depth}``.
+    ///
+    /// # Panics
+    ///
+    /// If the width, the depth or the capacity is zero.
+    pub fn count_min(key: TopologyKey, width: usize, depth: usize, capacity: usize) -> Self {
+        assert!(width > 0 && depth > 0, "The sketch must have some counters.");
+        assert!(capacity > 0, "Some topologies must be kept.");
+
+        let counts = Counts::Sketch { rows: vec![vec![0; width]; depth], capacity, candidates: HashMap::new() };
+        TopologyCounter { key, total: 0, counts }
+    }
+
+    /// Key of the topologies.
+    pub fn key(&self) -> TopologyKey {
+        self.key
+    }
+
+    /// Number of genealogies counted.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Count the topology of a genealogy.
+    pub fn add(&mut self, genealogy: &Genealogy) -> &mut Self {
+        let topology = self.key.key(genealogy);
+        self.total += 1;
+
+        match &mut self.counts {
+            Counts::Exact(counts) => *counts.entry(topology).or_default() += 1,
+            Counts::Sketch { rows, capacity, candidates } => {
+                let mut estimate = u64::MAX;
+                for (row, counters) in rows.iter_mut().enumerate() {
+                    let width = counters.len();
+                    let counter = &mut counters[bucket(&topology, row, width)];
+                    *counter += 1;
+                    estimate = estimate.min(*counter);
+                }
+
+                // Replace the least frequent candidate if this topology is more frequent
+                if candidates.contains_key(&topology) || candidates.len() < *capacity {
+                    candidates.insert(topology, estimate);
+                } else {
+                    let (least, least_estimate) = candidates
+                        .iter()
+                        .min_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                        .map(|(least, &estimate)| (least.clone(), estimate))
+                        .unwrap();
+                    if estimate > least_estimate {
+                        candidates.remove(&least);
+                        candidates.insert(topology, estimate);
+                    }
+                }
+            },
+        }
+        self
+    }
+
+    /// Count of a topology, given by its key, which is an upper bound with a sketch.
+    pub fn count(&self, topology: &str) -> u64 {
+        match &self.counts {
+            Counts::Exact(counts) => counts.get(topology).cloned().unwrap_or(0),
+            Counts::Sketch { rows, .. } => rows
+                .iter()
+                .enumerate()
+                .map(|(row, counters)| counters[bucket(topology, row, counters.len())])
+                .min()
+                .unwrap(),
+        }
+    }
+
+    /// At most ``k`` of the most frequent topologies with their counts, from the most
+    /// frequent one, ties being broken by keys. With a sketch, only kept topologies are
+    /// reported, with their current estimates.
+    pub fn most_common(&self, k: usize) -> Vec<(String, u64)> {
+        let mut counts: Vec<(String, u64)> = match &self.counts {
+            Counts::Exact(counts) => counts.iter().map(|(topology, &count)| (topology.clone(), count)).collect(),
+            Counts::Sketch { candidates, .. } => {
+                candidates.keys().map(|topology| (topology.clone(), self.count(topology))).collect()
+            },
+        };
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(k);
+
+        counts
+    }
+}
+
+impl Extend<Genealogy> for TopologyCounter {
+    fn extend<I: IntoIterator<Item = Genealogy>>(&mut self, genealogies: I) {
+        for genealogy in genealogies {
+            self.add(&genealogy);
+        }
+    }
+}
+
+/// Counter of a topology in a row of a sketch, from a hash seeded by the row.
+fn bucket(topology: &str, row: usize, width: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    row.hash(&mut hasher);
+    topology.hash(&mut hasher);
+    (hasher.finish() % width as u64) as usize
+}
+
+/// Ranked shape, built bottom-up with the children of each node sorted by their keys.
+fn ranked_shape(genealogy: &Genealogy) -> String {
+    let group_size = genealogy.group_size();
+    let (parents, _) = genealogy.nodes();
+    let mut children: Vec<Vec<String>> = vec![Vec::new(); parents.len()];
+
+    // Children come before their parents
+    for node in 0..parents.len() {
+        let mut below = std::mem::take(&mut children[node]);
+        let key = match node < group_size {
+            true => String::new(),
+            false => {
+                below.sort_unstable();
+                format!("({}){}", below.join(","), node - group_size + 1)
+            },
+        };
+        match parents[node] == node {
+            true => return key,
+            false => children[parents[node]].push(key),
+        }
+    }
+    unreachable!("Genealogies have a root.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranked_shapes() {
+        assert_eq!(TopologyKey::RankedShape.key(&crate::fixtures::balanced()), "((,)1,(,)2)3");
+        assert_eq!(TopologyKey::RankedShape.key(&crate::Genealogy::from_events(1, &[]).unwrap()), "");
+
+        // Rankings of the same shape differ
+        let early = crate::Genealogy::from_events(5, &[(1.0, [0, 1]), (2.0, [2, 3]), (3.0, [0, 2]), (4.0, [0, 4])]).unwrap();
+        let late = crate::Genealogy::from_events(5, &[(1.0, [0, 1]), (2.0, [0, 4]), (3.0, [2, 3]), (4.0, [0, 2])]).unwrap();
+        assert_eq!(early.shape(), "(,((,),(,)))");
+        assert_ne!(TopologyKey::RankedShape.key(&early), TopologyKey::RankedShape.key(&late));
+    }
+
+    #[test]
+    fn sketches() {
+        let coalescent = crate::Coalescent::sampler(6);
+        let mut rng = rand::thread_rng();
+        let genealogies: Vec<Genealogy> = (0..5000).map(|_| coalescent.sample_genealogy(&mut rng)).collect();
+
+        let mut exact = TopologyCounter::exact(TopologyKey::RankedShape);
+        let mut sketch = TopologyCounter::count_min(TopologyKey::RankedShape, 64, 4, 5);
+        exact.extend(genealogies.iter().cloned());
+        sketch.extend(genealogies);
+        assert_eq!((exact.total(), sketch.total()), (5000, 5000));
+
+        // Estimates bound counts, and frequent topologies are kept
+        let expected = exact.most_common(3);
+        let reported = sketch.most_common(3);
+        assert_eq!(reported.len(), 3);
+        for (topology, count) in &expected {
+            assert!(sketch.count(topology) >= *count);
+        }
+        assert!(reported.iter().any(|(topology, _)| topology == &expected[0].0));
+        assert_eq!(exact.count("absent"), 0);
+    }
+}
@@ -1 +1,343 @@
+//! Traits of the crate.
+//!
 
+// Types
+use crate::{Coalescent, Event, FluctuatingCoalescent, Genealogy, PairwiseCoalescent, Partition};
+use rand_distr::Exp1;
+
+// Traits
+use markovian::traits::CMarkovChainTrait;
+use rand::distributions::Distribution;
+use rand::{Rng, RngCore};
+
+/// Exchangeable random partition of a group of individuals, like the states
+/// of the coalescent process, with statistics of the sizes of its blocks.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::traits::RandomPartition;
+/// let group_size = 10;
+/// let rng = rand::thread_rng();
+/// let mut coalescent = coalescence::Coalescent::new(group_size, rng);
+/// for _ in 0..6 {
+///     coalescent.next();
+/// }
+///
+/// assert_eq!(coalescent.number_of_blocks(), 4);
+/// assert_eq!(coalescent.block_sizes().iter().sum::<usize>(), group_size);
+/// let counts = coalescent.block_size_counts();
+/// assert_eq!(counts.iter().enumerate().map(|(i, count)| (i + 1) * count).sum::<usize>(), group_size);
+/// ```
+pub trait RandomPartition {
+    /// Current partition.
+    fn partition(&self) -> &Partition;
+
+    /// Number of individuals.
+    fn group_size(&self) -> usize {
+        self.partition().len()
+    }
+
+    /// Number of blocks of the partition.
+    fn number_of_blocks(&self) -> usize {
+        self.partition().amount_of_sets()
+    }
+
+    /// Sizes of the blocks, in decreasing order.
+    fn block_sizes(&self) -> Vec<usize> {
+        let partition = self.partition();
+        let mut sizes: Vec<usize> = partition
+            .representatives()
+            .iter()
+            .map(|&representative| partition.len_of_set(representative))
+            .collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        sizes
+    }
+
+    /// Number of blocks of each size, also known as the allelic partition:
+    /// the entry ``j`` is the number of blocks of size ``j + 1``, for ``j`` from 0 to (n - 1).
+    fn block_size_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.group_size()];
+        for size in self.block_sizes() {
+            counts[size - 1] += 1;
+        }
+        counts
+    }
+}
+
+impl RandomPartition for Partition {
+    fn partition(&self) -> &Partition {
+        self
+    }
+}
+
+impl<R> RandomPartition for Coalescent<R>
+where
+    R: Rng,
+{
+    fn partition(&self) -> &Partition {
+        self.state()
+    }
+}
+
+/// State of a continuous-time process with finitely many possible events, each with
+/// its own rate, simulated by the Gillespie algorithm: the waiting time is exponential
+/// with the total rate, and the event is chosen with probability proportional to its rate.
+///
+/// Implementors only list the rates of events and tell which event each one is;
+/// the structured coalescent and the coalescent with pair-specific rates share this engine.
+///
+/// # Examples
+///
+/// Each lineage of a group dies at rate one.
+/// ```
+/// use coalescence::traits::GillespieStep;
+///
+/// struct Deaths(usize);
+///
+/// impl GillespieStep for Deaths {
+///     type Event = usize;
+///
+///     fn event_rates(&self, rates: &mut Vec<f64>) {
+///         rates.extend(std::iter::repeat(1.0).take(self.0));
+///     }
+///
+///     fn event(&self, index: usize) -> usize {
+///         index
+///     }
+/// }
+///
+/// let mut rng = rand::thread_rng();
+/// let (waiting_time, lineage) = Deaths(5).gillespie_step(&mut rng).unwrap();
+/// assert!(waiting_time > 0.0 && lineage < 5);
+/// assert_eq!(Deaths(0).gillespie_step(&mut rng), None);
+/// ```
+pub trait GillespieStep {
+    /// Events of the process.
+    type Event;
+
+    /// Pushes the rate of each possible event from the current state into ``rates``,
+    /// which starts empty.
+    fn event_rates(&self, rates: &mut Vec<f64>);
+
+    /// Event of the given index in ``event_rates``.
+    fn event(&self, index: usize) -> Self::Event;
+
+    /// Waiting time to the next event and the event, or ``None`` if all rates are zero.
+    fn gillespie_step<R>(&self, rng: &mut R) -> Option<(f64, Self::Event)>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut rates = Vec::new();
+        self.event_rates(&mut rates);
+        gillespie(&rates, rng).map(|(waiting_time, index)| (waiting_time, self.event(index)))
+    }
+}
+
+/// One step of the Gillespie algorithm over the given rates: waiting time to the next
+/// event and its index, or ``None`` if all rates are zero.
+///
+/// # Panics
+///
+/// If some rate is negative or not finite.
+pub fn gillespie<R>(rates: &[f64], rng: &mut R) -> Option<(f64, usize)>
+where
+    R: Rng + ?Sized,
+{
+    assert!(rates.iter().all(|&rate| rate >= 0.0 && rate.is_finite()), "Rates must be non-negative and finite.");
+    let total_rate: f64 = rates.iter().sum();
+    if total_rate <= 0.0 {
+        return None;
+    }
+
+    let intensity: f64 = Exp1.sample(rng);
+    let mut point = rng.gen::<f64>() * total_rate;
+    let index = rates
+        .iter()
+        .position(|&rate| {
+            point -= rate;
+            rate > 0.0 && point < 0.0
+        })
+        .unwrap_or_else(|| rates.iter().rposition(|&rate| rate > 0.0).unwrap()); // if rounding overshoots
+
+    Some((intensity / total_rate, index))
+}
+
+/// Object-safe sampler of genealogies, so that models can be chosen at runtime, 
+/// e.g. from the command line or a configuration file, and stored as 
+/// ``Box<dyn DynGenealogySampler>`` without being generic over models or generators. 
+///
+/// Models that sample a fixed group implement it directly. Closures from a generator 
+/// to a genealogy implement it too, which covers models that take the sample as an 
+/// argument, like ``Sweep`` or ``BirthDeath``. 
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::models::ModelKind;
+/// use coalescence::traits::DynGenealogySampler;
+/// use coalescence::{BirthDeath, Coalescent};
+///
+/// let group_size = 10;
+/// let sampler: Box<dyn DynGenealogySampler> = match "yule".parse().unwrap() {
+///     ModelKind::Yule => {
+///         let yule = BirthDeath::yule(1.0);
+///         Box::new(move |rng: &mut dyn rand::RngCore| yule.sample_genealogy(group_size, rng))
+///     },
+///     _ => Box::new(Coalescent::sampler(group_size)),
+/// };
+///
+/// let genealogies = sampler.sample_genealogies(5, &mut rand::thread_rng());
+/// assert!(genealogies.iter().all(|genealogy| genealogy.group_size() == group_size));
+/// ```
+pub trait DynGenealogySampler {
+    /// Sample a genealogy, drawing from the given generator.
+    fn sample_genealogy_dyn(&self, rng: &mut dyn RngCore) -> Genealogy;
+
+    /// Sample independent genealogies.
+    fn sample_genealogies(&self, replicates: usize, rng: &mut dyn RngCore) -> Vec<Genealogy> {
+        (0..replicates).map(|_| self.sample_genealogy_dyn(rng)).collect()
+    }
+}
+
+impl<F> DynGenealogySampler for F
+where
+    F: Fn(&mut dyn RngCore) -> Genealogy,
+{
+    fn sample_genealogy_dyn(&self, rng: &mut dyn RngCore) -> Genealogy {
+        self(rng)
+    }
+}
+
+impl<R> DynGenealogySampler for Coalescent<R>
+where
+    R: Rng,
+{
+    fn sample_genealogy_dyn(&self, rng: &mut dyn RngCore) -> Genealogy {
+        self.sample_genealogy(rng)
+    }
+}
+
+impl DynGenealogySampler for PairwiseCoalescent {
+    fn sample_genealogy_dyn(&self, rng: &mut dyn RngCore) -> Genealogy {
+        self.sample_genealogy(rng)
+    }
+}
+
+impl DynGenealogySampler for FluctuatingCoalescent {
+    fn sample_genealogy_dyn(&self, rng: &mut dyn RngCore) -> Genealogy {
+        self.sample(rng).1
+    }
+}
+
+/// Online accumulator of statistics of genealogies, called on each event while sampling,
+/// so that statistics of very large groups never need the genealogy to be stored.
+///
+/// See ``sample_observed`` on Coalescent, and ``observers`` for common statistics.
+/// Pairs of observers observe the same events.
+///
+/// # Examples
+///
+/// ```
+/// use coalescence::traits::StatisticsObserver;
+/// use coalescence::Event;
+///
+/// /// Time while there were only two lineages
+/// #[derive(Default)]
+/// struct LastWait(f64);
+///
+/// impl StatisticsObserver for LastWait {
+///     fn observe(&mut self, event: &Event, _sizes: [usize; 2]) {
+///         if event.lineages == 1 {
+///             self.0 = event.time_step;
+///         }
+///     }
+/// }
+///
+/// let mut last_wait = LastWait::default();
+/// coalescence::Coalescent::sampler(100).sample_observed(&mut last_wait, &mut rand::thread_rng());
+///
+/// assert!(last_wait.0 > 0.0);
+/// ```
+pub trait StatisticsObserver {
+    /// Called once before the first event, with the number of individuals.
+    fn start(&mut self, _group_size: usize) {}
+
+    /// Called on each event, with the number of individuals below each of the joined lineages.
+    fn observe(&mut self, event: &Event, sizes: [usize; 2]);
+}
+
+impl<A, B> StatisticsObserver for (A, B)
+where
+    A: StatisticsObserver,
+    B: StatisticsObserver,
+{
+    fn start(&mut self, group_size: usize) {
+        self.0.start(group_size);
+        self.1.start(group_size);
+    }
+
+    fn observe(&mut self, event: &Event, sizes: [usize; 2]) {
+        self.0.observe(event, sizes);
+        self.1.observe(event, sizes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_statistics() {
+        let mut partition = Partition::singletons(6);
+        partition.union(0, 1);
+        partition.union(1, 2);
+        partition.union(3, 4);
+
+        assert_eq!(partition.number_of_blocks(), 3);
+        assert_eq!(partition.block_sizes(), vec![3, 2, 1]);
+        assert_eq!(partition.block_size_counts(), vec![1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn gillespie_steps() {
+        let mut rng = rand::thread_rng();
+        let rates = [0.0, 3.0, 0.0, 1.0];
+        let samples = 10_000;
+        let (mut waiting_time, mut counts) = (0.0, [0; 4]);
+        for _ in 0..samples {
+            let (time, index) = gillespie(&rates, &mut rng).unwrap();
+            waiting_time += time;
+            counts[index] += 1;
+        }
+        assert!((waiting_time / samples as f64 - 0.25).abs() < 0.02);
+        assert_eq!((counts[0], counts[2]), (0, 0));
+        assert!((counts[1] as f64 / samples as f64 - 0.75).abs() < 0.03);
+        assert_eq!(gillespie(&[0.0, 0.0], &mut rng), None);
+    }
+
+    #[test]
+    fn dynamic_samplers() {
+        use rand::SeedableRng;
+
+        let coalescent = Coalescent::sampler(6);
+        let samplers: Vec<Box<dyn DynGenealogySampler>> = vec![
+            Box::new(coalescent.clone()),
+            Box::new(PairwiseCoalescent::exchangeable(6)),
+            Box::new(|rng: &mut dyn RngCore| crate::Sweep::new(1e4, 0.01, 0.0, 0.0).sample_genealogy(6, rng)),
+        ];
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(1);
+        for sampler in &samplers {
+            assert_eq!(sampler.sample_genealogy_dyn(&mut rng).group_size(), 6);
+        }
+
+        // Same genealogies as the generic method, from the same stream
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(2);
+        let mut other_rng = rand_pcg::Pcg32::seed_from_u64(2);
+        let genealogies = samplers[0].sample_genealogies(3, &mut rng);
+        for genealogy in genealogies {
+            assert_eq!(genealogy.depth(), coalescent.sample_genealogy(&mut other_rng).depth());
+        }
+    }
+}
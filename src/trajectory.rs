@@ -0,0 +1,172 @@
+//! Genealogies conditional on the trajectory of an allele.
+//!
+//! A selected site splits the population in two classes: carriers of the derived
+//! allele, with frequency x(t) at time t in the past, and carriers of the ancestral
+//! allele. The genealogy at a linked locus follows a structured coalescent with those
+//! classes as demes (Kaplan, Hudson and Langley, 1989): pairs of lineages coalesce
+//! at rate 1 / x(t) among derived carriers and 1 / (1 - x(t)) among ancestral ones,
+//! and recombination between the locus and the site, at rate ρ/2 per lineage,
+//! moves a lineage to the other class with probability equal to its frequency.
+//! At the origin of the derived allele, all lineages carrying it move to the ancestral class.
+//!
+
+// Types
+use crate::{Genealogy, StructuredCoalescent};
+
+// Traits
+use rand::Rng;
+
+/// Index of the class of carriers of the derived allele.
+const DERIVED: usize = 0;
+/// Index of the class of carriers of the ancestral allele.
+const ANCESTRAL: usize = 1;
+
+/// Frequency of a derived allele through time, constant within intervals.
+///
+/// # Examples
+///
+/// Without recombination, carriers of the derived allele form a clade below its origin.
+/// ```
+/// use coalescence::Trajectory;
+///
+/// let trajectory = Trajectory::new(vec![0.0, 0.5, 1.0], vec![0.4, 0.1]);
+///
+/// let mut rng = rand::thread_rng();
+/// let genealogy = trajectory.sample_genealogy(3, 4, 0.0, &mut rng);
+///
+/// assert!(genealogy.branches().iter().any(|branch| branch.leaves == vec![0, 1, 2]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    times: Vec<f64>, // bounds of the intervals, from zero to the origin
+    frequencies: Vec<f64>,
+}
+
+impl Trajectory {
+    /// Creates a new trajectory, where the frequency of the derived allele is
+    /// ``frequencies[i]`` between times ``times[i]`` and ``times[i + 1]`` in the past.
+    /// The last time is the origin of the allele.
+    ///
+    /// # Panics
+    ///
+    /// If there is not one more time than frequencies, if times do not increase from zero,
+    /// or if some frequency is not positive and at most one.
+    pub fn new(times: Vec<f64>, frequencies: Vec<f64>) -> Self {
+        assert_eq!(times.len(), frequencies.len() + 1, "There must be one more time than frequencies.");
+        assert!(!frequencies.is_empty(), "There must be at least one frequency.");
+        assert_eq!(times[0], 0.0, "Times must start at zero.");
+        assert!(
+            times.windows(2).all(|pair| pair[0] < pair[1]) && times.last().unwrap().is_finite(),
+            "Times must increase."
+        );
+        assert!(
+            frequencies.iter().all(|&frequency| frequency > 0.0 && frequency <= 1.0),
+            "Frequencies must be positive and at most one."
+        );
+
+        Trajectory { times, frequencies }
+    }
+
+    /// Time of the origin of the derived allele.
+    pub fn origin(&self) -> f64 {
+        *self.times.last().unwrap()
+    }
+
+    /// Frequency of the derived allele at time ``t`` in the past, zero after its origin.
+    pub fn frequency_at(&self, t: f64) -> f64 {
+        match self.times.windows(2).position(|pair| pair[0] <= t && t < pair[1]) {
+            Some(index) => self.frequencies[index],
+            None => 0.0,
+        }
+    }
+
+    /// Structured coalescent of the carriers of each allele, where the first deme is the class
+    /// of the derived allele, and the recombination rate ρ between the locus and the selected site
+    /// is measured as 4 N r.
+    ///
+    /// # Panics
+    ///
+    /// If the recombination rate is negative or not finite.
+    pub fn structured_coalescent(&self, recombination_rate: f64) -> StructuredCoalescent {
+        assert!(
+            recombination_rate >= 0.0 && recombination_rate.is_finite(),
+            "The recombination rate must be non-negative and finite."
+        );
+        let rate = recombination_rate / 2.0;
+
+        let epoch = |frequency: f64| {
+            let sizes = vec![frequency, 1.0 - frequency];
+            let migration = vec![vec![0.0, rate * (1.0 - frequency)], vec![rate * frequency, 0.0]];
+            (sizes, migration)
+        };
+
+        let (sizes, migration) = epoch(self.frequencies[0]);
+        let mut structured = StructuredCoalescent::new(sizes, migration);
+        for (&start, &frequency) in self.times.iter().zip(&self.frequencies).skip(1) {
+            let (sizes, migration) = epoch(frequency);
+            structured = structured.add_epoch(start, sizes, migration);
+        }
+
+        // After the origin, there is only the ancestral class
+
+        let none = vec![vec![0.0; 2]; 2];
+        structured
+            .add_epoch(self.origin(), vec![1.0, 1.0], none)
+            .add_pulse(self.origin(), DERIVED, ANCESTRAL, 1.0)
+    }
+
+    /// Sample the genealogy at a locus linked to the selected site, of a group with
+    /// ``derived`` carriers of the derived allele, numbered first, and ``ancestral``
+    /// carriers of the ancestral allele.
+    ///
+    /// See ``structured_coalescent`` for the recombination rate.
+    ///
+    /// # Panics
+    ///
+    /// If there are carriers of the ancestral allele while the derived allele is fixed,
+    /// i.e. its frequency is one at present.
+    ///
+    /// # Remarks
+    ///
+    /// Lineages still carrying the derived allele at its origin move together to the ancestral class.
+    /// For this to be rare, trajectories should end at a low frequency, like 1 / (2N).
+    pub fn sample_genealogy<R>(&self, derived: usize, ancestral: usize, recombination_rate: f64, rng: &mut R) -> Genealogy
+    where
+        R: Rng + ?Sized,
+    {
+        let mut samples = vec![0; 2];
+        samples[DERIVED] = derived;
+        samples[ANCESTRAL] = ancestral;
+
+        self.structured_coalescent(recombination_rate).sample_genealogy(&samples, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rare_allele_coalesces_fast() {
+        // Pairs of carriers coalesce at rate 1 / x
+        let trajectory = Trajectory::new(vec![0.0, 100.0], vec![0.25]);
+        let samples = 4000;
+
+        let mut rng = rand::thread_rng();
+        let mean_depth = (0..samples)
+            .map(|_| trajectory.sample_genealogy(2, 0, 0.0, &mut rng).depth())
+            .sum::<f64>() / samples as f64;
+
+        assert!((mean_depth - 0.25).abs() < 0.025);
+    }
+
+    #[test]
+    fn frequencies() {
+        let trajectory = Trajectory::new(vec![0.0, 1.0, 3.0], vec![0.9, 0.2]);
+
+        assert_eq!(trajectory.frequency_at(0.5), 0.9);
+        assert_eq!(trajectory.frequency_at(1.0), 0.2);
+        assert_eq!(trajectory.frequency_at(3.0), 0.0);
+        assert_eq!(trajectory.origin(), 3.0);
+    }
+}
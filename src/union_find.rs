@@ -0,0 +1,93 @@
+//! Disjoint sets of individuals, merged as lineages coalesce.
+//!
+
+// Types
+use std::cell::Cell;
+
+/// Union–find over ``0..n`` with union by size and path halving,
+/// so that each operation takes almost constant time.
+///
+/// Parents are cells, so that finding representatives compresses paths
+/// without needing a mutable reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnionFind {
+    parents: Vec<Cell<usize>>,
+    sizes: Vec<usize>, // only meaningful for roots
+}
+
+impl UnionFind {
+    /// Singletons ``{0}, {1}, ..., {n - 1}``.
+    pub(crate) fn new(n: usize) -> Self {
+        UnionFind { parents: (0..n).map(Cell::new).collect(), sizes: vec![1; n] }
+    }
+
+    /// No elements, with space for ``capacity`` of them.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        UnionFind { parents: Vec::with_capacity(capacity), sizes: Vec::with_capacity(capacity) }
+    }
+
+    /// Number of elements.
+    pub(crate) fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Adds a new singleton, returning its element.
+    pub(crate) fn push(&mut self) -> usize {
+        let element = self.parents.len();
+        self.parents.push(Cell::new(element));
+        self.sizes.push(1);
+        element
+    }
+
+    /// Representative of the set of ``element``.
+    pub(crate) fn find(&self, mut element: usize) -> usize {
+        loop {
+            let parent = self.parents[element].get();
+            if parent == element {
+                return element;
+            }
+            let grandparent = self.parents[parent].get();
+            self.parents[element].set(grandparent);
+            element = grandparent;
+        }
+    }
+
+    /// Number of elements in the set of ``element``.
+    pub(crate) fn size(&self, element: usize) -> usize {
+        self.sizes[self.find(element)]
+    }
+
+    /// Merge the sets of both elements, returning the representative of the union.
+    pub(crate) fn union(&mut self, first: usize, second: usize) -> usize {
+        let (mut first, mut second) = (self.find(first), self.find(second));
+        if first == second {
+            return first;
+        }
+        if self.sizes[first] < self.sizes[second] {
+            std::mem::swap(&mut first, &mut second);
+        }
+        self.parents[second].set(first);
+        self.sizes[first] += self.sizes[second];
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges() {
+        let mut sets = UnionFind::new(5);
+        sets.union(0, 1);
+        sets.union(3, 4);
+        assert_eq!(sets.find(0), sets.find(1));
+        assert_ne!(sets.find(1), sets.find(3));
+        let root = sets.union(1, 4);
+        assert_eq!(sets.find(0), root);
+        assert_eq!(sets.size(3), 4);
+        assert_eq!(sets.size(2), 1);
+        assert_eq!(sets.push(), 5);
+        assert_eq!(sets.len(), 6);
+    }
+}
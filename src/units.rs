@@ -0,0 +1,171 @@
+//! Units of time.
+//!
+//! Genealogies measure time in coalescent units, where each pair of lineages
+//! coalesces at rate one. In a population of ``N`` gene copies, e.g. ``2 Ne`` for
+//! a diploid population of effective size ``Ne``, one coalescent unit is ``N``
+//! generations, and one generation is a number of years given by the generation time.
+//!
+//! Each unit has its own type, so that times in different units can not be mixed,
+//! and conversions go through a ``TimeScale``.
+//!
+//! # Examples
+//!
+//! ```
+//! use coalescence::{CoalescentTime, Generations, TimeScale, TimeUnit, Years};
+//!
+//! // Humans: Ne = 10 000 and 25 years per generation
+//! let scale = TimeScale::diploid(10_000.0, 25.0);
+//!
+//! let generations: Generations = CoalescentTime(1.0).convert(&scale);
+//! assert_eq!(generations, Generations(20_000.0));
+//! assert_eq!(generations.convert::<Years>(&scale), Years(500_000.0));
+//! assert_eq!(Years(500_000.0).convert::<CoalescentTime>(&scale), CoalescentTime(1.0));
+//! ```
+//!
+
+// Traits
+use std::ops::{Add, Sub};
+
+/// Relation between units of time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale {
+    copies: f64,
+    generation_time: f64,
+}
+
+impl TimeScale {
+    /// Time scale of a population of ``copies`` gene copies, with ``generation_time``
+    /// years per generation.
+    ///
+    /// # Panics
+    ///
+    /// If some value is not positive and finite.
+    pub fn new(copies: f64, generation_time: f64) -> Self {
+        assert!(copies > 0.0 && copies.is_finite(), "The number of gene copies must be positive and finite.");
+        assert!(
+            generation_time > 0.0 && generation_time.is_finite(),
+            "The generation time must be positive and finite."
+        );
+
+        TimeScale { copies, generation_time }
+    }
+
+    /// Time scale of a diploid population of effective size ``effective_size``,
+    /// i.e. with twice as many gene copies.
+    pub fn diploid(effective_size: f64, generation_time: f64) -> Self {
+        TimeScale::new(2.0 * effective_size, generation_time)
+    }
+
+    /// Number of gene copies, i.e. generations per unit of coalescent time.
+    pub fn copies(&self) -> f64 {
+        self.copies
+    }
+
+    /// Years per generation.
+    pub fn generation_time(&self) -> f64 {
+        self.generation_time
+    }
+}
+
+/// Unit of time, converted to others through generations.
+pub trait TimeUnit: Copy {
+    /// Time of the given number of generations.
+    fn from_generations(generations: f64, scale: &TimeScale) -> Self;
+
+    /// Number of generations of the time.
+    fn generations(self, scale: &TimeScale) -> f64;
+
+    /// Same time in another unit.
+    fn convert<T: TimeUnit>(self, scale: &TimeScale) -> T {
+        T::from_generations(self.generations(scale), scale)
+    }
+}
+
+/// Time in coalescent units, where each pair of lineages coalesces at rate one.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct CoalescentTime(pub f64);
+
+/// Time in generations.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Generations(pub f64);
+
+/// Time in years.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Years(pub f64);
+
+impl TimeUnit for CoalescentTime {
+    fn from_generations(generations: f64, scale: &TimeScale) -> Self {
+        CoalescentTime(generations / scale.copies)
+    }
+
+    fn generations(self, scale: &TimeScale) -> f64 {
+        self.0 * scale.copies
+    }
+}
+
+impl TimeUnit for Generations {
+    fn from_generations(generations: f64, _: &TimeScale) -> Self {
+        Generations(generations)
+    }
+
+    fn generations(self, _: &TimeScale) -> f64 {
+        self.0
+    }
+}
+
+impl TimeUnit for Years {
+    fn from_generations(generations: f64, scale: &TimeScale) -> Self {
+        Years(generations * scale.generation_time)
+    }
+
+    fn generations(self, scale: &TimeScale) -> f64 {
+        self.0 / scale.generation_time
+    }
+}
+
+/// Sums and differences of times in the same unit.
+macro_rules! impl_arithmetic {
+    ($($unit:ident),*) => {
+        $(
+            impl Add for $unit {
+                type Output = $unit;
+
+                fn add(self, other: $unit) -> $unit {
+                    $unit(self.0 + other.0)
+                }
+            }
+
+            impl Sub for $unit {
+                type Output = $unit;
+
+                fn sub(self, other: $unit) -> $unit {
+                    $unit(self.0 - other.0)
+                }
+            }
+        )*
+    };
+}
+
+impl_arithmetic!(CoalescentTime, Generations, Years);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let scale = TimeScale::new(1000.0, 2.0);
+        let time = CoalescentTime(0.5);
+        let years: Years = time.convert(&scale);
+        assert_eq!(years, Years(1000.0));
+        assert_eq!(years.convert::<Generations>(&scale), Generations(500.0));
+        assert_eq!(time.convert::<CoalescentTime>(&scale), time);
+        assert_eq!(Years(3.0) - Years(1.0) + Years(0.5), Years(2.5));
+
+        // Units of a genealogy
+        let genealogy = crate::fixtures::balanced();
+        assert_eq!(genealogy.depth_in::<Generations>(&scale), Generations(4000.0));
+        assert_eq!(genealogy.length_in::<CoalescentTime>(&scale), CoalescentTime(genealogy.length()));
+        assert_eq!(genealogy.divergence_in::<Years>(0, 2, &scale).unwrap(), Years(16_000.0));
+    }
+}